@@ -0,0 +1,12 @@
+-- Balance checkpoints: periodic per-account balance snapshots that bound
+-- point-in-time reconstruction to "latest checkpoint + postings after it".
+
+CREATE TABLE IF NOT EXISTS balance_checkpoints (
+  account_id TEXT NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+  as_of TIMESTAMPTZ NOT NULL,
+  balance_units BIGINT NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  PRIMARY KEY (account_id, as_of)
+);
+
+CREATE INDEX IF NOT EXISTS idx_postings_account_time ON postings(account_id, created_at);
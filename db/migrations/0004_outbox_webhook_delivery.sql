@@ -0,0 +1,9 @@
+-- Webhook delivery state for the transactional outbox.
+-- Independent of published_at (NATS), so both sinks can run side by side.
+
+ALTER TABLE outbox_events ADD COLUMN IF NOT EXISTS delivered_at TIMESTAMPTZ NULL;
+ALTER TABLE outbox_events ADD COLUMN IF NOT EXISTS attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE outbox_events ADD COLUMN IF NOT EXISTS next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT now();
+ALTER TABLE outbox_events ADD COLUMN IF NOT EXISTS last_error TEXT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_outbox_undelivered ON outbox_events(delivered_at, next_attempt_at);
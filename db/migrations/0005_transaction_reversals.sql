@@ -0,0 +1,6 @@
+-- Compensating transactions: a reversal points at the transaction it undoes.
+-- The partial unique index guarantees a transaction is reversed at most once.
+
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS reverses_txn_id UUID NULL REFERENCES transactions(id);
+
+CREATE UNIQUE INDEX IF NOT EXISTS uq_transactions_reverses ON transactions(reverses_txn_id) WHERE reverses_txn_id IS NOT NULL;
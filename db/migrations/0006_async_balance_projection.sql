@@ -0,0 +1,7 @@
+-- Deferred balance projection: postings written with projected_at NULL are
+-- folded into balances by a background projector (BALANCE_PROJECTION=async).
+-- Existing rows and synchronous writers get now() via the default.
+
+ALTER TABLE postings ADD COLUMN IF NOT EXISTS projected_at TIMESTAMPTZ NULL DEFAULT now();
+
+CREATE INDEX IF NOT EXISTS idx_postings_unprojected ON postings(created_at) WHERE projected_at IS NULL;
@@ -0,0 +1,6 @@
+-- Postgres already raises on bigint overflow during the projection upsert; the
+-- application checks first so clients get a clean 422. This constraint also keeps
+-- i64::MIN out of balances so a balance can always be negated (reversals, reports).
+
+ALTER TABLE balances DROP CONSTRAINT IF EXISTS balances_units_range;
+ALTER TABLE balances ADD CONSTRAINT balances_units_range CHECK (balance_units > -9223372036854775808);
@@ -0,0 +1,4 @@
+-- Per-zone transfer rate limit override (transfers/second). NULL uses the
+-- service default (ZONE_RATE_LIMIT_PER_SEC); 0 disables limiting for the zone.
+
+ALTER TABLE zones ADD COLUMN IF NOT EXISTS rate_limit_per_sec INTEGER NULL CHECK (rate_limit_per_sec >= 0);
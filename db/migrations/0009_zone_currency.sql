@@ -0,0 +1,4 @@
+-- Optional per-zone currency (ISO 4217 code). When set, transfers whose
+-- metadata carries a different `currency` hint are rejected.
+
+ALTER TABLE zones ADD COLUMN IF NOT EXISTS currency TEXT NULL;
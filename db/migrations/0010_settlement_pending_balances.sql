@@ -0,0 +1,10 @@
+-- Settlement delay: credits land in pending_units and are promoted to
+-- balance_units (available) once the posting is older than the configured delay.
+-- Existing postings are treated as settled.
+
+ALTER TABLE balances ADD COLUMN IF NOT EXISTS pending_units BIGINT NOT NULL DEFAULT 0;
+
+ALTER TABLE postings ADD COLUMN IF NOT EXISTS settled_at TIMESTAMPTZ NULL DEFAULT now();
+
+CREATE INDEX IF NOT EXISTS idx_postings_unsettled
+  ON postings(created_at) WHERE settled_at IS NULL;
@@ -0,0 +1,4 @@
+-- Intentionally empty. This migration used to drop the unique constraint on
+-- transactions.request_id for IDEMPOTENCY_TTL_SECONDS; key reuse is handled
+-- by 0026 without ever running the table without a unique key.
+SELECT 1;
@@ -0,0 +1,8 @@
+-- Per-account counterparty whitelist (default-deny for listed accounts).
+-- An account without a row, or with an empty list, is unrestricted.
+
+CREATE TABLE IF NOT EXISTS account_whitelists (
+  account_id TEXT PRIMARY KEY,
+  counterparties TEXT[] NOT NULL DEFAULT '{}',
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,11 @@
+-- Transfer attempts per zone, bucketed by minute and outcome (posted,
+-- idempotent_replay, spooled, or a rejection reason). Rejected attempts roll
+-- back with their transaction, so they are counted here separately.
+
+CREATE TABLE IF NOT EXISTS zone_transfer_attempts (
+  zone_id TEXT NOT NULL REFERENCES zones(id),
+  bucket TIMESTAMPTZ NOT NULL,
+  outcome TEXT NOT NULL,
+  attempts BIGINT NOT NULL DEFAULT 0,
+  PRIMARY KEY (zone_id, bucket, outcome)
+);
@@ -0,0 +1,5 @@
+-- Synthetic zone that ledger-integrity incidents (balance drift found by the
+-- reconciler) are filed under, since incidents must reference a zone.
+
+INSERT INTO zones(id, name, status) VALUES ('zone-ledger', 'Ledger integrity', 'OK')
+ON CONFLICT (id) DO NOTHING;
@@ -0,0 +1,3 @@
+-- When an incident was resolved; NULL while it is OPEN or ACK.
+
+ALTER TABLE incidents ADD COLUMN IF NOT EXISTS resolved_at TIMESTAMPTZ NULL;
@@ -0,0 +1,17 @@
+-- Currencies with their minor-unit scale: amount_units 1050 is 10.50 USD
+-- (scale 2) but 1050 JPY (scale 0). Transactions record their currency;
+-- an account's currency is fixed by its first transfer that carries one.
+
+CREATE TABLE IF NOT EXISTS currencies (
+  code TEXT PRIMARY KEY CHECK (code ~ '^[A-Z]{3}$'),
+  minor_unit_scale INT NOT NULL CHECK (minor_unit_scale BETWEEN 0 AND 4)
+);
+
+INSERT INTO currencies(code, minor_unit_scale) VALUES
+  ('USD', 2), ('EUR', 2), ('GBP', 2), ('AUD', 2), ('ZAR', 2), ('BRL', 2),
+  ('NGN', 2), ('KES', 2), ('JPY', 0), ('KRW', 0), ('KWD', 3), ('BHD', 3)
+ON CONFLICT (code) DO NOTHING;
+
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS currency TEXT NULL REFERENCES currencies(code);
+ALTER TABLE accounts ADD COLUMN IF NOT EXISTS currency TEXT NULL REFERENCES currencies(code);
+ALTER TABLE spooled_transfers ADD COLUMN IF NOT EXISTS currency TEXT NULL;
@@ -0,0 +1,18 @@
+-- Planned maintenance windows. The maintenance scheduler sets the zone DOWN at
+-- starts_at and back to OK at ends_at, recording started_at/ended_at as it goes.
+-- A window that passed entirely while nothing was running gets ended_at only.
+
+CREATE TABLE IF NOT EXISTS zone_maintenance (
+  id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+  zone_id TEXT NOT NULL REFERENCES zones(id),
+  starts_at TIMESTAMPTZ NOT NULL,
+  ends_at TIMESTAMPTZ NOT NULL,
+  reason TEXT NOT NULL DEFAULT '',
+  created_by TEXT NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  started_at TIMESTAMPTZ NULL,
+  ended_at TIMESTAMPTZ NULL,
+  CONSTRAINT zone_maintenance_ordered CHECK (ends_at > starts_at)
+);
+
+CREATE INDEX IF NOT EXISTS zone_maintenance_open ON zone_maintenance (starts_at) WHERE ended_at IS NULL;
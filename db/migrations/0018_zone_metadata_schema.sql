@@ -0,0 +1,4 @@
+-- Optional JSON Schema that transfer metadata in the zone must satisfy.
+-- NULL accepts any metadata.
+
+ALTER TABLE zones ADD COLUMN IF NOT EXISTS metadata_schema JSONB NULL;
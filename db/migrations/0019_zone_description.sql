@@ -0,0 +1,3 @@
+-- Free-text description shown alongside the zone name; set via PATCH /v1/zones/{zone_id}.
+
+ALTER TABLE zones ADD COLUMN IF NOT EXISTS description TEXT NULL;
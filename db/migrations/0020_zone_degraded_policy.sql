@@ -0,0 +1,4 @@
+-- How a DEGRADED zone sheds load, e.g. {"shed_percent": 50, "seed": "...", "max_amount_units": 100000}.
+-- NULL keeps DEGRADED behaving like OK.
+
+ALTER TABLE zones ADD COLUMN IF NOT EXISTS degraded_policy JSONB NULL;
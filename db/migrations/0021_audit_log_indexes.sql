@@ -0,0 +1,5 @@
+-- GET /v1/audit filters by actor or target and pages newest first.
+
+CREATE INDEX IF NOT EXISTS idx_audit_log_time ON audit_log(created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_audit_log_actor_time ON audit_log(actor, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_audit_log_target_time ON audit_log(target_type, target_id, created_at DESC);
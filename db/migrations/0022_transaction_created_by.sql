@@ -0,0 +1,5 @@
+-- Subject of the bearer token that initiated a transaction, or 'anonymous'.
+-- Spooled transfers keep it until they are replayed.
+
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS created_by TEXT NOT NULL DEFAULT 'anonymous';
+ALTER TABLE spooled_transfers ADD COLUMN IF NOT EXISTS created_by TEXT NOT NULL DEFAULT 'anonymous';
@@ -0,0 +1,20 @@
+-- POST /v1/sim/archive moves old transactions and their postings here. The
+-- archive tables mirror the live ones column for column (archived_at last), so
+-- a column added to transactions or postings must be added here too.
+
+CREATE TABLE IF NOT EXISTS transactions_archive (LIKE transactions INCLUDING DEFAULTS);
+ALTER TABLE transactions_archive ADD COLUMN IF NOT EXISTS archived_at TIMESTAMPTZ NOT NULL DEFAULT now();
+CREATE UNIQUE INDEX IF NOT EXISTS idx_transactions_archive_id ON transactions_archive(id);
+CREATE INDEX IF NOT EXISTS idx_transactions_archive_time ON transactions_archive(created_at);
+
+CREATE TABLE IF NOT EXISTS postings_archive (LIKE postings INCLUDING DEFAULTS);
+ALTER TABLE postings_archive ADD COLUMN IF NOT EXISTS archived_at TIMESTAMPTZ NOT NULL DEFAULT now();
+CREATE UNIQUE INDEX IF NOT EXISTS idx_postings_archive_id ON postings_archive(id);
+CREATE INDEX IF NOT EXISTS idx_postings_archive_txn ON postings_archive(txn_id);
+CREATE INDEX IF NOT EXISTS idx_postings_archive_account ON postings_archive(account_id);
+
+-- Every posting ever made, live or archived; what the reconciler sums balances from.
+CREATE OR REPLACE VIEW ledger_postings AS
+  SELECT id, txn_id, account_id, direction, amount_units, created_at, projected_at, settled_at FROM postings
+  UNION ALL
+  SELECT id, txn_id, account_id, direction, amount_units, created_at, projected_at, settled_at FROM postings_archive;
@@ -0,0 +1,15 @@
+-- Wake outbox delivery as soon as events commit instead of waiting for the next
+-- poll. NOTIFY is delivered at commit, and only once per transaction for equal
+-- payloads, so a statement-level trigger with an empty payload is enough.
+
+CREATE OR REPLACE FUNCTION notify_outbox_new() RETURNS trigger AS $$
+BEGIN
+  PERFORM pg_notify('outbox_new', '');
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS outbox_events_notify ON outbox_events;
+CREATE TRIGGER outbox_events_notify
+  AFTER INSERT ON outbox_events
+  FOR EACH STATEMENT EXECUTE FUNCTION notify_outbox_new();
@@ -0,0 +1,5 @@
+-- GET /v1/transactions pages newest first with id as the tiebreaker
+-- (ORDER BY created_at DESC, id DESC). Both columns descending so the index
+-- serves that order directly; the CSV export's ascending order scans it backwards.
+
+CREATE INDEX IF NOT EXISTS idx_transactions_created_at_id ON transactions(created_at DESC, id DESC);
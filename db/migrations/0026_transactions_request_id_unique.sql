@@ -0,0 +1,24 @@
+-- Idempotency keys expire after IDEMPOTENCY_TTL_SECONDS (Rust sim), so a
+-- request_id may be used again once its window has passed. Each use is kept
+-- as written and numbered by request_epoch (0 for the first); the pair is
+-- unique. The Go service checks for a key and then inserts without the Rust
+-- sim's advisory lock, and its inserts take epoch 0, so the index still stops
+-- two of its requests double-posting.
+--
+-- Databases that ran the earlier 0011 have no unique key and may hold reused
+-- keys: those are numbered oldest first. Everything runs in this migration's
+-- transaction, so the table is never committed without a unique key.
+
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS request_epoch INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE transactions_archive ADD COLUMN IF NOT EXISTS request_epoch INTEGER NOT NULL DEFAULT 0;
+
+UPDATE transactions t
+   SET request_epoch = n.epoch
+  FROM (SELECT id, row_number() OVER (PARTITION BY request_id ORDER BY created_at, id) - 1 AS epoch
+          FROM transactions) n
+ WHERE n.id = t.id AND n.epoch > 0;
+
+ALTER TABLE transactions DROP CONSTRAINT IF EXISTS transactions_request_id_key;
+DROP INDEX IF EXISTS transactions_request_id_key;
+CREATE UNIQUE INDEX IF NOT EXISTS transactions_request_id_epoch_key ON transactions(request_id, request_epoch);
+DROP INDEX IF EXISTS idx_transactions_request_id;
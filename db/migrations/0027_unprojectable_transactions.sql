@@ -0,0 +1,9 @@
+-- Async balance projection: a transaction whose postings would take a balance
+-- outside the storable range is set aside here instead of failing its batch on
+-- every tick. Its postings stay unprojected; the projector skips it.
+
+CREATE TABLE IF NOT EXISTS unprojectable_transactions (
+  txn_id UUID PRIMARY KEY REFERENCES transactions(id) ON DELETE CASCADE,
+  reason TEXT NOT NULL,
+  detected_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
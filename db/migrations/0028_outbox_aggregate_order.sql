@@ -0,0 +1,7 @@
+-- Outbox delivery keeps each aggregate's events in order: a row is only due once
+-- every older undelivered row of the same aggregate has gone out. This index
+-- serves that "older undelivered sibling" lookup.
+
+CREATE INDEX IF NOT EXISTS idx_outbox_undelivered_aggregate
+  ON outbox_events(aggregate_type, aggregate_id, created_at, id)
+  WHERE delivered_at IS NULL;
@@ -0,0 +1,8 @@
+-- Zones that exist only so ledger-wide records (reconciler and invariant
+-- incidents, audit entries, the adjustment clearing account) have a zone to
+-- reference. They are not listed and take no transfers, status changes or
+-- maintenance windows.
+
+ALTER TABLE zones ADD COLUMN IF NOT EXISTS internal BOOLEAN NOT NULL DEFAULT false;
+
+UPDATE zones SET internal = true WHERE id = 'zone-ledger';
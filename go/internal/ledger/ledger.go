@@ -56,7 +56,7 @@ func IsZoneDown(err error) bool { return errors.Is(err, ErrZoneDown) }
 func IsZoneBlocked(err error) bool { return errors.Is(err, ErrZoneBlocked) }
 
 func (l *Ledger) ListZones(ctx context.Context) ([]Zone, error) {
-  rows, err := l.db.Query(ctx, `SELECT id,name,status,updated_at FROM zones ORDER BY id`)
+  rows, err := l.db.Query(ctx, `SELECT id,name,status,updated_at FROM zones WHERE NOT internal ORDER BY id`)
   if err != nil { return nil, err }
   defer rows.Close()
   out := []Zone{}
@@ -70,7 +70,7 @@ func (l *Ledger) ListZones(ctx context.Context) ([]Zone, error) {
 
 func (l *Ledger) getZoneStatusTx(ctx context.Context, tx pgx.Tx, zoneID string) (string, error) {
   var status string
-  err := tx.QueryRow(ctx, `SELECT status FROM zones WHERE id=$1`, zoneID).Scan(&status)
+  err := tx.QueryRow(ctx, `SELECT status FROM zones WHERE id=$1 AND NOT internal`, zoneID).Scan(&status)
   if err != nil { return "", err }
   return status, nil
 }
@@ -120,7 +120,7 @@ func (l *Ledger) CreateTransfer(ctx context.Context, in CreateTransferInput) (*T
   var existingID string
   var existingHash string
   var createdAt time.Time
-  err = tx.QueryRow(ctx, `SELECT id::text,payload_hash,created_at FROM transactions WHERE request_id=$1`, in.RequestID).
+  err = tx.QueryRow(ctx, `SELECT id::text,payload_hash,created_at FROM transactions WHERE request_id=$1 ORDER BY request_epoch DESC LIMIT 1`, in.RequestID).
     Scan(&existingID, &existingHash, &createdAt)
   if err == nil {
     if existingHash != in.PayloadHash {
@@ -185,7 +185,7 @@ func (l *Ledger) SetZoneStatus(ctx context.Context, zoneID, status, actor, reaso
 
   var z Zone
   err = tx.QueryRow(ctx, `
-    UPDATE zones SET status=$2, updated_at=now() WHERE id=$1
+    UPDATE zones SET status=$2, updated_at=now() WHERE id=$1 AND NOT internal
     RETURNING id,name,status,updated_at
   `, zoneID, status).Scan(&z.ID, &z.Name, &z.Status, &z.UpdatedAt)
   if err != nil { return nil, err }
@@ -822,7 +822,7 @@ func (l *Ledger) ApplyTransferBypass(ctx context.Context, in CreateTransferInput
   var existingID string
   var existingHash string
   var createdAt time.Time
-  err = tx.QueryRow(ctx, `SELECT id::text,payload_hash,created_at FROM transactions WHERE request_id=$1`, in.RequestID).
+  err = tx.QueryRow(ctx, `SELECT id::text,payload_hash,created_at FROM transactions WHERE request_id=$1 ORDER BY request_epoch DESC LIMIT 1`, in.RequestID).
     Scan(&existingID, &existingHash, &createdAt)
   if err == nil {
     if existingHash != in.PayloadHash {
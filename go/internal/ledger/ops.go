@@ -111,7 +111,7 @@ func (l *Ledger) ReplaySpool(ctx context.Context, zoneID string, limit int, acto
   if limit <= 0 || limit > 500 { limit = 50 }
   // Do not replay if zone is still blocked/down.
   var status string
-  err := l.db.QueryRow(ctx, `SELECT status FROM zones WHERE id=$1`, zoneID).Scan(&status)
+  err := l.db.QueryRow(ctx, `SELECT status FROM zones WHERE id=$1 AND NOT internal`, zoneID).Scan(&status)
   if err != nil { return nil, err }
   c, err := l.GetZoneControls(ctx, zoneID)
   if err != nil { return nil, err }
@@ -0,0 +1,275 @@
+use axum::{middleware, routing::{get, patch, post}, Router};
+use std::sync::Arc;
+use tracing::info;
+
+use crate::clock::SystemClock;
+use crate::config::Config;
+use crate::db;
+use crate::handlers::{
+    accounts, adjustments, admin, anomalies, archive, audit, balances, controls, events, explain, incidents, openapi, spool, splits, success_rate,
+    topology, transactions, transfers, whitelists, zones,
+};
+use crate::jwt::{require_jwt, JwtVerifier};
+use crate::middleware::{access_log, cors, request_id};
+use crate::state::{init_metrics, AppState};
+
+/// Pools, metrics and shared state for `config`. Connections are opened lazily,
+/// so this succeeds without a reachable database. Background workers are
+/// started by the caller.
+pub async fn build_state(config: Config) -> anyhow::Result<AppState> {
+    let (registry, metrics) = init_metrics().map_err(|e| anyhow::anyhow!("metrics: {e}"))?;
+    let pool = db::build_pool(&config.database_url, &config.pool).map_err(|e| anyhow::anyhow!("DATABASE_URL: {e}"))?;
+    if config.database_replica_url.is_some() {
+        info!("DATABASE_REPLICA_URL set, routing read-only handlers to the replica");
+    }
+    let db_read = db::read_pool(&pool, config.database_replica_url.as_deref(), &config.pool)
+        .map_err(|e| anyhow::anyhow!("DATABASE_REPLICA_URL: {e}"))?;
+
+    Ok(AppState {
+        db: pool,
+        db_read,
+        admin_keys: config.admin_keys,
+        api_tokens: config.api_tokens,
+        metrics_require_admin: config.metrics_require_admin,
+        registry,
+        metrics,
+        balance_projection: config.balance_projection,
+        zone_rate_limit: config.zone_rate_limit,
+        zone_buckets: Default::default(),
+        settlement_delay: config.settlement_delay,
+        reject_overdrafts: config.reject_overdrafts,
+        idempotency_ttl: config.idempotency_ttl,
+        transfer_batch_max: config.transfer_batch_max,
+        transfer_limits: config.transfer_limits,
+        idempotency_hash_exclude: config.idempotency_hash_exclude,
+        transfer_batcher: None,
+        audit_tx: tokio::sync::broadcast::channel(256).0,
+        events_tx: tokio::sync::broadcast::channel(1024).0,
+        admin_ops: Arc::new(tokio::sync::Semaphore::new(1)),
+        cors: Arc::new(config.cors),
+        jwt: config.jwt.as_ref().map(JwtVerifier::new).transpose().map_err(|e| anyhow::anyhow!(e))?.map(Arc::new),
+        clock: Arc::new(SystemClock),
+        zone_transitions: config.zone_transitions,
+        shutdown: Default::default(),
+    })
+}
+
+/// Every route, wrapped in the CORS, access log and request id layers.
+/// Every write that moves money or changes a zone, and the sim writes,
+/// additionally need a JWT when one is configured. A route added here must also be listed
+/// in [`openapi::ApiDoc`].
+pub fn build_app(state: AppState) -> Router {
+    let jwt = middleware::from_fn_with_state(state.jwt.clone(), require_jwt);
+    Router::new()
+        .route("/healthz", get(admin::healthz))
+        .route("/readyz", get(admin::readyz))
+        .route("/metrics", get(admin::metrics))
+        .route("/v1/version", get(admin::version))
+        .route("/v1/openapi.json", get(openapi::openapi_json))
+        .route("/v1/zones", get(zones::list_zones))
+        .route("/v1/topology", get(topology::get_topology))
+        .route("/v1/transfers", post(transfers::create_transfer).route_layer(jwt.clone()))
+        .route("/v1/transfers/batch", post(transfers::create_transfer_batch).route_layer(jwt.clone()))
+        .route("/v1/transfers/explain", post(explain::explain_transfer))
+        .route("/v1/balances", get(balances::list_balances))
+        .route("/v1/balance-summary", get(balances::balance_summary))
+        .route("/v1/accounts/{account_id}/statement", get(accounts::account_statement))
+        .route("/v1/accounts/{account_id}/balance", get(accounts::account_balance))
+        .route("/v1/accounts/{account_id}/balance-proof", get(accounts::balance_proof))
+        .route("/v1/accounts/{account_id}/turnover", get(accounts::account_turnover))
+        .route("/v1/accounts/{account_id}/adjust", post(adjustments::adjust_balance).route_layer(jwt.clone()))
+        .route(
+            "/v1/accounts/{account_id}/whitelist",
+            get(whitelists::get_whitelist).put(whitelists::set_whitelist).delete(whitelists::clear_whitelist),
+        )
+        .route("/v1/transactions", get(transactions::list_transactions).merge(post(splits::create_transaction).route_layer(jwt.clone())))
+        .route("/v1/transactions.csv", get(transactions::export_transactions_csv))
+        .route("/v1/transactions/{transaction_id}", get(transactions::get_transaction))
+        .route("/v1/transactions/{transaction_id}/reverse", post(transfers::reverse_transaction).route_layer(jwt.clone()))
+        .route("/v1/zones/status", post(zones::set_zones_status).route_layer(jwt.clone()))
+        .route("/v1/zones/{zone_id}", get(zones::get_zone).merge(patch(zones::update_zone).route_layer(jwt.clone())))
+        .route("/v1/zones/{zone_id}/status", get(zones::get_zone_status).merge(post(zones::set_zone_status).route_layer(jwt.clone())))
+        .route("/v1/zones/{zone_id}/ws", get(zones::zone_status_ws))
+        .route("/v1/zones/{zone_id}/maintenance", post(zones::schedule_maintenance).route_layer(jwt.clone()))
+        .route("/v1/zones/{zone_id}/balance-summary", get(balances::zone_balance_summary))
+        .route("/v1/zones/{zone_id}/transactions", get(transactions::list_zone_transactions))
+        .route("/v1/zones/{zone_id}/success-rate", get(success_rate::zone_success_rate))
+        .route("/v1/zones/{zone_id}/incidents", get(incidents::list_incidents_by_zone))
+        .route("/v1/incidents", get(incidents::list_recent_incidents))
+        .route("/v1/incidents/{incident_id}", get(incidents::get_incident))
+        .route("/v1/incidents/{incident_id}/action", post(incidents::apply_incident_action))
+        .route("/v1/incidents/{incident_id}/acknowledge", post(incidents::acknowledge_incident))
+        .route("/v1/incidents/{incident_id}/resolve", post(incidents::resolve_incident))
+        .route("/v1/zones/{zone_id}/controls", get(controls::get_zone_controls).merge(post(controls::set_zone_controls).route_layer(jwt.clone())))
+        .route("/v1/zones/{zone_id}/spool", get(spool::get_spool_stats))
+        .route("/v1/zones/{zone_id}/spool/replay", post(spool::replay_spool).route_layer(jwt.clone()))
+        .route("/v1/zones/{zone_id}/audit", get(audit::list_audit))
+        .route("/v1/audit", get(audit::search_audit))
+        .route("/v1/audit/stream", get(audit::stream_audit))
+        .route("/v1/events/stream", get(events::stream_events))
+        .route("/v1/sim/snapshot", post(admin::snapshot).route_layer(jwt.clone()))
+        .route("/v1/sim/restore", post(admin::restore).route_layer(jwt.clone()))
+        .route("/v1/sim/export", get(admin::export))
+        .route("/v1/sim/checkpoint", post(admin::checkpoint).route_layer(jwt.clone()))
+        .route("/v1/sim/archive", post(archive::archive).route_layer(jwt.clone()))
+        .route("/v1/sim/reconcile", post(admin::reconcile).route_layer(jwt))
+        .route("/v1/sim/anomalies", get(anomalies::list_anomalies))
+        .route("/v1/sim/balance-merkle", get(admin::balance_merkle))
+        .layer(middleware::from_fn_with_state(state.cors.clone(), cors))
+        .layer(middleware::from_fn(access_log))
+        .layer(middleware::from_fn(request_id))
+        .with_state(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use http_body_util::BodyExt;
+    use std::net::TcpListener;
+    use tower::ServiceExt;
+
+    /// App against a database that accepts connections but never answers:
+    /// routing, extraction and middleware run for real, and anything that
+    /// needs the pool times out as a 503. Keep the listener alive for the test.
+    async fn app() -> (Router, TcpListener) {
+        app_with(|_| {}).await
+    }
+
+    async fn app_with(configure: impl FnOnce(&mut Config)) -> (Router, TcpListener) {
+        let silent = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut config = Config::new(format!("postgres://ledger@127.0.0.1:{}/ledger", silent.local_addr().unwrap().port()));
+        config.pool.acquire_timeout = Some(std::time::Duration::from_millis(200));
+        configure(&mut config);
+        (build_app(build_state(config).await.unwrap()), silent)
+    }
+
+    fn transfer(body: &str) -> Request<Body> {
+        Request::post("/v1/transfers")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn healthz_through_the_router() {
+        let (app, _db) = app().await;
+        let res = app
+            .oneshot(Request::get("/healthz").header("x-request-id", "req-42").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()["x-request-id"], "req-42");
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], b"ok");
+    }
+
+    #[tokio::test]
+    async fn transfer_through_the_router() {
+        let (app, _db) = app().await;
+        let res = app
+            .clone()
+            .oneshot(transfer(
+                r#"{"request_id":"req-1","from_account":"acct-a","to_account":"acct-b","amount_units":100,"zone_id":"zone-eu"}"#,
+            ))
+            .await
+            .unwrap();
+        // a well-formed transfer reaches the pool, which sheds it
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body: serde_json::Value =
+            serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        assert_eq!(body["code"], "unavailable");
+
+        // a body missing required fields never gets that far
+        let res = app.clone().oneshot(transfer(r#"{"request_id":"req-2"}"#)).await.unwrap();
+        assert!(res.status().is_client_error());
+
+        // nor does one with a misspelt field
+        let res = app
+            .oneshot(transfer(
+                r#"{"request_id":"req-3","from_account":"acct-a","to_account":"acct-b","amount_unit":100,"zone_id":"zone-eu"}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value =
+            serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        assert_eq!(body["code"], "bad_request");
+        assert!(body["error"].as_str().unwrap().contains("unknown field `amount_unit`"), "{body}");
+    }
+
+    #[tokio::test]
+    async fn unknown_route_is_404() {
+        let (app, _db) = app().await;
+        let res = app.oneshot(Request::get("/v1/nope").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn money_and_zone_writes_need_the_jwt() {
+        use crate::jwt::tests::{token, valid_claims, verifier_config};
+        let (app, _db) = app_with(|c| c.jwt = Some(verifier_config())).await;
+        let post = |uri: &str, bearer: Option<&str>, body: &str| {
+            let mut req = Request::post(uri).header("content-type", "application/json");
+            if let Some(t) = bearer {
+                req = req.header("authorization", format!("Bearer {t}"));
+            }
+            req.body(Body::from(body.to_string())).unwrap()
+        };
+        let guarded = [
+            ("POST", "/v1/transfers"),
+            ("POST", "/v1/transfers/batch"),
+            ("POST", "/v1/transactions"),
+            ("POST", "/v1/transactions/txn-1/reverse"),
+            ("POST", "/v1/accounts/acct-a/adjust"),
+            ("PATCH", "/v1/zones/zone-eu"),
+            ("POST", "/v1/zones/zone-eu/status"),
+            ("POST", "/v1/zones/status"),
+            ("POST", "/v1/zones/zone-eu/maintenance"),
+            ("POST", "/v1/zones/zone-eu/controls"),
+            ("POST", "/v1/zones/zone-eu/spool/replay"),
+        ];
+        for (method, uri) in guarded {
+            let req = Request::builder().method(method).uri(uri).header("content-type", "application/json").body(Body::from("{}")).unwrap();
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED, "{method} {uri}");
+        }
+        // reads sharing a path with a guarded write stay open
+        for uri in ["/v1/transactions", "/v1/zones/zone-eu", "/v1/zones/zone-eu/status", "/v1/zones/zone-eu/controls"] {
+            let res = app.clone().oneshot(Request::get(uri).body(Body::empty()).unwrap()).await.unwrap();
+            assert_ne!(res.status(), StatusCode::UNAUTHORIZED, "GET {uri}");
+        }
+
+        // the verified token is the principal, not checked against API_TOKENS
+        let jwt = token(valid_claims());
+        let res = app.clone().oneshot(post("/v1/transfers/batch", Some(&jwt), r#"{"transfers":[]}"#)).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "reaches the handler");
+        let res = app.oneshot(post("/v1/transactions", Some(&jwt), r#"{"request_id":"req-1"}"#)).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "reaches the handler");
+    }
+
+    #[tokio::test]
+    async fn open_event_stream_does_not_hold_up_shutdown() {
+        let silent = TcpListener::bind("127.0.0.1:0").unwrap();
+        let st = build_state(Config::new(format!("postgres://ledger@127.0.0.1:{}/ledger", silent.local_addr().unwrap().port())))
+            .await
+            .unwrap();
+        let streams = st.shutdown.clone();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let stop = tokio_util::sync::CancellationToken::new();
+        let signal = stop.clone();
+        let server = tokio::spawn(crate::shutdown::serve_until(listener, build_app(st), async move {
+            signal.cancelled().await;
+            streams.cancel();
+        }));
+
+        let res = reqwest::get(format!("http://{addr}/v1/events/stream")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        stop.cancel();
+        let body = tokio::time::timeout(std::time::Duration::from_secs(5), res.text()).await.unwrap();
+        assert!(body.is_ok(), "stream ends cleanly: {body:?}");
+        tokio::time::timeout(std::time::Duration::from_secs(5), server).await.unwrap().unwrap().unwrap();
+    }
+}
@@ -0,0 +1,68 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use time::OffsetDateTime;
+
+/// Source of the current time wherever the app, rather than the database,
+/// decides what "now" is: idempotency windows, default `as_of` values,
+/// maintenance boundaries. Timestamps Postgres writes with `now()` do not go
+/// through it.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> OffsetDateTime;
+}
+
+/// Wall-clock time, UTC.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> OffsetDateTime {
+        OffsetDateTime::now_utc()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> OffsetDateTime {
+        (**self).now()
+    }
+}
+
+/// A clock that only moves when told to, so tests can step across TTLs and
+/// window boundaries instead of sleeping through them. Clones share the time.
+#[derive(Clone)]
+pub struct FixedClock(Arc<Mutex<OffsetDateTime>>);
+
+impl FixedClock {
+    pub fn at(t: OffsetDateTime) -> Self {
+        Self(Arc::new(Mutex::new(t)))
+    }
+
+    pub fn set(&self, t: OffsetDateTime) {
+        *self.0.lock().unwrap() = t;
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.0.lock().unwrap() += by;
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> OffsetDateTime {
+        *self.0.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_clock_moves_only_when_told_and_clones_share_it() {
+        let t = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let clock = FixedClock::at(t);
+        let shared: Arc<dyn Clock> = Arc::new(clock.clone());
+        assert_eq!(shared.now(), t);
+        clock.advance(Duration::from_secs(90));
+        assert_eq!(shared.now(), t + Duration::from_secs(90));
+        clock.set(t);
+        assert_eq!(shared.now(), t);
+    }
+}
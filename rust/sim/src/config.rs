@@ -0,0 +1,433 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use crate::auth::{parse_api_tokens, ApiToken};
+use crate::db::PoolSettings;
+use crate::handlers::admin::parse_admin_keys;
+use crate::handlers::transfers::TransferLimits;
+use crate::handlers::zones::TransitionPolicy;
+use crate::jwt::JwtConfig;
+use crate::messaging::sink::OutboxSinkKind;
+use crate::middleware::CorsConfig;
+use crate::projection::BalanceProjection;
+use crate::settlement::parse_delay;
+use crate::shutdown::bind_addr;
+
+/// Everything read from the environment, parsed and validated once at startup.
+/// Blank variables count as unset.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub database_url: String,
+    /// Read-only handlers use this replica when set.
+    pub database_replica_url: Option<String>,
+    pub pool: PoolSettings,
+    /// `BIND_ADDR`, or all interfaces on `PORT` (default 8081).
+    pub addr: SocketAddr,
+    pub admin_keys: Vec<String>,
+    pub api_tokens: Vec<ApiToken>,
+    /// Verifies bearer JWTs on the protected routes; None leaves them open.
+    pub jwt: Option<JwtConfig>,
+    /// `/metrics` requires the admin key too; off so Prometheus can scrape on trusted networks.
+    pub metrics_require_admin: bool,
+    pub cors: CorsConfig,
+    pub balance_projection: BalanceProjection,
+    /// Default per-zone transfer rate (per second); 0 disables limiting.
+    pub zone_rate_limit: u32,
+    pub settlement_delay: Option<Duration>,
+    /// `REJECT_OVERDRAFTS`: refuse debits the source's available balance does not
+    /// cover. Defaults to on exactly when `SETTLEMENT_DELAY_SECONDS` is set.
+    pub reject_overdrafts: bool,
+    pub idempotency_ttl: Option<Duration>,
+    pub transfer_batch_max: usize,
+    /// `TRANSFER_MAX_AMOUNT_UNITS` (default unlimited) and `TRANSFER_MAX_METADATA_BYTES` (default 16 KiB).
+    pub transfer_limits: TransferLimits,
+    /// `IDEMPOTENCY_HASH_EXCLUDE`: comma-separated dotted paths inside `metadata`
+    /// left out of the idempotency hash, e.g. `client_ts,gateway.received_at`.
+    pub idempotency_hash_exclude: Vec<String>,
+    /// Micro-batching window; None writes each transfer in its own transaction.
+    pub microbatch_window: Option<Duration>,
+    pub microbatch_max: usize,
+    /// None disables the background reconciler.
+    pub reconcile_interval: Option<Duration>,
+    pub drift_threshold_units: i64,
+    /// None disables the background ledger imbalance check.
+    pub imbalance_check_interval: Option<Duration>,
+    pub nats_url: Option<String>,
+    /// `OUTBOX_SINK`; defaults to `webhook` when `WEBHOOK_URL` is set, else `none`.
+    pub outbox_sink: OutboxSinkKind,
+    pub webhook_url: Option<String>,
+    /// `OUTBOX_LISTEN` (default on): wake outbox delivery on `NOTIFY outbox_new`.
+    pub outbox_listen: bool,
+    /// `OUTBOX_POLL_MS`: delivery poll interval, the fallback when listening.
+    /// Defaults to 5s when listening and 500ms when not.
+    pub outbox_poll_interval: Duration,
+    pub webhook_max_backoff: Duration,
+    pub webhook_signing_secret: Option<String>,
+    /// Bootstrap servers for `OUTBOX_SINK=kafka`.
+    pub kafka_brokers: Option<String>,
+    /// Topic for `OUTBOX_SINK=kafka`; may contain `{event_type}`.
+    pub kafka_topic: Option<String>,
+    /// `ZONE_STATUS_TRANSITIONS`: `permissive` (default) or `strict`.
+    pub zone_transitions: TransitionPolicy,
+}
+
+impl Config {
+    /// Defaults for everything but the database.
+    pub fn new(database_url: impl Into<String>) -> Self {
+        Self {
+            database_url: database_url.into(),
+            database_replica_url: None,
+            pool: PoolSettings::default(),
+            addr: SocketAddr::from(([0, 0, 0, 0], 8081)),
+            admin_keys: Vec::new(),
+            api_tokens: Vec::new(),
+            jwt: None,
+            metrics_require_admin: false,
+            cors: CorsConfig::from_lookup(|_| None),
+            balance_projection: BalanceProjection::Sync,
+            zone_rate_limit: 0,
+            settlement_delay: None,
+            reject_overdrafts: false,
+            idempotency_ttl: None,
+            transfer_batch_max: 1000,
+            transfer_limits: TransferLimits::default(),
+            idempotency_hash_exclude: Vec::new(),
+            microbatch_window: None,
+            microbatch_max: 256,
+            reconcile_interval: None,
+            drift_threshold_units: 0,
+            imbalance_check_interval: None,
+            nats_url: None,
+            outbox_sink: OutboxSinkKind::None,
+            webhook_url: None,
+            outbox_listen: true,
+            outbox_poll_interval: Duration::from_secs(5),
+            webhook_max_backoff: Duration::from_secs(300),
+            webhook_signing_secret: None,
+            kafka_brokers: None,
+            kafka_topic: None,
+            zone_transitions: TransitionPolicy::Permissive,
+        }
+    }
+
+    pub fn from_env() -> Result<Self, String> {
+        Self::from_lookup(|k| std::env::var(k).ok())
+    }
+
+    pub fn from_lookup(var: impl Fn(&str) -> Option<String>) -> Result<Self, String> {
+        let get = |name: &str| var(name).map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+        let num = |name: &str, what: &str| -> Result<Option<u64>, String> {
+            get(name)
+                .map(|v| v.parse::<u64>().map_err(|_| format!("{name} must be {what}, got {v:?}")))
+                .transpose()
+        };
+        let secs = |name: &str| -> Result<Option<Duration>, String> {
+            Ok(num(name, "a non-negative number of seconds")?.filter(|&s| s > 0).map(Duration::from_secs))
+        };
+        let flag = |name: &str| -> Result<bool, String> {
+            match get(name).map(|v| v.to_ascii_lowercase()).as_deref() {
+                None | Some("0" | "false" | "no" | "off") => Ok(false),
+                Some("1" | "true" | "yes" | "on") => Ok(true),
+                Some(v) => Err(format!("{name} must be true or false, got {v:?}")),
+            }
+        };
+
+        let database_url = get("DATABASE_URL").ok_or("DATABASE_URL is required")?;
+        let d = Self::new(database_url);
+        // validated here; CorsConfig reads it leniently
+        flag("CORS_ALLOW_CREDENTIALS")?;
+        let outbox_listen = match get("OUTBOX_LISTEN") {
+            Some(_) => flag("OUTBOX_LISTEN")?,
+            None => d.outbox_listen,
+        };
+        let settlement_delay = match get("SETTLEMENT_DELAY_SECONDS") {
+            Some(v) => parse_delay(&v).map_err(|_| format!("SETTLEMENT_DELAY_SECONDS must be a non-negative integer, got {v:?}"))?,
+            None => d.settlement_delay,
+        };
+        let reject_overdrafts = match get("REJECT_OVERDRAFTS") {
+            Some(_) => flag("REJECT_OVERDRAFTS")?,
+            None => settlement_delay.is_some(),
+        };
+
+        let c = Self {
+            database_replica_url: get("DATABASE_REPLICA_URL"),
+            pool: PoolSettings::from_lookup(&var)?,
+            addr: bind_addr(get("BIND_ADDR").as_deref(), get("PORT").as_deref().unwrap_or("8081"))?,
+            admin_keys: parse_admin_keys(get("ADMIN_KEY").as_deref()),
+            api_tokens: parse_api_tokens(get("API_TOKENS").as_deref())?,
+            jwt: JwtConfig::from_lookup(&var)?,
+            metrics_require_admin: flag("METRICS_REQUIRE_ADMIN")?,
+            cors: CorsConfig::from_lookup(&var),
+            balance_projection: match get("BALANCE_PROJECTION") {
+                Some(v) => BalanceProjection::parse(&v)
+                    .ok_or_else(|| format!("BALANCE_PROJECTION must be sync or async, got {v:?}"))?,
+                None => d.balance_projection,
+            },
+            zone_rate_limit: match num("ZONE_RATE_LIMIT_PER_SEC", "a non-negative integer")? {
+                Some(n) => u32::try_from(n).map_err(|_| "ZONE_RATE_LIMIT_PER_SEC is too large".to_string())?,
+                None => d.zone_rate_limit,
+            },
+            settlement_delay,
+            reject_overdrafts,
+            idempotency_ttl: secs("IDEMPOTENCY_TTL_SECONDS")?,
+            transfer_batch_max: num("TRANSFER_BATCH_MAX", "a positive integer")?.map_or(d.transfer_batch_max, |n| n as usize),
+            transfer_limits: TransferLimits {
+                max_amount_units: match num("TRANSFER_MAX_AMOUNT_UNITS", "a positive integer")? {
+                    Some(n) => i64::try_from(n).map_err(|_| "TRANSFER_MAX_AMOUNT_UNITS is too large".to_string())?,
+                    None => d.transfer_limits.max_amount_units,
+                },
+                max_metadata_bytes: num("TRANSFER_MAX_METADATA_BYTES", "a non-negative integer")?
+                    .map_or(d.transfer_limits.max_metadata_bytes, |n| n as usize),
+            },
+            idempotency_hash_exclude: match get("IDEMPOTENCY_HASH_EXCLUDE") {
+                Some(v) => parse_hash_exclude(&v)?,
+                None => d.idempotency_hash_exclude,
+            },
+            microbatch_window: num("TRANSFER_MICROBATCH_MS", "a non-negative integer")?
+                .filter(|&ms| ms > 0)
+                .map(Duration::from_millis),
+            microbatch_max: num("TRANSFER_MICROBATCH_MAX", "a positive integer")?.map_or(d.microbatch_max, |n| n as usize),
+            reconcile_interval: match num("RECONCILE_INTERVAL_SECONDS", "a non-negative integer")? {
+                Some(s) => (s > 0).then(|| Duration::from_secs(s)),
+                None => d.reconcile_interval,
+            },
+            drift_threshold_units: match get("DRIFT_INCIDENT_THRESHOLD_UNITS") {
+                Some(v) => v
+                    .parse()
+                    .map_err(|_| format!("DRIFT_INCIDENT_THRESHOLD_UNITS must be an integer, got {v:?}"))?,
+                None => d.drift_threshold_units,
+            },
+            imbalance_check_interval: match num("IMBALANCE_CHECK_INTERVAL_SECONDS", "a non-negative integer")? {
+                Some(s) => (s > 0).then(|| Duration::from_secs(s)),
+                None => d.imbalance_check_interval,
+            },
+            nats_url: get("NATS_URL"),
+            outbox_sink: match get("OUTBOX_SINK") {
+                Some(v) => OutboxSinkKind::parse(&v)
+                    .ok_or_else(|| format!("OUTBOX_SINK must be webhook, kafka or none, got {v:?}"))?,
+                None if get("WEBHOOK_URL").is_some() => OutboxSinkKind::Webhook,
+                None => d.outbox_sink,
+            },
+            webhook_url: get("WEBHOOK_URL"),
+            outbox_listen,
+            outbox_poll_interval: match num("OUTBOX_POLL_MS", "a positive number of milliseconds")? {
+                Some(0) => return Err("OUTBOX_POLL_MS must be at least 1".into()),
+                Some(ms) => Duration::from_millis(ms),
+                None if outbox_listen => d.outbox_poll_interval,
+                None => Duration::from_millis(500),
+            },
+            webhook_max_backoff: num("WEBHOOK_MAX_BACKOFF_SECS", "a non-negative integer")?
+                .map_or(d.webhook_max_backoff, Duration::from_secs),
+            webhook_signing_secret: get("WEBHOOK_SIGNING_SECRET"),
+            kafka_brokers: get("KAFKA_BROKERS"),
+            kafka_topic: get("KAFKA_TOPIC"),
+            zone_transitions: match get("ZONE_STATUS_TRANSITIONS") {
+                Some(v) => TransitionPolicy::parse(&v)
+                    .ok_or_else(|| format!("ZONE_STATUS_TRANSITIONS must be strict or permissive, got {v:?}"))?,
+                None => d.zone_transitions,
+            },
+            ..d
+        };
+
+        if c.transfer_batch_max == 0 {
+            return Err("TRANSFER_BATCH_MAX must be at least 1".into());
+        }
+        if c.transfer_limits.max_amount_units == 0 {
+            return Err("TRANSFER_MAX_AMOUNT_UNITS must be at least 1".into());
+        }
+        if c.microbatch_max == 0 {
+            return Err("TRANSFER_MICROBATCH_MAX must be at least 1".into());
+        }
+        if c.outbox_sink == OutboxSinkKind::Webhook && c.webhook_url.is_none() {
+            return Err("OUTBOX_SINK=webhook requires WEBHOOK_URL".into());
+        }
+        if c.outbox_sink == OutboxSinkKind::Kafka {
+            if !cfg!(feature = "kafka") {
+                return Err("OUTBOX_SINK=kafka needs a build with the kafka feature".into());
+            }
+            if c.kafka_brokers.is_none() || c.kafka_topic.is_none() {
+                return Err("OUTBOX_SINK=kafka requires KAFKA_BROKERS and KAFKA_TOPIC".into());
+            }
+        }
+        // the async projector would book deferred credits straight into available balance
+        if c.settlement_delay.is_some() && c.balance_projection != BalanceProjection::Sync {
+            return Err("SETTLEMENT_DELAY_SECONDS requires BALANCE_PROJECTION=sync".into());
+        }
+        // the check reads balances the async projector has not caught up with
+        if c.reject_overdrafts && c.balance_projection != BalanceProjection::Sync {
+            return Err("REJECT_OVERDRAFTS requires BALANCE_PROJECTION=sync".into());
+        }
+        Ok(c)
+    }
+}
+
+/// Metadata paths from `IDEMPOTENCY_HASH_EXCLUDE`; blank entries are skipped,
+/// a path with an empty segment (`a..b`, `.a`) is an error.
+fn parse_hash_exclude(v: &str) -> Result<Vec<String>, String> {
+    v.split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(|p| match p.split('.').any(str::is_empty) {
+            true => Err(format!("IDEMPOTENCY_HASH_EXCLUDE has an empty path segment in {p:?}")),
+            false => Ok(p.to_string()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(vars: &[(&str, &str)]) -> Result<Config, String> {
+        Config::from_lookup(|k| vars.iter().find(|(n, _)| *n == k).map(|(_, v)| v.to_string()))
+    }
+
+    const DB: (&str, &str) = ("DATABASE_URL", "postgres://ledger@db/ledger");
+
+    #[test]
+    fn database_url_is_required() {
+        assert_eq!(config(&[]).unwrap_err(), "DATABASE_URL is required");
+        assert_eq!(config(&[("DATABASE_URL", "  ")]).unwrap_err(), "DATABASE_URL is required");
+    }
+
+    #[test]
+    fn defaults_when_unset() {
+        let c = config(&[DB]).unwrap();
+        assert_eq!(c.database_url, "postgres://ledger@db/ledger");
+        assert_eq!(c.addr, "0.0.0.0:8081".parse().unwrap());
+        assert_eq!(c.pool, PoolSettings::default());
+        assert_eq!(c.balance_projection, BalanceProjection::Sync);
+        assert_eq!(c.zone_transitions, TransitionPolicy::Permissive);
+        assert_eq!(c.transfer_batch_max, 1000);
+        assert_eq!(c.transfer_limits, TransferLimits::default());
+        assert!(c.reconcile_interval.is_none() && c.imbalance_check_interval.is_none(), "background checks are opt-in");
+        assert!(c.outbox_listen);
+        assert_eq!(c.outbox_poll_interval, Duration::from_secs(5));
+        assert_eq!(c.webhook_max_backoff, Duration::from_secs(300));
+        assert!(c.admin_keys.is_empty() && c.api_tokens.is_empty());
+        assert!(!c.metrics_require_admin);
+        assert!(c.database_replica_url.is_none() && c.nats_url.is_none() && c.webhook_url.is_none());
+        assert_eq!(c.outbox_sink, OutboxSinkKind::None);
+        assert!(c.settlement_delay.is_none() && c.idempotency_ttl.is_none() && c.microbatch_window.is_none());
+    }
+
+    #[test]
+    fn typed_values_parse() {
+        let c = config(&[
+            DB,
+            ("PORT", "9000"),
+            ("ADMIN_KEY", "a,b"),
+            ("IDEMPOTENCY_TTL_SECONDS", "3600"),
+            ("RECONCILE_INTERVAL_SECONDS", "60"),
+            ("IMBALANCE_CHECK_INTERVAL_SECONDS", "5"),
+            ("TRANSFER_MICROBATCH_MS", "5"),
+            ("DATABASE_REPLICA_URL", ""),
+            ("METRICS_REQUIRE_ADMIN", "true"),
+            ("TRANSFER_MAX_AMOUNT_UNITS", "1000000"),
+            ("TRANSFER_MAX_METADATA_BYTES", "512"),
+            ("ZONE_STATUS_TRANSITIONS", " Strict "),
+        ])
+        .unwrap();
+        assert_eq!(c.addr.port(), 9000);
+        assert_eq!(c.admin_keys, vec!["a", "b"]);
+        assert_eq!(c.idempotency_ttl, Some(Duration::from_secs(3600)));
+        assert_eq!(c.reconcile_interval, Some(Duration::from_secs(60)));
+        assert_eq!(c.imbalance_check_interval, Some(Duration::from_secs(5)));
+        assert_eq!(c.microbatch_window, Some(Duration::from_millis(5)));
+        assert!(c.database_replica_url.is_none());
+        assert!(c.metrics_require_admin);
+        assert_eq!(c.transfer_limits, TransferLimits { max_amount_units: 1_000_000, max_metadata_bytes: 512 });
+        assert_eq!(c.zone_transitions, TransitionPolicy::Strict);
+    }
+
+    #[test]
+    fn outbox_polling_follows_listen() {
+        let c = config(&[DB, ("OUTBOX_LISTEN", "false")]).unwrap();
+        assert!(!c.outbox_listen);
+        assert_eq!(c.outbox_poll_interval, Duration::from_millis(500));
+        let c = config(&[DB, ("OUTBOX_POLL_MS", "250")]).unwrap();
+        assert!(c.outbox_listen);
+        assert_eq!(c.outbox_poll_interval, Duration::from_millis(250));
+        assert!(config(&[DB, ("OUTBOX_POLL_MS", "0")]).is_err());
+        assert!(config(&[DB, ("OUTBOX_LISTEN", "maybe")]).is_err());
+    }
+
+    #[test]
+    fn api_tokens_parse() {
+        let c = config(&[DB, ("API_TOKENS", "payments-svc:tok-1")]).unwrap();
+        assert_eq!(c.api_tokens[0].subject, "payments-svc");
+        let err = config(&[DB, ("API_TOKENS", "payments-svc")]).unwrap_err();
+        assert!(err.contains("API_TOKENS"), "{err}");
+    }
+
+    #[test]
+    fn hash_exclude_paths_parse() {
+        assert!(config(&[DB]).unwrap().idempotency_hash_exclude.is_empty());
+        let c = config(&[DB, ("IDEMPOTENCY_HASH_EXCLUDE", " client_ts, gateway.received_at ,")]).unwrap();
+        assert_eq!(c.idempotency_hash_exclude, vec!["client_ts", "gateway.received_at"]);
+        let err = config(&[DB, ("IDEMPOTENCY_HASH_EXCLUDE", "gateway..received_at")]).unwrap_err();
+        assert!(err.contains("IDEMPOTENCY_HASH_EXCLUDE"), "{err}");
+    }
+
+    #[test]
+    fn bad_values_name_the_variable() {
+        for (name, value) in [
+            ("PORT", "eighty"),
+            ("ZONE_RATE_LIMIT_PER_SEC", "-1"),
+            ("BALANCE_PROJECTION", "lazy"),
+            ("WEBHOOK_MAX_BACKOFF_SECS", "5m"),
+            ("CORS_ALLOW_CREDENTIALS", "maybe"),
+            ("METRICS_REQUIRE_ADMIN", "sometimes"),
+            ("OUTBOX_SINK", "carrier-pigeon"),
+            ("TRANSFER_BATCH_MAX", "0"),
+            ("TRANSFER_MAX_AMOUNT_UNITS", "0"),
+            ("TRANSFER_MAX_AMOUNT_UNITS", "9223372036854775808"),
+            ("TRANSFER_MAX_METADATA_BYTES", "16k"),
+            ("ZONE_STATUS_TRANSITIONS", "lenient"),
+        ] {
+            let err = config(&[DB, (name, value)]).unwrap_err();
+            assert!(err.contains(name), "{name}: {err}");
+        }
+        let err = config(&[DB, ("SETTLEMENT_DELAY_SECONDS", "30"), ("BALANCE_PROJECTION", "async")]).unwrap_err();
+        assert!(err.contains("requires BALANCE_PROJECTION=sync"));
+        let err = config(&[DB, ("REJECT_OVERDRAFTS", "on"), ("BALANCE_PROJECTION", "async")]).unwrap_err();
+        assert!(err.contains("REJECT_OVERDRAFTS requires BALANCE_PROJECTION=sync"), "{err}");
+    }
+
+    #[test]
+    fn overdraft_check_defaults_to_settlement_but_has_its_own_flag() {
+        let settle = ("SETTLEMENT_DELAY_SECONDS", "30");
+        assert!(!config(&[DB]).unwrap().reject_overdrafts);
+        assert!(config(&[DB, settle]).unwrap().reject_overdrafts);
+        assert!(!config(&[DB, settle, ("REJECT_OVERDRAFTS", "false")]).unwrap().reject_overdrafts);
+        let c = config(&[DB, ("REJECT_OVERDRAFTS", "true")]).unwrap();
+        assert!(c.reject_overdrafts && c.settlement_delay.is_none());
+        assert!(config(&[DB, ("REJECT_OVERDRAFTS", "maybe")]).unwrap_err().contains("REJECT_OVERDRAFTS"));
+    }
+
+    #[test]
+    fn outbox_sink_follows_webhook_url() {
+        let hook = ("WEBHOOK_URL", "https://hooks.example/ledger");
+        assert_eq!(config(&[DB, hook]).unwrap().outbox_sink, OutboxSinkKind::Webhook);
+        assert_eq!(config(&[DB, hook, ("OUTBOX_SINK", "none")]).unwrap().outbox_sink, OutboxSinkKind::None);
+        assert_eq!(config(&[DB, hook, ("OUTBOX_SINK", "Webhook")]).unwrap().outbox_sink, OutboxSinkKind::Webhook);
+        let err = config(&[DB, ("OUTBOX_SINK", "webhook")]).unwrap_err();
+        assert!(err.contains("requires WEBHOOK_URL"), "{err}");
+    }
+
+    #[test]
+    fn kafka_sink_needs_brokers_and_topic() {
+        let kafka = ("OUTBOX_SINK", "kafka");
+        let brokers = ("KAFKA_BROKERS", "kafka-1:9092,kafka-2:9092");
+        let topic = ("KAFKA_TOPIC", "ledger.{event_type}");
+        if cfg!(feature = "kafka") {
+            let c = config(&[DB, kafka, brokers, topic]).unwrap();
+            assert_eq!(c.outbox_sink, OutboxSinkKind::Kafka);
+            assert_eq!(c.kafka_brokers.as_deref(), Some("kafka-1:9092,kafka-2:9092"));
+            assert!(config(&[DB, kafka, brokers]).unwrap_err().contains("KAFKA_TOPIC"));
+        } else {
+            let err = config(&[DB, kafka, brokers, topic]).unwrap_err();
+            assert!(err.contains("kafka feature"), "{err}");
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use deadpool_postgres::{Manager, Pool, Runtime};
+use std::time::Duration;
+use tokio_postgres::NoTls;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+/// Pool sizing and timeouts, read from `DB_MAX_CONNECTIONS`, `DB_MIN_CONNECTIONS`,
+/// `DB_ACQUIRE_TIMEOUT_MS` and `DB_IDLE_TIMEOUT_MS`. A timeout of `0` disables it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PoolSettings {
+    pub max_size: usize,
+    /// Idle connections kept open by [`IdleReaper`].
+    pub min_idle: usize,
+    /// Bounds waiting for a free slot and opening a new connection; exceeding it is a 503.
+    pub acquire_timeout: Option<Duration>,
+    /// Idle connections unused for this long are closed (down to `min_idle`).
+    pub idle_timeout: Option<Duration>,
+}
+
+impl Default for PoolSettings {
+    fn default() -> Self {
+        Self {
+            max_size: 16,
+            min_idle: 0,
+            acquire_timeout: Some(Duration::from_secs(5)),
+            idle_timeout: Some(Duration::from_secs(600)),
+        }
+    }
+}
+
+impl PoolSettings {
+    pub fn from_env() -> Result<Self, String> {
+        Self::from_lookup(|k| std::env::var(k).ok())
+    }
+
+    pub fn from_lookup(var: impl Fn(&str) -> Option<String>) -> Result<Self, String> {
+        let num = |name: &str| -> Result<Option<u64>, String> {
+            var(name)
+                .map(|v| v.trim().parse::<u64>().map_err(|_| format!("{name} must be a non-negative integer")))
+                .transpose()
+        };
+        let millis = |name: &str, default: Option<Duration>| -> Result<Option<Duration>, String> {
+            Ok(match num(name)? {
+                Some(0) => None,
+                Some(ms) => Some(Duration::from_millis(ms)),
+                None => default,
+            })
+        };
+        let d = Self::default();
+        let s = Self {
+            max_size: num("DB_MAX_CONNECTIONS")?.map_or(d.max_size, |n| n as usize),
+            min_idle: num("DB_MIN_CONNECTIONS")?.map_or(d.min_idle, |n| n as usize),
+            acquire_timeout: millis("DB_ACQUIRE_TIMEOUT_MS", d.acquire_timeout)?,
+            idle_timeout: millis("DB_IDLE_TIMEOUT_MS", d.idle_timeout)?,
+        };
+        if s.max_size == 0 {
+            return Err("DB_MAX_CONNECTIONS must be at least 1".into());
+        }
+        if s.min_idle > s.max_size {
+            return Err("DB_MIN_CONNECTIONS must not exceed DB_MAX_CONNECTIONS".into());
+        }
+        Ok(s)
+    }
+}
+
+/// Build a connection pool for `url`. Connections are opened lazily on first use.
+pub fn build_pool(url: &str, settings: &PoolSettings) -> Result<Pool, String> {
+    let config = url
+        .parse::<tokio_postgres::Config>()
+        .map_err(|e| format!("invalid database url: {e}"))?;
+    Pool::builder(Manager::new(config, NoTls))
+        .max_size(settings.max_size)
+        .wait_timeout(settings.acquire_timeout)
+        .create_timeout(settings.acquire_timeout)
+        .recycle_timeout(settings.acquire_timeout)
+        .runtime(Runtime::Tokio1)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// Pool for read-only handlers: a replica when `replica_url` is set, otherwise
+/// the primary pool itself.
+pub fn read_pool(primary: &Pool, replica_url: Option<&str>, settings: &PoolSettings) -> Result<Pool, String> {
+    match replica_url {
+        Some(url) => build_pool(url, settings),
+        None => Ok(primary.clone()),
+    }
+}
+
+/// Closes connections idle for longer than `idle_timeout` and tops the pool back
+/// up to `min_idle` open connections.
+pub struct IdleReaper {
+    db: Pool,
+    settings: PoolSettings,
+}
+
+impl IdleReaper {
+    pub fn new(db: Pool, settings: PoolSettings) -> Self {
+        Self { db, settings }
+    }
+
+    pub async fn run(&self, cancel: CancellationToken) {
+        let mut interval = tokio::time::interval(Duration::from_secs(30));
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => return,
+                _ = interval.tick() => {
+                    self.reap();
+                    if let Err(e) = self.top_up().await {
+                        warn!(error = %e, "failed to open minimum DB connections");
+                    }
+                }
+            }
+        }
+    }
+
+    fn reap(&self) {
+        let Some(idle_timeout) = self.settings.idle_timeout else { return };
+        let min_idle = self.settings.min_idle;
+        let mut kept = 0;
+        self.db.retain(|_, metrics| {
+            let keep = kept < min_idle || metrics.last_used() < idle_timeout;
+            if keep {
+                kept += 1;
+            }
+            keep
+        });
+    }
+
+    async fn top_up(&self) -> Result<(), deadpool_postgres::PoolError> {
+        let missing = self.settings.min_idle.saturating_sub(self.db.status().size);
+        // hold them all at once so each get opens a distinct connection
+        let conns = futures::future::join_all((0..missing).map(|_| self.db.get())).await;
+        conns.into_iter().collect::<Result<Vec<_>, _>>().map(drop)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::AppError;
+    use axum::{http::StatusCode, response::IntoResponse};
+
+    const PRIMARY: &str = "postgres://ledger@primary.invalid/ledger";
+    const REPLICA: &str = "postgres://ledger@replica.invalid/ledger";
+
+    fn sized(max_size: usize) -> PoolSettings {
+        PoolSettings { max_size, ..PoolSettings::default() }
+    }
+
+    fn settings(vars: &[(&str, &str)]) -> Result<PoolSettings, String> {
+        PoolSettings::from_lookup(|k| vars.iter().find(|(n, _)| *n == k).map(|(_, v)| v.to_string()))
+    }
+
+    #[test]
+    fn replica_gets_its_own_pool() {
+        let primary = build_pool(PRIMARY, &sized(16)).unwrap();
+        let replica = read_pool(&primary, Some(REPLICA), &sized(8)).unwrap();
+        assert_eq!(replica.status().max_size, 8);
+        assert_eq!(primary.status().max_size, 16);
+    }
+
+    #[test]
+    fn without_replica_reads_use_primary() {
+        let primary = build_pool(PRIMARY, &sized(16)).unwrap();
+        let read = read_pool(&primary, None, &sized(8)).unwrap();
+        // same underlying pool, so the primary's size applies
+        assert_eq!(read.status().max_size, 16);
+    }
+
+    #[test]
+    fn invalid_url_is_rejected() {
+        assert!(build_pool("not a url at all ::", &sized(4)).is_err());
+    }
+
+    #[test]
+    fn settings_from_env() {
+        assert_eq!(settings(&[]).unwrap(), PoolSettings::default());
+        let s = settings(&[
+            ("DB_MAX_CONNECTIONS", "4"),
+            ("DB_MIN_CONNECTIONS", "2"),
+            ("DB_ACQUIRE_TIMEOUT_MS", "250"),
+            ("DB_IDLE_TIMEOUT_MS", "0"),
+        ])
+        .unwrap();
+        assert_eq!(s.max_size, 4);
+        assert_eq!(s.min_idle, 2);
+        assert_eq!(s.acquire_timeout, Some(Duration::from_millis(250)));
+        assert_eq!(s.idle_timeout, None);
+        assert!(settings(&[("DB_MAX_CONNECTIONS", "0")]).is_err());
+        assert!(settings(&[("DB_MAX_CONNECTIONS", "2"), ("DB_MIN_CONNECTIONS", "3")]).is_err());
+        assert!(settings(&[("DB_ACQUIRE_TIMEOUT_MS", "soon")]).is_err());
+    }
+
+    #[tokio::test]
+    async fn exhausted_pool_is_503_not_a_hang() {
+        // accepts TCP connections but never answers the startup message, like a
+        // database stuck behind a slow transfer
+        let silent = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("postgres://ledger@127.0.0.1:{}/ledger", silent.local_addr().unwrap().port());
+        let pool = build_pool(
+            &url,
+            &PoolSettings { max_size: 1, acquire_timeout: Some(Duration::from_millis(200)), ..PoolSettings::default() },
+        )
+        .unwrap();
+
+        let first = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.get().await.map(drop) }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let second = tokio::time::timeout(Duration::from_secs(5), pool.get())
+            .await
+            .expect("acquire must time out instead of hanging")
+            .unwrap_err();
+        assert_eq!(AppError::from(second).into_response().status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let first = first.await.unwrap().unwrap_err();
+        assert_eq!(AppError::from(first).into_response().status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
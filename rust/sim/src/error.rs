@@ -1,31 +1,111 @@
-use axum::{http::StatusCode, response::IntoResponse, Json};
-use serde_json::json;
+use axum::{extract::rejection::JsonRejection, http::{header, StatusCode}, response::IntoResponse, Json};
+use serde::Serialize;
+use utoipa::ToSchema;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum AppError {
     BadRequest(String),
+    Forbidden(String),
     NotFound(String),
     Conflict(String),
     Unavailable(String),
     Internal(String),
+    /// 429 with a `Retry-After` header (whole seconds, at least 1).
+    TooManyRequests { message: String, retry_after_secs: u64 },
+    /// Error with a machine-readable code and structured `details` for the client.
+    Detailed {
+        status: StatusCode,
+        code: &'static str,
+        message: String,
+        details: serde_json::Value,
+    },
 }
 
+/// JSON body of every error response.
+#[derive(Serialize, ToSchema)]
+pub struct ErrorBody {
+    pub error: String,
+    /// Machine-readable code, e.g. `zone_down` or `unknown_zone`.
+    pub code: &'static str,
+    /// Structured context for `Detailed` errors.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+impl AppError {
+    /// HTTP status and machine-readable `code` as rendered in the response body.
+    pub fn status_and_code(&self) -> (StatusCode, &'static str) {
+        match self {
+            Self::BadRequest(_) => (StatusCode::BAD_REQUEST, "bad_request"),
+            Self::Forbidden(_) => (StatusCode::FORBIDDEN, "forbidden"),
+            Self::NotFound(_) => (StatusCode::NOT_FOUND, "not_found"),
+            Self::Conflict(_) => (StatusCode::CONFLICT, "conflict"),
+            Self::Unavailable(_) => (StatusCode::SERVICE_UNAVAILABLE, "unavailable"),
+            Self::Internal(_) => (StatusCode::INTERNAL_SERVER_ERROR, "internal"),
+            Self::TooManyRequests { .. } => (StatusCode::TOO_MANY_REQUESTS, "rate_limited"),
+            Self::Detailed { status, code, .. } => (*status, *code),
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Self::BadRequest(m) | Self::Forbidden(m) | Self::NotFound(m) | Self::Conflict(m) | Self::Unavailable(m) | Self::Internal(m) => m,
+            Self::TooManyRequests { message, .. } | Self::Detailed { message, .. } => message,
+        }
+    }
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+/// Lets streamed response bodies fail with an `AppError` after the headers are sent.
+impl std::error::Error for AppError {}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> axum::response::Response {
-        let (status, code, message) = match self {
-            Self::BadRequest(m) => (StatusCode::BAD_REQUEST, "bad_request", m),
-            Self::NotFound(m) => (StatusCode::NOT_FOUND, "not_found", m),
-            Self::Conflict(m) => (StatusCode::CONFLICT, "conflict", m),
-            Self::Unavailable(m) => (StatusCode::SERVICE_UNAVAILABLE, "unavailable", m),
-            Self::Internal(m) => (StatusCode::INTERNAL_SERVER_ERROR, "internal", m),
-        };
-        (status, Json(json!({ "error": message, "code": code }))).into_response()
+        let (status, code) = self.status_and_code();
+        match self {
+            Self::TooManyRequests { message, retry_after_secs } => {
+                let retry_after = retry_after_secs.max(1).to_string();
+                let body = ErrorBody { error: message, code, details: None };
+                (status, [(header::RETRY_AFTER, retry_after)], Json(body)).into_response()
+            }
+            Self::Detailed { message, details, .. } => {
+                (status, Json(ErrorBody { error: message, code, details: Some(details) })).into_response()
+            }
+            other => (status, Json(ErrorBody { error: other.message().to_string(), code, details: None })).into_response(),
+        }
     }
 }
 
 impl From<deadpool_postgres::PoolError> for AppError {
     fn from(e: deadpool_postgres::PoolError) -> Self {
-        Self::Internal(e.to_string())
+        match e {
+            // pool exhausted or database not answering: shed load instead of a generic 500
+            deadpool_postgres::PoolError::Timeout(_) => Self::Unavailable(format!("database unavailable: {e}")),
+            e => Self::Internal(e.to_string()),
+        }
+    }
+}
+
+/// Take a `Result<Json<T>, JsonRejection>` in handlers and `?` it, so body errors
+/// use the same error shape as everything else.
+impl From<JsonRejection> for AppError {
+    fn from(r: JsonRejection) -> Self {
+        match r {
+            // unknown or missing fields and wrong types; axum alone would answer 422
+            JsonRejection::JsonDataError(e) => Self::BadRequest(e.body_text()),
+            JsonRejection::JsonSyntaxError(e) => Self::BadRequest(e.body_text()),
+            other => Self::Detailed {
+                status: other.status(),
+                code: "invalid_body",
+                message: other.body_text(),
+                details: serde_json::json!({}),
+            },
+        }
     }
 }
 
@@ -40,6 +120,7 @@ mod tests {
     use super::*;
     use axum::response::IntoResponse;
     use http_body_util::BodyExt;
+    use serde_json::json;
 
     async fn error_body(err: AppError) -> (StatusCode, serde_json::Value) {
         let response = err.into_response();
@@ -84,4 +165,34 @@ mod tests {
         assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
         assert_eq!(body["code"], "internal");
     }
+
+    #[tokio::test]
+    async fn detailed_includes_details_object() {
+        let (status, body) = error_body(AppError::Detailed {
+            status: StatusCode::CONFLICT,
+            code: "conflict",
+            message: "payload mismatch".into(),
+            details: json!({ "request_id": "req-1" }),
+        })
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], "conflict");
+        assert_eq!(body["error"], "payload mismatch");
+        assert_eq!(body["details"]["request_id"], "req-1");
+    }
+
+    #[tokio::test]
+    async fn too_many_requests_sets_retry_after() {
+        let response = AppError::TooManyRequests { message: "slow down".into(), retry_after_secs: 0 }.into_response();
+        assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+        let (status, body) = error_body(AppError::TooManyRequests { message: "slow down".into(), retry_after_secs: 3 }).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(body["code"], "rate_limited");
+    }
+
+    #[tokio::test]
+    async fn plain_variants_omit_details() {
+        let (_, body) = error_body(AppError::BadRequest("actor required".into())).await;
+        assert!(body.get("details").is_none());
+    }
 }
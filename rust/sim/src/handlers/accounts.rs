@@ -0,0 +1,367 @@
+use axum::{extract::{Path, Query, State}, Json};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use utoipa::IntoParams;
+
+use crate::error::{AppError, ErrorBody};
+use crate::merkle::{leaf_hash, merkle_proof, merkle_root};
+use crate::state::AppState;
+use crate::util::{parse_rfc3339, to_rfc3339};
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StatementQuery {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    #[serde(default)]
+    pub opening_balance: i64,
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+}
+
+fn default_limit() -> i64 { 500 }
+
+#[derive(Serialize)]
+struct StatementLine {
+    transaction_id: String,
+    request_id: String,
+    zone_id: String,
+    direction: String,
+    amount_units: i64,
+    /// The account on the other side, or null when a split has several there.
+    counterparty: Option<String>,
+    running_balance: i64,
+    created_at: String,
+}
+
+/// Signed effect of a posting on the account balance, matching the balances
+/// projection: DEBIT moves units out of the account, CREDIT moves them in.
+pub fn signed_amount(direction: &str, amount_units: i64) -> i64 {
+    if direction == "DEBIT" { -amount_units } else { amount_units }
+}
+
+/// Running balance after each posting, starting from `opening`.
+pub fn running_balances<'a>(
+    opening: i64,
+    postings: impl IntoIterator<Item = (&'a str, i64)>,
+) -> Vec<i64> {
+    let mut bal = opening;
+    postings
+        .into_iter()
+        .map(|(direction, amount)| {
+            bal = bal.saturating_add(signed_amount(direction, amount));
+            bal
+        })
+        .collect()
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/accounts/{account_id}/statement",
+    tag = "accounts",
+    params(("account_id" = String, Path, description = "Account id"), StatementQuery),
+    responses(
+        (status = 200, description = "Postings with running balance", body = serde_json::Value),
+        (status = 400, description = "Invalid time range", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn account_statement(
+    State(st): State<AppState>,
+    Path(account_id): Path<String>,
+    Query(q): Query<StatementQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let limit = q.limit.clamp(1, 5000);
+    let from = q.from.as_deref().map(|s| parse_rfc3339("from", s)).transpose()?;
+    let to = q.to.as_deref().map(|s| parse_rfc3339("to", s)).transpose()?;
+    let client = st.db_read.get().await?;
+
+    let rows = client
+        .query(
+            "SELECT t.id::text AS transaction_id, t.request_id, t.zone_id, p.direction, p.amount_units, \
+             (SELECT CASE WHEN COUNT(DISTINCT o.account_id)=1 THEN MIN(o.account_id) END \
+              FROM postings o WHERE o.txn_id=p.txn_id AND o.direction<>p.direction) AS counterparty, \
+             p.created_at \
+             FROM postings p JOIN transactions t ON t.id=p.txn_id \
+             WHERE p.account_id=$1 \
+             AND ($2::timestamptz IS NULL OR p.created_at >= $2) \
+             AND ($3::timestamptz IS NULL OR p.created_at < $3) \
+             ORDER BY p.created_at ASC, p.id ASC LIMIT $4",
+            &[&account_id, &from, &to, &limit],
+        )
+        .await?;
+
+    let balances = running_balances(
+        q.opening_balance,
+        rows.iter().map(|r| (r.get::<_, &str>("direction"), r.get::<_, i64>("amount_units"))),
+    );
+
+    let lines: Vec<StatementLine> = rows
+        .iter()
+        .zip(balances.iter())
+        .map(|(r, bal)| -> Result<_, AppError> {
+            let dt: time::OffsetDateTime = r.get("created_at");
+            Ok(StatementLine {
+                transaction_id: r.get("transaction_id"),
+                request_id: r.get("request_id"),
+                zone_id: r.get("zone_id"),
+                direction: r.get("direction"),
+                amount_units: r.get("amount_units"),
+                counterparty: r.get("counterparty"),
+                running_balance: *bal,
+                created_at: to_rfc3339(dt)?,
+            })
+        })
+        .collect::<Result<_, _>>()?;
+
+    let closing = balances.last().copied().unwrap_or(q.opening_balance);
+    Ok(Json(json!({
+        "account_id": account_id,
+        "opening_balance": q.opening_balance,
+        "closing_balance": closing,
+        "entries": lines,
+    })))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BalanceAsOfQuery {
+    pub as_of: Option<String>,
+}
+
+/// Reconstruct an account balance at `as_of` from the latest checkpoint at or
+/// before it plus only the postings recorded after that checkpoint.
+/// Returns the balance and the checkpoint it was based on, if any.
+pub async fn balance_as_of(
+    client: &tokio_postgres::Client,
+    account_id: &str,
+    as_of: time::OffsetDateTime,
+) -> Result<(i64, Option<time::OffsetDateTime>), AppError> {
+    let row = client
+        .query_one(
+            "WITH cp AS (SELECT as_of, balance_units FROM balance_checkpoints \
+             WHERE account_id=$1 AND as_of <= $2 ORDER BY as_of DESC LIMIT 1) \
+             SELECT (COALESCE((SELECT balance_units FROM cp), 0) + COALESCE((SELECT SUM(CASE WHEN direction='CREDIT' THEN amount_units ELSE -amount_units END) \
+             FROM postings WHERE account_id=$1 AND created_at <= $2 \
+             AND created_at > COALESCE((SELECT as_of FROM cp), '-infinity'::timestamptz)), 0))::bigint AS balance_units, \
+             (SELECT as_of FROM cp) AS checkpoint_as_of",
+            &[&account_id, &as_of],
+        )
+        .await?;
+    Ok((row.get("balance_units"), row.get("checkpoint_as_of")))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/accounts/{account_id}/balance",
+    tag = "accounts",
+    params(("account_id" = String, Path, description = "Account id"), BalanceAsOfQuery),
+    responses(
+        (status = 200, description = "Balance now, or at as_of", body = serde_json::Value),
+        (status = 400, description = "Invalid as_of", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn account_balance(
+    State(st): State<AppState>,
+    Path(account_id): Path<String>,
+    Query(q): Query<BalanceAsOfQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let as_of = match q.as_of.as_deref() {
+        Some(s) => parse_rfc3339("as_of", s)?,
+        None => st.clock.now(),
+    };
+    let client = st.db_read.get().await?;
+    let (balance_units, checkpoint) = balance_as_of(&client, &account_id, as_of).await?;
+    Ok(Json(json!({
+        "account_id": account_id,
+        "as_of": to_rfc3339(as_of)?,
+        "balance_units": balance_units,
+        "checkpoint_as_of": checkpoint.map(to_rfc3339).transpose()?,
+    })))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TurnoverQuery {
+    /// Window length in seconds, ending now.
+    #[serde(default = "default_turnover_window")]
+    pub window: i64,
+}
+
+fn default_turnover_window() -> i64 { 86_400 }
+
+const TURNOVER_SAMPLES: u32 = 48;
+
+/// Mean of the balance sampled at the end of each of `samples` equal slices of
+/// `[start, end]`, replaying `postings` (time-ordered, signed) from `opening`.
+pub fn sampled_average_balance(
+    opening: i64,
+    start: time::OffsetDateTime,
+    end: time::OffsetDateTime,
+    postings: &[(time::OffsetDateTime, i64)],
+    samples: u32,
+) -> f64 {
+    let samples = samples.max(1);
+    let span = end - start;
+    let mut bal = opening;
+    let mut next = postings.iter().peekable();
+    let mut total = 0f64;
+    for i in 1..=samples {
+        let at = start + span * i / samples;
+        while let Some(&&(t, delta)) = next.peek() {
+            if t > at {
+                break;
+            }
+            bal = bal.saturating_add(delta);
+            next.next();
+        }
+        total += bal as f64;
+    }
+    total / samples as f64
+}
+
+/// Volume moved through the account per unit of average balance. None when the
+/// average balance is zero (nothing to compare against).
+pub fn turnover_ratio(volume_units: i64, average_balance: f64) -> Option<f64> {
+    (average_balance != 0.0).then(|| volume_units as f64 / average_balance.abs())
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/accounts/{account_id}/turnover",
+    tag = "accounts",
+    params(("account_id" = String, Path, description = "Account id"), TurnoverQuery),
+    responses(
+        (status = 200, description = "Volume, average balance and turnover ratio", body = serde_json::Value),
+        (status = 400, description = "Invalid window", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn account_turnover(
+    State(st): State<AppState>,
+    Path(account_id): Path<String>,
+    Query(q): Query<TurnoverQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let window = q.window.clamp(60, 86_400 * 90);
+    let end = st.clock.now();
+    let start = end - time::Duration::seconds(window);
+    let client = st.db_read.get().await?;
+
+    let (opening, _) = balance_as_of(&client, &account_id, start).await?;
+    let rows = client
+        .query(
+            "SELECT direction, amount_units, created_at FROM postings \
+             WHERE account_id=$1 AND created_at > $2 AND created_at <= $3 ORDER BY created_at, id",
+            &[&account_id, &start, &end],
+        )
+        .await?;
+    let postings: Vec<(time::OffsetDateTime, i64)> = rows
+        .iter()
+        .map(|r| (r.get("created_at"), signed_amount(r.get("direction"), r.get("amount_units"))))
+        .collect();
+    let volume_units = rows.iter().fold(0i64, |acc, r| acc.saturating_add(r.get::<_, i64>("amount_units")));
+    let average_balance = sampled_average_balance(opening, start, end, &postings, TURNOVER_SAMPLES);
+
+    Ok(Json(json!({
+        "account_id": account_id,
+        "window_secs": window,
+        "from": to_rfc3339(start)?,
+        "to": to_rfc3339(end)?,
+        "volume_units": volume_units,
+        "average_balance_units": average_balance,
+        "turnover_ratio": turnover_ratio(volume_units, average_balance),
+    })))
+}
+
+/// Current balances sorted by account id (byte order): the leaf order of the balance Merkle tree.
+pub async fn balance_leaves(client: &tokio_postgres::Client) -> Result<Vec<(String, i64)>, AppError> {
+    let rows = client.query("SELECT account_id, balance_units FROM balances", &[]).await?;
+    let mut balances: Vec<(String, i64)> = rows.iter().map(|r| (r.get(0), r.get(1))).collect();
+    balances.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    Ok(balances)
+}
+
+/// Merkle inclusion proof for one account's current balance against the
+/// root published by `/v1/sim/balance-merkle`.
+#[utoipa::path(
+    get,
+    path = "/v1/accounts/{account_id}/balance-proof",
+    tag = "accounts",
+    params(("account_id" = String, Path, description = "Account id")),
+    responses(
+        (status = 200, description = "Merkle inclusion proof of the balance", body = serde_json::Value),
+        (status = 404, description = "Account has no balance", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn balance_proof(
+    State(st): State<AppState>,
+    Path(account_id): Path<String>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let client = st.db_read.get().await?;
+    let balances = balance_leaves(&client).await?;
+    let index = balances
+        .binary_search_by(|(a, _)| a.as_str().cmp(&account_id))
+        .map_err(|_| AppError::NotFound(format!("account not found: {account_id}")))?;
+    let leaves: Vec<String> = balances.iter().map(|(a, b)| leaf_hash(a, *b)).collect();
+    let proof = merkle_proof(&leaves, index).unwrap_or_default();
+    Ok(Json(json!({
+        "account_id": account_id,
+        "balance_units": balances[index].1,
+        "leaf": leaves[index],
+        "proof": proof,
+        "root": merkle_root(&leaves),
+        "leaf_count": leaves.len(),
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn running_balance_matches_projection() {
+        let transfers = [("a", "b", 100), ("b", "a", 30), ("a", "c", 5), ("c", "a", 50)];
+
+        // balances projection, as apply_transfer_inner maintains it
+        let mut projection: HashMap<&str, i64> = HashMap::new();
+        // postings touching "a", in insertion order
+        let mut postings: Vec<(&str, i64)> = Vec::new();
+        for (from, to, amount) in transfers {
+            *projection.entry(from).or_default() -= amount;
+            *projection.entry(to).or_default() += amount;
+            if from == "a" { postings.push(("DEBIT", amount)); }
+            if to == "a" { postings.push(("CREDIT", amount)); }
+        }
+
+        let running = running_balances(0, postings);
+        assert_eq!(running, vec![-100, -70, -75, -25]);
+        assert_eq!(*running.last().unwrap(), projection["a"]);
+    }
+
+    #[test]
+    fn sampled_average_weights_by_time() {
+        let start = time::OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let end = start + time::Duration::seconds(100);
+        // +100 a quarter of the way in, -100 three quarters in
+        let postings = [(start + time::Duration::seconds(25), 100), (start + time::Duration::seconds(75), -100)];
+        assert_eq!(sampled_average_balance(0, start, end, &postings, 4), 50.0);
+        assert_eq!(sampled_average_balance(40, start, end, &[], 4), 40.0);
+    }
+
+    #[test]
+    fn turnover_distinguishes_dormant_from_active() {
+        assert_eq!(turnover_ratio(0, 1_000_000.0), Some(0.0));
+        assert_eq!(turnover_ratio(5000, 100.0), Some(50.0));
+        assert_eq!(turnover_ratio(5000, -100.0), Some(50.0));
+        assert_eq!(turnover_ratio(5000, 0.0), None);
+    }
+
+    #[test]
+    fn running_balance_starts_from_opening() {
+        assert_eq!(running_balances(1000, [("CREDIT", 10), ("DEBIT", 25)]), vec![1010, 985]);
+        assert!(running_balances(7, []).is_empty());
+    }
+}
@@ -0,0 +1,262 @@
+use axum::{
+    extract::{rejection::JsonRejection, Path, State},
+    http::HeaderMap,
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::error;
+use utoipa::ToSchema;
+
+use crate::auth::resolve_principal;
+use crate::error::{AppError, ErrorBody};
+use crate::handlers::admin::admin_guard;
+use crate::handlers::audit::publish_audit;
+use crate::handlers::events::publish_event;
+use crate::handlers::transfers::{check_amount, post_legs, Leg, PostedLegs, TransferInput, TransferLimits};
+use crate::jwt::Claims;
+use crate::reconcile::LEDGER_ZONE;
+use crate::state::AppState;
+use crate::util::{payload_hash, to_rfc3339};
+use crate::Direction;
+
+/// Account on the other side of every adjustment, in [`LEDGER_ZONE`]. Its
+/// balance is minus the net of all adjustments, so the ledger still sums to zero.
+pub const CLEARING_ACCOUNT: &str = "system";
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AdjustmentRequest {
+    /// Signed, in minor units: positive credits the account, negative debits it.
+    pub amount_units: i64,
+    pub reason: String,
+    pub actor: String,
+}
+
+/// The two legs of an adjustment of `account` by `amount_units`; the clearing
+/// account takes the opposite side. `amount_units` must not be zero or `i64::MIN`.
+fn adjustment_legs(account: &str, amount_units: i64) -> [Leg<'_>; 2] {
+    let (account_side, clearing_side) =
+        if amount_units > 0 { (Direction::Credit, Direction::Debit) } else { (Direction::Debit, Direction::Credit) };
+    let amount_units = amount_units.abs();
+    [
+        Leg { account, direction: account_side, amount_units },
+        Leg { account: CLEARING_ACCOUNT, direction: clearing_side, amount_units },
+    ]
+}
+
+fn validate_adjustment(account_id: &str, req: &AdjustmentRequest, limits: &TransferLimits) -> Result<(), AppError> {
+    if req.actor.is_empty() {
+        return Err(AppError::BadRequest("actor required".into()));
+    }
+    if req.reason.is_empty() {
+        return Err(AppError::BadRequest("reason required".into()));
+    }
+    if account_id == CLEARING_ACCOUNT {
+        return Err(AppError::BadRequest(format!("{CLEARING_ACCOUNT} is the clearing account and cannot be adjusted")));
+    }
+    // i64::MIN has no positive counterpart and is rejected as not positive
+    let magnitude = if req.amount_units == i64::MIN { 0 } else { req.amount_units.abs() };
+    check_amount("amount_units", magnitude, limits)
+}
+
+/// Post a manual correction to one account, e.g. after a bad data import. The
+/// other leg goes to the [`CLEARING_ACCOUNT`], so double-entry holds. The
+/// transaction is booked in the account's zone but skips the zone gate, rate
+/// limit and whitelists, and is recorded in the audit log as `ADJUST_BALANCE`.
+#[utoipa::path(
+    post,
+    path = "/v1/accounts/{account_id}/adjust",
+    tag = "accounts",
+    params(
+        ("account_id" = String, Path, description = "Account id"),
+        ("x-admin-key" = String, Header, description = "One of the comma-separated ADMIN_KEY values"),
+    ),
+    request_body = AdjustmentRequest,
+    responses(
+        (status = 200, description = "Adjustment posted; balances are omitted with async projection", body = serde_json::Value),
+        (status = 400, description = "Malformed body, missing actor or reason, zero amount, or the clearing account itself", body = ErrorBody),
+        (status = 401, description = "invalid_token: Authorization is not a known bearer token", body = ErrorBody),
+        (status = 403, description = "Missing or wrong admin key", body = ErrorBody),
+        (status = 404, description = "Account not found", body = ErrorBody),
+        (status = 422, description = "balance_overflow", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn adjust_balance(
+    State(st): State<AppState>,
+    Path(account_id): Path<String>,
+    headers: HeaderMap,
+    claims: Option<Extension<Claims>>,
+    body: Result<Json<AdjustmentRequest>, JsonRejection>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    admin_guard(&st, &headers)?;
+    let principal = resolve_principal(&st.api_tokens, &headers, claims.as_deref())?;
+    let Json(req) = body?;
+    validate_adjustment(&account_id, &req, &st.transfer_limits)?;
+
+    // adjustments carry no idempotency key of their own; each call posts once
+    let request_id = format!("adjust-{}", uuid::Uuid::new_v4());
+    let hash = payload_hash(&json!({ "account_id": account_id, "adjustment": req }))?;
+    let metadata = json!({ "adjustment": true, "reason": req.reason });
+    let legs = adjustment_legs(&account_id, req.amount_units);
+    let (debit, credit) = if legs[0].direction == Direction::Debit { (&legs[0], &legs[1]) } else { (&legs[1], &legs[0]) };
+
+    let mut client = st.db.get().await?;
+    let tx = client.transaction().await?;
+    let zone_id: String = tx
+        .query_opt("SELECT zone_id FROM accounts WHERE id=$1", &[&account_id])
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("account not found: {account_id}")))?
+        .get(0);
+    tx.execute("INSERT INTO accounts(id, zone_id) VALUES($1, $2) ON CONFLICT DO NOTHING", &[&CLEARING_ACCOUNT, &LEDGER_ZONE])
+        .await?;
+    let clearing_zone: String = tx.query_one("SELECT zone_id FROM accounts WHERE id=$1", &[&CLEARING_ACCOUNT]).await?.get(0);
+    if clearing_zone != LEDGER_ZONE {
+        error!(zone_id = %clearing_zone, "clearing account exists outside the ledger zone");
+        return Err(AppError::Internal(format!("account {CLEARING_ACCOUNT} is not the clearing account")));
+    }
+
+    // no currency: it would pin the clearing account to this account's currency
+    let PostedLegs { txn_id, created_at, event, balances } = post_legs(
+        &tx,
+        &TransferInput {
+            request_id: &request_id,
+            payload_hash: &hash,
+            from_account: debit.account,
+            to_account: credit.account,
+            amount_units: debit.amount_units,
+            zone_id: &zone_id,
+            metadata: &metadata,
+            currency: None,
+            reverses_txn_id: None,
+            created_by: principal.subject(),
+        },
+        &legs,
+        &st,
+    )
+    .await?;
+
+    let details = json!({ "transaction_id": txn_id, "amount_units": req.amount_units, "clearing_account": CLEARING_ACCOUNT });
+    let audit = tx
+        .query_one(
+            "INSERT INTO audit_log(actor,action,target_type,target_id,reason,details) VALUES($1,'ADJUST_BALANCE','account',$2,$3,$4) \
+             RETURNING id::text, actor, action, target_type, target_id, reason, details, created_at",
+            &[&req.actor, &account_id, &req.reason, &details],
+        )
+        .await?;
+    tx.commit().await?;
+    publish_audit(&st, &audit);
+    publish_event(&st, event);
+    st.metrics.transfers_total.with_label_values(&[zone_id.as_str(), "posted"]).inc();
+
+    let balance_of = |account: &str| balances.as_ref().map(|b| b[account]);
+    Ok(Json(json!({
+        "status": "APPLIED",
+        "transaction_id": txn_id,
+        "account_id": account_id,
+        "amount_units": req.amount_units,
+        "clearing_account": CLEARING_ACCOUNT,
+        "created_at": to_rfc3339(created_at)?,
+        "balance_units": balance_of(&account_id),
+        "clearing_balance_units": balance_of(CLEARING_ACCOUNT),
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+
+    fn request(amount_units: i64) -> AdjustmentRequest {
+        AdjustmentRequest { amount_units, reason: "import fix".into(), actor: "ops".into() }
+    }
+
+    #[test]
+    fn sign_picks_the_account_side() {
+        let [account, clearing] = adjustment_legs("acct-a", 150);
+        assert_eq!((account.account, account.direction, account.amount_units), ("acct-a", Direction::Credit, 150));
+        assert_eq!((clearing.account, clearing.direction, clearing.amount_units), (CLEARING_ACCOUNT, Direction::Debit, 150));
+
+        let [account, clearing] = adjustment_legs("acct-a", -40);
+        assert_eq!((account.direction, account.amount_units), (Direction::Debit, 40));
+        assert_eq!((clearing.direction, clearing.amount_units), (Direction::Credit, 40));
+    }
+
+    #[test]
+    fn rejects_zero_min_and_the_clearing_account() {
+        let limits = TransferLimits::default();
+        assert!(validate_adjustment("acct-a", &request(-5), &limits).is_ok());
+        for amount in [0, i64::MIN] {
+            let err = validate_adjustment("acct-a", &request(amount), &limits).unwrap_err();
+            assert_eq!(err.status_and_code().1, "invalid_transfer", "{amount}");
+        }
+        let err = validate_adjustment(CLEARING_ACCOUNT, &request(5), &limits).unwrap_err();
+        assert_eq!(err.status_and_code().0, StatusCode::BAD_REQUEST);
+        let missing_actor = AdjustmentRequest { actor: String::new(), ..request(5) };
+        assert!(validate_adjustment("acct-a", &missing_actor, &limits).is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn adjustment_moves_the_account_and_mirrors_the_clearing_account() {
+        use crate::handlers::balances::balance_summary;
+        use crate::handlers::transfers::{create_transfer, CreateTransferQuery, CreateTransferRequest};
+        use axum::extract::Query;
+        let mut config = crate::testing::test_db_config();
+        config.admin_keys = vec!["test-key".into()];
+        let st = crate::app::build_state(config).await.unwrap();
+        let run = uuid::Uuid::new_v4();
+        let account = format!("acct-adj-{run}");
+        let req = CreateTransferRequest {
+            request_id: format!("req-adj-{run}"),
+            from_account: format!("acct-src-{run}"),
+            to_account: account.clone(),
+            amount_units: 100,
+            zone_id: "zone-eu".into(),
+            metadata: json!({}),
+            currency: None,
+        };
+        let q = Query(CreateTransferQuery { include_balances: false, dry_run: false });
+        create_transfer(State(st.clone()), q, Default::default(), None, Ok(Json(req))).await.unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-admin-key", "test-key".parse().unwrap());
+        let client = st.db.get().await.unwrap();
+        let balance = |account: String| {
+            let client = &client;
+            async move {
+                client
+                    .query_opt("SELECT balance_units FROM balances WHERE account_id=$1", &[&account])
+                    .await
+                    .unwrap()
+                    .map_or(0, |r| r.get::<_, i64>(0))
+            }
+        };
+        let clearing_before = balance(CLEARING_ACCOUNT.into()).await;
+
+        let Json(up) = adjust_balance(State(st.clone()), Path(account.clone()), headers.clone(), None, Ok(Json(request(250)))).await.unwrap();
+        assert_eq!(up["balance_units"], 350);
+        assert_eq!(balance(account.clone()).await, 350);
+        assert_eq!(balance(CLEARING_ACCOUNT.into()).await, clearing_before - 250);
+
+        let Json(down) = adjust_balance(State(st.clone()), Path(account.clone()), headers.clone(), None, Ok(Json(request(-50)))).await.unwrap();
+        assert_eq!(down["balance_units"], 300);
+        assert_eq!(balance(CLEARING_ACCOUNT.into()).await, clearing_before - 200);
+
+        let Json(system) = balance_summary(State(st.clone())).await.unwrap();
+        assert_eq!(system.total_units, 0);
+
+        let audit: String = client
+            .query_one("SELECT action FROM audit_log WHERE target_id=$1 ORDER BY created_at DESC LIMIT 1", &[&account])
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(audit, "ADJUST_BALANCE");
+
+        let err = adjust_balance(State(st.clone()), Path(format!("acct-missing-{run}")), headers, None, Ok(Json(request(5)))).await.unwrap_err();
+        assert_eq!(err.status_and_code().0, StatusCode::NOT_FOUND);
+        let err = adjust_balance(State(st), Path(account), HeaderMap::new(), None, Ok(Json(request(5)))).await.unwrap_err();
+        assert_eq!(err.status_and_code().0, StatusCode::FORBIDDEN);
+    }
+}
@@ -1,15 +1,101 @@
-use axum::{extract::State, http::{HeaderMap, StatusCode}, response::IntoResponse, Json};
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
+use std::time::Duration;
+use subtle::{Choice, ConstantTimeEq};
+use tracing::{error, warn};
+use tokio_postgres::IsolationLevel;
+use utoipa::IntoParams;
 
-use crate::error::AppError;
+use crate::auth::ANONYMOUS;
+use crate::error::{AppError, ErrorBody};
+use crate::handlers::accounts::balance_leaves;
+use crate::handlers::audit::publish_audit;
+use crate::handlers::transactions::posting_direction;
+use crate::merkle::{leaf_hash, merkle_root};
+use crate::projection::fold_deltas;
+use crate::reconcile::{balance_discrepancies, LEDGER_BALANCES_SQL, LEDGER_ZONE};
 use crate::state::AppState;
-use crate::util::fmt_rfc3339;
+use crate::util::{parse_rfc3339, to_rfc3339};
+use crate::{postings_balanced, Direction, ZoneStatus};
 
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "health",
+    responses(
+        (status = 200, description = "Process is up", body = String),
+    )
+)]
 pub async fn healthz() -> impl IntoResponse {
     (StatusCode::OK, "ok")
 }
 
+const READY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Format written by `/v1/sim/snapshot`; restore also accepts v2.
+const SNAPSHOT_VERSION: &str = "v3";
+/// Transactions carried by one snapshot, oldest first. Past this the snapshot
+/// is marked `history_complete: false` and restore keeps its balances.
+const SNAPSHOT_MAX_TRANSACTIONS: i64 = 100_000;
+
+/// Readiness of `pool`: `SELECT 1` must succeed within [`READY_TIMEOUT`].
+/// The body always carries the pool's connection counts.
+async fn readiness(pool: &deadpool_postgres::Pool) -> (StatusCode, Json<serde_json::Value>) {
+    let check = tokio::time::timeout(READY_TIMEOUT, async {
+        let client = pool.get().await?;
+        client.query_one("SELECT 1", &[]).await?;
+        Ok::<_, AppError>(())
+    })
+    .await
+    .unwrap_or_else(|_| Err(AppError::Unavailable("database check timed out".into())));
+
+    let s = pool.status();
+    let pool_json = json!({
+        "max_size": s.max_size,
+        "size": s.size,
+        "idle": s.available,
+        "in_use": s.size.saturating_sub(s.available),
+        "waiting": s.waiting,
+    });
+    match check {
+        Ok(()) => (StatusCode::OK, Json(json!({ "status": "ok", "pool": pool_json }))),
+        Err(e) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "status": "unavailable", "error": e.message(), "pool": pool_json })),
+        ),
+    }
+}
+
+/// Readiness probe: 503 unless the primary database answers. `healthz` stays
+/// the liveness probe.
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "health",
+    responses(
+        (status = 200, description = "Database reachable; pool stats", body = serde_json::Value),
+        (status = 503, description = "Database unreachable; pool stats", body = serde_json::Value),
+    )
+)]
+pub async fn readyz(State(st): State<AppState>) -> impl IntoResponse {
+    readiness(&st.db).await
+}
+
+/// Build identity, shared by `/v1/version` and the `build_info` metric.
+pub(crate) const LANGUAGE: &str = "rust";
+pub(crate) const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Commit the binary was built from, when `GIT_SHA` was set at build time.
+pub(crate) const REVISION: Option<&str> = option_env!("GIT_SHA");
+
 #[derive(serde::Serialize)]
 struct VersionInfo {
     service: &'static str,
@@ -17,124 +103,825 @@ struct VersionInfo {
     version: &'static str,
     revision: Option<&'static str>,
     build_time: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diagnostics: Option<Diagnostics>,
 }
 
-pub async fn version() -> impl IntoResponse {
-    Json(VersionInfo {
+/// Deploy diagnostics, only returned to admins with `?verbose=true`.
+#[derive(serde::Serialize)]
+struct Diagnostics {
+    schema_version: Option<String>,
+    database_version: Option<String>,
+    features: serde_json::Value,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct VersionQuery {
+    #[serde(default)]
+    pub verbose: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/version",
+    tag = "health",
+    params(VersionQuery, ("x-admin-key" = Option<String>, Header, description = "Admin key; needed for diagnostics")),
+    responses(
+        (status = 200, description = "Build information; diagnostics when verbose with a valid admin key", body = serde_json::Value),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn version(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Query(q): Query<VersionQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let diagnostics = if q.verbose && admin_guard(&st, &headers).is_ok() {
+        Some(diagnostics(&st).await?)
+    } else {
+        None
+    };
+    let info = VersionInfo {
         service: "time-ledger-sim",
-        language: "rust",
-        version: env!("CARGO_PKG_VERSION"),
-        revision: option_env!("GIT_SHA"),
+        language: LANGUAGE,
+        version: VERSION,
+        revision: REVISION,
         build_time: option_env!("BUILD_TIME"),
+        diagnostics,
+    };
+    Ok(Json(json!(info)))
+}
+
+async fn diagnostics(st: &AppState) -> Result<Diagnostics, AppError> {
+    let client = st.db.get().await?;
+    // migrations are applied by Flyway; the table is absent if they were applied by hand
+    let schema_version = client
+        .query_opt(
+            "SELECT version FROM flyway_schema_history WHERE success ORDER BY installed_rank DESC LIMIT 1",
+            &[],
+        )
+        .await
+        .ok()
+        .flatten()
+        .and_then(|r| r.get::<_, Option<String>>(0));
+    let database_version = client
+        .query_one("SELECT version()", &[])
+        .await
+        .ok()
+        .map(|r| r.get::<_, String>(0));
+    let configured = |name: &str| env::var(name).map(|v| !v.is_empty()).unwrap_or(false);
+    Ok(Diagnostics {
+        schema_version,
+        database_version,
+        features: json!({
+            "balance_projection": st.balance_projection.as_str(),
+            "nats": configured("NATS_URL"),
+            "webhook": configured("WEBHOOK_URL"),
+            "webhook_signing": configured("WEBHOOK_SIGNING_SECRET"),
+        }),
     })
 }
 
-pub async fn metrics(State(st): State<AppState>) -> impl IntoResponse {
+/// Prometheus exposition. Open by default; with `METRICS_REQUIRE_ADMIN` set it
+/// needs the admin key like the other operator endpoints.
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "health",
+    params(("x-admin-key" = Option<String>, Header, description = "Required when METRICS_REQUIRE_ADMIN is set")),
+    responses(
+        (status = 200, description = "Prometheus text exposition", body = String),
+        (status = 403, description = "METRICS_REQUIRE_ADMIN is set and the admin key is missing or wrong", body = ErrorBody),
+    )
+)]
+pub async fn metrics(State(st): State<AppState>, headers: HeaderMap) -> Result<impl IntoResponse, AppError> {
     use prometheus::Encoder;
-    let mf = st.registry.gather();
-    let mut buf = Vec::new();
+    if st.metrics_require_admin {
+        admin_guard(&st, &headers)?;
+    }
     let enc = prometheus::TextEncoder::new();
-    enc.encode(&mf, &mut buf).unwrap();
-    (StatusCode::OK, String::from_utf8_lossy(&buf).to_string())
+    let mut buf = Vec::new();
+    // the text encoder writes UTF-8, so the bytes go out as-is
+    enc.encode(&st.registry.gather(), &mut buf).map_err(|e| {
+        error!(error = %e, "failed to encode metrics");
+        AppError::Internal(format!("metrics encoding failed: {e}"))
+    })?;
+    Ok(([(header::CONTENT_TYPE, enc.format_type().to_string())], buf))
 }
 
-pub fn admin_guard(st: &AppState, headers: &HeaderMap) -> Result<(), StatusCode> {
-    match &st.admin_key {
-        None => Err(StatusCode::FORBIDDEN),
-        Some(k) => {
-            let got = headers
-                .get("x-admin-key")
-                .and_then(|v| v.to_str().ok())
-                .unwrap_or("");
-            if got == k { Ok(()) } else { Err(StatusCode::FORBIDDEN) }
-        }
+/// Accepted admin keys from a comma-separated `ADMIN_KEY`, so a new key can be
+/// rolled out before the old one is retired. Blank entries are ignored.
+pub fn parse_admin_keys(value: Option<&str>) -> Vec<String> {
+    value
+        .unwrap_or("")
+        .split(',')
+        .map(str::trim)
+        .filter(|k| !k.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Whether `got` equals one of `keys`. Every key is compared in constant time
+/// and all of them are checked, so timing reveals neither the key nor which
+/// one matched (only key lengths).
+fn admin_key_matches(keys: &[String], got: &str) -> bool {
+    if got.is_empty() {
+        return false;
     }
+    let matched = keys.iter().fold(Choice::from(0), |acc, k| acc | k.as_bytes().ct_eq(got.as_bytes()));
+    matched.into()
 }
 
+/// 403 unless `X-Admin-Key` matches a configured key. With no key configured
+/// admin routes are always refused.
+pub fn admin_guard(st: &AppState, headers: &HeaderMap) -> Result<(), AppError> {
+    let got = headers.get("x-admin-key").and_then(|v| v.to_str().ok()).unwrap_or("");
+    if admin_key_matches(&st.admin_keys, got) {
+        Ok(())
+    } else {
+        Err(AppError::Forbidden("missing or invalid admin key".into()))
+    }
+}
+
+/// Permit for a snapshot, restore or archive, held for the whole operation; 429
+/// while another one runs so a misbehaving cron cannot stack them up.
+pub(crate) fn admin_op_permit(st: &AppState) -> Result<tokio::sync::SemaphorePermit<'_>, AppError> {
+    st.admin_ops.try_acquire().map_err(|_| AppError::TooManyRequests {
+        message: "another snapshot, restore or archive is in progress".into(),
+        retry_after_secs: 1,
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/sim/snapshot",
+    tag = "sim",
+    params(("x-admin-key" = String, Header, description = "One of the comma-separated ADMIN_KEY values")),
+    responses(
+        (status = 200, description = "Snapshot of the simulation state", body = serde_json::Value),
+        (status = 400, description = "Invalid input", body = ErrorBody),
+        (status = 401, description = "With JWT verification enabled: missing_token, invalid_token or token_expired", body = ErrorBody),
+        (status = 403, description = "Missing or wrong admin key, or token_not_accepted (JWT for another audience or issuer)", body = ErrorBody),
+        (status = 429, description = "Another snapshot, restore or archive is running", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
 pub async fn snapshot(
     State(st): State<AppState>,
     headers: HeaderMap,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    admin_guard(&st, &headers).map_err(|_| AppError::BadRequest("forbidden".into()))?;
-    let client = st.db.get().await?;
+    admin_guard(&st, &headers)?;
+    let _permit = admin_op_permit(&st)?;
+    let mut client = st.db_read.get().await?;
+    // one repeatable-read view so balances and history agree with each other
+    let client = client
+        .build_transaction()
+        .isolation_level(IsolationLevel::RepeatableRead)
+        .read_only(true)
+        .start()
+        .await?;
 
     let mut snap = json!({
-        "version": "v2",
-        "created_at": fmt_rfc3339(time::OffsetDateTime::now_utc()),
-        "note": "Restore resets transaction history; balances/incidents/controls/spool/audit are restored.",
+        "version": SNAPSHOT_VERSION,
+        "created_at": to_rfc3339(st.clock.now())?,
+        "note": "Restore replays transactions/postings and recomputes balances when history_complete; incidents/controls/spool/audit are restored.",
     });
 
     // zones
     let rows = client.query("SELECT id,name,status,updated_at FROM zones ORDER BY id", &[]).await?;
-    let zones: Vec<serde_json::Value> = rows.iter().map(|r| {
+    let zones: Vec<serde_json::Value> = rows.iter().map(|r| -> Result<_, AppError> {
         let dt: time::OffsetDateTime = r.get("updated_at");
-        json!({"id": r.get::<_,String>("id"), "name": r.get::<_,String>("name"), "status": r.get::<_,String>("status"), "updated_at": fmt_rfc3339(dt)})
-    }).collect();
+        Ok(json!({"id": r.get::<_,String>("id"), "name": r.get::<_,String>("name"), "status": r.get::<_,String>("status"), "updated_at": to_rfc3339(dt)?}))
+    }).collect::<Result<_, _>>()?;
     snap["zones"] = json!(zones);
 
     // zone controls
     let rows = client.query("SELECT zone_id, writes_blocked, cross_zone_throttle, spool_enabled, updated_at FROM zone_controls ORDER BY zone_id", &[]).await?;
-    let ctrls: Vec<serde_json::Value> = rows.iter().map(|r| {
+    let ctrls: Vec<serde_json::Value> = rows.iter().map(|r| -> Result<_, AppError> {
         let dt: time::OffsetDateTime = r.get("updated_at");
-        json!({"zone_id": r.get::<_,String>("zone_id"), "writes_blocked": r.get::<_,bool>("writes_blocked"), "cross_zone_throttle": r.get::<_,i32>("cross_zone_throttle"), "spool_enabled": r.get::<_,bool>("spool_enabled"), "updated_at": fmt_rfc3339(dt)})
-    }).collect();
+        Ok(json!({"zone_id": r.get::<_,String>("zone_id"), "writes_blocked": r.get::<_,bool>("writes_blocked"), "cross_zone_throttle": r.get::<_,i32>("cross_zone_throttle"), "spool_enabled": r.get::<_,bool>("spool_enabled"), "updated_at": to_rfc3339(dt)?}))
+    }).collect::<Result<_, _>>()?;
     snap["zone_controls"] = json!(ctrls);
 
     // accounts + balances
-    let rows = client.query("SELECT a.id, a.zone_id, COALESCE(b.balance_units,0) as balance_units FROM accounts a LEFT JOIN balances b ON b.account_id=a.id ORDER BY a.id LIMIT 20000", &[]).await?;
-    let accts: Vec<serde_json::Value> = rows.iter().map(|r| {
-        json!({"id": r.get::<_,String>("id"), "zone_id": r.get::<_,String>("zone_id"), "balance_units": r.get::<_,i64>("balance_units")})
+    let rows = client.query("SELECT a.id, a.zone_id, a.currency, COALESCE(b.balance_units,0) as balance_units FROM accounts a LEFT JOIN balances b ON b.account_id=a.id ORDER BY a.id LIMIT 20000", &[]).await?;
+    let accts: Vec<SnapshotAccount> = rows.iter().map(|r| SnapshotAccount {
+        id: r.get("id"),
+        zone_id: r.get("zone_id"),
+        balance_units: r.get("balance_units"),
+        currency: r.get("currency"),
     }).collect();
     snap["accounts"] = json!(accts);
 
+    // transaction history, oldest first, bounded at SNAPSHOT_MAX_TRANSACTIONS
+    let limit = SNAPSHOT_MAX_TRANSACTIONS + 1;
+    let rows = client.query(
+        &format!("SELECT {SNAPSHOT_TXN_COLUMNS} FROM transactions ORDER BY created_at, id LIMIT $1"),
+        &[&limit],
+    ).await?;
+    let history_complete = rows.len() as i64 <= SNAPSHOT_MAX_TRANSACTIONS;
+    let txns: Vec<SnapshotTransaction> =
+        rows.iter().take(SNAPSHOT_MAX_TRANSACTIONS as usize).map(SnapshotTransaction::from_row).collect::<Result<_, _>>()?;
+
+    let rows = client.query(
+        "WITH t AS (SELECT id FROM transactions ORDER BY created_at, id LIMIT $1) \
+         SELECT p.txn_id::text, p.account_id, p.direction, p.amount_units, p.created_at, p.projected_at, p.settled_at \
+         FROM postings p JOIN t ON t.id=p.txn_id ORDER BY p.created_at, p.id",
+        &[&SNAPSHOT_MAX_TRANSACTIONS],
+    ).await?;
+    let postings: Vec<SnapshotPosting> = rows.iter().map(SnapshotPosting::from_row).collect::<Result<_, _>>()?;
+    snap["history_complete"] = json!(history_complete);
+    snap["transactions"] = json!(txns);
+    snap["postings"] = json!(postings);
+
     // incidents
     let rows = client.query("SELECT id::text, zone_id, related_txn_id::text, severity, status, title, details, detected_at FROM incidents ORDER BY detected_at DESC LIMIT 5000", &[]).await?;
-    let incs: Vec<serde_json::Value> = rows.iter().map(|r| {
+    let incs: Vec<serde_json::Value> = rows.iter().map(|r| -> Result<_, AppError> {
         let dt: time::OffsetDateTime = r.get("detected_at");
         let rel: Option<String> = r.get("related_txn_id");
-        json!({"id": r.get::<_,String>("id"), "zone_id": r.get::<_,String>("zone_id"), "related_txn_id": rel, "severity": r.get::<_,String>("severity"), "status": r.get::<_,String>("status"), "title": r.get::<_,String>("title"), "details": r.get::<_,serde_json::Value>("details"), "detected_at": fmt_rfc3339(dt)})
-    }).collect();
+        Ok(json!({"id": r.get::<_,String>("id"), "zone_id": r.get::<_,String>("zone_id"), "related_txn_id": rel, "severity": r.get::<_,String>("severity"), "status": r.get::<_,String>("status"), "title": r.get::<_,String>("title"), "details": r.get::<_,serde_json::Value>("details"), "detected_at": to_rfc3339(dt)?}))
+    }).collect::<Result<_, _>>()?;
     snap["incidents"] = json!(incs);
 
     // spooled transfers
     let rows = client.query("SELECT id::text, request_id, payload_hash, from_account, to_account, amount_units, zone_id, metadata, status, fail_reason, created_at, updated_at, applied_at FROM spooled_transfers ORDER BY created_at DESC LIMIT 5000", &[]).await?;
-    let spools: Vec<serde_json::Value> = rows.iter().map(|r| {
+    let spools: Vec<serde_json::Value> = rows.iter().map(|r| -> Result<_, AppError> {
         let ca: time::OffsetDateTime = r.get("created_at");
         let ua: time::OffsetDateTime = r.get("updated_at");
         let aa: Option<time::OffsetDateTime> = r.get("applied_at");
-        json!({
+        Ok(json!({
             "id": r.get::<_,String>("id"), "request_id": r.get::<_,String>("request_id"),
             "payload_hash": r.get::<_,String>("payload_hash"),
             "from_account": r.get::<_,String>("from_account"), "to_account": r.get::<_,String>("to_account"),
             "amount_units": r.get::<_,i64>("amount_units"), "zone_id": r.get::<_,String>("zone_id"),
             "metadata": r.get::<_,serde_json::Value>("metadata"),
             "status": r.get::<_,String>("status"), "fail_reason": r.get::<_,Option<String>>("fail_reason"),
-            "created_at": fmt_rfc3339(ca), "updated_at": fmt_rfc3339(ua),
-            "applied_at": aa.map(fmt_rfc3339),
-        })
-    }).collect();
+            "created_at": to_rfc3339(ca)?, "updated_at": to_rfc3339(ua)?,
+            "applied_at": aa.map(to_rfc3339).transpose()?,
+        }))
+    }).collect::<Result<_, _>>()?;
     snap["spooled_transfers"] = json!(spools);
 
     // audit tail
     let rows = client.query("SELECT id::text, actor, action, target_type, target_id, reason, details, created_at FROM audit_log ORDER BY created_at DESC LIMIT 2000", &[]).await?;
-    let audits: Vec<serde_json::Value> = rows.iter().map(|r| {
+    let audits: Vec<serde_json::Value> = rows.iter().map(|r| -> Result<_, AppError> {
         let dt: time::OffsetDateTime = r.get("created_at");
-        json!({"id": r.get::<_,String>("id"), "actor": r.get::<_,String>("actor"), "action": r.get::<_,String>("action"), "target_type": r.get::<_,String>("target_type"), "target_id": r.get::<_,String>("target_id"), "reason": r.get::<_,Option<String>>("reason"), "details": r.get::<_,serde_json::Value>("details"), "created_at": fmt_rfc3339(dt)})
-    }).collect();
+        Ok(json!({"id": r.get::<_,String>("id"), "actor": r.get::<_,String>("actor"), "action": r.get::<_,String>("action"), "target_type": r.get::<_,String>("target_type"), "target_id": r.get::<_,String>("target_id"), "reason": r.get::<_,Option<String>>("reason"), "details": r.get::<_,serde_json::Value>("details"), "created_at": to_rfc3339(dt)?}))
+    }).collect::<Result<_, _>>()?;
     snap["audit_log"] = json!(audits);
 
     Ok(Json(snap))
 }
 
+/// One entry of a snapshot's `accounts`: the account, its zone and its balance.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct SnapshotAccount {
+    id: String,
+    #[serde(default)]
+    zone_id: String,
+    #[serde(default)]
+    balance_units: i64,
+    #[serde(default)]
+    currency: Option<String>,
+}
+
+/// One entry of a snapshot's `transactions`, timestamps as RFC3339 strings.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct SnapshotTransaction {
+    id: String,
+    request_id: String,
+    payload_hash: String,
+    from_account: String,
+    to_account: String,
+    amount_units: i64,
+    zone_id: String,
+    #[serde(default)]
+    metadata: serde_json::Value,
+    #[serde(default)]
+    currency: Option<String>,
+    #[serde(default)]
+    reverses_txn_id: Option<String>,
+    /// Older snapshots predate it; their transactions restore as anonymous.
+    #[serde(default = "anonymous")]
+    created_by: String,
+    created_at: String,
+    /// Which use of `request_id` this is; older snapshots have only first uses.
+    #[serde(default)]
+    request_epoch: i32,
+}
+
+fn anonymous() -> String {
+    ANONYMOUS.into()
+}
+
+const SNAPSHOT_TXN_COLUMNS: &str = "id::text, request_id, payload_hash, from_account, to_account, amount_units, zone_id, metadata, currency, reverses_txn_id::text, created_by, created_at, request_epoch";
+
+impl SnapshotTransaction {
+    /// From a row selecting [`SNAPSHOT_TXN_COLUMNS`].
+    fn from_row(r: &tokio_postgres::Row) -> Result<Self, AppError> {
+        Ok(Self {
+            id: r.get("id"),
+            request_id: r.get("request_id"),
+            payload_hash: r.get("payload_hash"),
+            from_account: r.get("from_account"),
+            to_account: r.get("to_account"),
+            amount_units: r.get("amount_units"),
+            zone_id: r.get("zone_id"),
+            metadata: r.get("metadata"),
+            currency: r.get("currency"),
+            reverses_txn_id: r.get("reverses_txn_id"),
+            created_by: r.get("created_by"),
+            created_at: to_rfc3339(r.get("created_at"))?,
+            request_epoch: r.get("request_epoch"),
+        })
+    }
+}
+
+/// One entry of a snapshot's `postings`. `projected_at`/`settled_at` decide
+/// whether the posting counts towards the recomputed balance or pending units.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct SnapshotPosting {
+    txn_id: String,
+    account_id: String,
+    direction: Direction,
+    amount_units: i64,
+    created_at: String,
+    #[serde(default)]
+    projected_at: Option<String>,
+    #[serde(default)]
+    settled_at: Option<String>,
+}
+
+impl SnapshotPosting {
+    /// From a row selecting `txn_id::text, account_id, direction, amount_units, created_at, projected_at, settled_at`.
+    fn from_row(r: &tokio_postgres::Row) -> Result<Self, AppError> {
+        let direction = posting_direction(r)?;
+        let projected_at: Option<time::OffsetDateTime> = r.get("projected_at");
+        let settled_at: Option<time::OffsetDateTime> = r.get("settled_at");
+        Ok(Self {
+            txn_id: r.get("txn_id"),
+            account_id: r.get("account_id"),
+            direction,
+            amount_units: r.get("amount_units"),
+            created_at: to_rfc3339(r.get("created_at"))?,
+            projected_at: projected_at.map(to_rfc3339).transpose()?,
+            settled_at: settled_at.map(to_rfc3339).transpose()?,
+        })
+    }
+}
+
+/// History carried by a v3 snapshot. `complete` is false when the snapshot
+/// was cut at [`SNAPSHOT_MAX_TRANSACTIONS`]; balances are then taken as-is.
+#[derive(Debug, Default)]
+struct SnapshotHistory {
+    transactions: Vec<SnapshotTransaction>,
+    postings: Vec<SnapshotPosting>,
+    complete: bool,
+}
+
+fn invalid_snapshot(message: String, details: serde_json::Value) -> AppError {
+    AppError::Detailed { status: StatusCode::BAD_REQUEST, code: "invalid_snapshot", message, details }
+}
+
+/// The snapshot's accounts, each of which must name one of `zones`. Entries
+/// without an id are skipped, as before.
+fn snapshot_accounts(snap: &serde_json::Value, zones: &HashSet<String>) -> Result<Vec<SnapshotAccount>, AppError> {
+    let Some(accounts) = snap.get("accounts") else { return Ok(Vec::new()) };
+    let accounts: Vec<SnapshotAccount> = serde_json::from_value(accounts.clone())
+        .map_err(|e| invalid_snapshot(format!("invalid accounts: {e}"), json!({})))?;
+    accounts
+        .into_iter()
+        .filter(|a| !a.id.is_empty())
+        .map(|a| {
+            if zones.contains(&a.zone_id) {
+                Ok(a)
+            } else {
+                Err(invalid_snapshot(
+                    format!("account {} references unknown zone {:?}", a.id, a.zone_id),
+                    json!({ "account_id": a.id, "zone_id": a.zone_id }),
+                ))
+            }
+        })
+        .collect()
+}
+
+/// The snapshot's format version. v2 snapshots carry balances only; v3 adds
+/// transaction history.
+fn snapshot_version(snap: &serde_json::Value) -> Result<&str, AppError> {
+    match snap.get("version").and_then(|v| v.as_str()) {
+        Some(v @ ("v2" | "v3")) => Ok(v),
+        other => Err(AppError::Detailed {
+            status: StatusCode::BAD_REQUEST,
+            code: "unsupported_snapshot_version",
+            message: format!("snapshot version must be one of v2, v3 (got {other:?})"),
+            details: json!({ "version": other, "supported": ["v2", "v3"] }),
+        }),
+    }
+}
+
+/// Transactions and postings of a v3 snapshot. Every posting must belong to a
+/// snapshot transaction and one of `accounts`, every transaction's postings must
+/// balance, and every timestamp must parse, so restore fails before truncating.
+fn snapshot_history(snap: &serde_json::Value, accounts: &HashSet<&str>) -> Result<SnapshotHistory, AppError> {
+    fn field<T: serde::de::DeserializeOwned>(snap: &serde_json::Value, name: &str) -> Result<Vec<T>, AppError> {
+        let value = snap.get(name).cloned().unwrap_or_else(|| json!([]));
+        serde_json::from_value(value).map_err(|e| invalid_snapshot(format!("invalid {name}: {e}"), json!({})))
+    }
+    let transactions: Vec<SnapshotTransaction> = field(snap, "transactions")?;
+    let postings: Vec<SnapshotPosting> = field(snap, "postings")?;
+    let timestamp = |name: &str, s: &str| parse_rfc3339(name, s).map_err(|e| invalid_snapshot(e.message().to_string(), json!({})));
+
+    let mut per_txn: HashMap<&str, Vec<(Direction, i64)>> =
+        transactions.iter().map(|t| (t.id.as_str(), Vec::new())).collect();
+    for t in &transactions {
+        timestamp("transactions.created_at", &t.created_at)?;
+    }
+    for p in &postings {
+        let Some(legs) = per_txn.get_mut(p.txn_id.as_str()) else {
+            return Err(invalid_snapshot(
+                format!("posting references unknown transaction {}", p.txn_id),
+                json!({ "txn_id": p.txn_id }),
+            ));
+        };
+        if !accounts.contains(p.account_id.as_str()) {
+            return Err(invalid_snapshot(
+                format!("posting references unknown account {}", p.account_id),
+                json!({ "txn_id": p.txn_id, "account_id": p.account_id }),
+            ));
+        }
+        timestamp("postings.created_at", &p.created_at)?;
+        for ts in [&p.projected_at, &p.settled_at].into_iter().flatten() {
+            timestamp("postings timestamp", ts)?;
+        }
+        legs.push((p.direction, p.amount_units));
+    }
+    if let Some((id, _)) = per_txn.iter().find(|(_, legs)| !postings_balanced(legs)) {
+        return Err(invalid_snapshot(
+            format!("postings of transaction {id} do not balance"),
+            json!({ "txn_id": id }),
+        ));
+    }
+
+    let complete = snap.get("history_complete").and_then(|v| v.as_bool()).unwrap_or(false);
+    Ok(SnapshotHistory { transactions, postings, complete })
+}
+
+/// `(balance_units, pending_units)` per account from projected postings, the
+/// way the projector and settler would have left them: unsettled credits are
+/// pending, everything else is available. None if a balance leaves the i64 range.
+fn recompute_balances(postings: &[SnapshotPosting]) -> Option<BTreeMap<&str, (i64, i64)>> {
+    let projected = || postings.iter().filter(|p| p.projected_at.is_some());
+    let legs = |settled: bool| {
+        fold_deltas(
+            projected()
+                .filter(move |p| p.settled_at.is_some() == settled)
+                .map(|p| (p.account_id.as_str(), p.direction, p.amount_units)),
+        )
+    };
+    let mut out: BTreeMap<&str, (i64, i64)> = BTreeMap::new();
+    for (account, delta) in legs(true)? {
+        out.entry(account).or_default().0 = delta;
+    }
+    for (account, delta) in legs(false)? {
+        out.entry(account).or_default().1 = delta;
+    }
+    Some(out)
+}
+
+/// Transactions fetched from the export cursor per round trip.
+const EXPORT_BATCH: i64 = 500;
+
+/// One line of `/v1/sim/export`: a transaction in snapshot form plus its postings.
+#[derive(Serialize)]
+struct ExportLine<'a> {
+    #[serde(flatten)]
+    transaction: &'a SnapshotTransaction,
+    postings: Vec<&'a SnapshotPosting>,
+}
+
+/// NDJSON for one fetched batch, one line per transaction in batch order.
+fn ndjson_lines(transactions: &[SnapshotTransaction], postings: &[SnapshotPosting]) -> String {
+    let mut by_txn: HashMap<&str, Vec<&SnapshotPosting>> = HashMap::new();
+    for p in postings {
+        by_txn.entry(p.txn_id.as_str()).or_default().push(p);
+    }
+    let mut out = String::new();
+    for t in transactions {
+        let line = ExportLine { transaction: t, postings: by_txn.remove(t.id.as_str()).unwrap_or_default() };
+        out.push_str(&serde_json::to_string(&line).unwrap_or_default());
+        out.push('\n');
+    }
+    out
+}
+
+/// A connection holding the `ledger_export` cursor inside a read-only
+/// repeatable-read transaction. If the export does not run to the end (client
+/// went away, query failed) the connection is detached from the pool on drop
+/// rather than handed back mid-transaction.
+struct ExportCursor {
+    client: Option<deadpool_postgres::Client>,
+    done: bool,
+}
+
+impl ExportCursor {
+    async fn open(client: deadpool_postgres::Client) -> Result<Self, AppError> {
+        let cursor = Self { client: Some(client), done: false };
+        if let Some(client) = &cursor.client {
+            client
+                .batch_execute(&format!(
+                    "BEGIN ISOLATION LEVEL REPEATABLE READ READ ONLY; \
+                     DECLARE ledger_export NO SCROLL CURSOR FOR SELECT {SNAPSHOT_TXN_COLUMNS} FROM transactions ORDER BY created_at, id"
+                ))
+                .await?;
+        }
+        Ok(cursor)
+    }
+
+    /// The next batch as NDJSON, or None once the cursor is exhausted.
+    async fn next_batch(&mut self) -> Result<Option<String>, AppError> {
+        let Some(client) = &self.client else { return Ok(None) };
+        let rows = client.query(&format!("FETCH {EXPORT_BATCH} FROM ledger_export"), &[]).await?;
+        if rows.is_empty() {
+            client.batch_execute("CLOSE ledger_export; COMMIT").await?;
+            self.done = true;
+            self.client = None;
+            return Ok(None);
+        }
+        let transactions = rows.iter().map(SnapshotTransaction::from_row).collect::<Result<Vec<_>, _>>()?;
+        let ids: Vec<&str> = transactions.iter().map(|t| t.id.as_str()).collect();
+        let postings: Vec<SnapshotPosting> = client
+            .query(
+                "SELECT txn_id::text, account_id, direction, amount_units, created_at, projected_at, settled_at \
+                 FROM postings WHERE txn_id = ANY($1::text[]::uuid[]) ORDER BY created_at, id",
+                &[&ids],
+            )
+            .await?
+            .iter()
+            .map(SnapshotPosting::from_row)
+            .collect::<Result<_, _>>()?;
+        Ok(Some(ndjson_lines(&transactions, &postings)))
+    }
+
+    fn discard(&mut self) {
+        if let Some(client) = self.client.take() {
+            drop(deadpool_postgres::Client::take(client));
+        }
+    }
+}
+
+impl Drop for ExportCursor {
+    fn drop(&mut self) {
+        if !self.done {
+            self.discard();
+        }
+    }
+}
+
+/// Every transaction with its postings as newline-delimited JSON, oldest first.
+/// Rows come from a server-side cursor in batches of [`EXPORT_BATCH`], so memory
+/// stays flat however large the ledger is. Lines use the snapshot's
+/// transaction and posting shapes.
+#[utoipa::path(
+    get,
+    path = "/v1/sim/export",
+    tag = "sim",
+    params(("x-admin-key" = String, Header, description = "One of the comma-separated ADMIN_KEY values")),
+    responses(
+        (status = 200, description = "One transaction with its postings per line", body = String, content_type = "application/x-ndjson"),
+        (status = 400, description = "Invalid input", body = ErrorBody),
+        (status = 403, description = "Missing or wrong admin key", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn export(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    admin_guard(&st, &headers)?;
+    let cursor = ExportCursor::open(st.db_read.get().await?).await?;
+
+    let lines = futures::stream::unfold(cursor, |mut cursor| async move {
+        match cursor.next_batch().await {
+            Ok(Some(lines)) => Some((Ok(lines), cursor)),
+            Ok(None) => None,
+            Err(e) => {
+                warn!(error = %e, "ledger export failed");
+                cursor.discard();
+                Some((Err(e), cursor))
+            }
+        }
+    });
+    Ok(([(header::CONTENT_TYPE, "application/x-ndjson")], Body::from_stream(lines)))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CheckpointQuery {
+    pub as_of: Option<String>,
+}
+
+/// Checkpoint every account at `$1`: its previous checkpoint plus the postings
+/// after it, up to and including `$1`.
+pub(crate) const CHECKPOINT_SQL: &str = "INSERT INTO balance_checkpoints(account_id, as_of, balance_units) \
+     SELECT a.id, $1, (COALESCE(cp.balance_units, 0) + COALESCE((SELECT SUM(CASE WHEN p.direction='CREDIT' THEN p.amount_units ELSE -p.amount_units END) \
+     FROM postings p WHERE p.account_id=a.id AND p.created_at <= $1 \
+     AND p.created_at > COALESCE(cp.as_of, '-infinity'::timestamptz)), 0))::bigint \
+     FROM accounts a LEFT JOIN LATERAL (SELECT bc.as_of, bc.balance_units FROM balance_checkpoints bc \
+     WHERE bc.account_id=a.id AND bc.as_of <= $1 ORDER BY bc.as_of DESC LIMIT 1) cp ON true \
+     ON CONFLICT (account_id, as_of) DO UPDATE SET balance_units=EXCLUDED.balance_units";
+
+/// Record every account's balance as of a timestamp into `balance_checkpoints`,
+/// so historical reconstruction only replays postings after the checkpoint.
+#[utoipa::path(
+    post,
+    path = "/v1/sim/checkpoint",
+    tag = "sim",
+    params(CheckpointQuery, ("x-admin-key" = String, Header, description = "One of the comma-separated ADMIN_KEY values")),
+    responses(
+        (status = 200, description = "Checkpoint recorded", body = serde_json::Value),
+        (status = 400, description = "Invalid input", body = ErrorBody),
+        (status = 401, description = "With JWT verification enabled: missing_token, invalid_token or token_expired", body = ErrorBody),
+        (status = 403, description = "Missing or wrong admin key, or token_not_accepted (JWT for another audience or issuer)", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn checkpoint(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Query(q): Query<CheckpointQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    admin_guard(&st, &headers)?;
+    let as_of = match q.as_of.as_deref() {
+        Some(s) => parse_rfc3339("as_of", s)?,
+        None => st.clock.now(),
+    };
+    let client = st.db.get().await?;
+
+    let accounts = client.execute(CHECKPOINT_SQL, &[&as_of]).await?;
+
+    Ok(Json(json!({ "as_of": to_rfc3339(as_of)?, "accounts": accounts })))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ReconcileQuery {
+    /// Write the ledger values over the drifted balance rows.
+    #[serde(default)]
+    pub apply: bool,
+    /// Recorded in the audit entry when applying (default `admin`).
+    pub actor: Option<String>,
+    pub reason: Option<String>,
+}
+
+/// Recompute every account's balance from its projected postings and list the
+/// balance rows that disagree. With `apply=true` the rows are corrected in one
+/// transaction, under a lock on the balance table, and the correction is audited
+/// against the ledger zone.
+#[utoipa::path(
+    post,
+    path = "/v1/sim/reconcile",
+    tag = "sim",
+    params(ReconcileQuery, ("x-admin-key" = String, Header, description = "One of the comma-separated ADMIN_KEY values")),
+    responses(
+        (status = 200, description = "Balance rows that disagree with the postings", body = serde_json::Value),
+        (status = 400, description = "Invalid input", body = ErrorBody),
+        (status = 401, description = "With JWT verification enabled: missing_token, invalid_token or token_expired", body = ErrorBody),
+        (status = 403, description = "Missing or wrong admin key, or token_not_accepted (JWT for another audience or issuer)", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn reconcile(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Query(q): Query<ReconcileQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    admin_guard(&st, &headers)?;
+    let mut client = if q.apply { st.db.get().await? } else { st.db_read.get().await? };
+    let tx = client.transaction().await?;
+
+    // transfers update balances after inserting postings, so holding the
+    // balance rows keeps in-flight ones out of both sides of the comparison
+    let lock = if q.apply { " FOR UPDATE" } else { "" };
+    let stored: BTreeMap<String, (i64, i64)> = tx
+        .query(&format!("SELECT account_id, balance_units, pending_units FROM balances{lock}"), &[])
+        .await?
+        .iter()
+        .map(|r| (r.get(0), (r.get(1), r.get(2))))
+        .collect();
+    let ledger: BTreeMap<String, (i64, i64)> = tx
+        .query(LEDGER_BALANCES_SQL, &[])
+        .await?
+        .iter()
+        .map(|r| (r.get(0), (r.get(1), r.get(2))))
+        .collect();
+    let discrepancies = balance_discrepancies(&stored, &ledger);
+
+    let mut audit = None;
+    if q.apply && !discrepancies.is_empty() {
+        for d in &discrepancies {
+            tx.execute(
+                "INSERT INTO balances(account_id,balance_units,pending_units,updated_at) VALUES($1,$2,$3,now()) \
+                 ON CONFLICT (account_id) DO UPDATE SET balance_units=EXCLUDED.balance_units, pending_units=EXCLUDED.pending_units, updated_at=now()",
+                &[&d.account_id, &d.ledger_balance_units, &d.ledger_pending_units],
+            )
+            .await?;
+        }
+        let actor = q.actor.as_deref().unwrap_or("admin");
+        let details = json!({
+            "accounts": discrepancies.len(),
+            "drift_units": discrepancies.iter().fold(0i64, |total, d| total.saturating_add(d.drift_units.saturating_abs())),
+            "account_ids": discrepancies.iter().map(|d| d.account_id.as_str()).collect::<Vec<_>>(),
+        });
+        audit = Some(
+            tx.query_one(
+                "INSERT INTO audit_log(actor,action,target_type,target_id,reason,details) VALUES($1,'RECONCILE_BALANCES','zone',$2,$3,$4) \
+                 RETURNING id::text, actor, action, target_type, target_id, reason, details, created_at",
+                &[&actor, &LEDGER_ZONE, &q.reason, &details],
+            )
+            .await?,
+        );
+    }
+    tx.commit().await?;
+    if let Some(row) = &audit {
+        publish_audit(&st, row);
+    }
+
+    Ok(Json(json!({
+        "accounts_checked": stored.keys().chain(ledger.keys()).collect::<HashSet<_>>().len(),
+        "discrepancies": discrepancies,
+        "applied": audit.is_some(),
+    })))
+}
+
+/// Merkle root over all current `(account_id, balance_units)` leaves, sorted by account id.
+/// Individual accounts can prove inclusion via `/v1/accounts/{id}/balance-proof`.
+#[utoipa::path(
+    get,
+    path = "/v1/sim/balance-merkle",
+    tag = "sim",
+    params(("x-admin-key" = String, Header, description = "One of the comma-separated ADMIN_KEY values")),
+    responses(
+        (status = 200, description = "Merkle root over all balances", body = serde_json::Value),
+        (status = 400, description = "Invalid input", body = ErrorBody),
+        (status = 403, description = "Missing or wrong admin key", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn balance_merkle(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, AppError> {
+    admin_guard(&st, &headers)?;
+    let client = st.db_read.get().await?;
+    let leaves: Vec<String> = balance_leaves(&client)
+        .await?
+        .iter()
+        .map(|(a, b)| leaf_hash(a, *b))
+        .collect();
+    Ok(Json(json!({
+        "root": merkle_root(&leaves),
+        "leaf_count": leaves.len(),
+        "computed_at": to_rfc3339(st.clock.now())?,
+    })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/sim/restore",
+    tag = "sim",
+    params(("x-admin-key" = String, Header, description = "One of the comma-separated ADMIN_KEY values")),
+    request_body = serde_json::Value,
+    responses(
+        (status = 200, description = "Snapshot restored", body = serde_json::Value),
+        (status = 400, description = "unsupported_snapshot_version, or invalid_snapshot: malformed accounts/history or an account in an unknown zone", body = ErrorBody),
+        (status = 401, description = "With JWT verification enabled: missing_token, invalid_token or token_expired", body = ErrorBody),
+        (status = 403, description = "Missing or wrong admin key, or token_not_accepted (JWT for another audience or issuer)", body = ErrorBody),
+        (status = 429, description = "Another snapshot, restore or archive is running", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
 pub async fn restore(
     State(st): State<AppState>,
     headers: HeaderMap,
     Json(snap): Json<serde_json::Value>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    admin_guard(&st, &headers).map_err(|_| AppError::BadRequest("forbidden".into()))?;
+    admin_guard(&st, &headers)?;
+    let _permit = admin_op_permit(&st)?;
     let mut client = st.db.get().await?;
     let tx = client.transaction().await?;
 
+    // validate before anything is truncated
+    let zones: HashSet<String> = tx.query("SELECT id FROM zones", &[]).await?.iter().map(|r| r.get(0)).collect();
+    let version = snapshot_version(&snap)?;
+    let accounts = snapshot_accounts(&snap, &zones)?;
+    let history = if version == "v3" {
+        let ids: HashSet<&str> = accounts.iter().map(|a| a.id.as_str()).collect();
+        snapshot_history(&snap, &ids)?
+    } else {
+        SnapshotHistory::default()
+    };
+
     // truncate mutable tables
     for table in &[
         "postings", "transactions", "balances", "accounts", "incidents",
@@ -148,7 +935,7 @@ pub async fn restore(
         for z in zs {
             let id = z.get("id").and_then(|v| v.as_str()).unwrap_or("");
             let status = z.get("status").and_then(|v| v.as_str()).unwrap_or("");
-            if !id.is_empty() && (status == "OK" || status == "DEGRADED" || status == "DOWN") {
+            if !id.is_empty() && ZoneStatus::parse(status).is_some() {
                 tx.execute("UPDATE zones SET status=$2, updated_at=now() WHERE id=$1", &[&id, &status]).await?;
             }
         }
@@ -171,16 +958,44 @@ pub async fn restore(
         tx.execute("INSERT INTO zone_controls(zone_id) SELECT id FROM zones ON CONFLICT DO NOTHING", &[]).await?;
     }
 
-    // accounts + balances
-    if let Some(acs) = snap.get("accounts").and_then(|v| v.as_array()) {
-        for a in acs {
-            let id = a.get("id").and_then(|v| v.as_str()).unwrap_or("");
-            if id.is_empty() { continue; }
-            let zid = a.get("zone_id").and_then(|v| v.as_str()).unwrap_or("zone-eu");
-            let bal = a.get("balance_units").and_then(|v| v.as_i64()).unwrap_or(0);
-            tx.execute("INSERT INTO accounts(id, zone_id) VALUES($1,$2) ON CONFLICT DO NOTHING", &[&id, &zid]).await?;
-            tx.execute("INSERT INTO balances(account_id,balance_units,updated_at) VALUES($1,$2,now()) ON CONFLICT (account_id) DO UPDATE SET balance_units=EXCLUDED.balance_units, updated_at=now()", &[&id, &bal]).await?;
-        }
+    // accounts, each in its original zone
+    for a in &accounts {
+        tx.execute("INSERT INTO accounts(id, zone_id, currency) VALUES($1,$2,$3) ON CONFLICT DO NOTHING", &[&a.id, &a.zone_id, &a.currency]).await?;
+    }
+
+    // transaction history, oldest first so reversals follow their originals
+    for t in &history.transactions {
+        tx.execute(
+            "INSERT INTO transactions(id,request_id,payload_hash,from_account,to_account,amount_units,zone_id,metadata,currency,reverses_txn_id,created_by,created_at,request_epoch) \
+             VALUES($1::uuid,$2,$3,$4,$5,$6,$7,$8,$9,$10::uuid,$11,$12::text::timestamptz,$13)",
+            &[&t.id, &t.request_id, &t.payload_hash, &t.from_account, &t.to_account, &t.amount_units, &t.zone_id, &t.metadata, &t.currency, &t.reverses_txn_id, &t.created_by, &t.created_at, &t.request_epoch],
+        ).await?;
+    }
+    for p in &history.postings {
+        tx.execute(
+            "INSERT INTO postings(txn_id,account_id,direction,amount_units,created_at,projected_at,settled_at) \
+             VALUES($1::uuid,$2,$3,$4,$5::text::timestamptz,$6::text::timestamptz,$7::text::timestamptz)",
+            &[&p.txn_id, &p.account_id, &p.direction.as_str(), &p.amount_units, &p.created_at, &p.projected_at, &p.settled_at],
+        ).await?;
+    }
+
+    // balances: recomputed from complete history, otherwise trusted from the snapshot
+    let recomputed = if history.complete {
+        let r = recompute_balances(&history.postings);
+        Some(r.ok_or_else(|| AppError::BadRequest("snapshot postings overflow an account balance".into()))?)
+    } else {
+        None
+    };
+    for a in &accounts {
+        let (balance_units, pending_units) = match &recomputed {
+            Some(r) => r.get(a.id.as_str()).copied().unwrap_or_default(),
+            None => (a.balance_units, 0),
+        };
+        tx.execute(
+            "INSERT INTO balances(account_id,balance_units,pending_units,updated_at) VALUES($1,$2,$3,now()) \
+             ON CONFLICT (account_id) DO UPDATE SET balance_units=EXCLUDED.balance_units, pending_units=EXCLUDED.pending_units, updated_at=now()",
+            &[&a.id, &balance_units, &pending_units],
+        ).await?;
     }
 
     // incidents
@@ -242,5 +1057,427 @@ pub async fn restore(
     }
 
     tx.commit().await?;
-    Ok(Json(json!({"status": "ok"})))
+    Ok(Json(json!({
+        "status": "ok",
+        "version": version,
+        "transactions": history.transactions.len(),
+        "postings": history.postings.len(),
+        "balances": if recomputed.is_some() { "recomputed" } else { "from_snapshot" },
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{build_pool, PoolSettings};
+    use crate::projection::BalanceProjection;
+    use http_body_util::BodyExt;
+    use std::sync::Arc;
+
+    fn test_state(db: deadpool_postgres::Pool) -> AppState {
+        let (registry, metrics) = crate::state::init_metrics().unwrap();
+        AppState {
+            db: db.clone(),
+            db_read: db,
+            admin_keys: vec!["test-key".into()],
+            api_tokens: Vec::new(),
+            metrics_require_admin: false,
+            registry,
+            metrics,
+            balance_projection: BalanceProjection::Sync,
+            zone_rate_limit: 0,
+            zone_buckets: Default::default(),
+            settlement_delay: None,
+            reject_overdrafts: false,
+            idempotency_ttl: None,
+            transfer_batch_max: 1000,
+            transfer_limits: Default::default(),
+            idempotency_hash_exclude: Vec::new(),
+            transfer_batcher: None,
+            audit_tx: tokio::sync::broadcast::channel(1).0,
+            events_tx: tokio::sync::broadcast::channel(1).0,
+            admin_ops: Arc::new(tokio::sync::Semaphore::new(1)),
+            cors: Arc::new(crate::middleware::CorsConfig::from_lookup(|_| None)),
+            jwt: None,
+            clock: Arc::new(crate::clock::SystemClock),
+            zone_transitions: Default::default(),
+            shutdown: Default::default(),
+        }
+    }
+
+    fn account(id: &str, zone_id: &str, balance_units: i64) -> SnapshotAccount {
+        SnapshotAccount { id: id.into(), zone_id: zone_id.into(), balance_units, currency: None }
+    }
+
+    #[test]
+    fn snapshot_round_trip_keeps_zones() {
+        let zones: HashSet<String> = ["zone-eu".to_string(), "zone-us".to_string()].into();
+        let taken = vec![account("acct-eu-1", "zone-eu", 500), account("acct-us-1", "zone-us", -500)];
+        // the snapshot goes over the wire as JSON and comes back as the restore body
+        let body: serde_json::Value = serde_json::from_str(&json!({ "accounts": taken }).to_string()).unwrap();
+
+        let restored = snapshot_accounts(&body, &zones).unwrap();
+        assert_eq!(restored, taken);
+        assert_eq!(restored[1].zone_id, "zone-us");
+    }
+
+    #[test]
+    fn snapshot_with_unknown_or_missing_zone_is_400() {
+        let zones: HashSet<String> = ["zone-eu".to_string()].into();
+        let err = snapshot_accounts(&json!({ "accounts": [account("acct-1", "zone-mars", 1)] }), &zones).unwrap_err();
+        assert_eq!(err.status_and_code(), (StatusCode::BAD_REQUEST, "invalid_snapshot"));
+        assert!(err.message().contains("zone-mars"));
+
+        // no more silent fallback to zone-eu
+        let err = snapshot_accounts(&json!({ "accounts": [{ "id": "acct-1", "balance_units": 1 }] }), &zones).unwrap_err();
+        assert_eq!(err.status_and_code().0, StatusCode::BAD_REQUEST);
+
+        assert!(snapshot_accounts(&json!({}), &zones).unwrap().is_empty());
+        assert!(snapshot_accounts(&json!({ "accounts": [{ "id": "" }] }), &zones).unwrap().is_empty());
+    }
+
+    fn txn(id: &str, from: &str, to: &str, amount_units: i64) -> SnapshotTransaction {
+        SnapshotTransaction {
+            id: id.into(),
+            request_id: format!("req-{id}"),
+            payload_hash: "hash".into(),
+            from_account: from.into(),
+            to_account: to.into(),
+            amount_units,
+            zone_id: "zone-eu".into(),
+            metadata: json!({}),
+            currency: Some("EUR".into()),
+            reverses_txn_id: None,
+            created_by: "anonymous".into(),
+            created_at: "2026-05-01T10:00:00Z".into(),
+            request_epoch: 0,
+        }
+    }
+
+    fn legs(t: &SnapshotTransaction, settled: bool) -> [SnapshotPosting; 2] {
+        let posting = |account: &str, direction, settled_at: Option<&str>| SnapshotPosting {
+            txn_id: t.id.clone(),
+            account_id: account.into(),
+            direction,
+            amount_units: t.amount_units,
+            created_at: t.created_at.clone(),
+            projected_at: Some(t.created_at.clone()),
+            settled_at: settled_at.map(String::from),
+        };
+        [
+            posting(&t.from_account, Direction::Debit, Some(t.created_at.as_str())),
+            posting(&t.to_account, Direction::Credit, settled.then_some(t.created_at.as_str())),
+        ]
+    }
+
+    #[test]
+    fn snapshot_history_round_trip_recomputes_balances() {
+        let zones: HashSet<String> = ["zone-eu".to_string()].into();
+        let transactions = vec![
+            txn("00000000-0000-0000-0000-000000000001", "acct-a", "acct-b", 700),
+            txn("00000000-0000-0000-0000-000000000002", "acct-b", "acct-a", 200),
+            txn("00000000-0000-0000-0000-000000000003", "acct-a", "acct-b", 50),
+        ];
+        let mut postings: Vec<SnapshotPosting> = Vec::new();
+        postings.extend(legs(&transactions[0], true));
+        postings.extend(legs(&transactions[1], true));
+        postings.extend(legs(&transactions[2], false));
+        // stale balances in the snapshot are not trusted when history is complete
+        let accounts = vec![account("acct-a", "zone-eu", 1), account("acct-b", "zone-eu", 1)];
+        let taken = json!({
+            "version": SNAPSHOT_VERSION,
+            "history_complete": true,
+            "accounts": accounts,
+            "transactions": transactions,
+            "postings": postings,
+        });
+        let body: serde_json::Value = serde_json::from_str(&taken.to_string()).unwrap();
+
+        assert_eq!(snapshot_version(&body).unwrap(), "v3");
+        let restored = snapshot_accounts(&body, &zones).unwrap();
+        let ids: HashSet<&str> = restored.iter().map(|a| a.id.as_str()).collect();
+        let history = snapshot_history(&body, &ids).unwrap();
+        assert!(history.complete);
+        assert_eq!(history.transactions.len(), 3);
+        assert_eq!(history.postings.len(), 6);
+        assert_eq!(history.transactions, transactions);
+        assert_eq!(history.postings, postings);
+
+        let balances = recompute_balances(&history.postings).unwrap();
+        assert_eq!(balances.get("acct-a"), Some(&(-550, 0)));
+        // the last credit is still pending settlement
+        assert_eq!(balances.get("acct-b"), Some(&(500, 50)));
+    }
+
+    #[test]
+    fn snapshot_history_must_be_consistent() {
+        let ids: HashSet<&str> = ["acct-a", "acct-b"].into();
+        let t = txn("00000000-0000-0000-0000-000000000001", "acct-a", "acct-b", 700);
+        let [debit, mut credit] = legs(&t, true);
+
+        credit.amount_units = 699;
+        let err = snapshot_history(&json!({ "transactions": [&t], "postings": [&debit, &credit] }), &ids).unwrap_err();
+        assert_eq!(err.status_and_code(), (StatusCode::BAD_REQUEST, "invalid_snapshot"));
+
+        credit.amount_units = 700;
+        let err = snapshot_history(&json!({ "transactions": [], "postings": [&debit] }), &ids).unwrap_err();
+        assert!(err.message().contains("unknown transaction"));
+
+        let err = snapshot_history(&json!({ "transactions": [&t], "postings": [&debit, &credit] }), &["acct-a"].into()).unwrap_err();
+        assert!(err.message().contains("unknown account acct-b"));
+
+        let ok = snapshot_history(&json!({ "transactions": [&t], "postings": [&debit, &credit] }), &ids).unwrap();
+        // cut-off snapshots keep their balances
+        assert!(!ok.complete);
+    }
+
+    #[test]
+    fn export_is_one_json_transaction_per_line() {
+        let transactions: Vec<SnapshotTransaction> = (1..=5)
+            .map(|i| txn(&format!("00000000-0000-0000-0000-00000000000{i}"), "acct-a", "acct-b", i * 100))
+            .collect();
+        let postings: Vec<SnapshotPosting> = transactions.iter().flat_map(|t| legs(t, true)).collect();
+
+        // two cursor batches, each batch's postings fetched alongside it
+        let (first, rest) = transactions.split_at(3);
+        let mut body = ndjson_lines(first, &postings[..6]);
+        body.push_str(&ndjson_lines(rest, &postings[6..]));
+
+        let lines: Vec<serde_json::Value> = body
+            .lines()
+            .map(|l| serde_json::from_str(l).expect("every line is a JSON object"))
+            .collect();
+        assert_eq!(lines.len(), transactions.len());
+        assert!(body.ends_with('\n'));
+        for (line, t) in lines.iter().zip(&transactions) {
+            assert_eq!(line["id"], t.id.as_str());
+            assert_eq!(line["amount_units"], t.amount_units);
+            assert_eq!(line["postings"].as_array().unwrap().len(), 2);
+            assert_eq!(line["postings"][0]["direction"], "DEBIT");
+        }
+    }
+
+    #[test]
+    fn snapshot_version_is_required() {
+        assert_eq!(snapshot_version(&json!({ "version": "v2" })).unwrap(), "v2");
+        for snap in [json!({}), json!({ "version": "v9" }), json!({ "version": 3 })] {
+            let err = snapshot_version(&snap).unwrap_err();
+            assert_eq!(err.status_and_code(), (StatusCode::BAD_REQUEST, "unsupported_snapshot_version"));
+        }
+    }
+
+    #[tokio::test]
+    async fn concurrent_restore_is_429() {
+        // a database that never answers keeps the first restore busy until the acquire timeout
+        let silent = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("postgres://ledger@127.0.0.1:{}/ledger", silent.local_addr().unwrap().port());
+        let settings = PoolSettings { acquire_timeout: Some(Duration::from_millis(500)), ..PoolSettings::default() };
+        let st = test_state(build_pool(&url, &settings).unwrap());
+        let mut headers = HeaderMap::new();
+        headers.insert("x-admin-key", "test-key".parse().unwrap());
+
+        let first = restore(State(st.clone()), headers.clone(), Json(json!({})));
+        let second = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            restore(State(st.clone()), headers.clone(), Json(json!({}))).await
+        };
+        let (first, second) = tokio::join!(first, second);
+
+        assert_eq!(second.unwrap_err().status_and_code().0, StatusCode::TOO_MANY_REQUESTS);
+        // the first one got the permit and only failed on the unreachable database
+        assert_eq!(first.unwrap_err().status_and_code().0, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(st.admin_ops.available_permits(), 1);
+        drop(silent);
+    }
+
+    #[tokio::test]
+    async fn metrics_open_by_default() {
+        let st = test_state(build_pool("postgres://ledger@primary.invalid/ledger", &PoolSettings::default()).unwrap());
+        st.metrics.transfers_total.with_label_values(&["zone-eu", "posted"]).inc();
+        let res = metrics(State(st), HeaderMap::new()).await.into_response();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(res.headers()[header::CONTENT_TYPE].to_str().unwrap().starts_with("text/plain"));
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        let text = std::str::from_utf8(&body).unwrap();
+        assert!(text.contains("transfers_total{outcome=\"posted\",zone_id=\"zone-eu\"} 1"));
+    }
+
+    #[tokio::test]
+    async fn metrics_expose_build_info() {
+        let st = test_state(build_pool("postgres://ledger@primary.invalid/ledger", &PoolSettings::default()).unwrap());
+        let res = metrics(State(st), HeaderMap::new()).await.into_response();
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        let text = std::str::from_utf8(&body).unwrap();
+        let line = text.lines().find(|l| l.starts_with("build_info{")).expect("build_info series");
+        assert!(line.contains(&format!("version=\"{}\"", env!("CARGO_PKG_VERSION"))), "{line}");
+        assert!(line.contains("language=\"rust\""), "{line}");
+        assert!(line.contains(&format!("revision=\"{}\"", REVISION.unwrap_or("unknown"))), "{line}");
+        assert!(line.ends_with(" 1"), "{line}");
+    }
+
+    #[tokio::test]
+    async fn metrics_can_require_admin_key() {
+        let mut st = test_state(build_pool("postgres://ledger@primary.invalid/ledger", &PoolSettings::default()).unwrap());
+        st.metrics_require_admin = true;
+
+        let res = metrics(State(st.clone()), HeaderMap::new()).await.into_response();
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+        let mut wrong = HeaderMap::new();
+        wrong.insert("x-admin-key", "nope".parse().unwrap());
+        assert_eq!(metrics(State(st.clone()), wrong).await.into_response().status(), StatusCode::FORBIDDEN);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-admin-key", "test-key".parse().unwrap());
+        let res = metrics(State(st), headers).await.into_response();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn readyz_is_503_when_pool_closed() {
+        let pool = build_pool("postgres://ledger@primary.invalid/ledger", &PoolSettings::default()).unwrap();
+        pool.close();
+        let (status, Json(body)) = readiness(&pool).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "unavailable");
+        assert_eq!(body["pool"]["in_use"], 0);
+    }
+
+    #[test]
+    fn admin_key_must_match_exactly() {
+        let keys = parse_admin_keys(Some("s3cret-key"));
+        assert!(admin_key_matches(&keys, "s3cret-key"));
+        // same length, different bytes
+        assert!(!admin_key_matches(&keys, "s3cret-kez"));
+        assert!(!admin_key_matches(&keys, "s3cret"));
+        assert!(!admin_key_matches(&keys, ""));
+    }
+
+    #[test]
+    fn rotation_accepts_old_and_new_keys() {
+        let keys = parse_admin_keys(Some(" old-key , new-key ,"));
+        assert_eq!(keys, vec!["old-key", "new-key"]);
+        assert!(admin_key_matches(&keys, "old-key"));
+        assert!(admin_key_matches(&keys, "new-key"));
+        assert!(!admin_key_matches(&keys, "other-k"));
+    }
+
+    #[test]
+    fn no_configured_key_refuses_everything() {
+        assert!(parse_admin_keys(None).is_empty());
+        assert!(parse_admin_keys(Some(" , ")).is_empty());
+        assert!(!admin_key_matches(&[], "anything"));
+        assert!(!admin_key_matches(&[], ""));
+        let err = AppError::Forbidden("missing or invalid admin key".into());
+        assert_eq!(err.status_and_code(), (StatusCode::FORBIDDEN, "forbidden"));
+    }
+
+    /// Transfer of 100 from `acct-a-{run}` to `acct-b-{run}` in zone-eu.
+    /// Posts transfer `n` of 100 units from `acct-a-{run}` to `acct-b-{run}`.
+    async fn post_transfer(st: &AppState, run: uuid::Uuid, n: u32) {
+        use crate::handlers::transfers::{create_transfer, CreateTransferQuery, CreateTransferRequest};
+        let req: CreateTransferRequest = serde_json::from_value(json!({
+            "request_id": format!("req-{run}-{n}"),
+            "from_account": format!("acct-a-{run}"),
+            "to_account": format!("acct-b-{run}"),
+            "amount_units": 100,
+            "zone_id": "zone-eu",
+        }))
+        .unwrap();
+        let q = Query(CreateTransferQuery { include_balances: false, dry_run: false });
+        create_transfer(State(st.clone()), q, Default::default(), None, Ok(Json(req))).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn reconcile_apply_rewrites_a_corrupted_balance_and_audits_it() {
+        let mut config = crate::testing::test_db_config();
+        config.admin_keys = vec!["test-key".into()];
+        let st = crate::app::build_state(config).await.unwrap();
+        let run = uuid::Uuid::new_v4();
+        post_transfer(&st, run, 1).await;
+        let account = format!("acct-b-{run}");
+        let client = st.db.get().await.unwrap();
+        client
+            .execute("UPDATE balances SET balance_units=balance_units+7 WHERE account_id=$1", &[&account])
+            .await
+            .unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-admin-key", "test-key".parse().unwrap());
+        let actor = format!("auditor-{run}");
+        let q = ReconcileQuery { apply: true, actor: Some(actor.clone()), reason: Some("corrupted row".into()) };
+        let Json(body) = reconcile(State(st.clone()), headers, Query(q)).await.unwrap();
+
+        assert_eq!(body["applied"], true);
+        let found = body["discrepancies"].as_array().unwrap().iter().find(|d| d["account_id"] == account.as_str()).unwrap();
+        assert_eq!((found["stored_balance_units"].as_i64(), found["ledger_balance_units"].as_i64()), (Some(107), Some(100)));
+        assert_eq!(found["drift_units"], 7);
+
+        let balance: i64 = client
+            .query_one("SELECT balance_units FROM balances WHERE account_id=$1", &[&account])
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(balance, 100, "the ledger value was written back");
+        let audit = client
+            .query_one(
+                "SELECT target_id, reason, details FROM audit_log WHERE actor=$1 AND action='RECONCILE_BALANCES'",
+                &[&actor],
+            )
+            .await
+            .unwrap();
+        assert_eq!(audit.get::<_, Option<String>>(1).as_deref(), Some("corrupted row"));
+        let details: serde_json::Value = audit.get(2);
+        assert!(details["account_ids"].as_array().unwrap().contains(&json!(account)), "{details}");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn checkpoint_then_balance_as_of_replays_only_later_postings() {
+        use crate::handlers::accounts::{account_balance, BalanceAsOfQuery};
+        use axum::extract::Path;
+        let mut config = crate::testing::test_db_config();
+        config.admin_keys = vec!["test-key".into()];
+        let st = crate::app::build_state(config).await.unwrap();
+        let run = uuid::Uuid::new_v4();
+        let account = format!("acct-b-{run}");
+        let client = st.db.get().await.unwrap();
+
+        post_transfer(&st, run, 1).await;
+        let cut: time::OffsetDateTime = client.query_one("SELECT clock_timestamp()", &[]).await.unwrap().get(0);
+        let cut_str = to_rfc3339(cut).unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("x-admin-key", "test-key".parse().unwrap());
+        let Json(body) =
+            checkpoint(State(st.clone()), headers, Query(CheckpointQuery { as_of: Some(cut_str.clone()) })).await.unwrap();
+        assert!(body["accounts"].as_u64().unwrap() >= 2, "{body}");
+        let stored: i64 = client
+            .query_one("SELECT balance_units FROM balance_checkpoints WHERE account_id=$1 AND as_of=$2", &[&account, &cut])
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(stored, 100);
+        post_transfer(&st, run, 2).await;
+
+        let balance = |as_of: Option<String>| {
+            let st = st.clone();
+            let account = account.clone();
+            async move {
+                let Json(body) = account_balance(State(st), Path(account), Query(BalanceAsOfQuery { as_of })).await.unwrap();
+                (body["balance_units"].as_i64().unwrap(), body["checkpoint_as_of"].clone())
+            }
+        };
+        assert_eq!(balance(Some(cut_str.clone())).await, (100, json!(cut_str)));
+        assert_eq!(balance(None).await, (200, json!(cut_str)));
+
+        // the checkpoint is trusted rather than re-derived from the postings before it
+        client
+            .execute("UPDATE balance_checkpoints SET balance_units=balance_units+5 WHERE account_id=$1 AND as_of=$2", &[&account, &cut])
+            .await
+            .unwrap();
+        assert_eq!(balance(Some(cut_str.clone())).await, (105, json!(cut_str)));
+        assert_eq!(balance(None).await, (205, json!(cut_str)));
+        let before = to_rfc3339(cut - time::Duration::seconds(60)).unwrap();
+        assert_eq!(balance(Some(before)).await, (0, serde_json::Value::Null));
+    }
 }
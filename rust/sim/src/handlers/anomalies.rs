@@ -0,0 +1,149 @@
+use axum::{extract::{Query, State}, http::HeaderMap, Json};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use utoipa::IntoParams;
+
+use crate::error::{AppError, ErrorBody};
+use crate::handlers::admin::admin_guard;
+use crate::state::AppState;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AnomalyQuery {
+    #[serde(default = "default_window_secs")]
+    pub window_secs: i64,
+    #[serde(default = "default_move_threshold")]
+    pub move_threshold: i64,
+    #[serde(default = "default_frequency_threshold")]
+    pub frequency_threshold: i64,
+}
+
+fn default_window_secs() -> i64 { 300 }
+fn default_move_threshold() -> i64 { 3600 }
+fn default_frequency_threshold() -> i64 { 100 }
+
+/// Per-account activity within the window.
+pub struct WindowStats {
+    pub account_id: String,
+    pub net_units: i64,
+    pub postings: i64,
+    pub balance_units: i64,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Anomaly {
+    LargeMove { account_id: String, net_units: i64, threshold: i64 },
+    WentNegative { account_id: String, balance_before: i64, balance_units: i64 },
+    HighFrequency { account_id: String, postings: i64, threshold: i64 },
+}
+
+pub fn classify(s: &WindowStats, move_threshold: i64, frequency_threshold: i64) -> Vec<Anomaly> {
+    let mut out = Vec::new();
+    if s.net_units.saturating_abs() > move_threshold {
+        out.push(Anomaly::LargeMove {
+            account_id: s.account_id.clone(),
+            net_units: s.net_units,
+            threshold: move_threshold,
+        });
+    }
+    let balance_before = s.balance_units.saturating_sub(s.net_units);
+    if balance_before > 0 && s.balance_units < 0 {
+        out.push(Anomaly::WentNegative {
+            account_id: s.account_id.clone(),
+            balance_before,
+            balance_units: s.balance_units,
+        });
+    }
+    if s.postings > frequency_threshold {
+        out.push(Anomaly::HighFrequency {
+            account_id: s.account_id.clone(),
+            postings: s.postings,
+            threshold: frequency_threshold,
+        });
+    }
+    out
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/sim/anomalies",
+    tag = "sim",
+    params(AnomalyQuery, ("x-admin-key" = String, Header, description = "One of the comma-separated ADMIN_KEY values")),
+    responses(
+        (status = 200, description = "Anomalies in the window", body = serde_json::Value),
+        (status = 400, description = "Invalid input", body = ErrorBody),
+        (status = 403, description = "Missing or wrong admin key", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn list_anomalies(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Query(q): Query<AnomalyQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    admin_guard(&st, &headers)?;
+    let window_secs = q.window_secs.clamp(1, 86_400 * 30) as f64;
+    let client = st.db_read.get().await?;
+
+    let rows = client
+        .query(
+            "SELECT w.account_id, w.net_units, w.postings, COALESCE(b.balance_units, 0) AS balance_units FROM \
+             (SELECT account_id, SUM(CASE WHEN direction='CREDIT' THEN amount_units ELSE -amount_units END)::bigint AS net_units, COUNT(*) AS postings \
+             FROM postings WHERE created_at >= now() - make_interval(secs => $1) GROUP BY account_id) w \
+             LEFT JOIN balances b ON b.account_id=w.account_id ORDER BY w.account_id",
+            &[&window_secs],
+        )
+        .await?;
+
+    let anomalies: Vec<Anomaly> = rows
+        .iter()
+        .flat_map(|r| {
+            let stats = WindowStats {
+                account_id: r.get("account_id"),
+                net_units: r.get("net_units"),
+                postings: r.get("postings"),
+                balance_units: r.get("balance_units"),
+            };
+            classify(&stats, q.move_threshold, q.frequency_threshold)
+        })
+        .collect();
+
+    Ok(Json(json!({ "window_secs": window_secs as i64, "anomalies": anomalies })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(net_units: i64, postings: i64, balance_units: i64) -> WindowStats {
+        WindowStats { account_id: "acct".into(), net_units, postings, balance_units }
+    }
+
+    #[test]
+    fn quiet_account_has_no_anomalies() {
+        assert!(classify(&stats(-50, 3, 500), 3600, 100).is_empty());
+    }
+
+    #[test]
+    fn large_move_in_either_direction() {
+        assert!(matches!(classify(&stats(5000, 1, 5000), 3600, 100)[..], [Anomaly::LargeMove { .. }]));
+        assert!(matches!(classify(&stats(-5000, 1, -5000), 3600, 100)[..], [Anomaly::LargeMove { .. }]));
+    }
+
+    #[test]
+    fn positive_to_negative() {
+        assert_eq!(classify(&stats(-300, 2, -100), 3600, 100), vec![Anomaly::WentNegative {
+            account_id: "acct".into(),
+            balance_before: 200,
+            balance_units: -100,
+        }]);
+        // started at zero: not a crossing
+        assert!(classify(&stats(-300, 2, -300), 3600, 100).is_empty());
+    }
+
+    #[test]
+    fn high_frequency() {
+        assert!(matches!(classify(&stats(0, 101, 0), 3600, 100)[..], [Anomaly::HighFrequency { .. }]));
+    }
+}
@@ -0,0 +1,329 @@
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+use time::OffsetDateTime;
+use utoipa::IntoParams;
+
+use crate::error::{AppError, ErrorBody};
+use crate::handlers::admin::{admin_guard, admin_op_permit, CHECKPOINT_SQL};
+use crate::handlers::audit::publish_audit;
+use crate::reconcile::LEDGER_ZONE;
+use crate::state::AppState;
+use crate::util::{parse_rfc3339, to_rfc3339};
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ArchiveQuery {
+    /// RFC 3339 cutoff; transactions created strictly before it move. Must not be in the future.
+    pub before: String,
+    /// Only count what would move.
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Transactions moved per database transaction, clamped to 1..=10000.
+    #[serde(default = "default_batch_size")]
+    pub batch_size: i64,
+    /// Recorded in the audit entry (default `admin`).
+    pub actor: Option<String>,
+    pub reason: Option<String>,
+}
+
+fn default_batch_size() -> i64 { 1000 }
+
+/// Archived `request_id`s no longer count for idempotency, so only transactions
+/// whose key has already expired may move: the cutoff must be at least the
+/// idempotency TTL in the past, and nothing moves while keys never expire.
+fn check_cutoff(before: OffsetDateTime, now: OffsetDateTime, ttl: Option<Duration>) -> Result<(), AppError> {
+    if before > now {
+        return Err(AppError::BadRequest("before must not be in the future".into()));
+    }
+    let Some(ttl) = ttl else {
+        return Err(AppError::Detailed {
+            status: StatusCode::CONFLICT,
+            code: "idempotency_keys_never_expire",
+            message: "archiving needs IDEMPOTENCY_TTL_SECONDS; archived request_ids could be reused".into(),
+            details: json!({}),
+        });
+    };
+    let latest = now - ttl;
+    if before > latest {
+        return Err(AppError::Detailed {
+            status: StatusCode::BAD_REQUEST,
+            code: "cutoff_inside_idempotency_window",
+            message: "before must be at least IDEMPOTENCY_TTL_SECONDS in the past".into(),
+            details: json!({
+                "before": to_rfc3339(before)?,
+                "latest_cutoff": to_rfc3339(latest)?,
+                "idempotency_ttl_secs": ttl.as_secs(),
+            }),
+        });
+    }
+    Ok(())
+}
+
+/// `alias` (a `transactions` row) is old enough to archive and nothing live
+/// still needs it: no incident points at it and every posting is projected and
+/// settled, so neither background worker will look for it again.
+fn movable(alias: &str) -> String {
+    format!(
+        "{alias}.created_at < $1 \
+         AND NOT EXISTS (SELECT 1 FROM incidents i WHERE i.related_txn_id={alias}.id) \
+         AND NOT EXISTS (SELECT 1 FROM postings p WHERE p.txn_id={alias}.id AND (p.projected_at IS NULL OR p.settled_at IS NULL))"
+    )
+}
+
+/// Transactions that must stay because a reversal of them (or of a reversal of
+/// them, and so on) stays: `reverses_txn_id` would otherwise point nowhere.
+fn pinned_cte() -> String {
+    format!(
+        "WITH RECURSIVE pinned(id) AS ( \
+         SELECT r.reverses_txn_id FROM transactions r WHERE r.reverses_txn_id IS NOT NULL AND NOT ({}) \
+         UNION SELECT t.reverses_txn_id FROM transactions t JOIN pinned ON pinned.id=t.id WHERE t.reverses_txn_id IS NOT NULL)",
+        movable("r")
+    )
+}
+
+/// Every archivable transaction. Newest first: a reversal is always newer than
+/// what it reverses, so it leaves in the same or an earlier batch.
+fn candidates_sql() -> String {
+    format!(
+        "{} SELECT t.id::text FROM transactions t WHERE {} AND t.id NOT IN (SELECT id FROM pinned WHERE id IS NOT NULL) \
+         ORDER BY t.created_at DESC, t.id",
+        pinned_cte(),
+        movable("t")
+    )
+}
+
+/// Every column of `transactions`, named because `transactions_archive` has
+/// `archived_at` before the columns added after migration 0023.
+const TRANSACTION_COLUMNS: &str = "id, request_id, payload_hash, from_account, to_account, amount_units, zone_id, metadata, \
+     created_at, reverses_txn_id, currency, created_by, request_epoch";
+
+/// Move transactions created before `before`, with their postings, into
+/// `transactions_archive` and `postings_archive`.
+///
+/// Balances are untouched. A checkpoint at the cutoff is written first, so
+/// balance-as-of lookups at or after it no longer need the archived postings;
+/// the reconciler reads them through the `ledger_postings` view.
+#[utoipa::path(
+    post,
+    path = "/v1/sim/archive",
+    tag = "sim",
+    params(ArchiveQuery, ("x-admin-key" = String, Header, description = "One of the comma-separated ADMIN_KEY values")),
+    responses(
+        (status = 200, description = "Transactions and postings moved, or that would move with dry_run", body = serde_json::Value),
+        (status = 400, description = "Invalid or future cutoff, or cutoff_inside_idempotency_window", body = ErrorBody),
+        (status = 401, description = "With JWT verification enabled: missing_token, invalid_token or token_expired", body = ErrorBody),
+        (status = 403, description = "Missing or wrong admin key, or token_not_accepted (JWT for another audience or issuer)", body = ErrorBody),
+        (status = 409, description = "idempotency_keys_never_expire: IDEMPOTENCY_TTL_SECONDS is unset", body = ErrorBody),
+        (status = 429, description = "Another snapshot, restore or archive is running", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn archive(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Query(q): Query<ArchiveQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    admin_guard(&st, &headers)?;
+    let before = parse_rfc3339("before", &q.before)?;
+    check_cutoff(before, st.clock.now(), st.idempotency_ttl)?;
+    let batch_size = q.batch_size.clamp(1, 10_000);
+
+    if q.dry_run {
+        let client = st.db_read.get().await?;
+        let row = client
+            .query_one(
+                &format!(
+                    "WITH c AS ({}) SELECT COUNT(*) AS transactions, \
+                     (SELECT COUNT(*) FROM postings p WHERE p.txn_id IN (SELECT id::uuid FROM c)) AS postings FROM c",
+                    candidates_sql()
+                ),
+                &[&before],
+            )
+            .await?;
+        return Ok(Json(json!({
+            "before": to_rfc3339(before)?,
+            "dry_run": true,
+            "transactions": row.get::<_, i64>("transactions"),
+            "postings": row.get::<_, i64>("postings"),
+        })));
+    }
+
+    // a snapshot taken halfway would miss the rows already moved
+    let _permit = admin_op_permit(&st)?;
+    let mut client = st.db.get().await?;
+    client.execute(CHECKPOINT_SQL, &[&before]).await?;
+
+    let batch_sql = format!("{} LIMIT $2 FOR UPDATE OF t SKIP LOCKED", candidates_sql());
+    let (mut transactions, mut postings, mut batches) = (0u64, 0u64, 0u64);
+    loop {
+        let tx = client.transaction().await?;
+        let ids: Vec<String> = tx.query(&batch_sql, &[&before, &batch_size]).await?.iter().map(|r| r.get(0)).collect();
+        if ids.is_empty() {
+            break;
+        }
+        postings += tx
+            .execute(
+                "INSERT INTO postings_archive SELECT p.*, now() FROM postings p WHERE p.txn_id = ANY($1::text[]::uuid[])",
+                &[&ids],
+            )
+            .await?;
+        transactions += tx
+            .execute(
+                &format!(
+                    "INSERT INTO transactions_archive({TRANSACTION_COLUMNS}) SELECT {TRANSACTION_COLUMNS} FROM transactions \
+                     WHERE id = ANY($1::text[]::uuid[])"
+                ),
+                &[&ids],
+            )
+            .await?;
+        // postings go with their transaction (ON DELETE CASCADE)
+        tx.execute("DELETE FROM transactions WHERE id = ANY($1::text[]::uuid[])", &[&ids]).await?;
+        tx.commit().await?;
+        batches += 1;
+    }
+
+    if transactions > 0 {
+        let actor = q.actor.as_deref().unwrap_or("admin");
+        let details = json!({ "before": to_rfc3339(before)?, "transactions": transactions, "postings": postings });
+        let row = client
+            .query_one(
+                "INSERT INTO audit_log(actor,action,target_type,target_id,reason,details) VALUES($1,'ARCHIVE_TRANSACTIONS','zone',$2,$3,$4) \
+                 RETURNING id::text, actor, action, target_type, target_id, reason, details, created_at",
+                &[&actor, &LEDGER_ZONE, &q.reason, &details],
+            )
+            .await?;
+        publish_audit(&st, &row);
+    }
+
+    Ok(Json(json!({
+        "before": to_rfc3339(before)?,
+        "dry_run": false,
+        "transactions": transactions,
+        "postings": postings,
+        "batches": batches,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reversals_of_kept_transactions_pin_the_original() {
+        let sql = candidates_sql();
+        assert!(sql.starts_with("WITH RECURSIVE pinned(id) AS ("));
+        assert!(sql.contains("NOT (r.created_at < $1 AND"));
+        assert!(sql.contains("WHERE t.created_at < $1 AND"));
+        assert!(sql.ends_with("ORDER BY t.created_at DESC, t.id"));
+    }
+
+    #[test]
+    fn cutoff_must_be_past_the_idempotency_window() {
+        let now = OffsetDateTime::now_utc();
+        let ttl = Some(Duration::from_secs(3600));
+        assert!(check_cutoff(now - Duration::from_secs(3600), now, ttl).is_ok());
+        let err = check_cutoff(now - Duration::from_secs(3599), now, ttl).unwrap_err();
+        assert_eq!(err.status_and_code(), (StatusCode::BAD_REQUEST, "cutoff_inside_idempotency_window"));
+        let err = check_cutoff(now + Duration::from_secs(1), now, ttl).unwrap_err();
+        assert_eq!(err.status_and_code().0, StatusCode::BAD_REQUEST);
+        // keys that never expire would all become reusable
+        let err = check_cutoff(now - Duration::from_secs(86_400 * 365), now, None).unwrap_err();
+        assert_eq!(err.status_and_code(), (StatusCode::CONFLICT, "idempotency_keys_never_expire"));
+    }
+
+    async fn count(client: &deadpool_postgres::Object, sql: &str, ids: &[String]) -> i64 {
+        client.query_one(sql, &[&ids]).await.unwrap().get(0)
+    }
+
+    async fn balances(client: &deadpool_postgres::Object, accounts: &[String]) -> Vec<i64> {
+        client
+            .query("SELECT balance_units FROM balances WHERE account_id = ANY($1) ORDER BY account_id", &[&accounts])
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| r.get(0))
+            .collect()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn archives_transactions_before_the_cutoff() {
+        use crate::handlers::transfers::{create_transfer, CreateTransferQuery, CreateTransferRequest};
+        use http_body_util::BodyExt;
+        let mut config = crate::testing::test_db_config();
+        config.admin_keys = vec!["k".into()];
+        config.idempotency_ttl = Some(Duration::from_secs(86_400));
+        let st = crate::app::build_state(config).await.unwrap();
+        let run = uuid::Uuid::new_v4();
+        let mut ids = Vec::new();
+        for i in 0..3 {
+            let req = CreateTransferRequest {
+                request_id: format!("req-archive-{i}-{run}"),
+                from_account: format!("acct-a-{run}"),
+                to_account: format!("acct-b-{run}"),
+                amount_units: 10 + i,
+                zone_id: "zone-eu".into(),
+                metadata: json!({}),
+                currency: None,
+            };
+            let q = Query(CreateTransferQuery { include_balances: false, dry_run: false });
+            let res = create_transfer(State(st.clone()), q, Default::default(), None, Ok(Json(req))).await.unwrap();
+            let created: serde_json::Value =
+                serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+            ids.push(created["transaction_id"].as_str().unwrap().to_string());
+        }
+
+        // backdate two of them well before anything another test writes
+        let client = st.db.get().await.unwrap();
+        let old = &ids[..2];
+        for (sql, id_column) in [("transactions", "id"), ("postings", "txn_id")] {
+            client
+                .execute(
+                    &format!("UPDATE {sql} SET created_at='1990-01-01T00:00:00Z' WHERE {id_column} = ANY($1::text[]::uuid[])"),
+                    &[&old],
+                )
+                .await
+                .unwrap();
+        }
+        let accounts = [format!("acct-a-{run}"), format!("acct-b-{run}")];
+        let before_balances = balances(&client, &accounts).await;
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-admin-key", "k".parse().unwrap());
+        let query = |dry_run: bool| {
+            Query(ArchiveQuery {
+                before: "1990-01-02T00:00:00Z".into(),
+                dry_run,
+                batch_size: 1,
+                actor: None,
+                reason: Some(format!("test {run}")),
+            })
+        };
+        let Json(dry) = archive(State(st.clone()), headers.clone(), query(true)).await.unwrap();
+        assert_eq!((dry["transactions"].as_i64(), dry["postings"].as_i64()), (Some(2), Some(4)));
+
+        let Json(done) = archive(State(st.clone()), headers.clone(), query(false)).await.unwrap();
+        assert_eq!((done["transactions"].as_u64(), done["postings"].as_u64()), (Some(2), Some(4)));
+        assert_eq!(done["batches"], 2);
+
+        let live = "SELECT COUNT(*) FROM transactions WHERE id = ANY($1::text[]::uuid[])";
+        let live_postings = "SELECT COUNT(*) FROM postings WHERE txn_id = ANY($1::text[]::uuid[])";
+        let archived = "SELECT COUNT(*) FROM transactions_archive WHERE id = ANY($1::text[]::uuid[])";
+        let archived_postings = "SELECT COUNT(*) FROM postings_archive WHERE txn_id = ANY($1::text[]::uuid[])";
+        assert_eq!(count(&client, live, old).await, 0);
+        assert_eq!(count(&client, live_postings, old).await, 0);
+        assert_eq!(count(&client, archived, old).await, 2);
+        assert_eq!(count(&client, archived_postings, old).await, 4);
+        assert_eq!(count(&client, live, &ids[2..]).await, 1);
+        assert_eq!(balances(&client, &accounts).await, before_balances);
+
+        let Json(again) = archive(State(st), headers, query(true)).await.unwrap();
+        assert_eq!(again["transactions"], 0);
+    }
+}
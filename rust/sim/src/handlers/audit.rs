@@ -1,12 +1,25 @@
-use axum::{extract::{Path, Query, State}, Json};
+use axum::{
+    extract::{Path, Query, State},
+    http::HeaderMap,
+    response::sse::{Event, KeepAlive, Sse},
+    Json,
+};
+use futures::{Stream, StreamExt};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::convert::Infallible;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_postgres::types::ToSql;
+use tracing::warn;
+use utoipa::IntoParams;
 
-use crate::error::AppError;
+use crate::error::{AppError, ErrorBody};
+use crate::handlers::admin::admin_guard;
 use crate::state::AppState;
-use crate::util::fmt_rfc3339;
+use crate::util::{parse_rfc3339, to_rfc3339};
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct AuditQuery {
     #[serde(default = "default_limit")]
     pub limit: i64,
@@ -14,25 +27,165 @@ pub struct AuditQuery {
 
 fn default_limit() -> i64 { 100 }
 
-#[derive(Serialize)]
-struct AuditEntry {
-    id: String,
-    actor: String,
-    action: String,
-    target_type: String,
-    target_id: String,
-    reason: Option<String>,
-    details: serde_json::Value,
-    created_at: String,
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditStreamQuery {
+    pub actor: Option<String>,
 }
 
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditSearchQuery {
+    pub actor: Option<String>,
+    /// e.g. `SET_ZONE_STATUS`.
+    pub action: Option<String>,
+    /// e.g. `zone` or `incident`.
+    pub target_type: Option<String>,
+    pub target_id: Option<String>,
+    /// Inclusive lower bound on `created_at`, RFC3339.
+    pub since: Option<String>,
+    /// Exclusive upper bound on `created_at`, RFC3339.
+    pub until: Option<String>,
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+    #[serde(default)]
+    pub offset: i64,
+}
+
+/// Parsed and bounded [`AuditSearchQuery`].
+struct AuditFilter {
+    actor: Option<String>,
+    action: Option<String>,
+    target_type: Option<String>,
+    target_id: Option<String>,
+    since: Option<time::OffsetDateTime>,
+    until: Option<time::OffsetDateTime>,
+    limit: i64,
+    offset: i64,
+}
+
+impl AuditFilter {
+    /// Reject bad timestamps, an empty range or a negative offset and clamp `limit` to 1..=500.
+    fn from_query(q: AuditSearchQuery) -> Result<Self, AppError> {
+        let since = q.since.as_deref().map(|s| parse_rfc3339("since", s)).transpose()?;
+        let until = q.until.as_deref().map(|s| parse_rfc3339("until", s)).transpose()?;
+        if let (Some(since), Some(until)) = (since, until) {
+            if since >= until {
+                return Err(AppError::BadRequest("since must be before until".into()));
+            }
+        }
+        if q.offset < 0 {
+            return Err(AppError::BadRequest("offset must be >= 0".into()));
+        }
+        Ok(Self {
+            actor: q.actor,
+            action: q.action,
+            target_type: q.target_type,
+            target_id: q.target_id,
+            since,
+            until,
+            limit: q.limit.clamp(1, 500),
+            offset: q.offset,
+        })
+    }
+
+    /// The page query with only the given filters, newest first; `id` breaks
+    /// ties so pages do not overlap.
+    fn page_sql(&self) -> (String, Vec<&(dyn ToSql + Sync)>) {
+        let mut sql = String::from(
+            "SELECT id::text AS id, actor, action, target_type, target_id, reason, details, created_at FROM audit_log",
+        );
+        let mut conds: Vec<String> = Vec::new();
+        let mut params: Vec<&(dyn ToSql + Sync)> = Vec::new();
+        for (column, value) in [
+            ("actor", &self.actor),
+            ("action", &self.action),
+            ("target_type", &self.target_type),
+            ("target_id", &self.target_id),
+        ] {
+            if let Some(value) = value {
+                params.push(value);
+                conds.push(format!("{column}=${}", params.len()));
+            }
+        }
+        if let Some(since) = &self.since {
+            params.push(since);
+            conds.push(format!("created_at >= ${}", params.len()));
+        }
+        if let Some(until) = &self.until {
+            params.push(until);
+            conds.push(format!("created_at < ${}", params.len()));
+        }
+        if !conds.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conds.join(" AND "));
+        }
+        params.push(&self.limit);
+        params.push(&self.offset);
+        sql.push_str(&format!(" ORDER BY created_at DESC, id DESC LIMIT ${} OFFSET ${}", params.len() - 1, params.len()));
+        (sql, params)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AuditEntry {
+    pub id: String,
+    pub actor: String,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: String,
+    pub reason: Option<String>,
+    pub details: serde_json::Value,
+    pub created_at: String,
+}
+
+impl AuditEntry {
+    /// Build from a row carrying `id::text, actor, action, target_type, target_id, reason, details, created_at`,
+    /// as returned by `INSERT INTO audit_log ... RETURNING ...`.
+    pub fn from_row(r: &tokio_postgres::Row) -> Result<Self, AppError> {
+        let dt: time::OffsetDateTime = r.get("created_at");
+        Ok(Self {
+            id: r.get("id"),
+            actor: r.get("actor"),
+            action: r.get("action"),
+            target_type: r.get("target_type"),
+            target_id: r.get("target_id"),
+            reason: r.get("reason"),
+            details: r.get("details"),
+            created_at: to_rfc3339(dt)?,
+        })
+    }
+}
+
+/// Push a committed audit entry to live stream subscribers. Call only after commit.
+pub fn publish_audit(st: &AppState, row: &tokio_postgres::Row) {
+    // the entry is committed either way; an unformattable one is logged and not streamed
+    let Ok(entry) = AuditEntry::from_row(row) else { return };
+    // no subscribers is the common case, not an error
+    let _ = st.audit_tx.send(entry);
+}
+
+fn matches_actor(entry: &AuditEntry, actor: Option<&str>) -> bool {
+    actor.is_none_or(|a| entry.actor == a)
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/zones/{zone_id}/audit",
+    tag = "audit",
+    params(("zone_id" = String, Path, description = "Zone id"), AuditQuery),
+    responses(
+        (status = 200, description = "Audit entries for the zone", body = serde_json::Value),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
 pub async fn list_audit(
     State(st): State<AppState>,
     Path(zone_id): Path<String>,
     Query(q): Query<AuditQuery>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     let limit = q.limit.clamp(1, 500);
-    let client = st.db.get().await?;
+    let client = st.db_read.get().await?;
 
     let rows = client
         .query(
@@ -49,22 +202,224 @@ pub async fn list_audit(
         )
         .await?;
 
-    let entries: Vec<AuditEntry> = rows
-        .into_iter()
-        .map(|r| {
-            let dt: time::OffsetDateTime = r.get("created_at");
-            AuditEntry {
-                id: r.get("id"),
-                actor: r.get("actor"),
-                action: r.get("action"),
-                target_type: r.get("target_type"),
-                target_id: r.get("target_id"),
-                reason: r.get("reason"),
-                details: r.get("details"),
-                created_at: fmt_rfc3339(dt),
-            }
-        })
-        .collect();
+    let entries = rows.iter().map(AuditEntry::from_row).collect::<Result<Vec<_>, _>>()?;
 
     Ok(Json(json!({ "audit": entries })))
 }
+
+/// Audit entries across all zones and targets for compliance review, newest first.
+#[utoipa::path(
+    get,
+    path = "/v1/audit",
+    tag = "audit",
+    params(AuditSearchQuery, ("x-admin-key" = String, Header, description = "One of the comma-separated ADMIN_KEY values")),
+    responses(
+        (status = 200, description = "Matching audit entries", body = serde_json::Value),
+        (status = 400, description = "Invalid since/until or offset", body = ErrorBody),
+        (status = 403, description = "Missing or wrong admin key", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn search_audit(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Query(q): Query<AuditSearchQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    admin_guard(&st, &headers)?;
+    let filter = AuditFilter::from_query(q)?;
+    let (sql, params) = filter.page_sql();
+    let client = st.db_read.get().await?;
+    let rows = client.query(&sql, &params).await?;
+    let entries = rows.iter().map(AuditEntry::from_row).collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Json(json!({ "audit": entries, "limit": filter.limit, "offset": filter.offset })))
+}
+
+/// Live tail of audit entries as Server-Sent Events, optionally filtered by actor.
+/// Slow consumers that fall behind the channel skip the missed entries. The
+/// stream ends when the server shuts down.
+#[utoipa::path(
+    get,
+    path = "/v1/audit/stream",
+    tag = "audit",
+    params(AuditStreamQuery, ("x-admin-key" = String, Header, description = "One of the comma-separated ADMIN_KEY values")),
+    responses(
+        (status = 200, description = "Server-sent events, one audit entry each", body = String),
+        (status = 400, description = "Invalid input", body = ErrorBody),
+        (status = 403, description = "Missing or wrong admin key", body = ErrorBody),
+    )
+)]
+pub async fn stream_audit(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Query(q): Query<AuditStreamQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    admin_guard(&st, &headers)?;
+    let rx = st.audit_tx.subscribe();
+    let actor = q.actor;
+
+    let stream = futures::stream::unfold((rx, actor), |(mut rx, actor)| async move {
+        loop {
+            match rx.recv().await {
+                Ok(entry) if matches_actor(&entry, actor.as_deref()) => {
+                    let event = Event::default().event("audit").json_data(&entry).ok()?;
+                    return Some((Ok(event), (rx, actor)));
+                }
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(skipped, "audit stream subscriber lagged");
+                    continue;
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    let stream = stream.take_until(st.shutdown.clone().cancelled_owned());
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(actor: &str) -> AuditEntry {
+        AuditEntry {
+            id: "a1".into(),
+            actor: actor.into(),
+            action: "SET_ZONE_STATUS".into(),
+            target_type: "zone".into(),
+            target_id: "zone-eu".into(),
+            reason: None,
+            details: json!({}),
+            created_at: "2026-01-01T00:00:00Z".into(),
+        }
+    }
+
+    fn search(pairs: &[(&str, &str)]) -> AuditSearchQuery {
+        let value = |key: &str| pairs.iter().find(|(k, _)| *k == key).map(|(_, v)| v.to_string());
+        AuditSearchQuery {
+            actor: value("actor"),
+            action: value("action"),
+            target_type: value("target_type"),
+            target_id: value("target_id"),
+            since: value("since"),
+            until: value("until"),
+            limit: default_limit(),
+            offset: 0,
+        }
+    }
+
+    #[test]
+    fn search_by_actor() {
+        let filter = AuditFilter::from_query(search(&[("actor", "alice")])).unwrap();
+        let (sql, params) = filter.page_sql();
+        assert!(sql.ends_with("FROM audit_log WHERE actor=$1 ORDER BY created_at DESC, id DESC LIMIT $2 OFFSET $3"), "{sql}");
+        assert_eq!(params.len(), 3);
+    }
+
+    #[test]
+    fn search_by_target_and_time() {
+        let q = search(&[
+            ("target_type", "zone"),
+            ("target_id", "zone-eu"),
+            ("since", "2026-01-01T00:00:00Z"),
+            ("until", "2026-02-01T00:00:00Z"),
+        ]);
+        let filter = AuditFilter::from_query(q).unwrap();
+        let (sql, params) = filter.page_sql();
+        assert!(
+            sql.contains("WHERE target_type=$1 AND target_id=$2 AND created_at >= $3 AND created_at < $4 ORDER BY"),
+            "{sql}"
+        );
+        assert_eq!(params.len(), 6);
+    }
+
+    #[test]
+    fn search_bounds() {
+        let no_filters = AuditFilter::from_query(search(&[])).unwrap();
+        assert!(!no_filters.page_sql().0.contains("WHERE"));
+        assert_eq!(no_filters.limit, 100);
+
+        let mut q = search(&[]);
+        q.limit = 10_000;
+        assert_eq!(AuditFilter::from_query(q).unwrap().limit, 500);
+        let mut q = search(&[]);
+        q.offset = -1;
+        assert!(AuditFilter::from_query(q).is_err());
+        let inverted = search(&[("since", "2026-02-01T00:00:00Z"), ("until", "2026-01-01T00:00:00Z")]);
+        assert!(AuditFilter::from_query(inverted).is_err());
+        assert!(AuditFilter::from_query(search(&[("since", "yesterday")])).is_err());
+    }
+
+    #[tokio::test]
+    async fn search_requires_admin_key() {
+        let mut config = crate::config::Config::new("postgres://ledger@primary.invalid/ledger");
+        config.admin_keys = vec!["test-key".into()];
+        let st = crate::app::build_state(config).await.unwrap();
+        let mut headers = HeaderMap::new();
+        for key in [None, Some("wrong-key")] {
+            if let Some(key) = key {
+                headers.insert("x-admin-key", key.parse().unwrap());
+            }
+            let err = search_audit(State(st.clone()), headers.clone(), Query(search(&[]))).await.unwrap_err();
+            assert_eq!(err.status_and_code(), (axum::http::StatusCode::FORBIDDEN, "forbidden"));
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn search_filters_by_actor_and_target() {
+        let mut config = crate::testing::test_db_config();
+        config.admin_keys = vec!["test-key".into()];
+        let st = crate::app::build_state(config).await.unwrap();
+        let run = uuid::Uuid::new_v4();
+        let (alice, bob, target) = (format!("alice-{run}"), format!("bob-{run}"), format!("acct-{run}"));
+        let client = st.db.get().await.unwrap();
+        for (actor, action, target_id) in [
+            (&alice, "FREEZE", &target),
+            (&alice, "UNFREEZE", &target),
+            (&bob, "FREEZE", &target),
+            (&bob, "FREEZE", &format!("other-{run}")),
+        ] {
+            client
+                .execute(
+                    "INSERT INTO audit_log(actor,action,target_type,target_id,details) VALUES($1,$2,'account',$3,$4)",
+                    &[actor, &action, target_id, &json!({ "run": run.to_string() })],
+                )
+                .await
+                .unwrap();
+        }
+        let mut headers = HeaderMap::new();
+        headers.insert("x-admin-key", "test-key".parse().unwrap());
+        let find = |pairs: Vec<(&'static str, String)>| {
+            let st = st.clone();
+            let headers = headers.clone();
+            async move {
+                let pairs: Vec<(&str, &str)> = pairs.iter().map(|(k, v)| (*k, v.as_str())).collect();
+                let Json(body) = search_audit(State(st), headers, Query(search(&pairs))).await.unwrap();
+                body["audit"].as_array().unwrap().clone()
+            }
+        };
+
+        let by_alice = find(vec![("actor", alice.clone())]).await;
+        assert_eq!(by_alice.len(), 2);
+        assert!(by_alice.iter().all(|e| e["actor"] == alice.as_str()));
+        assert_eq!(by_alice[0]["action"], "UNFREEZE", "newest first");
+        assert_eq!(by_alice[0]["details"]["run"], run.to_string());
+        assert!(by_alice[0]["created_at"].is_string());
+
+        let by_target = find(vec![("target_type", "account".into()), ("target_id", target.clone())]).await;
+        assert_eq!(by_target.len(), 3);
+        let bob_on_target =
+            find(vec![("actor", bob.clone()), ("target_type", "account".into()), ("target_id", target.clone())]).await;
+        assert_eq!(bob_on_target.len(), 1);
+    }
+
+    #[test]
+    fn actor_filter() {
+        assert!(matches_actor(&entry("alice"), None));
+        assert!(matches_actor(&entry("alice"), Some("alice")));
+        assert!(!matches_actor(&entry("alice"), Some("bob")));
+    }
+}
@@ -1,40 +1,328 @@
-use axum::{extract::State, http::StatusCode, Json};
-use serde::Serialize;
+use axum::{extract::{Path, Query, State}, Json};
+use serde::{Deserialize, Serialize};
 use serde_json::json;
+use tokio_postgres::types::ToSql;
+use utoipa::{IntoParams, ToSchema};
 
+use crate::error::{AppError, ErrorBody};
+use crate::handlers::zones::require_zone;
+use crate::projection::BalanceProjection;
 use crate::state::AppState;
-use crate::util::fmt_rfc3339;
+use crate::util::to_rfc3339;
 
 #[derive(Serialize)]
 struct BalanceRow {
     account_id: String,
     balance_units: i64,
+    /// Unsettled credits; only reported when a settlement delay is configured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pending_units: Option<i64>,
     updated_at: String,
 }
 
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BalanceQuery {
+    /// Exact account id.
+    pub account: Option<String>,
+    /// Only accounts in this zone.
+    pub zone_id: Option<String>,
+    /// Inclusive lower bound on `balance_units`.
+    pub min_balance: Option<i64>,
+    /// Inclusive upper bound on `balance_units`.
+    pub max_balance: Option<i64>,
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+    #[serde(default)]
+    pub offset: i64,
+}
+
+fn default_limit() -> i64 { 100 }
+
+impl BalanceQuery {
+    /// Reject an inverted range or negative offset and clamp `limit` to 1..=1000.
+    fn validated(mut self) -> Result<Self, AppError> {
+        if let (Some(min), Some(max)) = (self.min_balance, self.max_balance) {
+            if min > max {
+                return Err(AppError::BadRequest("min_balance must be <= max_balance".into()));
+            }
+        }
+        if self.offset < 0 {
+            return Err(AppError::BadRequest("offset must be >= 0".into()));
+        }
+        self.limit = self.limit.clamp(1, 1000);
+        Ok(self)
+    }
+
+    /// The page query with only the given filters, most recently updated first.
+    /// `zone_id` joins through `accounts`.
+    fn page_sql(&self) -> (String, Vec<&(dyn ToSql + Sync)>) {
+        let mut sql = String::from("SELECT b.account_id, b.balance_units, b.pending_units, b.updated_at FROM balances b");
+        let mut conds: Vec<String> = Vec::new();
+        let mut params: Vec<&(dyn ToSql + Sync)> = Vec::new();
+        if let Some(zone_id) = &self.zone_id {
+            sql.push_str(" JOIN accounts a ON a.id=b.account_id");
+            params.push(zone_id);
+            conds.push(format!("a.zone_id=${}", params.len()));
+        }
+        if let Some(account) = &self.account {
+            params.push(account);
+            conds.push(format!("b.account_id=${}", params.len()));
+        }
+        if let Some(min) = &self.min_balance {
+            params.push(min);
+            conds.push(format!("b.balance_units >= ${}", params.len()));
+        }
+        if let Some(max) = &self.max_balance {
+            params.push(max);
+            conds.push(format!("b.balance_units <= ${}", params.len()));
+        }
+        if !conds.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conds.join(" AND "));
+        }
+        params.push(&self.limit);
+        params.push(&self.offset);
+        sql.push_str(&format!(" ORDER BY b.updated_at DESC LIMIT ${} OFFSET ${}", params.len() - 1, params.len()));
+        (sql, params)
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/balances",
+    tag = "accounts",
+    params(BalanceQuery),
+    responses(
+        (status = 200, description = "Account balances", body = serde_json::Value),
+        (status = 400, description = "Invalid range or offset", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
 pub async fn list_balances(
     State(st): State<AppState>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let client = st.db.get().await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-    let rows = client
-        .query(
-            "SELECT account_id, balance_units, updated_at FROM balances ORDER BY updated_at DESC LIMIT 100",
-            &[],
-        )
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Query(q): Query<BalanceQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let q = q.validated()?;
+    let (sql, params) = q.page_sql();
+    let client = st.db_read.get().await?;
+    let rows = client.query(&sql, &params).await?;
 
     let balances: Vec<BalanceRow> = rows
         .into_iter()
-        .map(|r| {
+        .map(|r| -> Result<_, AppError> {
             let updated_at: time::OffsetDateTime = r.get("updated_at");
-            BalanceRow {
+            Ok(BalanceRow {
                 account_id: r.get("account_id"),
                 balance_units: r.get("balance_units"),
-                updated_at: fmt_rfc3339(updated_at),
-            }
+                pending_units: st.settlement_delay.map(|_| r.get("pending_units")),
+                updated_at: to_rfc3339(updated_at)?,
+            })
         })
-        .collect();
+        .collect::<Result<_, _>>()?;
+
+    if st.balance_projection == BalanceProjection::Async {
+        // balances may lag postings until the projector catches up
+        let lag: i64 = client
+            .query_one("SELECT COUNT(*) FROM postings WHERE projected_at IS NULL", &[])
+            .await?
+            .get(0);
+        return Ok(Json(json!({
+            "balances": balances,
+            "projection": st.balance_projection.as_str(),
+            "unprojected_postings": lag,
+        })));
+    }
 
     Ok(Json(json!({ "balances": balances })))
 }
+
+/// Aggregate over the accounts in a zone or the whole ledger. Accounts without
+/// a balances row count as zero.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BalanceSummary {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zone_id: Option<String>,
+    pub account_count: i64,
+    /// Sum of `balance_units`. System-wide this is zero, as every transfer
+    /// debits and credits the same amount.
+    pub total_units: i64,
+    /// Sum of unsettled credits; only reported when a settlement delay is
+    /// configured, in which case `total_units + pending_units` nets to zero.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pending_units: Option<i64>,
+    /// Smallest balance; null when there are no accounts.
+    pub min_balance_units: Option<i64>,
+    /// Largest balance; null when there are no accounts.
+    pub max_balance_units: Option<i64>,
+}
+
+/// One aggregate row over `accounts` left-joined to `balances`. Sums are cast
+/// back from numeric, as in the topology totals.
+const SUMMARY_COLUMNS: &str = "COUNT(a.id) AS account_count, \
+     COALESCE(SUM(b.balance_units), 0)::bigint AS total_units, \
+     COALESCE(SUM(b.pending_units), 0)::bigint AS pending_units, \
+     MIN(COALESCE(b.balance_units, 0)) FILTER (WHERE a.id IS NOT NULL) AS min_balance_units, \
+     MAX(COALESCE(b.balance_units, 0)) FILTER (WHERE a.id IS NOT NULL) AS max_balance_units";
+
+impl BalanceSummary {
+    fn from_row(st: &AppState, zone_id: Option<String>, r: &tokio_postgres::Row) -> Self {
+        Self {
+            zone_id,
+            account_count: r.get("account_count"),
+            total_units: r.get("total_units"),
+            pending_units: st.settlement_delay.map(|_| r.get("pending_units")),
+            min_balance_units: r.get("min_balance_units"),
+            max_balance_units: r.get("max_balance_units"),
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/zones/{zone_id}/balance-summary",
+    tag = "accounts",
+    params(("zone_id" = String, Path, description = "Zone id")),
+    responses(
+        (status = 200, description = "Balance totals for the zone's accounts", body = BalanceSummary),
+        (status = 404, description = "Unknown zone", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn zone_balance_summary(
+    State(st): State<AppState>,
+    Path(zone_id): Path<String>,
+) -> Result<Json<BalanceSummary>, AppError> {
+    let client = st.db_read.get().await?;
+    let row = client
+        .query_opt(
+            &format!(
+                "SELECT {SUMMARY_COLUMNS} FROM zones z LEFT JOIN accounts a ON a.zone_id=z.id \
+                 LEFT JOIN balances b ON b.account_id=a.id WHERE z.id=$1 GROUP BY z.id"
+            ),
+            &[&zone_id],
+        )
+        .await?;
+    let r = require_zone(row, &zone_id)?;
+    Ok(Json(BalanceSummary::from_row(&st, Some(zone_id), &r)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/balance-summary",
+    tag = "accounts",
+    responses(
+        (status = 200, description = "Balance totals across every account", body = BalanceSummary),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn balance_summary(State(st): State<AppState>) -> Result<Json<BalanceSummary>, AppError> {
+    let client = st.db_read.get().await?;
+    let r = client
+        .query_one(
+            &format!("SELECT {SUMMARY_COLUMNS} FROM accounts a LEFT JOIN balances b ON b.account_id=a.id"),
+            &[],
+        )
+        .await?;
+    Ok(Json(BalanceSummary::from_row(&st, None, &r)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(account: Option<&str>, zone_id: Option<&str>, min: Option<i64>, max: Option<i64>) -> BalanceQuery {
+        BalanceQuery {
+            account: account.map(String::from),
+            zone_id: zone_id.map(String::from),
+            min_balance: min,
+            max_balance: max,
+            limit: default_limit(),
+            offset: 0,
+        }
+    }
+
+    #[test]
+    fn no_filters_is_the_old_query() {
+        let q = query(None, None, None, None).validated().unwrap();
+        let (sql, params) = q.page_sql();
+        assert_eq!(
+            sql,
+            "SELECT b.account_id, b.balance_units, b.pending_units, b.updated_at FROM balances b ORDER BY b.updated_at DESC LIMIT $1 OFFSET $2"
+        );
+        assert_eq!(params.len(), 2);
+        assert_eq!(q.limit, 100);
+    }
+
+    #[test]
+    fn account_lookup() {
+        let q = query(Some("acct-1"), None, None, None);
+        let (sql, params) = q.page_sql();
+        assert!(sql.ends_with("FROM balances b WHERE b.account_id=$1 ORDER BY b.updated_at DESC LIMIT $2 OFFSET $3"));
+        assert_eq!(params.len(), 3);
+    }
+
+    #[test]
+    fn zone_filter_joins_accounts() {
+        let q = query(Some("acct-1"), Some("zone-eu"), None, None);
+        let (sql, params) = q.page_sql();
+        assert!(sql.contains("FROM balances b JOIN accounts a ON a.id=b.account_id WHERE a.zone_id=$1 AND b.account_id=$2 ORDER BY"));
+        assert_eq!(params.len(), 4);
+    }
+
+    #[test]
+    fn balance_range() {
+        let (sql, _) = query(None, None, Some(-10), Some(500)).page_sql();
+        assert!(sql.contains("WHERE b.balance_units >= $1 AND b.balance_units <= $2 ORDER BY"));
+        let (sql, _) = query(None, None, None, Some(0)).page_sql();
+        assert!(sql.contains("WHERE b.balance_units <= $1 ORDER BY"));
+
+        assert!(query(None, None, Some(5), Some(5)).validated().is_ok());
+        let err = query(None, None, Some(6), Some(5)).validated().unwrap_err();
+        assert!(matches!(err, AppError::BadRequest(_)));
+    }
+
+    #[test]
+    fn paging_is_bounded() {
+        let mut q = query(None, None, None, None);
+        q.limit = 1_000_000;
+        assert_eq!(q.validated().unwrap().limit, 1000);
+        let mut q = query(None, None, None, None);
+        q.offset = -1;
+        assert!(q.validated().is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn balance_summary_nets_to_zero_system_wide() {
+        use crate::handlers::transfers::{create_transfer, CreateTransferQuery, CreateTransferRequest};
+        let st = crate::testing::test_db().await;
+        let run = uuid::Uuid::new_v4();
+        for (i, (from, to, amount)) in [("a", "b", 700), ("b", "c", 250), ("c", "a", 40)].into_iter().enumerate() {
+            let req = CreateTransferRequest {
+                request_id: format!("req-summary-{i}-{run}"),
+                from_account: format!("acct-{from}-{run}"),
+                to_account: format!("acct-{to}-{run}"),
+                amount_units: amount,
+                zone_id: "zone-eu".into(),
+                metadata: json!({}),
+                currency: None,
+            };
+            let q = Query(CreateTransferQuery { include_balances: false, dry_run: false });
+            create_transfer(State(st.clone()), q, Default::default(), None, Ok(Json(req))).await.unwrap();
+        }
+
+        let Json(system) = balance_summary(State(st.clone())).await.unwrap();
+        assert_eq!(system.total_units, 0);
+        assert!(system.account_count >= 3);
+        assert!(system.min_balance_units.unwrap() <= -660);
+        assert!(system.max_balance_units.unwrap() >= 450);
+
+        let Json(zone) = zone_balance_summary(State(st.clone()), Path("zone-eu".into())).await.unwrap();
+        assert_eq!(zone.zone_id.as_deref(), Some("zone-eu"));
+        assert!(zone.account_count >= 3);
+
+        let err = zone_balance_summary(State(st), Path(format!("zone-missing-{run}"))).await.unwrap_err();
+        assert_eq!(err.status_and_code().1, "unknown_zone");
+    }
+}
@@ -1,11 +1,15 @@
 use axum::{extract::{Path, State}, Json};
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+use utoipa::ToSchema;
 
-use crate::error::AppError;
+use crate::error::{AppError, ErrorBody};
+use crate::handlers::audit::publish_audit;
+use crate::handlers::incidents::open_incident;
 use crate::state::AppState;
-use crate::util::fmt_rfc3339;
+use crate::util::to_rfc3339;
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct ZoneControls {
     pub zone_id: String,
     pub writes_blocked: bool,
@@ -14,6 +18,16 @@ pub struct ZoneControls {
     pub updated_at: String,
 }
 
+#[utoipa::path(
+    get,
+    path = "/v1/zones/{zone_id}/controls",
+    tag = "zones",
+    params(("zone_id" = String, Path, description = "Zone id")),
+    responses(
+        (status = 200, description = "Zone controls", body = ZoneControls),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
 pub async fn get_zone_controls(
     State(st): State<AppState>,
     Path(zone_id): Path<String>,
@@ -33,7 +47,7 @@ pub async fn get_zone_controls(
             writes_blocked: r.get("writes_blocked"),
             cross_zone_throttle: r.get("cross_zone_throttle"),
             spool_enabled: r.get("spool_enabled"),
-            updated_at: fmt_rfc3339(updated_at),
+            updated_at: to_rfc3339(updated_at)?,
         }));
     }
 
@@ -54,11 +68,11 @@ pub async fn get_zone_controls(
         writes_blocked: r.get("writes_blocked"),
         cross_zone_throttle: r.get("cross_zone_throttle"),
         spool_enabled: r.get("spool_enabled"),
-        updated_at: fmt_rfc3339(updated_at),
+        updated_at: to_rfc3339(updated_at)?,
     }))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct SetZoneControlsRequest {
     pub writes_blocked: Option<bool>,
     pub cross_zone_throttle: Option<i32>,
@@ -69,6 +83,18 @@ pub struct SetZoneControlsRequest {
     pub reason: String,
 }
 
+#[utoipa::path(
+    post,
+    path = "/v1/zones/{zone_id}/controls",
+    tag = "zones",
+    params(("zone_id" = String, Path, description = "Zone id")),
+    request_body = SetZoneControlsRequest,
+    responses(
+        (status = 200, description = "Updated zone controls", body = ZoneControls),
+        (status = 400, description = "Invalid controls or missing actor", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
 pub async fn set_zone_controls(
     State(st): State<AppState>,
     Path(zone_id): Path<String>,
@@ -95,23 +121,28 @@ pub async fn set_zone_controls(
         )
         .await?;
 
-    tx.execute(
-        "INSERT INTO audit_log(actor,action,target_type,target_id,reason,details) VALUES($1,'SET_ZONE_CONTROLS','zone',$2,$3, jsonb_build_object('writes_blocked',$4,'cross_zone_throttle',$5,'spool_enabled',$6))",
-        &[&req.actor, &zone_id, &req.reason, &wb, &throttle, &spool],
-    )
-    .await?;
+    let audit = tx
+        .query_one(
+            "INSERT INTO audit_log(actor,action,target_type,target_id,reason,details) VALUES($1,'SET_ZONE_CONTROLS','zone',$2,$3, jsonb_build_object('writes_blocked',$4,'cross_zone_throttle',$5,'spool_enabled',$6)) RETURNING id::text, actor, action, target_type, target_id, reason, details, created_at",
+            &[&req.actor, &zone_id, &req.reason, &wb, &throttle, &spool],
+        )
+        .await?;
 
     if wb || throttle == 0 {
         let sev = if wb { "CRITICAL" } else { "WARN" };
         let title = if wb { "Writes blocked by operator" } else { "Zone controls tightened" };
-        tx.execute(
-            "INSERT INTO incidents(zone_id,severity,title,details) VALUES($1,$2,$3, jsonb_build_object('reason',$4,'actor',$5,'writes_blocked',$6,'cross_zone_throttle',$7,'spool_enabled',$8))",
-            &[&zone_id, &sev, &title, &req.reason, &req.actor, &wb, &throttle, &spool],
-        )
-        .await?;
+        let details = json!({
+            "reason": req.reason,
+            "actor": req.actor,
+            "writes_blocked": wb,
+            "cross_zone_throttle": throttle,
+            "spool_enabled": spool,
+        });
+        open_incident(&tx, &zone_id, sev, title, &details).await?;
     }
 
     tx.commit().await?;
+    publish_audit(&st, &audit);
 
     let updated_at: time::OffsetDateTime = r.get("updated_at");
     Ok(Json(ZoneControls {
@@ -119,6 +150,6 @@ pub async fn set_zone_controls(
         writes_blocked: r.get("writes_blocked"),
         cross_zone_throttle: r.get("cross_zone_throttle"),
         spool_enabled: r.get("spool_enabled"),
-        updated_at: fmt_rfc3339(updated_at),
+        updated_at: to_rfc3339(updated_at)?,
     }))
 }
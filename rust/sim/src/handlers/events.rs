@@ -0,0 +1,171 @@
+use axum::{
+    extract::State,
+    http::HeaderMap,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures::{Stream, StreamExt};
+use serde::Serialize;
+use std::collections::{HashSet, VecDeque};
+use std::convert::Infallible;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::warn;
+
+use crate::error::{AppError, ErrorBody};
+use crate::messaging::outbox::with_event_id;
+use crate::state::AppState;
+
+/// Most outbox events replayed for one `Last-Event-ID`; older gaps need `/v1/sim/export`.
+pub const EVENT_REPLAY_MAX: i64 = 1000;
+
+/// Outbox event types sent on `/v1/events/stream`.
+const STREAMED_EVENT_TYPES: [&str; 2] = ["TransferPosted", "ZoneStatusChanged"];
+
+/// A committed outbox event as sent to `/v1/events/stream` subscribers.
+/// `id` is the outbox row id and doubles as the SSE event id.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LedgerEvent {
+    pub id: String,
+    pub event_type: String,
+    pub payload: serde_json::Value,
+}
+
+impl LedgerEvent {
+    fn from_row(r: &tokio_postgres::Row) -> Self {
+        let id: String = r.get("id");
+        Self { payload: with_event_id(r.get("payload"), &id), event_type: r.get("event_type"), id }
+    }
+
+    fn to_sse(&self) -> Option<Event> {
+        Event::default().id(&self.id).event(&self.event_type).json_data(&self.payload).ok()
+    }
+}
+
+/// Write an outbox row inside `tx` and return it as the event to publish once `tx` commits.
+pub(crate) async fn insert_outbox_event(
+    tx: &deadpool_postgres::Transaction<'_>,
+    event_type: &str,
+    aggregate_type: &str,
+    aggregate_id: &str,
+    payload: &serde_json::Value,
+) -> Result<LedgerEvent, AppError> {
+    let row = tx
+        .query_one(
+            "INSERT INTO outbox_events(event_type,aggregate_type,aggregate_id,payload) VALUES($1,$2,$3,$4) RETURNING id::text, event_type, payload",
+            &[&event_type, &aggregate_type, &aggregate_id, payload],
+        )
+        .await?;
+    Ok(LedgerEvent::from_row(&row))
+}
+
+/// Push a committed event to live stream subscribers. Call only after commit.
+pub fn publish_event(st: &AppState, event: LedgerEvent) {
+    // no subscribers is the common case, not an error
+    let _ = st.events_tx.send(event);
+}
+
+/// Outbox events after `last_id`, oldest first. An unknown id replays nothing.
+async fn replay_since(st: &AppState, last_id: &str) -> Result<Vec<LedgerEvent>, AppError> {
+    let client = st.db_read.get().await?;
+    let rows = client
+        .query(
+            "WITH since AS (SELECT created_at, id FROM outbox_events WHERE id::text=$1) \
+             SELECT o.id::text, o.event_type, o.payload FROM outbox_events o, since \
+             WHERE o.event_type = ANY($2) AND (o.created_at, o.id) > (since.created_at, since.id) \
+             ORDER BY o.created_at, o.id LIMIT $3",
+            &[&last_id, &STREAMED_EVENT_TYPES.as_slice(), &EVENT_REPLAY_MAX],
+        )
+        .await?;
+    Ok(rows.iter().map(LedgerEvent::from_row).collect())
+}
+
+/// Replayed events first, then live ones, skipping live events already replayed.
+fn event_stream(
+    replay: Vec<LedgerEvent>,
+    rx: broadcast::Receiver<LedgerEvent>,
+) -> impl Stream<Item = Result<Event, Infallible>> {
+    let seen: HashSet<String> = replay.iter().map(|e| e.id.clone()).collect();
+    let replay: VecDeque<LedgerEvent> = replay.into();
+    futures::stream::unfold((replay, seen, rx), |(mut replay, seen, mut rx)| async move {
+        if let Some(event) = replay.pop_front() {
+            let sse = event.to_sse()?;
+            return Some((Ok(sse), (replay, seen, rx)));
+        }
+        loop {
+            match rx.recv().await {
+                Ok(event) if !seen.contains(&event.id) => {
+                    let sse = event.to_sse()?;
+                    return Some((Ok(sse), (replay, seen, rx)));
+                }
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(skipped, "event stream subscriber lagged");
+                    continue;
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    })
+}
+
+/// Live `TransferPosted` and `ZoneStatusChanged` events as Server-Sent Events.
+/// A `Last-Event-ID` header first replays up to [`EVENT_REPLAY_MAX`] later events
+/// from the outbox. Slow consumers that fall behind the channel skip the missed
+/// events; reconnecting with `Last-Event-ID` fills the gap. The stream ends when
+/// the server shuts down.
+#[utoipa::path(
+    get,
+    path = "/v1/events/stream",
+    tag = "events",
+    params(("Last-Event-ID" = Option<String>, Header, description = "Id of the last event received; later events are replayed first")),
+    responses(
+        (status = 200, description = "Server-sent events named after the event type, with the outbox event id", body = String, content_type = "text/event-stream"),
+        (status = 503, description = "Database unavailable while replaying", body = ErrorBody),
+    )
+)]
+pub async fn stream_events(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    // subscribe before replaying so nothing committed in between is lost
+    let rx = st.events_tx.subscribe();
+    let last_id = headers.get("last-event-id").and_then(|v| v.to_str().ok()).filter(|v| !v.is_empty());
+    let replay = match last_id {
+        Some(id) => replay_since(&st, id).await?,
+        None => Vec::new(),
+    };
+    let stream = event_stream(replay, rx).take_until(st.shutdown.clone().cancelled_owned());
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::response::IntoResponse;
+    use http_body_util::BodyExt;
+    use std::time::Duration;
+
+    fn event(id: &str, txn: &str) -> LedgerEvent {
+        LedgerEvent {
+            id: id.into(),
+            event_type: "TransferPosted".into(),
+            payload: serde_json::json!({ "event_id": id, "type": "TransferPosted", "transaction_id": txn }),
+        }
+    }
+
+    async fn next_chunk(body: &mut axum::body::Body) -> String {
+        let frame = tokio::time::timeout(Duration::from_secs(5), body.frame()).await.unwrap().unwrap().unwrap();
+        String::from_utf8(frame.into_data().unwrap().to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn replayed_events_are_not_sent_twice() {
+        let (tx, rx) = broadcast::channel(16);
+        let mut body = Sse::new(event_stream(vec![event("evt-1", "txn-1")], rx)).into_response().into_body();
+        // committed during the replay query: arrives both ways
+        tx.send(event("evt-1", "txn-1")).unwrap();
+        tx.send(event("evt-2", "txn-2")).unwrap();
+
+        assert!(next_chunk(&mut body).await.contains("id: evt-1"));
+        assert!(next_chunk(&mut body).await.contains("id: evt-2"));
+    }
+}
@@ -0,0 +1,398 @@
+use axum::{extract::{rejection::JsonRejection, State}, Json};
+use serde::Serialize;
+use serde_json::json;
+use std::time::Instant;
+
+use crate::error::{AppError, ErrorBody};
+use crate::handlers::transfers::{
+    balance_overflow, check_account_currencies, check_account_zones, check_currency, check_degraded_policy,
+    check_metadata_schema, checked_transfer, currency_scale, find_idempotent, idempotency_conflict, insufficient_available,
+    insufficient_funds, rate_limited, transfer_currency, transfer_hash, unknown_currency, validate_transfer, zone_blocked, zone_gate,
+    CreateTransferRequest, Existing,
+};
+use crate::handlers::whitelists::check_whitelists;
+use crate::handlers::zones::require_zone;
+use crate::projection::BalanceProjection;
+use crate::ratelimit::peek;
+use crate::state::AppState;
+use crate::util::{hash_percent, to_rfc3339};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum CheckResult {
+    Pass,
+    Fail,
+    Skipped,
+    /// Replay of an applied request: returned as-is, later checks do not run.
+    Duplicate,
+    /// Refused by the zone gate but spooled for later replay.
+    Spool,
+}
+
+#[derive(Debug, Serialize)]
+struct Check {
+    name: &'static str,
+    result: CheckResult,
+    /// The inputs the check looked at.
+    data: serde_json::Value,
+    /// The error a real transfer would get from this check.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<serde_json::Value>,
+}
+
+impl Check {
+    fn new(name: &'static str, result: CheckResult, data: serde_json::Value) -> Self {
+        Self { name, result, data, error: None }
+    }
+
+    fn skipped(name: &'static str, reason: &str) -> Self {
+        Self::new(name, CheckResult::Skipped, json!({ "reason": reason }))
+    }
+
+    fn from_result(name: &'static str, data: serde_json::Value, result: Result<(), AppError>) -> Self {
+        match result {
+            Ok(()) => Self::new(name, CheckResult::Pass, data),
+            Err(e) => Self { error: Some(error_json(&e)), ..Self::new(name, CheckResult::Fail, data) },
+        }
+    }
+}
+
+fn error_json(e: &AppError) -> serde_json::Value {
+    let (status, code) = e.status_and_code();
+    json!({ "status": status.as_u16(), "code": code, "message": e.message() })
+}
+
+/// Outcome of a transfer whose checks ran in pipeline order: the first check
+/// that did not pass (or skip) decides it.
+fn decide(checks: &[Check]) -> (&'static str, Option<&Check>) {
+    for check in checks {
+        let decision = match check.result {
+            CheckResult::Pass | CheckResult::Skipped => continue,
+            CheckResult::Fail => "REJECTED",
+            CheckResult::Duplicate => "DUPLICATE",
+            CheckResult::Spool => "SPOOLED",
+        };
+        return (decision, Some(check));
+    }
+    ("APPLIED", None)
+}
+
+/// Dry run of `POST /v1/transfers`: every check the transfer would go through,
+/// in order, with its result and the data it used. Runs in a transaction that
+/// is rolled back and takes no rate-limit token, so it has no side effects.
+#[utoipa::path(
+    post,
+    path = "/v1/transfers/explain",
+    tag = "transfers",
+    request_body = CreateTransferRequest,
+    responses(
+        (status = 200, description = "Every check the transfer would go through and the resulting decision", body = serde_json::Value),
+        (status = 400, description = "Malformed body or unknown field", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+        (status = 503, description = "Database unavailable", body = ErrorBody),
+    )
+)]
+pub async fn explain_transfer(
+    State(st): State<AppState>,
+    body: Result<Json<CreateTransferRequest>, JsonRejection>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let Json(req) = body?;
+    let mut checks = Vec::new();
+    let validation = validate_transfer(&req, &st.transfer_limits);
+    let valid = validation.is_ok();
+    checks.push(Check::from_result(
+        "request",
+        json!({ "request_id": req.request_id, "zone_id": req.zone_id, "amount_units": req.amount_units }),
+        validation,
+    ));
+
+    if valid {
+        let mut client = st.db.get().await?;
+        let tx = client.transaction().await?;
+        let result = run_checks(&st, &tx, &req, &mut checks).await;
+        tx.rollback().await?;
+        result?;
+    }
+
+    let (decision, decided_by) = decide(&checks);
+    let (decided_by, error) = (decided_by.map(|c| c.name), decided_by.and_then(|c| c.error.clone()));
+    Ok(Json(json!({
+        "request_id": req.request_id,
+        "decision": decision,
+        "decided_by": decided_by,
+        "error": error,
+        "checks": checks,
+    })))
+}
+
+async fn run_checks(
+    st: &AppState,
+    tx: &deadpool_postgres::Transaction<'_>,
+    req: &CreateTransferRequest,
+    checks: &mut Vec<Check>,
+) -> Result<(), AppError> {
+    let hash = transfer_hash(req, &st.idempotency_hash_exclude)?;
+
+    let zone_row = tx
+        .query_opt(
+            "SELECT status, rate_limit_per_sec, currency, metadata_schema, degraded_policy FROM zones WHERE id=$1 AND NOT internal",
+            &[&req.zone_id],
+        )
+        .await?;
+    let Some(zone_row) = zone_row else {
+        let missing = require_zone(None::<()>, &req.zone_id);
+        checks.push(Check::from_result("zone", json!({ "zone_id": req.zone_id }), missing));
+        return Ok(());
+    };
+    let status: String = zone_row.get(0);
+    let zone_rate_override: Option<i32> = zone_row.get(1);
+    let zone_currency: Option<String> = zone_row.get(2);
+    let metadata_schema: Option<serde_json::Value> = zone_row.get(3);
+    let degraded_policy: Option<serde_json::Value> = zone_row.get(4);
+    checks.push(Check::new(
+        "zone",
+        CheckResult::Pass,
+        json!({ "zone_id": req.zone_id, "status": status, "currency": zone_currency }),
+    ));
+    checks.push(match (&degraded_policy, status.as_str()) {
+        (Some(policy), "DEGRADED") => Check::from_result(
+            "degraded_policy",
+            json!({ "zone_id": req.zone_id, "policy": policy }),
+            check_degraded_policy(&req.zone_id, &status, Some(policy), req),
+        ),
+        (None, _) => Check::skipped("degraded_policy", "zone has no degraded policy"),
+        _ => Check::skipped("degraded_policy", "zone is not DEGRADED"),
+    });
+
+    let (currency, result) = match check_currency(zone_currency.as_deref(), &req.metadata)
+        .and_then(|_| transfer_currency(req.currency.as_deref(), zone_currency.as_deref()))
+    {
+        Ok(c) => (c, Ok(())),
+        Err(e) => (None, Err(e)),
+    };
+    let scale = match &currency {
+        Some(c) => currency_scale(tx, c).await?,
+        None => None,
+    };
+    let result = result.and_then(|_| match (&currency, scale) {
+        (Some(c), None) => Err(unknown_currency(c)),
+        _ => Ok(()),
+    });
+    checks.push(Check::from_result(
+        "currency",
+        json!({
+            "zone_currency": zone_currency,
+            "metadata_currency": req.metadata.get("currency"),
+            "currency": currency,
+            "minor_unit_scale": scale,
+        }),
+        result,
+    ));
+
+    checks.push(match &metadata_schema {
+        Some(schema) => Check::from_result(
+            "metadata_schema",
+            json!({ "zone_id": req.zone_id }),
+            check_metadata_schema(&req.zone_id, Some(schema), &req.metadata),
+        ),
+        None => Check::skipped("metadata_schema", "zone has no metadata schema"),
+    });
+
+    let existing = find_idempotent(tx, &req.request_id, st).await?;
+    let window_secs = st.idempotency_ttl.map(|d| d.as_secs());
+    checks.push(match existing {
+        Some(r) => {
+            let transaction_id: String = r.get(0);
+            let stored_hash: String = r.get(1);
+            let created_at: time::OffsetDateTime = r.get(2);
+            let data = json!({ "window_secs": window_secs, "transaction_id": transaction_id, "created_at": to_rfc3339(created_at)? });
+            if stored_hash == hash {
+                Check::new("idempotency", CheckResult::Duplicate, data)
+            } else {
+                let conflict = idempotency_conflict(&req.request_id, Existing::Transaction(&transaction_id), &stored_hash, &hash);
+                Check::from_result("idempotency", data, Err(conflict))
+            }
+        }
+        None => Check::new("idempotency", CheckResult::Pass, json!({ "window_secs": window_secs })),
+    });
+
+    let existing_spool = tx
+        .query_opt("SELECT id::text, payload_hash FROM spooled_transfers WHERE request_id=$1", &[&req.request_id])
+        .await?;
+    checks.push(match existing_spool {
+        Some(r) => {
+            let spool_id: String = r.get(0);
+            let stored_hash: String = r.get(1);
+            let data = json!({ "spool_id": spool_id });
+            if stored_hash == hash {
+                Check::new("spool_idempotency", CheckResult::Spool, data)
+            } else {
+                let conflict = idempotency_conflict(&req.request_id, Existing::Spooled(&spool_id), &stored_hash, &hash);
+                Check::from_result("spool_idempotency", data, Err(conflict))
+            }
+        }
+        None => Check::new("spool_idempotency", CheckResult::Pass, json!({})),
+    });
+
+    let whitelists = tx
+        .query(
+            "SELECT account_id, counterparties FROM account_whitelists WHERE account_id=$1 OR account_id=$2",
+            &[&req.from_account, &req.to_account],
+        )
+        .await?;
+    let whitelist_of = |account: &str| -> Option<Vec<String>> {
+        whitelists.iter().find(|r| r.get::<_, &str>(0) == account).map(|r| r.get(1))
+    };
+    let (from_list, to_list) = (whitelist_of(&req.from_account), whitelist_of(&req.to_account));
+    checks.push(Check::from_result(
+        "counterparty_whitelist",
+        json!({ "from_whitelist": from_list, "to_whitelist": to_list }),
+        check_whitelists(&req.from_account, &req.to_account, from_list.as_deref(), to_list.as_deref()),
+    ));
+
+    let zone_rate = zone_rate_override.map(|r| r.max(0) as u32).unwrap_or(st.zone_rate_limit);
+    checks.push(match peek(&st.zone_buckets, &req.zone_id, zone_rate, Instant::now()) {
+        Ok(tokens) => Check::new(
+            "zone_rate_limit",
+            CheckResult::Pass,
+            json!({ "rate_per_sec": zone_rate, "tokens_available": tokens }),
+        ),
+        Err(wait) => Check::from_result(
+            "zone_rate_limit",
+            json!({ "rate_per_sec": zone_rate, "tokens_available": 0 }),
+            Err(rate_limited(&req.zone_id, wait)),
+        ),
+    });
+
+    let ctrl_row = tx
+        .query_opt("SELECT writes_blocked, cross_zone_throttle, spool_enabled FROM zone_controls WHERE zone_id=$1", &[&req.zone_id])
+        .await?;
+    let (wb, throttle, spool_enabled) = ctrl_row
+        .map(|r| (r.get::<_, bool>(0), r.get::<_, i32>(1), r.get::<_, bool>(2)))
+        .unwrap_or((false, 100, false));
+    let data = json!({
+        "status": status,
+        "writes_blocked": wb,
+        "cross_zone_throttle": throttle,
+        "throttle_bucket": hash_percent(&req.request_id),
+        "spool_enabled": spool_enabled,
+    });
+    checks.push(match zone_gate(&status, wb, throttle, &req.request_id) {
+        None => Check::new("zone_gate", CheckResult::Pass, data),
+        Some((_, reason)) if spool_enabled => {
+            Check::new("zone_gate", CheckResult::Spool, json!({ "reason": reason, "controls": data }))
+        }
+        Some((code, reason)) => Check::from_result("zone_gate", data, Err(zone_blocked(&req.zone_id, code, reason))),
+    });
+
+    let account_zones: Vec<(String, String)> = tx
+        .query("SELECT id, zone_id FROM accounts WHERE id=$1 OR id=$2", &[&req.from_account, &req.to_account])
+        .await?
+        .iter()
+        .map(|r| (r.get(0), r.get(1)))
+        .collect();
+    let existing: serde_json::Map<String, serde_json::Value> =
+        account_zones.iter().map(|(id, zone)| (id.clone(), json!(zone))).collect();
+    checks.push(Check::from_result(
+        "account_zone",
+        json!({ "existing_accounts": existing }),
+        check_account_zones(&req.zone_id, &account_zones),
+    ));
+
+    let account_currencies: Vec<(String, Option<String>)> = tx
+        .query("SELECT id, currency FROM accounts WHERE id=$1 OR id=$2", &[&req.from_account, &req.to_account])
+        .await?
+        .iter()
+        .map(|r| (r.get(0), r.get(1)))
+        .collect();
+    let established: serde_json::Map<String, serde_json::Value> =
+        account_currencies.iter().map(|(id, c)| (id.clone(), json!(c))).collect();
+    checks.push(Check::from_result(
+        "account_currency",
+        json!({ "currency": currency, "account_currencies": established }),
+        check_account_currencies(currency.as_deref(), &account_currencies),
+    ));
+
+    // balance checks only run where the transfer updates balances inline
+    if st.balance_projection != BalanceProjection::Sync {
+        checks.push(Check::skipped("overdraft", "balances are projected asynchronously"));
+        checks.push(Check::skipped("balance_overflow", "balances are projected asynchronously"));
+    } else {
+        let rows = tx
+            .query(
+                "SELECT account_id, balance_units, pending_units FROM balances WHERE account_id=$1 OR account_id=$2",
+                &[&req.from_account, &req.to_account],
+            )
+            .await?;
+        let balance_of = |account: &str, column: &str| {
+            rows.iter()
+                .find(|r| r.get::<_, &str>("account_id") == account)
+                .map(|r| r.get::<_, i64>(column))
+                .unwrap_or(0)
+        };
+        let available = balance_of(&req.from_account, "balance_units");
+        let defer_credit = st.settlement_delay.is_some();
+
+        checks.push(if !st.reject_overdrafts {
+            Check::skipped("overdraft", "only enforced when REJECT_OVERDRAFTS is on")
+        } else {
+            let overdrawn = insufficient_available(available, req.amount_units);
+            Check::from_result(
+                "overdraft",
+                json!({ "available_units": available, "amount_units": req.amount_units }),
+                if overdrawn { Err(insufficient_funds(&req.from_account, available, req.amount_units)) } else { Ok(()) },
+            )
+        });
+
+        let credit_column = if defer_credit { "pending_units" } else { "balance_units" };
+        let to_balance = balance_of(&req.to_account, credit_column);
+        let fits = checked_transfer(available, to_balance, req.amount_units).is_some();
+        checks.push(Check::from_result(
+            "balance_overflow",
+            json!({ "from_balance_units": available, "to_balance_units": to_balance, "amount_units": req.amount_units }),
+            if fits { Ok(()) } else { Err(balance_overflow(&req.from_account, &req.to_account, req.amount_units)) },
+        ));
+    }
+
+    checks.push(Check::skipped("velocity", "no velocity limit is enforced"));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(name: &'static str, result: CheckResult) -> Check {
+        Check::new(name, result, json!({}))
+    }
+
+    #[test]
+    fn first_decisive_check_wins() {
+        let checks = [
+            check("request", CheckResult::Pass),
+            check("overdraft", CheckResult::Skipped),
+            check("idempotency", CheckResult::Duplicate),
+            check("zone_gate", CheckResult::Fail),
+        ];
+        let (decision, by) = decide(&checks);
+        assert_eq!(decision, "DUPLICATE");
+        assert_eq!(by.unwrap().name, "idempotency");
+
+        let (decision, by) = decide(&checks[..2]);
+        assert_eq!(decision, "APPLIED");
+        assert!(by.is_none());
+
+        let spooled = [check("zone_gate", CheckResult::Spool), check("overdraft", CheckResult::Fail)];
+        assert_eq!(decide(&spooled).0, "SPOOLED");
+    }
+
+    #[test]
+    fn failed_check_carries_the_real_error() {
+        let c = Check::from_result("currency", json!({}), check_currency(Some("EUR"), &json!({ "currency": "USD" })));
+        assert_eq!(c.result, CheckResult::Fail);
+        let error = c.error.unwrap();
+        assert_eq!(error["status"], 422);
+        assert_eq!(error["code"], "currency_mismatch");
+        assert_eq!(decide(&[Check::skipped("velocity", "n/a")]).0, "APPLIED");
+    }
+}
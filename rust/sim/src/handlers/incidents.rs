@@ -1,82 +1,134 @@
 use axum::{extract::{Path, Query, State}, http::StatusCode, Json};
 use serde::Deserialize;
 use serde_json::json;
+use utoipa::{IntoParams, ToSchema};
 
-use crate::error::AppError;
+use crate::error::{AppError, ErrorBody};
+use crate::handlers::audit::publish_audit;
 use crate::state::AppState;
-use crate::util::fmt_rfc3339;
+use crate::util::to_rfc3339;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct IncidentQuery {
     #[serde(default = "default_limit")]
     pub limit: i64,
 }
 fn default_limit() -> i64 { 100 }
 
-fn format_incident(r: &tokio_postgres::Row) -> serde_json::Value {
+/// Open an incident inside the caller's transaction. Operator actions and the
+/// reconciler all go through here so incidents share one shape.
+pub async fn open_incident(
+    tx: &deadpool_postgres::Transaction<'_>,
+    zone_id: &str,
+    severity: &str,
+    title: &str,
+    details: &serde_json::Value,
+) -> Result<(), tokio_postgres::Error> {
+    tx.execute(
+        "INSERT INTO incidents(zone_id,severity,title,details) VALUES($1,$2,$3,$4)",
+        &[&zone_id, &severity, &title, details],
+    )
+    .await?;
+    Ok(())
+}
+
+fn format_incident(r: &tokio_postgres::Row) -> Result<serde_json::Value, AppError> {
     let dt: time::OffsetDateTime = r.get("detected_at");
-    json!({
+    let resolved_at: Option<time::OffsetDateTime> = r.get("resolved_at");
+    Ok(json!({
         "id": r.get::<_, String>("id"),
         "zone_id": r.get::<_, String>("zone_id"),
         "severity": r.get::<_, String>("severity"),
         "status": r.get::<_, String>("status"),
         "title": r.get::<_, String>("title"),
         "details": r.get::<_, serde_json::Value>("details"),
-        "detected_at": fmt_rfc3339(dt),
-    })
+        "detected_at": to_rfc3339(dt)?,
+        "resolved_at": resolved_at.map(to_rfc3339).transpose()?,
+    }))
 }
 
+#[utoipa::path(
+    get,
+    path = "/v1/zones/{zone_id}/incidents",
+    tag = "incidents",
+    params(("zone_id" = String, Path, description = "Zone id")),
+    responses(
+        (status = 200, description = "Incidents of the zone", body = serde_json::Value),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
 pub async fn list_incidents_by_zone(
     State(st): State<AppState>,
     Path(zone_id): Path<String>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    let client = st.db.get().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+) -> Result<Json<serde_json::Value>, AppError> {
+    let client = st.db_read.get().await?;
     let rows = client
         .query(
-            "SELECT id::text, zone_id, severity, status, title, details, detected_at FROM incidents WHERE zone_id=$1 ORDER BY detected_at DESC LIMIT 200",
+            "SELECT id::text, zone_id, severity, status, title, details, detected_at, resolved_at FROM incidents WHERE zone_id=$1 ORDER BY detected_at DESC LIMIT 200",
             &[&zone_id],
         )
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .await?;
 
-    let incs: Vec<serde_json::Value> = rows.iter().map(format_incident).collect();
+    let incs = rows.iter().map(format_incident).collect::<Result<Vec<_>, _>>()?;
     Ok(Json(json!({ "incidents": incs })))
 }
 
+#[utoipa::path(
+    get,
+    path = "/v1/incidents",
+    tag = "incidents",
+    params(IncidentQuery),
+    responses(
+        (status = 200, description = "Most recent incidents", body = serde_json::Value),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
 pub async fn list_recent_incidents(
     State(st): State<AppState>,
     Query(q): Query<IncidentQuery>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     let limit = q.limit.clamp(1, 2000);
-    let client = st.db.get().await?;
+    let client = st.db_read.get().await?;
     let rows = client
         .query(
-            "SELECT id::text, zone_id, severity, status, title, details, detected_at FROM incidents ORDER BY detected_at DESC LIMIT $1",
+            "SELECT id::text, zone_id, severity, status, title, details, detected_at, resolved_at FROM incidents ORDER BY detected_at DESC LIMIT $1",
             &[&limit],
         )
         .await?;
 
-    let incs: Vec<serde_json::Value> = rows.iter().map(format_incident).collect();
+    let incs = rows.iter().map(format_incident).collect::<Result<Vec<_>, _>>()?;
     Ok(Json(json!({ "incidents": incs })))
 }
 
+#[utoipa::path(
+    get,
+    path = "/v1/incidents/{incident_id}",
+    tag = "incidents",
+    params(("incident_id" = String, Path, description = "Incident id")),
+    responses(
+        (status = 200, description = "Incident", body = serde_json::Value),
+        (status = 404, description = "Incident not found", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
 pub async fn get_incident(
     State(st): State<AppState>,
     Path(incident_id): Path<String>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    let client = st.db.get().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+) -> Result<Json<serde_json::Value>, AppError> {
+    let client = st.db_read.get().await?;
     let row = client
         .query_one(
-            "SELECT id::text, zone_id, severity, status, title, details, detected_at FROM incidents WHERE id=$1::uuid",
+            "SELECT id::text, zone_id, severity, status, title, details, detected_at, resolved_at FROM incidents WHERE id=$1::uuid",
             &[&incident_id],
         )
         .await
-        .map_err(|_| StatusCode::NOT_FOUND)?;
+        .map_err(|_| AppError::NotFound("incident not found".into()))?;
 
-    Ok(Json(format_incident(&row)))
+    Ok(Json(format_incident(&row)?))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct IncidentActionRequest {
     pub action: String,
     #[serde(default)]
@@ -89,6 +141,102 @@ pub struct IncidentActionRequest {
     pub reason: String,
 }
 
+/// Status after `action`: OPEN -> ACK -> RESOLVED, or OPEN -> RESOLVED directly.
+/// ASSIGN keeps the status but is not allowed once resolved.
+fn next_status<'a>(current: &'a str, action: &str) -> Result<&'a str, AppError> {
+    match (current, action) {
+        ("OPEN", "ACK") => Ok("ACK"),
+        ("OPEN" | "ACK", "RESOLVE") => Ok("RESOLVED"),
+        ("OPEN" | "ACK", "ASSIGN") => Ok(current),
+        _ => Err(AppError::Detailed {
+            status: StatusCode::CONFLICT,
+            code: "invalid_transition",
+            message: format!("cannot {action} an incident that is {current}"),
+            details: json!({ "status": current, "action": action }),
+        }),
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct IncidentTransitionRequest {
+    #[serde(default)]
+    pub actor: String,
+    #[serde(default)]
+    pub reason: String,
+    #[serde(default)]
+    pub note: String,
+}
+
+impl IncidentTransitionRequest {
+    fn into_action(self, action: &str) -> IncidentActionRequest {
+        IncidentActionRequest {
+            action: action.into(),
+            assignee: String::new(),
+            note: self.note,
+            actor: self.actor,
+            reason: self.reason,
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/incidents/{incident_id}/acknowledge",
+    tag = "incidents",
+    params(("incident_id" = String, Path, description = "Incident id")),
+    request_body = IncidentTransitionRequest,
+    responses(
+        (status = 200, description = "Incident acknowledged", body = serde_json::Value),
+        (status = 400, description = "Missing actor", body = ErrorBody),
+        (status = 404, description = "Incident not found", body = ErrorBody),
+        (status = 409, description = "invalid_transition", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn acknowledge_incident(
+    State(st): State<AppState>,
+    Path(incident_id): Path<String>,
+    Json(req): Json<IncidentTransitionRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    apply_incident_action(State(st), Path(incident_id), Json(req.into_action("ACK"))).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/incidents/{incident_id}/resolve",
+    tag = "incidents",
+    params(("incident_id" = String, Path, description = "Incident id")),
+    request_body = IncidentTransitionRequest,
+    responses(
+        (status = 200, description = "Incident resolved", body = serde_json::Value),
+        (status = 400, description = "Missing actor", body = ErrorBody),
+        (status = 404, description = "Incident not found", body = ErrorBody),
+        (status = 409, description = "invalid_transition", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn resolve_incident(
+    State(st): State<AppState>,
+    Path(incident_id): Path<String>,
+    Json(req): Json<IncidentTransitionRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    apply_incident_action(State(st), Path(incident_id), Json(req.into_action("RESOLVE"))).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/incidents/{incident_id}/action",
+    tag = "incidents",
+    params(("incident_id" = String, Path, description = "Incident id")),
+    request_body = IncidentActionRequest,
+    responses(
+        (status = 200, description = "Updated incident", body = serde_json::Value),
+        (status = 400, description = "Unknown action or missing actor", body = ErrorBody),
+        (status = 404, description = "Incident not found", body = ErrorBody),
+        (status = 409, description = "invalid_transition", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
 pub async fn apply_incident_action(
     State(st): State<AppState>,
     Path(incident_id): Path<String>,
@@ -110,11 +258,12 @@ pub async fn apply_incident_action(
     // fetch current incident
     let current = tx
         .query_one(
-            "SELECT id::text, zone_id, severity, status, title, details, detected_at FROM incidents WHERE id=$1::uuid",
+            "SELECT id::text, zone_id, severity, status, title, details, detected_at, resolved_at FROM incidents WHERE id=$1::uuid FOR UPDATE",
             &[&incident_id],
         )
         .await
         .map_err(|_| AppError::NotFound("incident not found".into()))?;
+    let new_status = next_status(current.get("status"), &req.action)?;
 
     let mut details: serde_json::Value = current.get("details");
 
@@ -124,7 +273,7 @@ pub async fn apply_incident_action(
     }
     if !req.note.is_empty() {
         let entry = json!({
-            "at": time::OffsetDateTime::now_utc().format(&time::format_description::well_known::Rfc3339).unwrap(),
+            "at": to_rfc3339(st.clock.now())?,
             "actor": req.actor,
             "note": req.note,
             "action": req.action,
@@ -138,27 +287,47 @@ pub async fn apply_incident_action(
         details.as_object_mut().map(|m| m.insert("notes".into(), json!(notes)));
     }
 
-    let new_status = match req.action.as_str() {
-        "ACK" => "ACK",
-        "RESOLVE" => "RESOLVED",
-        _ => current.get::<_, &str>("status"),
-    };
-
     let details_str = serde_json::to_string(&details).unwrap();
     let updated = tx
         .query_one(
-            "UPDATE incidents SET status=$2, details=$3::jsonb WHERE id=$1::uuid RETURNING id::text, zone_id, severity, status, title, details, detected_at",
+            "UPDATE incidents SET status=$2, details=$3::jsonb, resolved_at=CASE WHEN $2='RESOLVED' THEN now() ELSE resolved_at END \
+             WHERE id=$1::uuid RETURNING id::text, zone_id, severity, status, title, details, detected_at, resolved_at",
             &[&incident_id, &new_status, &details_str],
         )
         .await?;
 
     let audit_action = format!("INCIDENT_{}", req.action);
-    tx.execute(
-        "INSERT INTO audit_log(actor,action,target_type,target_id,reason,details) VALUES($1,$2,'incident',$3,$4, jsonb_build_object('assignee',$5,'note',$6,'status',$7))",
+    let audit = tx.query_one(
+        "INSERT INTO audit_log(actor,action,target_type,target_id,reason,details) VALUES($1,$2,'incident',$3,$4, jsonb_build_object('assignee',$5,'note',$6,'status',$7)) RETURNING id::text, actor, action, target_type, target_id, reason, details, created_at",
         &[&req.actor, &audit_action, &incident_id, &req.reason, &req.assignee, &req.note, &new_status],
     ).await?;
 
     tx.commit().await?;
+    publish_audit(&st, &audit);
+
+    Ok(Json(format_incident(&updated)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::response::IntoResponse;
 
-    Ok(Json(format_incident(&updated)))
+    #[test]
+    fn lifecycle_happy_path() {
+        assert_eq!(next_status("OPEN", "ACK").unwrap(), "ACK");
+        assert_eq!(next_status("ACK", "RESOLVE").unwrap(), "RESOLVED");
+        assert_eq!(next_status("OPEN", "RESOLVE").unwrap(), "RESOLVED");
+        assert_eq!(next_status("ACK", "ASSIGN").unwrap(), "ACK");
+    }
+
+    #[test]
+    fn illegal_transitions_are_409() {
+        for (status, action) in [("RESOLVED", "RESOLVE"), ("RESOLVED", "ACK"), ("ACK", "ACK"), ("RESOLVED", "ASSIGN")] {
+            let err = next_status(status, action).unwrap_err();
+            assert_eq!(err.status_and_code(), (StatusCode::CONFLICT, "invalid_transition"), "{status} {action}");
+        }
+        let res = next_status("RESOLVED", "RESOLVE").unwrap_err().into_response();
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+    }
 }
@@ -1,9 +1,20 @@
+pub mod accounts;
+pub mod adjustments;
 pub mod admin;
+pub mod anomalies;
+pub mod archive;
 pub mod audit;
 pub mod balances;
 pub mod controls;
+pub mod events;
+pub mod explain;
 pub mod incidents;
+pub mod openapi;
 pub mod spool;
+pub mod splits;
+pub mod success_rate;
+pub mod topology;
 pub mod transactions;
 pub mod transfers;
+pub mod whitelists;
 pub mod zones;
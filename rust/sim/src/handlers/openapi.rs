@@ -0,0 +1,161 @@
+use axum::Json;
+use utoipa::OpenApi;
+
+use crate::error::ErrorBody;
+use crate::handlers::{
+    accounts, adjustments, admin, anomalies, archive, audit, balances, controls, events, explain, incidents, spool, splits, success_rate, topology,
+    transactions, transfers, whitelists, zones,
+};
+
+/// OpenAPI 3.0 description of every route, assembled from the `#[utoipa::path]`
+/// annotations on the handlers. A route added in `build_app` must be listed here too.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "time-ledger sim", description = "Zoned double-entry ledger simulation API"),
+    paths(
+        admin::healthz,
+        admin::readyz,
+        admin::metrics,
+        admin::version,
+        openapi_json,
+        zones::list_zones,
+        topology::get_topology,
+        transfers::create_transfer,
+        transfers::create_transfer_batch,
+        explain::explain_transfer,
+        balances::list_balances,
+        balances::balance_summary,
+        accounts::account_statement,
+        accounts::account_balance,
+        accounts::balance_proof,
+        accounts::account_turnover,
+        adjustments::adjust_balance,
+        whitelists::get_whitelist,
+        whitelists::set_whitelist,
+        whitelists::clear_whitelist,
+        transactions::list_transactions,
+        splits::create_transaction,
+        transactions::export_transactions_csv,
+        transactions::get_transaction,
+        transfers::reverse_transaction,
+        zones::get_zone,
+        zones::update_zone,
+        zones::get_zone_status,
+        zones::set_zone_status,
+        zones::set_zones_status,
+        zones::zone_status_ws,
+        zones::schedule_maintenance,
+        balances::zone_balance_summary,
+        transactions::list_zone_transactions,
+        success_rate::zone_success_rate,
+        incidents::list_incidents_by_zone,
+        incidents::list_recent_incidents,
+        incidents::get_incident,
+        incidents::apply_incident_action,
+        incidents::acknowledge_incident,
+        incidents::resolve_incident,
+        controls::get_zone_controls,
+        controls::set_zone_controls,
+        spool::get_spool_stats,
+        spool::replay_spool,
+        audit::list_audit,
+        audit::search_audit,
+        audit::stream_audit,
+        events::stream_events,
+        admin::snapshot,
+        admin::restore,
+        admin::export,
+        admin::checkpoint,
+        archive::archive,
+        admin::reconcile,
+        anomalies::list_anomalies,
+        admin::balance_merkle,
+    ),
+    components(schemas(
+        ErrorBody,
+        transfers::CreateTransferRequest,
+        transfers::TransferResponse,
+        transfers::SpooledResponse,
+        transfers::BatchTransferRequest,
+        transfers::BatchItemResult,
+        transfers::BatchValidation,
+        transfers::ReverseRequest,
+        splits::CreateTransactionRequest,
+        splits::TransactionLeg,
+        splits::CreateTransactionResponse,
+        adjustments::AdjustmentRequest,
+        balances::BalanceSummary,
+        zones::Zone,
+        zones::ZoneList,
+        zones::ZoneDetail,
+        zones::SetZoneStatusRequest,
+        zones::BulkZoneStatusRequest,
+        zones::UpdateZoneRequest,
+        zones::ScheduleMaintenanceRequest,
+        zones::MaintenanceWindow,
+        controls::ZoneControls,
+        controls::SetZoneControlsRequest,
+        spool::SpoolStats,
+        spool::ReplayRequest,
+        spool::ReplayResult,
+        whitelists::AccountWhitelist,
+        whitelists::SetWhitelistRequest,
+        whitelists::ClearWhitelistRequest,
+        incidents::IncidentActionRequest,
+        incidents::IncidentTransitionRequest,
+    ))
+)]
+pub struct ApiDoc;
+
+#[utoipa::path(
+    get,
+    path = "/v1/openapi.json",
+    tag = "health",
+    responses(
+        (status = 200, description = "This OpenAPI document", body = serde_json::Value),
+    )
+)]
+pub async fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::response::IntoResponse;
+    use http_body_util::BodyExt;
+
+    async fn served() -> serde_json::Value {
+        let body = openapi_json().await.into_response().into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn documents_transfers() {
+        let doc = served().await;
+        assert!(doc["openapi"].as_str().unwrap().starts_with("3.0"));
+        let post = &doc["paths"]["/v1/transfers"]["post"];
+        for status in ["200", "202", "400", "404", "409", "422", "429", "503"] {
+            assert!(post["responses"][status].is_object(), "missing {status} response");
+        }
+        let schema = &doc["components"]["schemas"]["CreateTransferRequest"];
+        for field in ["request_id", "from_account", "to_account", "amount_units", "zone_id", "currency"] {
+            assert!(schema["properties"][field].is_object(), "missing {field}");
+        }
+    }
+
+    #[tokio::test]
+    async fn documents_every_routed_path() {
+        let doc = served().await;
+        let app = include_str!("../app.rs");
+        let routes: Vec<&str> = app
+            .split(".route(")
+            .skip(1)
+            .filter_map(|rest| rest.trim_start().strip_prefix('"')?.split('"').next())
+            .collect();
+        assert!(routes.len() > 30);
+        for route in routes {
+            assert!(doc["paths"][route].is_object(), "{route} is routed but not documented");
+        }
+    }
+}
@@ -0,0 +1,443 @@
+use axum::{
+    extract::{rejection::JsonRejection, State},
+    http::{HeaderMap, StatusCode},
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use utoipa::ToSchema;
+
+use crate::auth::{resolve_principal, Principal};
+use crate::error::{AppError, ErrorBody};
+use crate::handlers::events::publish_event;
+use crate::handlers::transfers::{
+    acquire_zone_token, apply_degraded_policy, check_account_zones, check_amount, check_id, check_metadata_schema,
+    check_metadata_size, currency_scale, find_idempotent, idempotency_conflict, invalid_transfer, leg_totals, post_legs,
+    transfer_currency, transfer_hash, unknown_currency, zone_blocked, zone_gate, Existing, Leg, PostedLegs,
+    TransferInput, TransferLimits,
+};
+use crate::handlers::whitelists::check_whitelists;
+use crate::handlers::zones::require_zone;
+use crate::jwt::Claims;
+use crate::state::AppState;
+use crate::util::to_rfc3339;
+use crate::{postings_balanced, Direction};
+
+/// Most legs accepted in one transaction.
+pub const MAX_LEGS: usize = 100;
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CreateTransactionRequest {
+    /// Idempotency key, shared with `/v1/transfers`.
+    pub request_id: String,
+    pub zone_id: String,
+    /// Two to [`MAX_LEGS`] postings; debits must equal credits.
+    pub legs: Vec<TransactionLeg>,
+    #[serde(default)]
+    pub metadata: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct TransactionLeg {
+    pub account: String,
+    /// DEBIT or CREDIT.
+    #[schema(value_type = String, example = "DEBIT")]
+    pub direction: Direction,
+    /// Positive, in minor units.
+    pub amount: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CreateTransactionResponse {
+    pub status: String,
+    pub transaction_id: String,
+    pub request_id: String,
+    pub created_at: String,
+    pub payload_hash: String,
+}
+
+/// Check the legs before touching the database; returns the total debited.
+fn validate_legs(req: &CreateTransactionRequest, limits: &TransferLimits) -> Result<i64, AppError> {
+    check_id("request_id", &req.request_id)?;
+    check_id("zone_id", &req.zone_id)?;
+    if req.legs.len() < 2 {
+        return Err(invalid_transfer("legs", "min_items", "a transaction needs at least two legs".into(), json!(2)));
+    }
+    if req.legs.len() > MAX_LEGS {
+        return Err(invalid_transfer(
+            "legs",
+            "max_items",
+            format!("{} legs, more than {MAX_LEGS}", req.legs.len()),
+            json!(MAX_LEGS),
+        ));
+    }
+    for (i, leg) in req.legs.iter().enumerate() {
+        check_id(&format!("legs[{i}].account"), &leg.account)?;
+        check_amount(&format!("legs[{i}].amount"), leg.amount, limits)?;
+    }
+    check_metadata_size(&req.metadata, limits)?;
+
+    let postings: Vec<(Direction, i64)> = req.legs.iter().map(|l| (l.direction, l.amount)).collect();
+    if !postings_balanced(&postings) {
+        let total = |side: Direction| -> i128 {
+            postings.iter().filter(|(d, _)| *d == side).map(|(_, a)| *a as i128).sum()
+        };
+        let (debits, credits) = (total(Direction::Debit), total(Direction::Credit));
+        return Err(AppError::Detailed {
+            status: StatusCode::BAD_REQUEST,
+            code: "unbalanced_transaction",
+            message: format!("debits ({debits}) must equal credits ({credits})"),
+            details: json!({ "debits": debits.to_string(), "credits": credits.to_string() }),
+        });
+    }
+
+    let totals = leg_totals(&legs(req)).ok_or_else(|| {
+        invalid_transfer("legs", "max_amount", "an account's legs add up past the i64 range".into(), json!(i64::MAX))
+    })?;
+    if let Some((account, _)) = totals.iter().find(|(_, (debits, credits))| *debits > 0 && *credits > 0) {
+        return Err(invalid_transfer(
+            "legs",
+            "distinct_sides",
+            format!("account {account} is both debited and credited"),
+            json!(null),
+        ));
+    }
+    let debited = totals.values().try_fold(0i64, |sum, (debits, _)| sum.checked_add(*debits));
+    debited.ok_or_else(|| invalid_transfer("legs", "max_amount", "total debits leave the i64 range".into(), json!(i64::MAX)))
+}
+
+fn legs(req: &CreateTransactionRequest) -> Vec<Leg<'_>> {
+    req.legs
+        .iter()
+        .map(|l| Leg { account: &l.account, direction: l.direction, amount_units: l.amount })
+        .collect()
+}
+
+/// Post one transaction with any number of legs, e.g. a payment split across
+/// several payees, atomically: every posting and balance is written or none is.
+/// It goes through the same zone gate, degraded policy, metadata schema,
+/// whitelists, account zones, rate limit and idempotency as `/v1/transfers`,
+/// which remains the two-leg shorthand. Blocked zones reject instead of spooling.
+#[utoipa::path(
+    post,
+    path = "/v1/transactions",
+    tag = "transactions",
+    request_body = CreateTransactionRequest,
+    responses(
+        (status = 200, description = "Applied, or idempotent replay of an applied request", body = CreateTransactionResponse),
+        (status = 400, description = "Malformed body or unknown field, invalid_transfer (see details.field and details.rule), unbalanced_transaction, invalid_metadata or unknown_currency", body = ErrorBody),
+        (status = 401, description = "invalid_token: Authorization is not a known bearer token", body = ErrorBody),
+        (status = 403, description = "counterparty_not_whitelisted", body = ErrorBody),
+        (status = 404, description = "unknown_zone", body = ErrorBody),
+        (status = 409, description = "Idempotency conflict (details carry the existing transaction id and both payload hashes), account_zone_mismatch or account_currency_mismatch", body = ErrorBody),
+        (status = 422, description = "insufficient_available_funds or balance_overflow", body = ErrorBody),
+        (status = 429, description = "rate_limited; see Retry-After", body = ErrorBody),
+        (status = 503, description = "zone_down, zone_degraded, writes_blocked, throttled, or database unavailable", body = ErrorBody),
+    )
+)]
+pub async fn create_transaction(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    claims: Option<Extension<Claims>>,
+    body: Result<Json<CreateTransactionRequest>, JsonRejection>,
+) -> Result<Json<CreateTransactionResponse>, AppError> {
+    let principal = resolve_principal(&st.api_tokens, &headers, claims.as_deref())?;
+    let Json(req) = body?;
+    let _timer = st.metrics.transfer_duration_seconds.start_timer();
+    let result = post_transaction(&st, &req, &principal).await;
+    let (response, posted) = result.inspect_err(|e| st.metrics.record_rejection(e))?;
+    match posted {
+        Some((event, amount_units)) => {
+            st.metrics.transfers_total.with_label_values(&[req.zone_id.as_str(), "posted"]).inc();
+            st.metrics.transfer_amount_units.observe(amount_units as f64);
+            publish_event(&st, event);
+        }
+        None => {
+            st.metrics.transfers_total.with_label_values(&[req.zone_id.as_str(), "idempotent_replay"]).inc();
+        }
+    }
+    Ok(Json(response))
+}
+
+/// Gate, deduplicate and commit `req`; the event and total are returned for a new
+/// transaction, None for a replay.
+async fn post_transaction(
+    st: &AppState,
+    req: &CreateTransactionRequest,
+    principal: &Principal,
+) -> Result<(CreateTransactionResponse, Option<(crate::handlers::events::LedgerEvent, i64)>), AppError> {
+    let amount_units = validate_legs(req, &st.transfer_limits)?;
+    let hash = transfer_hash(req, &st.idempotency_hash_exclude)?;
+    let legs = legs(req);
+    let mut accounts: Vec<&str> = legs.iter().map(|l| l.account).collect();
+    accounts.sort_unstable();
+    accounts.dedup();
+
+    let mut client = st.db.get().await?;
+    let tx = client.transaction().await?;
+
+    let zone_row = tx
+        .query_opt(
+            "SELECT status, rate_limit_per_sec, currency, metadata_schema, degraded_policy FROM zones WHERE id=$1 AND NOT internal",
+            &[&req.zone_id],
+        )
+        .await?;
+    let zone_row = require_zone(zone_row, &req.zone_id)?;
+    let status: String = zone_row.get(0);
+    let policy: Option<serde_json::Value> = zone_row.get(4);
+    apply_degraded_policy(&req.zone_id, &status, policy.as_ref(), &req.request_id, amount_units)?;
+    check_metadata_schema(&req.zone_id, zone_row.get::<_, Option<serde_json::Value>>(3).as_ref(), &req.metadata)?;
+    let currency = transfer_currency(None, zone_row.get(2))?;
+    if let Some(c) = &currency {
+        if currency_scale(&tx, c).await?.is_none() {
+            return Err(unknown_currency(c));
+        }
+    }
+    let zone_rate = zone_row
+        .get::<_, Option<i32>>(1)
+        .map(|r| r.max(0) as u32)
+        .unwrap_or(st.zone_rate_limit);
+
+    let ctrl_row = tx
+        .query_opt("SELECT writes_blocked, cross_zone_throttle FROM zone_controls WHERE zone_id=$1", &[&req.zone_id])
+        .await?;
+    let (wb, throttle) = ctrl_row.map(|r| (r.get::<_, bool>(0), r.get::<_, i32>(1))).unwrap_or((false, 100));
+    let blocked = zone_gate(&status, wb, throttle, &req.request_id);
+
+    if let Some(r) = find_idempotent(&tx, &req.request_id, st).await? {
+        let ph: String = r.get(1);
+        if ph != hash {
+            return Err(idempotency_conflict(&req.request_id, Existing::Transaction(r.get(0)), &ph, &hash));
+        }
+        tx.commit().await?;
+        let created_at: time::OffsetDateTime = r.get(2);
+        let response = CreateTransactionResponse {
+            status: "APPLIED".into(),
+            transaction_id: r.get(0),
+            request_id: req.request_id.clone(),
+            created_at: to_rfc3339(created_at)?,
+            payload_hash: ph,
+        };
+        return Ok((response, None));
+    }
+
+    // every debited account must accept every credited one, and vice versa
+    let whitelists = tx
+        .query("SELECT account_id, counterparties FROM account_whitelists WHERE account_id = ANY($1)", &[&accounts])
+        .await?;
+    let whitelist_of = |account: &str| -> Option<Vec<String>> {
+        whitelists.iter().find(|r| r.get::<_, &str>(0) == account).map(|r| r.get(1))
+    };
+    for debit in legs.iter().filter(|l| l.direction == Direction::Debit) {
+        for credit in legs.iter().filter(|l| l.direction == Direction::Credit) {
+            check_whitelists(
+                debit.account,
+                credit.account,
+                whitelist_of(debit.account).as_deref(),
+                whitelist_of(credit.account).as_deref(),
+            )?;
+        }
+    }
+
+    acquire_zone_token(st, &req.zone_id, zone_rate)?;
+    if let Some((code, reason)) = blocked {
+        return Err(zone_blocked(&req.zone_id, code, reason));
+    }
+
+    tx.execute(
+        "INSERT INTO accounts(id, zone_id) SELECT unnest($1::text[]), $2 ON CONFLICT DO NOTHING",
+        &[&accounts, &req.zone_id],
+    )
+    .await?;
+    let account_zones: Vec<(String, String)> = tx
+        .query("SELECT id, zone_id FROM accounts WHERE id = ANY($1)", &[&accounts])
+        .await?
+        .iter()
+        .map(|r| (r.get(0), r.get(1)))
+        .collect();
+    check_account_zones(&req.zone_id, &account_zones)?;
+
+    // the transaction row names the first debited and first credited account
+    let first = |side: Direction| legs.iter().find(|l| l.direction == side).map_or("", |l| l.account);
+    let PostedLegs { txn_id, created_at, event, .. } = post_legs(
+        &tx,
+        &TransferInput {
+            request_id: &req.request_id,
+            payload_hash: &hash,
+            from_account: first(Direction::Debit),
+            to_account: first(Direction::Credit),
+            amount_units,
+            zone_id: &req.zone_id,
+            metadata: &req.metadata,
+            currency: currency.as_deref(),
+            reverses_txn_id: None,
+            created_by: principal.subject(),
+        },
+        &legs,
+        st,
+    )
+    .await?;
+    tx.commit().await?;
+
+    let response = CreateTransactionResponse {
+        status: "APPLIED".into(),
+        transaction_id: txn_id,
+        request_id: req.request_id.clone(),
+        created_at: to_rfc3339(created_at)?,
+        payload_hash: hash,
+    };
+    Ok((response, Some((event, amount_units))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leg(account: &str, direction: Direction, amount: i64) -> TransactionLeg {
+        TransactionLeg { account: account.into(), direction, amount }
+    }
+
+    fn split(legs: Vec<TransactionLeg>) -> CreateTransactionRequest {
+        CreateTransactionRequest {
+            request_id: "req-split-1".into(),
+            zone_id: "zone-eu".into(),
+            legs,
+            metadata: json!({ "note": "dinner" }),
+        }
+    }
+
+    fn rule(err: AppError) -> (StatusCode, &'static str, serde_json::Value) {
+        match err {
+            AppError::Detailed { status, code, details, .. } => (status, code, details),
+            other => panic!("expected a detailed error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn balanced_three_leg_split_is_valid() {
+        let req = split(vec![
+            leg("acct-payer", Direction::Debit, 300),
+            leg("acct-alice", Direction::Credit, 100),
+            leg("acct-bob", Direction::Credit, 200),
+        ]);
+        assert_eq!(validate_legs(&req, &TransferLimits::default()).unwrap(), 300);
+        let totals = leg_totals(&legs(&req)).unwrap();
+        assert_eq!(totals["acct-payer"], (300, 0));
+        assert_eq!(totals["acct-bob"], (0, 200));
+    }
+
+    #[test]
+    fn unbalanced_split_is_rejected() {
+        let req = split(vec![
+            leg("acct-payer", Direction::Debit, 300),
+            leg("acct-alice", Direction::Credit, 100),
+            leg("acct-bob", Direction::Credit, 150),
+        ]);
+        let (status, code, details) = rule(validate_legs(&req, &TransferLimits::default()).unwrap_err());
+        assert_eq!((status, code), (StatusCode::BAD_REQUEST, "unbalanced_transaction"));
+        assert_eq!(details, json!({ "debits": "300", "credits": "250" }));
+    }
+
+    #[test]
+    fn malformed_legs_are_invalid_transfers() {
+        let limits = TransferLimits::default();
+        let cases = [
+            (vec![leg("acct-a", Direction::Debit, 100)], "min_items"),
+            (vec![leg("acct-a", Direction::Debit, 0), leg("acct-b", Direction::Credit, 0)], "positive"),
+            (vec![leg("acct a", Direction::Debit, 100), leg("acct-b", Direction::Credit, 100)], "charset"),
+            (
+                vec![
+                    leg("acct-a", Direction::Debit, 100),
+                    leg("acct-a", Direction::Credit, 50),
+                    leg("acct-b", Direction::Credit, 50),
+                ],
+                "distinct_sides",
+            ),
+        ];
+        for (legs, expected) in cases {
+            let (status, code, details) = rule(validate_legs(&split(legs), &limits).unwrap_err());
+            assert_eq!((status, code, details["rule"].as_str()), (StatusCode::BAD_REQUEST, "invalid_transfer", Some(expected)));
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn split_posts_every_leg_or_none() {
+        let st = crate::testing::test_db().await;
+        let run = uuid::Uuid::new_v4();
+        let (payer, alice, bob) = (format!("acct-payer-{run}"), format!("acct-alice-{run}"), format!("acct-bob-{run}"));
+        let request = |request_id: String, bob_amount: i64| CreateTransactionRequest {
+            request_id,
+            zone_id: "zone-eu".into(),
+            legs: vec![
+                leg(&payer, Direction::Debit, 300),
+                leg(&alice, Direction::Credit, 100),
+                leg(&bob, Direction::Credit, bob_amount),
+            ],
+            metadata: json!({}),
+        };
+        let client = st.db.get().await.unwrap();
+        let balance = |account: String| {
+            let client = &client;
+            async move {
+                client
+                    .query_opt("SELECT balance_units FROM balances WHERE account_id=$1", &[&account])
+                    .await
+                    .unwrap()
+                    .map_or(0, |r| r.get::<_, i64>(0))
+            }
+        };
+
+        let err = create_transaction(State(st.clone()), HeaderMap::new(), None, Ok(Json(request(format!("req-bad-{run}"), 150))))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(rule(err).1, "unbalanced_transaction");
+        assert_eq!(balance(payer.clone()).await, 0, "nothing written for a rejected split");
+
+        let Json(posted) = create_transaction(State(st.clone()), HeaderMap::new(), None, Ok(Json(request(format!("req-{run}"), 200)))).await.unwrap();
+        assert_eq!((balance(payer.clone()).await, balance(alice.clone()).await, balance(bob.clone()).await), (-300, 100, 200));
+        let postings: i64 = client
+            .query_one("SELECT COUNT(*) FROM postings WHERE txn_id::text=$1", &[&posted.transaction_id])
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(postings, 3);
+
+        let Json(replay) = create_transaction(State(st.clone()), HeaderMap::new(), None, Ok(Json(request(format!("req-{run}"), 200)))).await.unwrap();
+        assert_eq!(replay.transaction_id, posted.transaction_id);
+        assert_eq!(balance(payer).await, -300, "a replay posts nothing");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn split_statement_counterparty_comes_from_the_postings() {
+        use crate::handlers::accounts::{account_statement, StatementQuery};
+        use axum::extract::{Path, Query};
+        let st = crate::testing::test_db().await;
+        let run = uuid::Uuid::new_v4();
+        let (payer, alice, bob) = (format!("acct-payer-{run}"), format!("acct-alice-{run}"), format!("acct-bob-{run}"));
+        let req = CreateTransactionRequest {
+            request_id: format!("req-{run}"),
+            zone_id: "zone-eu".into(),
+            legs: vec![leg(&payer, Direction::Debit, 300), leg(&alice, Direction::Credit, 100), leg(&bob, Direction::Credit, 200)],
+            metadata: json!({}),
+        };
+        create_transaction(State(st.clone()), HeaderMap::new(), None, Ok(Json(req))).await.unwrap();
+
+        let counterparty = |account: String| {
+            let q = StatementQuery { from: None, to: None, opening_balance: 0, limit: 10 };
+            let st = st.clone();
+            async move {
+                let Json(statement) = account_statement(State(st), Path(account), Query(q)).await.unwrap();
+                statement["entries"][0]["counterparty"].clone()
+            }
+        };
+        // bob's credit is paired with the only debit; the payer paid two accounts
+        assert_eq!(counterparty(bob).await, json!(payer));
+        assert_eq!(counterparty(alice).await, json!(payer));
+        assert_eq!(counterparty(payer).await, serde_json::Value::Null);
+    }
+}
@@ -1,11 +1,14 @@
 use axum::{extract::{Path, State}, Json};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-use crate::error::AppError;
+use crate::error::{AppError, ErrorBody};
+use crate::handlers::audit::publish_audit;
 use crate::state::AppState;
 use crate::handlers::transfers::{apply_transfer_bypass, TransferInput};
+use crate::handlers::zones::require_zone;
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct SpoolStats {
     pub zone_id: String,
     pub pending: i64,
@@ -13,11 +16,21 @@ pub struct SpoolStats {
     pub failed: i64,
 }
 
+#[utoipa::path(
+    get,
+    path = "/v1/zones/{zone_id}/spool",
+    tag = "zones",
+    params(("zone_id" = String, Path, description = "Zone id")),
+    responses(
+        (status = 200, description = "Spooled transfer counts by status", body = SpoolStats),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
 pub async fn get_spool_stats(
     State(st): State<AppState>,
     Path(zone_id): Path<String>,
 ) -> Result<Json<SpoolStats>, AppError> {
-    let client = st.db.get().await?;
+    let client = st.db_read.get().await?;
     let row = client
         .query_one(
             "SELECT COUNT(*) FILTER (WHERE status='PENDING') as pending, COUNT(*) FILTER (WHERE status='APPLIED') as applied, COUNT(*) FILTER (WHERE status='FAILED') as failed FROM spooled_transfers WHERE zone_id=$1",
@@ -33,7 +46,7 @@ pub async fn get_spool_stats(
     }))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct ReplayRequest {
     #[serde(default = "default_limit")]
     pub limit: i64,
@@ -45,13 +58,26 @@ pub struct ReplayRequest {
 
 fn default_limit() -> i64 { 50 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct ReplayResult {
     pub zone_id: String,
     pub applied: i64,
     pub failed: i64,
 }
 
+#[utoipa::path(
+    post,
+    path = "/v1/zones/{zone_id}/spool/replay",
+    tag = "zones",
+    params(("zone_id" = String, Path, description = "Zone id")),
+    request_body = ReplayRequest,
+    responses(
+        (status = 200, description = "Replay summary", body = ReplayResult),
+        (status = 404, description = "unknown_zone", body = ErrorBody),
+        (status = 409, description = "Zone not ready for replay", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
 pub async fn replay_spool(
     State(st): State<AppState>,
     Path(zone_id): Path<String>,
@@ -62,9 +88,9 @@ pub async fn replay_spool(
 
     // check zone readiness
     let status_row = client
-        .query_one("SELECT status FROM zones WHERE id=$1", &[&zone_id])
+        .query_opt("SELECT status FROM zones WHERE id=$1 AND NOT internal", &[&zone_id])
         .await?;
-    let status: String = status_row.get(0);
+    let status: String = require_zone(status_row, &zone_id)?.get(0);
 
     let ctrl_row = client
         .query_opt("SELECT writes_blocked, cross_zone_throttle FROM zone_controls WHERE zone_id=$1", &[&zone_id])
@@ -80,7 +106,7 @@ pub async fn replay_spool(
     // fetch pending spooled transfers
     let rows = client
         .query(
-            "SELECT id::text, request_id, payload_hash, from_account, to_account, amount_units, zone_id, metadata FROM spooled_transfers WHERE zone_id=$1 AND status='PENDING' ORDER BY created_at ASC LIMIT $2",
+            "SELECT id::text, request_id, payload_hash, from_account, to_account, amount_units, zone_id, metadata, currency, created_by FROM spooled_transfers WHERE zone_id=$1 AND status='PENDING' ORDER BY created_at ASC LIMIT $2",
             &[&zone_id, &limit],
         )
         .await?;
@@ -97,11 +123,14 @@ pub async fn replay_spool(
         let amount_units: i64 = row.get("amount_units");
         let zone_id_val: String = row.get("zone_id");
         let metadata: serde_json::Value = row.get("metadata");
+        let currency: Option<String> = row.get("currency");
+        let created_by: String = row.get("created_by");
 
         let result = apply_transfer_bypass(&st, &TransferInput {
             request_id: &request_id, payload_hash: &payload_hash,
             from_account: &from_account, to_account: &to_account,
             amount_units, zone_id: &zone_id_val, metadata: &metadata,
+            currency: currency.as_deref(), reverses_txn_id: None, created_by: &created_by,
         }).await;
 
         match result {
@@ -128,12 +157,15 @@ pub async fn replay_spool(
     }
 
     // audit summary
-    let _ = client
-        .execute(
-            "INSERT INTO audit_log(actor,action,target_type,target_id,reason,details) VALUES($1,'REPLAY_SPOOL','zone',$2,$3, jsonb_build_object('applied',$4,'failed',$5,'limit',$6))",
+    if let Ok(audit) = client
+        .query_one(
+            "INSERT INTO audit_log(actor,action,target_type,target_id,reason,details) VALUES($1,'REPLAY_SPOOL','zone',$2,$3, jsonb_build_object('applied',$4,'failed',$5,'limit',$6)) RETURNING id::text, actor, action, target_type, target_id, reason, details, created_at",
             &[&req.actor, &zone_id, &req.reason, &applied, &failed, &limit],
         )
-        .await;
+        .await
+    {
+        publish_audit(&st, &audit);
+    }
 
     Ok(Json(ReplayResult { zone_id, applied, failed }))
 }
@@ -0,0 +1,270 @@
+use axum::{extract::{Path, Query, State}, Json};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use time::OffsetDateTime;
+use tracing::warn;
+use utoipa::IntoParams;
+
+use crate::error::{AppError, ErrorBody};
+use crate::handlers::transfers::TransferOutcome;
+use crate::handlers::zones::{require_zone, status_at};
+use crate::state::AppState;
+use crate::util::{parse_rfc3339, to_rfc3339};
+
+const SUCCEEDED: [&str; 2] = ["posted", "idempotent_replay"];
+
+/// `outcome` label for a transfer attempt on a known zone. Errors raised before
+/// the zone was validated, and server-side failures, are not attempts.
+pub(crate) fn attempt_outcome(result: &Result<TransferOutcome, AppError>) -> Option<&'static str> {
+    match result {
+        Ok(TransferOutcome::Applied(..)) => Some("posted"),
+        Ok(TransferOutcome::Duplicate(_)) => Some("idempotent_replay"),
+        Ok(TransferOutcome::Spooled { .. }) => Some("spooled"),
+        Err(e) => match e.status_and_code().1 {
+            code @ ("zone_down" | "writes_blocked" | "throttled" | "rate_limited" | "conflict" | "currency_mismatch") => {
+                Some(code)
+            }
+            "insufficient_available_funds" => Some("overdraft"),
+            "counterparty_not_whitelisted" => Some("not_whitelisted"),
+            code @ ("account_zone_mismatch" | "account_currency_mismatch" | "unknown_currency") => Some(code),
+            _ => None,
+        },
+    }
+}
+
+/// Count one attempt in `zone_transfer_attempts_total` and in the per-minute
+/// table behind `/success-rate`. The table write runs off the request path.
+pub(crate) fn record_attempt(st: &AppState, zone_id: &str, result: &Result<TransferOutcome, AppError>) {
+    let Some(outcome) = attempt_outcome(result) else { return };
+    st.metrics.zone_transfer_attempts.with_label_values(&[zone_id, outcome]).inc();
+
+    let db = st.db.clone();
+    let zone_id = zone_id.to_string();
+    tokio::spawn(async move {
+        let res = async {
+            let client = db.get().await?;
+            client
+                .execute(
+                    "INSERT INTO zone_transfer_attempts(zone_id,bucket,outcome,attempts) VALUES($1, date_trunc('minute', now()), $2, 1) \
+                     ON CONFLICT (zone_id,bucket,outcome) DO UPDATE SET attempts = zone_transfer_attempts.attempts + 1",
+                    &[&zone_id, &outcome],
+                )
+                .await?;
+            Ok::<_, AppError>(())
+        }
+        .await;
+        if let Err(e) = res {
+            warn!(zone_id = %zone_id, outcome, error = ?e, "failed to record transfer attempt");
+        }
+    });
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct AttemptSummary {
+    attempts: i64,
+    succeeded: i64,
+    spooled: i64,
+    rejected: i64,
+    /// None when there were no attempts.
+    success_rate: Option<f64>,
+    rejections: BTreeMap<String, i64>,
+}
+
+fn summarize(counts: &[(String, i64)]) -> AttemptSummary {
+    let mut s = AttemptSummary {
+        attempts: 0,
+        succeeded: 0,
+        spooled: 0,
+        rejected: 0,
+        success_rate: None,
+        rejections: BTreeMap::new(),
+    };
+    for (outcome, n) in counts {
+        s.attempts += n;
+        if SUCCEEDED.contains(&outcome.as_str()) {
+            s.succeeded += n;
+        } else if outcome == "spooled" {
+            s.spooled += n;
+        } else {
+            s.rejected += n;
+            *s.rejections.entry(outcome.clone()).or_default() += n;
+        }
+    }
+    s.success_rate = (s.attempts > 0).then(|| s.succeeded as f64 / s.attempts as f64);
+    s
+}
+
+/// Seconds spent in each status between `since` and `now`, walking the recorded
+/// transitions from `initial` (the status at `since`, if known). Time before the
+/// first transition is not counted when the initial status is unknown.
+fn status_seconds(
+    initial: Option<String>,
+    transitions: &[(String, OffsetDateTime)],
+    since: OffsetDateTime,
+    now: OffsetDateTime,
+) -> BTreeMap<String, f64> {
+    let mut out: BTreeMap<String, f64> = BTreeMap::new();
+    let mut current = initial;
+    let mut from = since;
+    for (status, at) in transitions {
+        if let Some(c) = current.replace(status.clone()) {
+            *out.entry(c).or_default() += (*at - from).as_seconds_f64().max(0.0);
+        }
+        from = *at;
+    }
+    if let Some(c) = current {
+        *out.entry(c).or_default() += (now - from).as_seconds_f64().max(0.0);
+    }
+    out
+}
+
+/// Share of the observed window the zone was not DOWN: the success rate a
+/// zone would show if status were the only reason to reject.
+fn expected_success_rate(status_seconds: &BTreeMap<String, f64>) -> Option<f64> {
+    let total: f64 = status_seconds.values().sum();
+    let down = status_seconds.get("DOWN").copied().unwrap_or(0.0);
+    (total > 0.0).then(|| (total - down) / total)
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SuccessRateQuery {
+    pub since: Option<String>,
+}
+
+/// Attempted vs succeeded transfers for a zone since `since` (default one hour
+/// ago, rounded down to the minute), with rejections broken down by reason and
+/// the success rate expected from the zone's status history alone.
+#[utoipa::path(
+    get,
+    path = "/v1/zones/{zone_id}/success-rate",
+    tag = "zones",
+    params(("zone_id" = String, Path, description = "Zone id"), SuccessRateQuery),
+    responses(
+        (status = 200, description = "Actual and expected success rate", body = serde_json::Value),
+        (status = 400, description = "Invalid since", body = ErrorBody),
+        (status = 404, description = "unknown_zone", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn zone_success_rate(
+    State(st): State<AppState>,
+    Path(zone_id): Path<String>,
+    Query(q): Query<SuccessRateQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let now = st.clock.now();
+    let since = match q.since.as_deref() {
+        Some(s) => parse_rfc3339("since", s)?,
+        None => now - time::Duration::hours(1),
+    };
+    if since >= now {
+        return Err(AppError::BadRequest("since must be in the past".into()));
+    }
+
+    let client = st.db_read.get().await?;
+    let zone = client
+        .query_opt(
+            "SELECT z.status, \
+             (SELECT a.details->>'status' FROM audit_log a WHERE a.action='SET_ZONE_STATUS' AND a.target_id=z.id AND a.created_at <= $2 ORDER BY a.created_at DESC LIMIT 1) AS status_before, \
+             (SELECT max(a.created_at) FROM audit_log a WHERE a.action='SET_ZONE_STATUS' AND a.target_id=z.id AND a.created_at <= $2) AS set_at \
+             FROM zones z WHERE z.id=$1",
+            &[&zone_id, &since],
+        )
+        .await?;
+    let zone = require_zone(zone, &zone_id)?;
+
+    let transitions: Vec<(String, OffsetDateTime)> = client
+        .query(
+            "SELECT details->>'status', created_at FROM audit_log WHERE action='SET_ZONE_STATUS' AND target_id=$1 AND created_at > $2 ORDER BY created_at",
+            &[&zone_id, &since],
+        )
+        .await?
+        .into_iter()
+        .map(|r| (r.get(0), r.get(1)))
+        .collect();
+
+    let counts: Vec<(String, i64)> = client
+        .query(
+            "SELECT outcome, SUM(attempts)::bigint FROM zone_transfer_attempts WHERE zone_id=$1 AND bucket >= date_trunc('minute', $2::timestamptz) GROUP BY outcome",
+            &[&zone_id, &since],
+        )
+        .await?
+        .into_iter()
+        .map(|r| (r.get(0), r.get(1)))
+        .collect();
+
+    let last_before = zone
+        .get::<_, Option<String>>("status_before")
+        .zip(zone.get::<_, Option<OffsetDateTime>>("set_at"));
+    let initial = status_at(last_before, !transitions.is_empty(), zone.get("status")).map(|(s, _)| s);
+    let seconds = status_seconds(initial, &transitions, since, now);
+
+    Ok(Json(json!({
+        "zone_id": zone_id,
+        "since": to_rfc3339(since)?,
+        "until": to_rfc3339(now)?,
+        "actual": summarize(&counts),
+        "expected_success_rate": expected_success_rate(&seconds),
+        "status_seconds": seconds,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+
+    fn detailed(code: &'static str) -> AppError {
+        AppError::Detailed { status: StatusCode::UNPROCESSABLE_ENTITY, code, message: String::new(), details: json!({}) }
+    }
+
+    #[test]
+    fn outcomes_from_errors() {
+        assert_eq!(attempt_outcome(&Err(detailed("zone_down"))), Some("zone_down"));
+        assert_eq!(attempt_outcome(&Err(detailed("insufficient_available_funds"))), Some("overdraft"));
+        assert_eq!(attempt_outcome(&Err(detailed("counterparty_not_whitelisted"))), Some("not_whitelisted"));
+        assert_eq!(
+            attempt_outcome(&Err(AppError::TooManyRequests { message: String::new(), retry_after_secs: 1 })),
+            Some("rate_limited")
+        );
+        // not attributable to a validated zone
+        assert_eq!(attempt_outcome(&Err(detailed("unknown_zone"))), None);
+        assert_eq!(attempt_outcome(&Err(AppError::BadRequest("amount_units must be > 0".into()))), None);
+        assert_eq!(attempt_outcome(&Err(AppError::Internal("db".into()))), None);
+    }
+
+    #[test]
+    fn summary_splits_rejections() {
+        let s = summarize(&[
+            ("posted".into(), 6),
+            ("idempotent_replay".into(), 1),
+            ("spooled".into(), 1),
+            ("throttled".into(), 1),
+            ("overdraft".into(), 1),
+        ]);
+        assert_eq!((s.attempts, s.succeeded, s.spooled, s.rejected), (10, 7, 1, 2));
+        assert_eq!(s.success_rate, Some(0.7));
+        assert_eq!(s.rejections.get("throttled"), Some(&1));
+        assert_eq!(s.rejections.get("overdraft"), Some(&1));
+        assert_eq!(summarize(&[]).success_rate, None);
+    }
+
+    #[test]
+    fn status_time_from_transitions() {
+        let t0 = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let at = |s: i64| t0 + time::Duration::seconds(s);
+        let transitions = vec![("DOWN".to_string(), at(60)), ("OK".to_string(), at(90))];
+
+        let secs = status_seconds(Some("OK".into()), &transitions, t0, at(120));
+        assert_eq!(secs.get("OK"), Some(&90.0));
+        assert_eq!(secs.get("DOWN"), Some(&30.0));
+        assert_eq!(expected_success_rate(&secs), Some(0.75));
+
+        // unknown start: only time from the first transition counts
+        let secs = status_seconds(None, &transitions, t0, at(120));
+        assert_eq!(secs.get("OK"), Some(&30.0));
+        assert_eq!(expected_success_rate(&secs), Some(0.5));
+        assert_eq!(expected_success_rate(&BTreeMap::new()), None);
+    }
+}
@@ -0,0 +1,167 @@
+use axum::{extract::{Query, State}, Json};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use utoipa::IntoParams;
+
+use crate::error::{AppError, ErrorBody};
+use crate::state::AppState;
+use crate::util::parse_rfc3339;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TopologyQuery {
+    pub since: Option<String>,
+    pub until: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Node {
+    id: String,
+    status: String,
+    total_balance_units: i64,
+}
+
+/// Directed cross-zone flow aggregated from transactions.
+pub struct Flow {
+    pub from_zone: String,
+    pub to_zone: String,
+    pub transfers: i64,
+    pub volume_units: i64,
+}
+
+/// Net flow between a pair of zones; `source` -> `target` is the direction of net movement.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Edge {
+    pub source: String,
+    pub target: String,
+    pub net_units: i64,
+    pub gross_units: i64,
+    pub transfers: i64,
+}
+
+/// Collapse directed flows into one edge per zone pair, netting opposite directions.
+pub fn net_edges(flows: &[Flow]) -> Vec<Edge> {
+    // keyed by (lo, hi) zone id; net is positive when lo -> hi dominates
+    let mut pairs: BTreeMap<(&str, &str), (i64, i64, i64)> = BTreeMap::new();
+    for f in flows {
+        let (key, sign) = if f.from_zone <= f.to_zone {
+            ((f.from_zone.as_str(), f.to_zone.as_str()), 1)
+        } else {
+            ((f.to_zone.as_str(), f.from_zone.as_str()), -1)
+        };
+        let e = pairs.entry(key).or_default();
+        e.0 = e.0.saturating_add(sign * f.volume_units);
+        e.1 = e.1.saturating_add(f.volume_units);
+        e.2 += f.transfers;
+    }
+    pairs
+        .into_iter()
+        .map(|((lo, hi), (net, gross, transfers))| {
+            let (source, target) = if net >= 0 { (lo, hi) } else { (hi, lo) };
+            Edge {
+                source: source.to_string(),
+                target: target.to_string(),
+                net_units: net.abs(),
+                gross_units: gross,
+                transfers,
+            }
+        })
+        .collect()
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/topology",
+    tag = "zones",
+    params(TopologyQuery),
+    responses(
+        (status = 200, description = "Zones and net flows between them", body = serde_json::Value),
+        (status = 400, description = "Invalid time range", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn get_topology(
+    State(st): State<AppState>,
+    Query(q): Query<TopologyQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let since = q.since.as_deref().map(|s| parse_rfc3339("since", s)).transpose()?;
+    let until = q.until.as_deref().map(|s| parse_rfc3339("until", s)).transpose()?;
+    let client = st.db_read.get().await?;
+
+    let rows = client
+        .query(
+            "SELECT z.id, z.status, COALESCE(SUM(b.balance_units), 0)::bigint AS total_balance_units \
+             FROM zones z LEFT JOIN accounts a ON a.zone_id=z.id LEFT JOIN balances b ON b.account_id=a.id \
+             GROUP BY z.id, z.status ORDER BY z.id",
+            &[],
+        )
+        .await?;
+    let nodes: Vec<Node> = rows
+        .iter()
+        .map(|r| Node {
+            id: r.get("id"),
+            status: r.get("status"),
+            total_balance_units: r.get("total_balance_units"),
+        })
+        .collect();
+
+    // from/to only name the first debit and credit of a split, but every leg of a
+    // split is in the transaction's zone (account_zone_mismatch), so splits never add an edge
+    let rows = client
+        .query(
+            "SELECT fa.zone_id AS from_zone, ta.zone_id AS to_zone, COUNT(*) AS transfers, SUM(t.amount_units)::bigint AS volume_units \
+             FROM transactions t JOIN accounts fa ON fa.id=t.from_account JOIN accounts ta ON ta.id=t.to_account \
+             WHERE fa.zone_id <> ta.zone_id \
+             AND ($1::timestamptz IS NULL OR t.created_at >= $1) \
+             AND ($2::timestamptz IS NULL OR t.created_at < $2) \
+             GROUP BY fa.zone_id, ta.zone_id",
+            &[&since, &until],
+        )
+        .await?;
+    let flows: Vec<Flow> = rows
+        .iter()
+        .map(|r| Flow {
+            from_zone: r.get("from_zone"),
+            to_zone: r.get("to_zone"),
+            transfers: r.get("transfers"),
+            volume_units: r.get("volume_units"),
+        })
+        .collect();
+
+    Ok(Json(json!({ "nodes": nodes, "edges": net_edges(&flows) })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flow(from: &str, to: &str, transfers: i64, volume_units: i64) -> Flow {
+        Flow { from_zone: from.into(), to_zone: to.into(), transfers, volume_units }
+    }
+
+    #[test]
+    fn opposite_flows_are_netted() {
+        let edges = net_edges(&[flow("zone-eu", "zone-na", 2, 300), flow("zone-na", "zone-eu", 1, 500)]);
+        assert_eq!(edges, vec![Edge {
+            source: "zone-na".into(),
+            target: "zone-eu".into(),
+            net_units: 200,
+            gross_units: 800,
+            transfers: 3,
+        }]);
+    }
+
+    #[test]
+    fn one_edge_per_pair() {
+        let edges = net_edges(&[
+            flow("zone-eu", "zone-uk", 1, 10),
+            flow("zone-af", "zone-me", 4, 40),
+            flow("zone-uk", "zone-eu", 1, 10),
+        ]);
+        assert_eq!(edges.len(), 2);
+        assert_eq!(edges[0].source, "zone-af");
+        let balanced = &edges[1];
+        assert_eq!((balanced.net_units, balanced.gross_units), (0, 20));
+    }
+}
@@ -1,9 +1,22 @@
-use axum::{extract::{Path, State}, http::StatusCode, Json};
-use serde::Serialize;
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
 use serde_json::json;
+use tokio_postgres::types::ToSql;
+use tracing::error;
+use utoipa::IntoParams;
 
+use crate::error::{AppError, ErrorBody};
+use crate::handlers::zones::require_zone;
 use crate::state::AppState;
-use crate::util::fmt_rfc3339;
+use crate::util::{csv_record, parse_rfc3339, to_rfc3339};
+use crate::Direction;
 
 #[derive(Serialize)]
 struct TxnRow {
@@ -13,59 +26,362 @@ struct TxnRow {
     to_account: String,
     amount_units: i64,
     zone_id: String,
+    currency: Option<String>,
+    /// Decimal places of `currency`: 1050 units at scale 2 is 10.50.
+    minor_unit_scale: Option<i32>,
     created_at: String,
+    /// Only with `include_hash=true`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    payload_hash: Option<String>,
 }
 
 #[derive(Serialize)]
 struct PostingRow {
     account_id: String,
-    direction: String,
+    direction: Direction,
     amount_units: i64,
 }
 
+/// `direction` of a postings row. The column is CHECK-constrained, so anything
+/// else means a corrupted ledger: logged and a 500, never guessed.
+pub(crate) fn posting_direction(r: &tokio_postgres::Row) -> Result<Direction, AppError> {
+    r.try_get("direction").map_err(|e| {
+        error!(error = %e, "posting with unknown direction");
+        AppError::Internal("posting has an unknown direction".into())
+    })
+}
+
+impl TxnRow {
+    fn from_row(r: &tokio_postgres::Row) -> Result<Self, AppError> {
+        let created_at: time::OffsetDateTime = r.get("created_at");
+        Ok(TxnRow {
+            id: r.get("id"),
+            request_id: r.get("request_id"),
+            from_account: r.get("from_account"),
+            to_account: r.get("to_account"),
+            amount_units: r.get("amount_units"),
+            zone_id: r.get("zone_id"),
+            currency: r.get("currency"),
+            minor_unit_scale: r.get("minor_unit_scale"),
+            created_at: to_rfc3339(created_at)?,
+            payload_hash: Some(r.get("payload_hash")),
+        })
+    }
+}
+
+const TXN_COLUMNS: &str = "t.id::text as id, t.request_id, t.from_account, t.to_account, t.amount_units, t.zone_id, t.currency, c.minor_unit_scale, t.created_at, t.payload_hash";
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TransactionQuery {
+    pub zone_id: Option<String>,
+    /// Transactions with this account on either side.
+    pub account: Option<String>,
+    /// Inclusive lower bound on `created_at`, RFC3339.
+    pub since: Option<String>,
+    /// Exclusive upper bound on `created_at`, RFC3339.
+    pub until: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TransactionPageQuery {
+    /// Page size, clamped to 1..=1000.
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+    #[serde(default)]
+    pub offset: i64,
+}
+
+fn default_limit() -> i64 { 100 }
+
+impl TransactionPageQuery {
+    /// Reject a negative offset and clamp `limit` to 1..=1000.
+    fn validated(mut self) -> Result<Self, AppError> {
+        if self.offset < 0 {
+            return Err(AppError::BadRequest("offset must be >= 0".into()));
+        }
+        self.limit = self.limit.clamp(1, 1000);
+        Ok(self)
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct IncludeHashQuery {
+    /// Add each transaction's idempotency `payload_hash`.
+    #[serde(default)]
+    pub include_hash: bool,
+}
+
+/// Parsed [`TransactionQuery`], shared by the JSON list and the CSV export.
+struct TransactionFilter {
+    zone_id: Option<String>,
+    account: Option<String>,
+    since: Option<time::OffsetDateTime>,
+    until: Option<time::OffsetDateTime>,
+}
+
+impl TransactionFilter {
+    fn from_query(q: TransactionQuery) -> Result<Self, AppError> {
+        Ok(Self {
+            zone_id: q.zone_id,
+            account: q.account,
+            since: q.since.as_deref().map(|s| parse_rfc3339("since", s)).transpose()?,
+            until: q.until.as_deref().map(|s| parse_rfc3339("until", s)).transpose()?,
+        })
+    }
+
+    /// ` WHERE ...` over `transactions t` for the given filters, or empty.
+    fn where_sql(&self) -> (String, Vec<&(dyn ToSql + Sync)>) {
+        let mut conds: Vec<String> = Vec::new();
+        let mut params: Vec<&(dyn ToSql + Sync)> = Vec::new();
+        if let Some(zone_id) = &self.zone_id {
+            params.push(zone_id);
+            conds.push(format!("t.zone_id=${}", params.len()));
+        }
+        if let Some(account) = &self.account {
+            params.push(account);
+            conds.push(format!("(t.from_account=${0} OR t.to_account=${0})", params.len()));
+        }
+        if let Some(since) = &self.since {
+            params.push(since);
+            conds.push(format!("t.created_at >= ${}", params.len()));
+        }
+        if let Some(until) = &self.until {
+            params.push(until);
+            conds.push(format!("t.created_at < ${}", params.len()));
+        }
+        let sql = if conds.is_empty() { String::new() } else { format!(" WHERE {}", conds.join(" AND ")) };
+        (sql, params)
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/transactions",
+    tag = "transactions",
+    params(TransactionQuery, TransactionPageQuery, IncludeHashQuery),
+    responses(
+        (status = 200, description = "A page of matching transactions, newest first", body = serde_json::Value),
+        (status = 400, description = "Invalid since/until or offset", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
 pub async fn list_transactions(
     State(st): State<AppState>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let client = st.db.get().await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Query(q): Query<TransactionQuery>,
+    Query(page): Query<TransactionPageQuery>,
+    Query(hash): Query<IncludeHashQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let filter = TransactionFilter::from_query(q)?;
+    let page = page.validated()?;
+    let client = st.db_read.get().await?;
+    let txns = transactions_page(&client, &filter, &page, hash.include_hash).await?;
+    Ok(Json(json!({ "transactions": txns })))
+}
+
+/// The page query for `filter`, newest first. `id` breaks ties between rows
+/// created in the same microsecond, so pages neither overlap nor skip rows, and
+/// the order matches `idx_transactions_created_at_id`.
+fn page_sql<'a>(filter: &'a TransactionFilter, page: &'a TransactionPageQuery) -> (String, Vec<&'a (dyn ToSql + Sync)>) {
+    let (cond, mut params) = filter.where_sql();
+    params.push(&page.limit);
+    params.push(&page.offset);
+    let sql = format!(
+        "SELECT {TXN_COLUMNS} FROM transactions t LEFT JOIN currencies c ON c.code=t.currency{cond} \
+         ORDER BY t.created_at DESC, t.id DESC LIMIT ${} OFFSET ${}",
+        params.len() - 1,
+        params.len()
+    );
+    (sql, params)
+}
+
+/// One page of the transactions matching `filter`, newest first.
+async fn transactions_page(
+    client: &tokio_postgres::Client,
+    filter: &TransactionFilter,
+    page: &TransactionPageQuery,
+    include_hash: bool,
+) -> Result<Vec<TxnRow>, AppError> {
+    let (sql, params) = page_sql(filter, page);
+    let rows = client.query(&sql, &params).await?;
+
+    let mut txns = rows.iter().map(TxnRow::from_row).collect::<Result<Vec<_>, _>>()?;
+    if !include_hash {
+        txns.iter_mut().for_each(|t| t.payload_hash = None);
+    }
+    Ok(txns)
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ZoneTransactionQuery {
+    /// Transactions with this account on either side.
+    pub account: Option<String>,
+    /// Inclusive lower bound on `created_at`, RFC3339.
+    pub since: Option<String>,
+    /// Exclusive upper bound on `created_at`, RFC3339.
+    pub until: Option<String>,
+}
+
+/// `list_transactions` narrowed to one zone, so an operator looking at a zone
+/// does not page through everyone else's traffic.
+#[utoipa::path(
+    get,
+    path = "/v1/zones/{zone_id}/transactions",
+    tag = "transactions",
+    params(("zone_id" = String, Path, description = "Zone id"), ZoneTransactionQuery, TransactionPageQuery, IncludeHashQuery),
+    responses(
+        (status = 200, description = "A page of the zone's matching transactions, newest first", body = serde_json::Value),
+        (status = 400, description = "Invalid since/until or offset", body = ErrorBody),
+        (status = 404, description = "unknown_zone", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn list_zone_transactions(
+    State(st): State<AppState>,
+    Path(zone_id): Path<String>,
+    Query(q): Query<ZoneTransactionQuery>,
+    Query(page): Query<TransactionPageQuery>,
+    Query(hash): Query<IncludeHashQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let q = TransactionQuery { zone_id: Some(zone_id.clone()), account: q.account, since: q.since, until: q.until };
+    let filter = TransactionFilter::from_query(q)?;
+    let page = page.validated()?;
+    let client = st.db_read.get().await?;
+    let zone = client.query_opt("SELECT 1 FROM zones WHERE id=$1", &[&zone_id]).await?;
+    require_zone(zone, &zone_id)?;
+    let txns = transactions_page(&client, &filter, &page, hash.include_hash).await?;
+    Ok(Json(json!({ "zone_id": zone_id, "transactions": txns })))
+}
+
+const CSV_COLUMNS: [&str; 10] = [
+    "id", "request_id", "from_account", "to_account", "amount_units", "zone_id", "currency", "minor_unit_scale",
+    "created_at", "metadata",
+];
+
+/// One CSV line in [`CSV_COLUMNS`] order; metadata is written as compact JSON.
+fn csv_line(t: &TxnRow, metadata: &serde_json::Value) -> String {
+    let amount_units = t.amount_units.to_string();
+    let scale = t.minor_unit_scale.map(|s| s.to_string()).unwrap_or_default();
+    let metadata = metadata.to_string();
+    csv_record(&[
+        &t.id,
+        &t.request_id,
+        &t.from_account,
+        &t.to_account,
+        &amount_units,
+        &t.zone_id,
+        t.currency.as_deref().unwrap_or(""),
+        &scale,
+        &t.created_at,
+        &metadata,
+    ])
+}
+
+/// Every matching transaction as CSV, oldest first. Rows are streamed from
+/// the database as they arrive rather than collected first.
+#[utoipa::path(
+    get,
+    path = "/v1/transactions.csv",
+    tag = "transactions",
+    params(TransactionQuery),
+    responses(
+        (status = 200, description = "Matching transactions with a header row", body = String, content_type = "text/csv"),
+        (status = 400, description = "Invalid since/until", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn export_transactions_csv(
+    State(st): State<AppState>,
+    Query(q): Query<TransactionQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let filter = TransactionFilter::from_query(q)?;
+    let (cond, params) = filter.where_sql();
+    let client = st.db_read.get().await?;
     let rows = client
-        .query(
-            "SELECT id::text as id, request_id, from_account, to_account, amount_units, zone_id, created_at FROM transactions ORDER BY created_at DESC LIMIT 100",
-            &[],
+        .query_raw(
+            &format!(
+                "SELECT {TXN_COLUMNS}, t.metadata FROM transactions t LEFT JOIN currencies c ON c.code=t.currency{cond} ORDER BY t.created_at, t.id"
+            ),
+            params.iter().map(|p| *p as &dyn ToSql),
         )
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-    let txns: Vec<TxnRow> = rows
-        .into_iter()
-        .map(|r| {
-            let created_at: time::OffsetDateTime = r.get("created_at");
-            TxnRow {
-                id: r.get("id"),
-                request_id: r.get("request_id"),
-                from_account: r.get("from_account"),
-                to_account: r.get("to_account"),
-                amount_units: r.get("amount_units"),
-                zone_id: r.get("zone_id"),
-                created_at: fmt_rfc3339(created_at),
-            }
-        })
-        .collect();
+        .await?;
 
-    Ok(Json(json!({ "transactions": txns })))
+    let header = stream::once(async { Ok::<_, AppError>(csv_record(&CSV_COLUMNS)) });
+    let lines = rows.map(move |r| -> Result<String, AppError> {
+        // the pooled connection stays checked out until the last row is sent
+        let _client = &client;
+        let row = r?;
+        Ok(csv_line(&TxnRow::from_row(&row)?, &row.get("metadata")))
+    });
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (header::CONTENT_DISPOSITION, "attachment; filename=\"transactions.csv\""),
+        ],
+        Body::from_stream(header.chain(lines)),
+    ))
+}
+
+/// Strong validator for a transaction. Transactions never change after they are
+/// written, and the payload hash pins what the request said.
+fn transaction_etag(id: &str, payload_hash: &str) -> String {
+    format!("\"{id}-{payload_hash}\"")
+}
+
+/// Whether an `If-None-Match` value matches `etag`: `*`, or any listed tag.
+/// The comparison is weak (RFC 9110 13.1.2), so a `W/` prefix is ignored.
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let value = if_none_match.trim();
+    value == "*" || value.split(',').map(str::trim).any(|t| t.strip_prefix("W/").unwrap_or(t) == etag)
 }
 
+#[utoipa::path(
+    get,
+    path = "/v1/transactions/{transaction_id}",
+    tag = "transactions",
+    params(
+        ("transaction_id" = String, Path, description = "Transaction id"),
+        ("if-none-match" = Option<String>, Header, description = "ETag of a copy the client already has"),
+    ),
+    responses(
+        (status = 200, description = "Transaction with its postings; carries an ETag and Cache-Control: immutable", body = serde_json::Value),
+        (status = 304, description = "If-None-Match matches the transaction's ETag; no body"),
+        (status = 404, description = "Transaction not found", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
 pub async fn get_transaction(
     Path(transaction_id): Path<String>,
     State(st): State<AppState>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let client = st.db.get().await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let txn = fetch_transaction(&st, &transaction_id).await?;
+    let etag = transaction_etag(txn["id"].as_str().unwrap_or_default(), txn["payload_hash"].as_str().unwrap_or_default());
+    let cache = [(header::ETAG, etag.clone()), (header::CACHE_CONTROL, "max-age=31536000, immutable".to_string())];
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| etag_matches(v, &etag));
+    if not_modified {
+        return Ok((StatusCode::NOT_MODIFIED, cache).into_response());
+    }
+    Ok((cache, Json(txn)).into_response())
+}
+
+/// A transaction with its postings, as `get_transaction` returns it.
+pub(crate) async fn fetch_transaction(st: &AppState, transaction_id: &str) -> Result<serde_json::Value, AppError> {
+    let client = st.db_read.get().await?;
     let row = client
-        .query_one(
-            "SELECT id::text as id, request_id, from_account, to_account, amount_units, zone_id, created_at, metadata FROM transactions WHERE id::text=$1",
+        .query_opt(
+            "SELECT t.id::text as id, t.request_id, t.from_account, t.to_account, t.amount_units, t.zone_id, t.currency, c.minor_unit_scale, t.created_at, t.metadata, t.payload_hash, t.created_by \
+             FROM transactions t LEFT JOIN currencies c ON c.code=t.currency WHERE t.id::text=$1",
             &[&transaction_id],
         )
-        .await
-        .map_err(|e| (StatusCode::NOT_FOUND, e.to_string()))?;
+        .await?
+        .ok_or_else(|| AppError::NotFound("transaction not found".into()))?;
 
     let id: String = row.get("id");
     let request_id: String = row.get("request_id");
@@ -73,31 +389,341 @@ pub async fn get_transaction(
     let to_account: String = row.get("to_account");
     let amount_units: i64 = row.get("amount_units");
     let zone_id: String = row.get("zone_id");
+    let currency: Option<String> = row.get("currency");
+    let minor_unit_scale: Option<i32> = row.get("minor_unit_scale");
     let created_at: time::OffsetDateTime = row.get("created_at");
     let metadata: serde_json::Value = row.get("metadata");
+    let payload_hash: String = row.get("payload_hash");
+    let created_by: String = row.get("created_by");
 
     let post_rows = client
         .query(
             "SELECT account_id, direction, amount_units FROM postings WHERE txn_id::text=$1 ORDER BY direction ASC",
             &[&transaction_id],
         )
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .await?;
 
     let postings: Vec<PostingRow> = post_rows
-        .into_iter()
-        .map(|r| PostingRow {
-            account_id: r.get("account_id"),
-            direction: r.get("direction"),
-            amount_units: r.get("amount_units"),
+        .iter()
+        .map(|r| -> Result<_, AppError> {
+            Ok(PostingRow {
+                account_id: r.get("account_id"),
+                direction: posting_direction(r)?,
+                amount_units: r.get("amount_units"),
+            })
         })
-        .collect();
+        .collect::<Result<_, _>>()?;
 
-    Ok(Json(json!({
+    Ok(json!({
         "id": id, "request_id": request_id,
         "from_account": from_account, "to_account": to_account,
         "amount_units": amount_units, "zone_id": zone_id,
-        "created_at": fmt_rfc3339(created_at),
-        "metadata": metadata, "postings": postings
-    })))
+        "currency": currency, "minor_unit_scale": minor_unit_scale,
+        "created_at": to_rfc3339(created_at)?, "created_by": created_by,
+        "metadata": metadata, "payload_hash": payload_hash, "postings": postings
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minimal RFC 4180 reader for checking the export.
+    fn parse_csv(input: &str) -> Vec<Vec<String>> {
+        let (mut records, mut record, mut field) = (Vec::new(), Vec::new(), String::new());
+        let mut chars = input.chars().peekable();
+        let mut quoted = false;
+        while let Some(c) = chars.next() {
+            match (quoted, c) {
+                (true, '"') if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                (true, '"') => quoted = false,
+                (true, c) => field.push(c),
+                (false, '"') => quoted = true,
+                (false, ',') => record.push(std::mem::take(&mut field)),
+                (false, '\r') => {}
+                (false, '\n') => {
+                    record.push(std::mem::take(&mut field));
+                    records.push(std::mem::take(&mut record));
+                }
+                (false, c) => field.push(c),
+            }
+        }
+        records
+    }
+
+    #[test]
+    fn if_none_match_lists_and_wildcards() {
+        let etag = transaction_etag("txn-1", "abc");
+        assert_eq!(etag, "\"txn-1-abc\"");
+        assert!(etag_matches("\"txn-1-abc\"", &etag));
+        assert!(etag_matches("\"other\", W/\"txn-1-abc\"", &etag));
+        assert!(etag_matches(" * ", &etag));
+        assert!(!etag_matches("\"txn-1-abd\"", &etag));
+        assert!(!etag_matches("txn-1-abc", &etag), "unquoted is not the same tag");
+    }
+
+    fn txn(id: &str, request_id: &str, currency: Option<&str>) -> TxnRow {
+        TxnRow {
+            id: id.into(),
+            request_id: request_id.into(),
+            from_account: "acct-a".into(),
+            to_account: "acct-b".into(),
+            amount_units: 1050,
+            zone_id: "zone-eu".into(),
+            currency: currency.map(String::from),
+            minor_unit_scale: currency.map(|_| 2),
+            created_at: "2026-05-01T10:00:00Z".into(),
+            payload_hash: Some("ab12".into()),
+        }
+    }
+
+    #[test]
+    fn csv_export_round_trips() {
+        let metadata = json!({ "note": "rent, \"May\"\nsecond line" });
+        let mut body = csv_record(&CSV_COLUMNS);
+        body.push_str(&csv_line(&txn("t-1", "req,1", Some("EUR")), &metadata));
+        body.push_str(&csv_line(&txn("t-2", "req-2", None), &json!({})));
+
+        let records = parse_csv(&body);
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0], CSV_COLUMNS);
+        assert_eq!(
+            records[1],
+            ["t-1", "req,1", "acct-a", "acct-b", "1050", "zone-eu", "EUR", "2", "2026-05-01T10:00:00Z", metadata.to_string().as_str()]
+        );
+        let parsed: serde_json::Value = serde_json::from_str(&records[1][9]).unwrap();
+        assert_eq!(parsed, metadata);
+        assert_eq!(records[2][1], "req-2");
+        assert_eq!((records[2][6].as_str(), records[2][7].as_str(), records[2][9].as_str()), ("", "", "{}"));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn postings_round_trip_with_typed_directions() {
+        use crate::handlers::transfers::{create_transfer, CreateTransferQuery, CreateTransferRequest};
+        use http_body_util::BodyExt;
+        let st = crate::testing::test_db().await;
+        let run = uuid::Uuid::new_v4();
+        let req = CreateTransferRequest {
+            request_id: format!("req-{run}"),
+            from_account: format!("acct-a-{run}"),
+            to_account: format!("acct-b-{run}"),
+            amount_units: 250,
+            zone_id: "zone-eu".into(),
+            metadata: json!({}),
+            currency: None,
+        };
+        let q = Query(CreateTransferQuery { include_balances: false, dry_run: false });
+        let res = create_transfer(State(st.clone()), q, Default::default(), None, Ok(Json(req))).await.unwrap();
+        let created: serde_json::Value = serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+
+        let txn_id = created["transaction_id"].as_str().unwrap().to_string();
+        let txn = fetch_transaction(&st, &txn_id).await.unwrap();
+        let postings: Vec<(String, Direction, i64)> = txn["postings"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| {
+                let direction = serde_json::from_value(p["direction"].clone()).unwrap();
+                (p["account_id"].as_str().unwrap().to_string(), direction, p["amount_units"].as_i64().unwrap())
+            })
+            .collect();
+        assert_eq!(
+            postings,
+            vec![(format!("acct-b-{run}"), Direction::Credit, 250), (format!("acct-a-{run}"), Direction::Debit, 250)]
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn created_by_records_the_bearer_subject_or_anonymous() {
+        use crate::handlers::transfers::{create_transfer, CreateTransferQuery, CreateTransferRequest};
+        use http_body_util::BodyExt;
+        let mut config = crate::testing::test_db_config();
+        config.api_tokens = crate::auth::parse_api_tokens(Some("payments-svc:tok-1")).unwrap();
+        let st = crate::app::build_state(config).await.unwrap();
+        let run = uuid::Uuid::new_v4();
+        let transfer = |name: &str, authorization: Option<&str>| {
+            let st = st.clone();
+            let req = CreateTransferRequest {
+                request_id: format!("req-{name}-{run}"),
+                from_account: format!("acct-a-{run}"),
+                to_account: format!("acct-b-{run}"),
+                amount_units: 10,
+                zone_id: "zone-eu".into(),
+                metadata: json!({}),
+                currency: None,
+            };
+            let mut headers = axum::http::HeaderMap::new();
+            if let Some(value) = authorization {
+                headers.insert(header::AUTHORIZATION, value.parse().unwrap());
+            }
+            async move {
+                let q = Query(CreateTransferQuery { include_balances: false, dry_run: false });
+                let res = create_transfer(State(st.clone()), q, headers, None, Ok(Json(req))).await?;
+                let created: serde_json::Value =
+                    serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+                let txn_id = created["transaction_id"].as_str().unwrap().to_string();
+                let txn = fetch_transaction(&st, &txn_id).await?;
+                Ok::<_, AppError>(txn["created_by"].clone())
+            }
+        };
+
+        assert_eq!(transfer("authenticated", Some("Bearer tok-1")).await.unwrap(), "payments-svc");
+        assert_eq!(transfer("anonymous", None).await.unwrap(), "anonymous");
+        let err = transfer("forged", Some("Bearer tok-2")).await.unwrap_err();
+        assert_eq!(err.status_and_code().1, "invalid_token");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn zone_transactions_only_lists_that_zone() {
+        use crate::handlers::transfers::{create_transfer, CreateTransferQuery, CreateTransferRequest};
+        let st = crate::testing::test_db().await;
+        let run = uuid::Uuid::new_v4();
+        for zone in ["zone-eu", "zone-uk"] {
+            let req = CreateTransferRequest {
+                request_id: format!("req-{zone}-{run}"),
+                from_account: format!("acct-a-{zone}-{run}"),
+                to_account: format!("acct-b-{zone}-{run}"),
+                amount_units: 30,
+                zone_id: zone.into(),
+                metadata: json!({}),
+                currency: None,
+            };
+            let q = Query(CreateTransferQuery { include_balances: false, dry_run: false });
+            create_transfer(State(st.clone()), q, Default::default(), None, Ok(Json(req))).await.unwrap();
+        }
+
+        let q = ZoneTransactionQuery { account: None, since: None, until: None };
+        let hash = IncludeHashQuery { include_hash: false };
+        let page = TransactionPageQuery { limit: 100, offset: 0 };
+        let Json(body) = list_zone_transactions(State(st.clone()), Path("zone-uk".into()), Query(q), Query(page), Query(hash)).await.unwrap();
+        assert_eq!(body["zone_id"], "zone-uk");
+        let txns = body["transactions"].as_array().unwrap();
+        assert!(txns.iter().all(|t| t["zone_id"] == "zone-uk"));
+        assert!(txns.iter().any(|t| t["request_id"] == format!("req-zone-uk-{run}")));
+        assert!(!txns.iter().any(|t| t["request_id"] == format!("req-zone-eu-{run}")));
+
+        let q = ZoneTransactionQuery { account: None, since: None, until: None };
+        let hash = IncludeHashQuery { include_hash: false };
+        let page = TransactionPageQuery { limit: 100, offset: 0 };
+        let err = list_zone_transactions(State(st), Path(format!("zone-missing-{run}")), Query(q), Query(page), Query(hash))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_and_code(), (StatusCode::NOT_FOUND, "unknown_zone"));
+    }
+
+    #[test]
+    fn filters_build_where_clause() {
+        let none = TransactionFilter::from_query(TransactionQuery { zone_id: None, account: None, since: None, until: None }).unwrap();
+        assert_eq!(none.where_sql().0, "");
+
+        let q = TransactionQuery {
+            zone_id: Some("zone-eu".into()),
+            account: Some("acct-a".into()),
+            since: Some("2026-05-01T00:00:00Z".into()),
+            until: None,
+        };
+        let filter = TransactionFilter::from_query(q).unwrap();
+        let (sql, params) = filter.where_sql();
+        assert_eq!(sql, " WHERE t.zone_id=$1 AND (t.from_account=$2 OR t.to_account=$2) AND t.created_at >= $3");
+        assert_eq!(params.len(), 3);
+
+        let bad = TransactionQuery { zone_id: None, account: None, since: None, until: Some("soon".into()) };
+        assert!(TransactionFilter::from_query(bad).is_err());
+    }
+
+    #[test]
+    fn page_breaks_created_at_ties_by_id() {
+        let filter = TransactionFilter::from_query(TransactionQuery { zone_id: Some("zone-eu".into()), account: None, since: None, until: None }).unwrap();
+        let page = TransactionPageQuery { limit: 5000, offset: 20 }.validated().unwrap();
+        assert_eq!(page.limit, 1000);
+        let (sql, params) = page_sql(&filter, &page);
+        assert!(sql.ends_with(" WHERE t.zone_id=$1 ORDER BY t.created_at DESC, t.id DESC LIMIT $2 OFFSET $3"), "{sql}");
+        assert_eq!(params.len(), 3);
+        assert!(TransactionPageQuery { limit: 10, offset: -1 }.validated().is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn same_timestamp_rows_page_completely_and_stably() {
+        let st = crate::testing::test_db().await;
+        let run = uuid::Uuid::new_v4();
+        let account = format!("acct-tie-{run}");
+        let client = st.db.get().await.unwrap();
+        let mut inserted: Vec<String> = Vec::new();
+        for i in 0..7 {
+            let row = client
+                .query_one(
+                    "INSERT INTO transactions(request_id,payload_hash,from_account,to_account,amount_units,zone_id,created_at) \
+                     VALUES($1,'h',$2,'acct-tie-other',1,'zone-eu','2001-02-03T04:05:06Z') RETURNING id::text",
+                    &[&format!("req-tie-{i}-{run}"), &account],
+                )
+                .await
+                .unwrap();
+            inserted.push(row.get(0));
+        }
+        inserted.sort_unstable_by(|a, b| b.cmp(a));
+
+        let page_of = |offset: i64| {
+            let q = TransactionQuery { zone_id: None, account: Some(account.clone()), since: None, until: None };
+            let page = TransactionPageQuery { limit: 3, offset };
+            list_transactions(State(st.clone()), Query(q), Query(page), Query(IncludeHashQuery { include_hash: false }))
+        };
+        let mut seen: Vec<String> = Vec::new();
+        for offset in [0, 3, 6] {
+            let Json(body) = page_of(offset).await.unwrap();
+            seen.extend(body["transactions"].as_array().unwrap().iter().map(|t| t["id"].as_str().unwrap().to_string()));
+        }
+        assert_eq!(seen, inserted, "every row exactly once, ordered by id within the shared timestamp");
+
+        let Json(again) = page_of(3).await.unwrap();
+        let again: Vec<&str> = again["transactions"].as_array().unwrap().iter().map(|t| t["id"].as_str().unwrap()).collect();
+        assert_eq!(again, seen[3..6].iter().map(String::as_str).collect::<Vec<_>>(), "the same page twice is the same rows");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn conditional_get_returns_304_for_a_matching_etag() {
+        use crate::handlers::transfers::{create_transfer, CreateTransferQuery, CreateTransferRequest};
+        use http_body_util::BodyExt;
+        let st = crate::testing::test_db().await;
+        let run = uuid::Uuid::new_v4();
+        let req = CreateTransferRequest {
+            request_id: format!("req-etag-{run}"),
+            from_account: format!("acct-a-{run}"),
+            to_account: format!("acct-b-{run}"),
+            amount_units: 42,
+            zone_id: "zone-eu".into(),
+            metadata: json!({}),
+            currency: None,
+        };
+        let q = Query(CreateTransferQuery { include_balances: false, dry_run: false });
+        let res = create_transfer(State(st.clone()), q, Default::default(), None, Ok(Json(req))).await.unwrap();
+        let created: serde_json::Value = serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        let txn_id = created["transaction_id"].as_str().unwrap().to_string();
+
+        let res = get_transaction(Path(txn_id.clone()), State(st.clone()), HeaderMap::new()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[header::CACHE_CONTROL], "max-age=31536000, immutable");
+        let etag = res.headers()[header::ETAG].clone();
+        let body: serde_json::Value = serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        assert_eq!(etag.to_str().unwrap(), transaction_etag(&txn_id, body["payload_hash"].as_str().unwrap()));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, etag.clone());
+        let res = get_transaction(Path(txn_id.clone()), State(st.clone()), headers).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(res.headers()[header::ETAG], etag);
+        assert!(res.into_body().collect().await.unwrap().to_bytes().is_empty());
+
+        let mut stale = HeaderMap::new();
+        stale.insert(header::IF_NONE_MATCH, "\"something-else\"".parse().unwrap());
+        let res = get_transaction(Path(txn_id), State(st), stale).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
 }
@@ -1,13 +1,39 @@
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum::{
+    extract::{rejection::JsonRejection, Path, Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::IntoResponse,
+    Extension, Json,
+};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+use tokio_postgres::error::SqlState;
+use tracing::error;
+use utoipa::{IntoParams, ToSchema};
 
-use crate::error::AppError;
+use crate::auth::{resolve_principal, Principal};
+use crate::error::{AppError, ErrorBody};
+use crate::handlers::adjustments::CLEARING_ACCOUNT;
+use crate::handlers::audit::publish_audit;
+use crate::handlers::events::{insert_outbox_event, publish_event, LedgerEvent};
+use crate::handlers::success_rate::record_attempt;
+use crate::handlers::whitelists::check_whitelists;
+use crate::handlers::zones::require_zone;
+use crate::jwt::Claims;
+use crate::microbatch::submit;
+use crate::projection::{apply_balance_delta, apply_pending_delta, BalanceProjection};
+use crate::ratelimit::{quota, try_acquire, try_acquire_n};
 use crate::state::AppState;
-use crate::util::{fmt_rfc3339, hash_percent, payload_hash};
+use crate::util::{hash_percent, payload_hash, remove_path, to_rfc3339};
+use crate::{postings_balanced, Direction};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
 pub struct CreateTransferRequest {
+    /// Idempotency key. May be omitted when sent as the `Idempotency-Key` header instead.
+    #[serde(default)]
     pub request_id: String,
     pub from_account: String,
     pub to_account: String,
@@ -15,40 +41,648 @@ pub struct CreateTransferRequest {
     pub zone_id: String,
     #[serde(default)]
     pub metadata: serde_json::Value,
+    /// ISO 4217 code the amount is denominated in; defaults to the zone's currency.
+    /// Omitted from the payload hash when absent, so older clients hash as before.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct TransferResponse {
     pub status: String,
     pub transaction_id: String,
     pub request_id: String,
     pub created_at: String,
+    /// Available balance of `from_account` after the transfer; only with
+    /// `include_balances=true`, and not for replays or asynchronous projection.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_balance: Option<i64>,
+    /// Available balance of `to_account` after the transfer; a credit held for
+    /// settlement is not in it yet.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_balance: Option<i64>,
+    /// Set on `?dry_run=true` responses: nothing was persisted.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+    /// Hash the server computed over the request for idempotency checks; a
+    /// retry with the same `request_id` must hash the same.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload_hash: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CreateTransferQuery {
+    /// Report `from_balance` and `to_balance` in the response.
+    #[serde(default)]
+    pub include_balances: bool,
+    /// Run every check and report the would-be outcome and balances, then roll back.
+    #[serde(default)]
+    pub dry_run: bool,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct SpooledResponse {
     pub status: String,
     pub spool_id: String,
     pub request_id: String,
+    /// Set on `?dry_run=true` responses: the transfer would be spooled, but was not.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+}
+
+/// Longest accepted `request_id`, account or zone id, in bytes.
+pub const MAX_ID_LEN: usize = 128;
+
+/// Configurable bounds on a single transfer; ids are checked against
+/// [`MAX_ID_LEN`] and [`valid_id_char`] regardless.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransferLimits {
+    pub max_amount_units: i64,
+    /// Size of `metadata` serialized as compact JSON.
+    pub max_metadata_bytes: usize,
+}
+
+impl Default for TransferLimits {
+    fn default() -> Self {
+        Self { max_amount_units: i64::MAX, max_metadata_bytes: 16 * 1024 }
+    }
+}
+
+/// Ids are plain ASCII tokens: letters, digits and `-_.:`.
+pub fn valid_id_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':')
+}
+
+pub(crate) fn invalid_transfer(field: &str, rule: &str, message: String, limit: serde_json::Value) -> AppError {
+    AppError::Detailed {
+        status: StatusCode::BAD_REQUEST,
+        code: "invalid_transfer",
+        message,
+        details: json!({ "field": field, "rule": rule, "limit": limit }),
+    }
+}
+
+/// 400 `invalid_transfer` naming the field and the violated rule
+/// (`required`, `max_length`, `charset`, `positive`, `max_amount` or `max_bytes`).
+pub(crate) fn validate_transfer(req: &CreateTransferRequest, limits: &TransferLimits) -> Result<(), AppError> {
+    for (field, id) in [
+        ("request_id", &req.request_id),
+        ("from_account", &req.from_account),
+        ("to_account", &req.to_account),
+        ("zone_id", &req.zone_id),
+    ] {
+        check_id(field, id)?;
+    }
+    check_amount("amount_units", req.amount_units, limits)?;
+    check_metadata_size(&req.metadata, limits)
+}
+
+/// `invalid_transfer` unless `id` is a non-empty token of at most [`MAX_ID_LEN`] valid characters.
+pub(crate) fn check_id(field: &str, id: &str) -> Result<(), AppError> {
+    if id.is_empty() {
+        return Err(invalid_transfer(field, "required", format!("{field} is required"), json!(null)));
+    }
+    if id.len() > MAX_ID_LEN {
+        return Err(invalid_transfer(
+            field,
+            "max_length",
+            format!("{field} is {} bytes, longer than {MAX_ID_LEN}", id.len()),
+            json!(MAX_ID_LEN),
+        ));
+    }
+    if let Some(c) = id.chars().find(|&c| !valid_id_char(c)) {
+        return Err(invalid_transfer(
+            field,
+            "charset",
+            format!("{field} contains {c:?}; only letters, digits and -_.: are allowed"),
+            json!("[A-Za-z0-9-_.:]"),
+        ));
+    }
+    Ok(())
+}
+
+/// `invalid_transfer` unless `amount` is positive and within the configured maximum.
+pub(crate) fn check_amount(field: &str, amount: i64, limits: &TransferLimits) -> Result<(), AppError> {
+    if amount <= 0 {
+        return Err(invalid_transfer(field, "positive", format!("{field} must be positive"), json!(1)));
+    }
+    if amount > limits.max_amount_units {
+        return Err(invalid_transfer(
+            field,
+            "max_amount",
+            format!("{field} {amount} exceeds the limit of {}", limits.max_amount_units),
+            json!(limits.max_amount_units),
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) fn check_metadata_size(metadata: &serde_json::Value, limits: &TransferLimits) -> Result<(), AppError> {
+    let metadata_bytes = serde_json::to_vec(metadata).map_or(usize::MAX, |b| b.len());
+    if metadata_bytes > limits.max_metadata_bytes {
+        return Err(invalid_transfer(
+            "metadata",
+            "max_bytes",
+            format!("metadata is {metadata_bytes} bytes, more than {}", limits.max_metadata_bytes),
+            json!(limits.max_metadata_bytes),
+        ));
+    }
+    Ok(())
+}
+
+/// What a reused `request_id` already belongs to.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Existing<'a> {
+    Transaction(&'a str),
+    Spooled(&'a str),
+}
+
+/// 409 for a `request_id` reused with a different payload. The body names the
+/// earlier transaction (or spooled transfer) and both hashes, so a client can
+/// tell a real conflict from a retry whose payload drifted.
+pub(crate) fn idempotency_conflict(request_id: &str, existing: Existing<'_>, stored_hash: &str, request_hash: &str) -> AppError {
+    let mut details = json!({
+        "request_id": request_id,
+        "reason": "payload_hash_mismatch",
+        "stored_payload_hash": stored_hash,
+        "request_payload_hash": request_hash,
+    });
+    match existing {
+        Existing::Transaction(id) => details["existing_transaction_id"] = json!(id),
+        Existing::Spooled(id) => details["existing_spool_id"] = json!(id),
+    }
+    AppError::Detailed {
+        status: StatusCode::CONFLICT,
+        code: "conflict",
+        message: "idempotency conflict: request_id was already used with a different payload (payload hash mismatch)".into(),
+        details,
+    }
+}
+
+/// Why the zone gate refuses `request_id`, as `(code, reason)`: the zone is DOWN,
+/// writes are blocked, or the request falls outside the cross-zone throttle.
+pub(crate) fn zone_gate(status: &str, writes_blocked: bool, throttle: i32, request_id: &str) -> Option<(&'static str, &'static str)> {
+    if status == "DOWN" {
+        Some(("zone_down", "zone down"))
+    } else if writes_blocked {
+        Some(("writes_blocked", "writes blocked"))
+    } else if throttle < 100 && (throttle <= 0 || hash_percent(request_id) >= throttle as u32) {
+        Some(("throttled", "throttled"))
+    } else {
+        None
+    }
+}
+
+/// 503 for a transfer the zone gate refused; `code` names the gate
+/// (`zone_down`, `writes_blocked` or `throttled`).
+pub(crate) fn zone_blocked(zone_id: &str, code: &'static str, reason: &str) -> AppError {
+    let message = if code == "zone_down" { reason.to_string() } else { format!("zone blocked: {reason}") };
+    AppError::Detailed {
+        status: StatusCode::SERVICE_UNAVAILABLE,
+        code,
+        message,
+        details: json!({ "zone_id": zone_id }),
+    }
+}
+
+/// 409 when an account already exists in a different zone than the transfer's.
+/// `accounts` holds `(account_id, zone_id)` for the transfer's accounts.
+pub(crate) fn check_account_zones(zone_id: &str, accounts: &[(String, String)]) -> Result<(), AppError> {
+    match accounts.iter().find(|(_, account_zone)| account_zone != zone_id) {
+        Some((account_id, account_zone)) => Err(AppError::Detailed {
+            status: StatusCode::CONFLICT,
+            code: "account_zone_mismatch",
+            message: format!("account {account_id} belongs to zone {account_zone}, not {zone_id}"),
+            details: json!({ "account_id": account_id, "account_zone": account_zone, "zone_id": zone_id }),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Reject a `metadata.currency` hint that disagrees with the zone's currency.
+/// Either side missing means there is nothing to compare.
+pub(crate) fn check_currency(zone_currency: Option<&str>, metadata: &serde_json::Value) -> Result<(), AppError> {
+    let hint = metadata.get("currency").and_then(|v| v.as_str());
+    match (zone_currency, hint) {
+        (Some(zone), Some(hint)) if !zone.eq_ignore_ascii_case(hint) => Err(AppError::Detailed {
+            status: StatusCode::UNPROCESSABLE_ENTITY,
+            code: "currency_mismatch",
+            message: format!("metadata currency {hint} does not match zone currency {zone}"),
+            details: json!({ "zone_currency": zone, "metadata_currency": hint }),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// `zones.degraded_policy`: how a DEGRADED zone sheds load. Without a policy,
+/// or with an empty one, DEGRADED zones accept transfers like OK ones.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct DegradedPolicy {
+    /// Share of transfers (0-100) to reject, picked deterministically by request id.
+    #[serde(default)]
+    shed_percent: u32,
+    /// Mixed into the shedding hash; changing it sheds a different set of request ids.
+    #[serde(default)]
+    seed: String,
+    /// Reject transfers with `amount_units` above this.
+    #[serde(default)]
+    max_amount_units: Option<i64>,
+}
+
+impl DegradedPolicy {
+    /// Why the policy rejects the transfer, if it does: `load_shed` or `amount_over_limit`.
+    fn rejects(&self, request_id: &str, amount_units: i64) -> Option<&'static str> {
+        if self.max_amount_units.is_some_and(|max| amount_units > max) {
+            Some("amount_over_limit")
+        } else if hash_percent(&format!("{}{request_id}", self.seed)) < self.shed_percent.min(100) {
+            Some("load_shed")
+        } else {
+            None
+        }
+    }
+}
+
+/// Apply the zone's `degraded_policy` when it is DEGRADED; a rejection is 503
+/// `zone_degraded` with the reason. A stored policy that does not parse is the
+/// operator's error, so it is a 500.
+pub(crate) fn check_degraded_policy(
+    zone_id: &str,
+    status: &str,
+    policy: Option<&serde_json::Value>,
+    req: &CreateTransferRequest,
+) -> Result<(), AppError> {
+    apply_degraded_policy(zone_id, status, policy, &req.request_id, req.amount_units)
+}
+
+/// [`check_degraded_policy`] for any request with an idempotency key and a total amount.
+pub(crate) fn apply_degraded_policy(
+    zone_id: &str,
+    status: &str,
+    policy: Option<&serde_json::Value>,
+    request_id: &str,
+    amount_units: i64,
+) -> Result<(), AppError> {
+    let (Some(policy), "DEGRADED") = (policy, status) else { return Ok(()) };
+    let policy = DegradedPolicy::deserialize(policy).map_err(|e| {
+        error!(zone_id, error = %e, "zone degraded_policy is invalid");
+        AppError::Internal(format!("degraded policy of zone {zone_id} is invalid"))
+    })?;
+    match policy.rejects(request_id, amount_units) {
+        None => Ok(()),
+        Some(reason) => Err(AppError::Detailed {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            code: "zone_degraded",
+            message: format!("zone degraded: {}", reason.replace('_', " ")),
+            details: json!({
+                "zone_id": zone_id,
+                "reason": reason,
+                "shed_percent": policy.shed_percent,
+                "max_amount_units": policy.max_amount_units,
+            }),
+        }),
+    }
+}
+
+/// Idempotency hash of a transfer with the `metadata` fields at the dotted
+/// `excluded` paths left out, so values a gateway stamps on each attempt (client
+/// timestamps, trace ids) do not turn a retry into a 409. The fields are still
+/// stored with the transfer; only the hash ignores them.
+pub(crate) fn transfer_hash<T: Serialize>(req: &T, excluded: &[String]) -> Result<String, AppError> {
+    if excluded.is_empty() {
+        return payload_hash(req);
+    }
+    let mut v = serde_json::to_value(req).map_err(|e| AppError::BadRequest(e.to_string()))?;
+    if let Some(metadata) = v.get_mut("metadata") {
+        for path in excluded {
+            remove_path(metadata, path);
+        }
+    }
+    payload_hash(&v)
+}
+
+/// Most schema violations listed in one `invalid_metadata` response.
+const MAX_SCHEMA_VIOLATIONS: usize = 20;
+
+/// Check `metadata` against the zone's `metadata_schema`, if it has one; a 400
+/// `invalid_metadata` lists each violation with its JSON pointer. A stored schema
+/// that does not compile is the operator's error, so it is a 500.
+pub(crate) fn check_metadata_schema(
+    zone_id: &str,
+    schema: Option<&serde_json::Value>,
+    metadata: &serde_json::Value,
+) -> Result<(), AppError> {
+    let Some(schema) = schema else { return Ok(()) };
+    let validator = jsonschema::validator_for(schema).map_err(|e| {
+        error!(zone_id, error = %e, "zone metadata_schema is not a valid JSON schema");
+        AppError::Internal(format!("metadata schema of zone {zone_id} is invalid"))
+    })?;
+    let violations: Vec<serde_json::Value> = validator
+        .iter_errors(metadata)
+        .take(MAX_SCHEMA_VIOLATIONS)
+        .map(|e| json!({ "path": e.instance_path.to_string(), "message": e.to_string() }))
+        .collect();
+    if violations.is_empty() {
+        return Ok(());
+    }
+    Err(AppError::Detailed {
+        status: StatusCode::BAD_REQUEST,
+        code: "invalid_metadata",
+        message: format!("metadata does not match the schema of zone {zone_id}"),
+        details: json!({ "zone_id": zone_id, "violations": violations }),
+    })
+}
+
+/// Currency of a transfer: the requested code, or the zone's when none was given.
+/// A requested code that differs from the zone's currency is a 422.
+pub(crate) fn transfer_currency(requested: Option<&str>, zone_currency: Option<&str>) -> Result<Option<String>, AppError> {
+    match (requested.map(|c| c.trim().to_ascii_uppercase()), zone_currency) {
+        (Some(currency), Some(zone)) if !zone.eq_ignore_ascii_case(&currency) => Err(AppError::Detailed {
+            status: StatusCode::UNPROCESSABLE_ENTITY,
+            code: "currency_mismatch",
+            message: format!("currency {currency} does not match zone currency {zone}"),
+            details: json!({ "zone_currency": zone, "currency": currency }),
+        }),
+        (Some(currency), _) => Ok(Some(currency)),
+        (None, zone) => Ok(zone.map(|z| z.to_ascii_uppercase())),
+    }
+}
+
+pub(crate) fn unknown_currency(currency: &str) -> AppError {
+    AppError::Detailed {
+        status: StatusCode::BAD_REQUEST,
+        code: "unknown_currency",
+        message: format!("unknown currency {currency}"),
+        details: json!({ "currency": currency }),
+    }
+}
+
+/// Minor-unit scale of `currency`, or None when it is not in `currencies`.
+pub(crate) async fn currency_scale(
+    tx: &deadpool_postgres::Transaction<'_>,
+    currency: &str,
+) -> Result<Option<i32>, AppError> {
+    let row = tx.query_opt("SELECT minor_unit_scale FROM currencies WHERE code=$1", &[&currency]).await?;
+    Ok(row.map(|r| r.get(0)))
+}
+
+/// 409 when an account's established currency differs from the transfer's, or,
+/// for a transfer without a currency, when the two accounts disagree.
+/// `accounts` holds `(account_id, currency)` for the transfer's accounts.
+pub(crate) fn check_account_currencies(currency: Option<&str>, accounts: &[(String, Option<String>)]) -> Result<(), AppError> {
+    let mut established = accounts.iter().filter_map(|(id, c)| c.as_deref().map(|c| (id, c)));
+    let expected = match currency {
+        Some(c) => c,
+        None => match established.next() {
+            Some((_, c)) => c,
+            None => return Ok(()),
+        },
+    };
+    match established.find(|(_, c)| *c != expected) {
+        Some((account_id, account_currency)) => Err(AppError::Detailed {
+            status: StatusCode::CONFLICT,
+            code: "account_currency_mismatch",
+            message: format!("account {account_id} holds {account_currency}, not {expected}"),
+            details: json!({ "account_id": account_id, "account_currency": account_currency, "currency": expected }),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Result of running one transfer request inside a caller-owned DB transaction.
+/// The caller commits, then calls [`TransferOutcome::finish`].
+pub enum TransferOutcome {
+    /// Carries the `TransferPosted` event to publish after commit.
+    Applied(TransferResponse, LedgerEvent),
+    /// Idempotent replay of an already-applied request.
+    Duplicate(TransferResponse),
+    Spooled { response: SpooledResponse, audit: Option<tokio_postgres::Row> },
+}
+
+impl TransferOutcome {
+    /// Post-commit side effects: metrics and the live audit and event streams.
+    /// `zone_id` has already been validated, which keeps the metric label set bounded.
+    fn finish(&self, st: &AppState, zone_id: &str, amount_units: i64) {
+        match self {
+            Self::Applied(_, event) => {
+                st.metrics.transfers_total.with_label_values(&[zone_id, "posted"]).inc();
+                st.metrics.transfer_amount_units.observe(amount_units as f64);
+                publish_event(st, event.clone());
+            }
+            Self::Duplicate(_) => {
+                st.metrics.transfers_total.with_label_values(&[zone_id, "idempotent_replay"]).inc();
+            }
+            Self::Spooled { audit: Some(audit), .. } => publish_audit(st, audit),
+            Self::Spooled { audit: None, .. } => {}
+        }
+    }
+
+    /// Whether this outcome costs a rate-limit token: everything but a replay of
+    /// an applied or spooled request.
+    fn takes_token(&self) -> bool {
+        !matches!(self, Self::Duplicate(_) | Self::Spooled { audit: None, .. })
+    }
+
+    fn mark_dry_run(&mut self) {
+        match self {
+            Self::Applied(r, _) | Self::Duplicate(r) => r.dry_run = true,
+            Self::Spooled { response, .. } => response.dry_run = true,
+        }
+    }
+
+    /// Drop the resulting balances, which are only sent on request.
+    fn hide_balances(&mut self) {
+        if let Self::Applied(r, _) = self {
+            r.from_balance = None;
+            r.to_balance = None;
+        }
+    }
 }
 
+impl IntoResponse for TransferOutcome {
+    fn into_response(self) -> axum::response::Response {
+        match self {
+            Self::Applied(r, _) | Self::Duplicate(r) => Json(r).into_response(),
+            Self::Spooled { response, .. } => (StatusCode::ACCEPTED, Json(response)).into_response(),
+        }
+    }
+}
+
+/// Every response after the transfer ran carries `X-RateLimit-Limit`, `-Remaining`
+/// and `-Reset` (seconds until the bucket is full) from the zone's bucket, once
+/// the zone has one: unlimited zones and zones only replayed to so far have none.
+#[utoipa::path(
+    post,
+    path = "/v1/transfers",
+    tag = "transfers",
+    params(
+        CreateTransferQuery,
+        ("Idempotency-Key" = Option<String>, Header, description = "Idempotency key when the body has no request_id; must match it otherwise"),
+    ),
+    request_body = CreateTransferRequest,
+    responses(
+        (status = 200, description = "Applied, or idempotent replay of an applied request", body = TransferResponse),
+        (status = 202, description = "Zone blocked and spooling enabled; queued for replay", body = SpooledResponse),
+        (status = 400, description = "Malformed body or unknown field, invalid_transfer (see details.field and details.rule), invalid_metadata (see details.violations), idempotency_key_mismatch or unknown_currency", body = ErrorBody),
+        (status = 401, description = "invalid_token: Authorization is not a known API token; with JWT verification enabled also missing_token or token_expired", body = ErrorBody),
+        (status = 403, description = "counterparty_not_whitelisted, or token_not_accepted (JWT for another audience or issuer)", body = ErrorBody),
+        (status = 404, description = "unknown_zone", body = ErrorBody),
+        (status = 409, description = "Idempotency conflict (details carry the existing transaction id and both payload hashes), account_zone_mismatch, account_currency_mismatch or duplicate_request_id (lost a race to a writer that the replay could not find)", body = ErrorBody),
+        (status = 422, description = "currency_mismatch, insufficient_available_funds or balance_overflow", body = ErrorBody),
+        (status = 429, description = "rate_limited; see Retry-After", body = ErrorBody),
+        (status = 503, description = "zone_down, zone_degraded, writes_blocked, throttled, or database unavailable", body = ErrorBody),
+    )
+)]
 pub async fn create_transfer(
     State(st): State<AppState>,
-    Json(req): Json<CreateTransferRequest>,
+    Query(q): Query<CreateTransferQuery>,
+    headers: HeaderMap,
+    claims: Option<Extension<Claims>>,
+    body: Result<Json<CreateTransferRequest>, JsonRejection>,
 ) -> Result<axum::response::Response, AppError> {
-    if req.amount_units <= 0 || req.request_id.is_empty() || req.zone_id.is_empty() {
-        return Err(AppError::BadRequest("missing required fields or invalid amount".into()));
+    let principal = resolve_principal(&st.api_tokens, &headers, claims.as_deref())?;
+    let Json(mut req) = body?;
+    req.request_id = resolve_request_id(req.request_id, headers.get(IDEMPOTENCY_KEY))?;
+    if q.dry_run {
+        return dry_run_transfer(&st, req, &principal).await;
+    }
+    let _timer = st.metrics.transfer_duration_seconds.start_timer();
+    let amount_units = req.amount_units;
+    let zone_id = req.zone_id.clone();
+    let result = match write_transfer(&st, req, principal).await {
+        // a writer without the advisory lock (the Go service) committed the same
+        // request_id after our check: replay what it wrote
+        Err(e) => match duplicate_of(&e) {
+            Some((request_id, hash)) => replay_committed(&st, request_id, hash).await,
+            None => Err(e),
+        },
+        result => result,
+    };
+    record_attempt(&st, &zone_id, &result);
+    let mut outcome = match result {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            st.metrics.record_rejection(&e);
+            return Ok(with_rate_limit_headers(&st, &zone_id, e.into_response()));
+        }
+    };
+    outcome.finish(&st, &zone_id, amount_units);
+    if !q.include_balances {
+        outcome.hide_balances();
+    }
+    Ok(with_rate_limit_headers(&st, &zone_id, outcome.into_response()))
+}
+
+fn with_rate_limit_headers(st: &AppState, zone_id: &str, mut response: axum::response::Response) -> axum::response::Response {
+    if let Some(q) = quota(&st.zone_buckets, zone_id, Instant::now()) {
+        let headers = response.headers_mut();
+        headers.insert("x-ratelimit-limit", HeaderValue::from(q.limit));
+        headers.insert("x-ratelimit-remaining", HeaderValue::from(q.remaining));
+        headers.insert("x-ratelimit-reset", HeaderValue::from(q.reset.as_secs_f64().ceil() as u64));
+    }
+    response
+}
+
+/// Apply one transfer through the micro-batcher if enabled, else in its own DB transaction.
+async fn write_transfer(st: &AppState, req: CreateTransferRequest, principal: Principal) -> Result<TransferOutcome, AppError> {
+    match &st.transfer_batcher {
+        Some(batcher) => submit(batcher, req, principal).await,
+        None => {
+            let mut client = st.db.get().await?;
+            let tx = client.transaction().await?;
+            let outcome = process_transfer(st, &tx, req, &principal, true).await?;
+            tx.commit().await?;
+            Ok(outcome)
+        }
+    }
+}
+
+/// The committed transaction that won a [`duplicate_request`] race, as a replay,
+/// or an idempotency conflict if its payload differs.
+async fn replay_committed(st: &AppState, request_id: String, hash: String) -> Result<TransferOutcome, AppError> {
+    let mut client = st.db.get().await?;
+    let tx = client.transaction().await?;
+    match find_idempotent(&tx, &request_id, st).await? {
+        Some(existing) => replay_existing(&existing, request_id, &hash),
+        None => Err(duplicate_request(&request_id, &hash)),
+    }
+}
+
+/// Header accepted in place of the body's `request_id`, for clients behind
+/// gateways that cannot add body fields.
+pub const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+/// The request's idempotency key: the body's `request_id`, or else the
+/// `Idempotency-Key` header. Both may be sent only if they agree.
+fn resolve_request_id(body: String, header: Option<&HeaderValue>) -> Result<String, AppError> {
+    let Some(header) = header else { return Ok(body) };
+    let Ok(key) = header.to_str() else {
+        return Err(invalid_transfer("request_id", "charset", "Idempotency-Key must be visible ASCII".into(), json!(null)));
+    };
+    if body.is_empty() || body == key {
+        return Ok(key.to_string());
     }
-    let hash = payload_hash(&req)?;
+    Err(AppError::Detailed {
+        status: StatusCode::BAD_REQUEST,
+        code: "idempotency_key_mismatch",
+        message: "Idempotency-Key header and body request_id differ".into(),
+        details: json!({ "request_id": body, "idempotency_key": key }),
+    })
+}
+
+/// `?dry_run=true`: the full transfer path in a transaction that is always rolled
+/// back, so the outbox row, spool entry and audit entry it writes never land.
+/// Rate limiting, attempt counting, metrics and live events are skipped; rejections
+/// come back exactly as a real transfer's would.
+async fn dry_run_transfer(
+    st: &AppState,
+    req: CreateTransferRequest,
+    principal: &Principal,
+) -> Result<axum::response::Response, AppError> {
     let mut client = st.db.get().await?;
     let tx = client.transaction().await?;
+    let mut outcome = process_transfer(st, &tx, req, principal, false).await?;
+    tx.rollback().await?;
+    outcome.mark_dry_run();
+    Ok(outcome.into_response())
+}
+
+/// Gate, deduplicate and apply (or spool) one transfer within `tx`.
+/// `rate_limit` is false for batch items, which are limited once per batch.
+pub(crate) async fn process_transfer(
+    st: &AppState,
+    tx: &deadpool_postgres::Transaction<'_>,
+    req: CreateTransferRequest,
+    principal: &Principal,
+    rate_limit: bool,
+) -> Result<TransferOutcome, AppError> {
+    validate_transfer(&req, &st.transfer_limits)?;
+    let hash = transfer_hash(&req, &st.idempotency_hash_exclude)?;
 
     // zone gate + controls
-    let status: String = tx
-        .query_one("SELECT status FROM zones WHERE id=$1", &[&req.zone_id])
-        .await
-        .map_err(|_| AppError::Internal("zone not found".into()))?
-        .get(0);
+    let zone_row = tx
+        .query_opt(
+            "SELECT status, rate_limit_per_sec, currency, metadata_schema, degraded_policy FROM zones WHERE id=$1 AND NOT internal",
+            &[&req.zone_id],
+        )
+        .await?;
+    let zone_row = require_zone(zone_row, &req.zone_id)?;
+    let status: String = zone_row.get(0);
+    check_degraded_policy(&req.zone_id, &status, zone_row.get::<_, Option<serde_json::Value>>(4).as_ref(), &req)?;
+    let zone_currency: Option<&str> = zone_row.get(2);
+    check_currency(zone_currency, &req.metadata)?;
+    check_metadata_schema(&req.zone_id, zone_row.get::<_, Option<serde_json::Value>>(3).as_ref(), &req.metadata)?;
+    let currency = transfer_currency(req.currency.as_deref(), zone_currency)?;
+    if let Some(c) = &currency {
+        if currency_scale(tx, c).await?.is_none() {
+            return Err(unknown_currency(c));
+        }
+    }
+    let zone_rate = zone_row
+        .get::<_, Option<i32>>(1)
+        .map(|r| r.max(0) as u32)
+        .unwrap_or(st.zone_rate_limit);
 
     let ctrl_row = tx
         .query_opt("SELECT writes_blocked, cross_zone_throttle, spool_enabled FROM zone_controls WHERE zone_id=$1", &[&req.zone_id])
@@ -57,33 +691,12 @@ pub async fn create_transfer(
         .map(|r| (r.get::<_, bool>(0), r.get::<_, i32>(1), r.get::<_, bool>(2)))
         .unwrap_or((false, 100, false));
 
-    let blocked_reason = if status == "DOWN" {
-        Some("zone down")
-    } else if wb {
-        Some("writes blocked")
-    } else if throttle < 100 && (throttle <= 0 || hash_percent(&req.request_id) >= throttle as u32) {
-        Some("throttled")
-    } else {
-        None
-    };
+    let blocked = zone_gate(&status, wb, throttle, &req.request_id);
 
     // idempotency check (transactions table)
-    let existing = tx
-        .query_opt("SELECT id::text, payload_hash, created_at FROM transactions WHERE request_id=$1", &[&req.request_id])
-        .await?;
+    let existing = find_idempotent(tx, &req.request_id, st).await?;
     if let Some(r) = existing {
-        let ph: String = r.get(1);
-        if ph != hash {
-            return Err(AppError::Conflict("idempotency conflict: same request_id, different payload".into()));
-        }
-        tx.commit().await?;
-        let created_at: time::OffsetDateTime = r.get(2);
-        return Ok(Json(TransferResponse {
-            status: "APPLIED".into(),
-            transaction_id: r.get(0),
-            request_id: req.request_id,
-            created_at: fmt_rfc3339(created_at),
-        }).into_response());
+        return replay_existing(&r, req.request_id, &hash);
     }
 
     // idempotency check (spooled_transfers table)
@@ -93,45 +706,69 @@ pub async fn create_transfer(
     if let Some(r) = existing_spool {
         let ph: String = r.get(1);
         if ph != hash {
-            return Err(AppError::Conflict("idempotency conflict: same request_id, different payload".into()));
+            return Err(idempotency_conflict(&req.request_id, Existing::Spooled(r.get(0)), &ph, &hash));
         }
-        tx.commit().await?;
-        return Ok((StatusCode::ACCEPTED, Json(SpooledResponse {
-            status: "SPOOLED".into(),
-            spool_id: r.get(0),
-            request_id: req.request_id,
-        })).into_response());
+        return Ok(TransferOutcome::Spooled {
+            response: SpooledResponse {
+                status: "SPOOLED".into(),
+                spool_id: r.get(0),
+                request_id: req.request_id,
+                dry_run: false,
+            },
+            audit: None,
+        });
+    }
+
+    // counterparty whitelists (default-deny for listed accounts)
+    let whitelists = tx
+        .query(
+            "SELECT account_id, counterparties FROM account_whitelists WHERE account_id=$1 OR account_id=$2",
+            &[&req.from_account, &req.to_account],
+        )
+        .await?;
+    let whitelist_of = |account: &str| -> Option<Vec<String>> {
+        whitelists.iter().find(|r| r.get::<_, &str>(0) == account).map(|r| r.get(1))
+    };
+    check_whitelists(
+        &req.from_account,
+        &req.to_account,
+        whitelist_of(&req.from_account).as_deref(),
+        whitelist_of(&req.to_account).as_deref(),
+    )?;
+
+    // per-zone rate limit (idempotent replays above do not consume tokens)
+    if rate_limit {
+        acquire_zone_token(st, &req.zone_id, zone_rate)?;
     }
 
     // blocked? spool or reject
-    if let Some(reason) = blocked_reason {
+    if let Some((code, reason)) = blocked {
         if spool_enabled {
             let spool_row = tx
                 .query_one(
-                    "INSERT INTO spooled_transfers(request_id,payload_hash,from_account,to_account,amount_units,zone_id,metadata,fail_reason) VALUES($1,$2,$3,$4,$5,$6,$7,$8) RETURNING id::text",
-                    &[&req.request_id, &hash, &req.from_account, &req.to_account, &req.amount_units, &req.zone_id, &req.metadata, &reason],
+                    "INSERT INTO spooled_transfers(request_id,payload_hash,from_account,to_account,amount_units,zone_id,metadata,fail_reason,currency,created_by) VALUES($1,$2,$3,$4,$5,$6,$7,$8,$9,$10) RETURNING id::text",
+                    &[&req.request_id, &hash, &req.from_account, &req.to_account, &req.amount_units, &req.zone_id, &req.metadata, &reason, &currency, &principal.subject()],
                 )
                 .await?;
             let spool_id: String = spool_row.get(0);
 
-            tx.execute(
-                "INSERT INTO audit_log(actor,action,target_type,target_id,reason,details) VALUES('system','SPOOL_TRANSFER','zone',$1,$2, jsonb_build_object('request_id',$3,'spool_id',$4))",
+            let audit = tx.query_one(
+                "INSERT INTO audit_log(actor,action,target_type,target_id,reason,details) VALUES('system','SPOOL_TRANSFER','zone',$1,$2, jsonb_build_object('request_id',$3,'spool_id',$4)) RETURNING id::text, actor, action, target_type, target_id, reason, details, created_at",
                 &[&req.zone_id, &reason, &req.request_id, &spool_id],
             ).await?;
 
-            tx.commit().await?;
-            return Ok((StatusCode::ACCEPTED, Json(SpooledResponse {
-                status: "SPOOLED".into(),
-                spool_id,
-                request_id: req.request_id,
-            })).into_response());
+            return Ok(TransferOutcome::Spooled {
+                response: SpooledResponse {
+                    status: "SPOOLED".into(),
+                    spool_id,
+                    request_id: req.request_id,
+                    dry_run: false,
+                },
+                audit: Some(audit),
+            });
         }
 
-        return if status == "DOWN" {
-            Err(AppError::Unavailable("zone down".into()))
-        } else {
-            Err(AppError::Unavailable(format!("zone blocked: {reason}")))
-        };
+        return Err(zone_blocked(&req.zone_id, code, reason));
     }
 
     // apply transfer
@@ -143,114 +780,1905 @@ pub async fn create_transfer(
         "INSERT INTO accounts(id, zone_id) VALUES($1,$2) ON CONFLICT DO NOTHING",
         &[&req.to_account, &req.zone_id],
     ).await?;
+    let account_zones: Vec<(String, String)> = tx
+        .query("SELECT id, zone_id FROM accounts WHERE id=$1 OR id=$2", &[&req.from_account, &req.to_account])
+        .await?
+        .iter()
+        .map(|r| (r.get(0), r.get(1)))
+        .collect();
+    check_account_zones(&req.zone_id, &account_zones)?;
 
-    let (txn_id, created_at) = apply_transfer_inner(&tx, &TransferInput {
+    let applied = apply_transfer_inner(tx, &TransferInput {
         request_id: &req.request_id, payload_hash: &hash,
         from_account: &req.from_account, to_account: &req.to_account,
         amount_units: req.amount_units, zone_id: &req.zone_id, metadata: &req.metadata,
-    }).await?;
+        currency: currency.as_deref(), reverses_txn_id: None, created_by: principal.subject(),
+    }, st).await?;
 
-    tx.commit().await?;
-    st.metrics.transfers_total.inc();
+    Ok(TransferOutcome::Applied(
+        TransferResponse {
+            status: "APPLIED".into(),
+            transaction_id: applied.txn_id,
+            request_id: req.request_id,
+            created_at: to_rfc3339(applied.created_at)?,
+            from_balance: applied.balances.map(|(from, _)| from),
+            to_balance: applied.balances.map(|(_, to)| to),
+            dry_run: false,
+            payload_hash: Some(hash),
+        },
+        applied.event,
+    ))
+}
 
-    Ok(Json(TransferResponse {
-        status: "APPLIED".into(),
-        transaction_id: txn_id,
-        request_id: req.request_id,
-        created_at: fmt_rfc3339(created_at),
-    }).into_response())
+#[derive(Deserialize, ToSchema)]
+pub struct BatchTransferRequest {
+    pub transfers: Vec<CreateTransferRequest>,
 }
 
-pub struct TransferInput<'a> {
-    pub request_id: &'a str,
-    pub payload_hash: &'a str,
-    pub from_account: &'a str,
-    pub to_account: &'a str,
-    pub amount_units: i64,
-    pub zone_id: &'a str,
-    pub metadata: &'a serde_json::Value,
+#[derive(Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BatchTransferQuery {
+    /// Run every item against the current state and report per item whether it
+    /// would succeed (BatchValidation), then roll back. Nothing is posted.
+    #[serde(default)]
+    pub validate_only: bool,
 }
 
-async fn apply_transfer_inner(
-    tx: &deadpool_postgres::Transaction<'_>,
-    inp: &TransferInput<'_>,
-) -> Result<(String, time::OffsetDateTime), AppError> {
-    let TransferInput { request_id, payload_hash: hash, from_account, to_account, amount_units, zone_id, metadata } = inp;
-    let row = tx
-        .query_one(
-            "INSERT INTO transactions(request_id,payload_hash,from_account,to_account,amount_units,zone_id,metadata) VALUES($1,$2,$3,$4,$5,$6,$7) RETURNING id::text, created_at",
-            &[&request_id, &hash, &from_account, &to_account, &amount_units, &zone_id, metadata],
-        )
-        .await?;
-    let txn_id: String = row.get(0);
-    let created_at: time::OffsetDateTime = row.get(1);
+/// One item of a `?validate_only=true` batch.
+#[derive(Debug, PartialEq, Serialize, ToSchema)]
+pub struct BatchValidation {
+    pub request_id: String,
+    pub would_succeed: bool,
+    /// APPLIED, DUPLICATE or SPOOLED, for an item that would succeed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<&'static str>,
+    /// Error code an item that would fail gets, e.g. `insufficient_available_funds`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
 
-    tx.execute(
-        "INSERT INTO postings(txn_id,account_id,direction,amount_units) VALUES($1::uuid,$2,'DEBIT',$3),($1::uuid,$4,'CREDIT',$3)",
-        &[&txn_id, &from_account, &amount_units, &to_account],
-    ).await?;
+impl BatchValidation {
+    fn new(request_id: String, result: &Result<TransferOutcome, AppError>) -> Self {
+        match result {
+            Ok(outcome) => Self {
+                request_id,
+                would_succeed: true,
+                status: Some(BatchItemResult::from(outcome).status),
+                reason: None,
+                message: None,
+            },
+            Err(e) => Self {
+                request_id,
+                would_succeed: false,
+                status: None,
+                reason: Some(e.status_and_code().1),
+                message: Some(e.message().to_string()),
+            },
+        }
+    }
+}
 
-    let neg_amount = -amount_units;
-    tx.execute(
-        "INSERT INTO balances(account_id,balance_units) VALUES($1,$2) ON CONFLICT (account_id) DO UPDATE SET balance_units=balances.balance_units + EXCLUDED.balance_units, updated_at=now()",
-        &[&from_account, &neg_amount],
-    ).await?;
-    tx.execute(
-        "INSERT INTO balances(account_id,balance_units) VALUES($1,$2) ON CONFLICT (account_id) DO UPDATE SET balance_units=balances.balance_units + EXCLUDED.balance_units, updated_at=now()",
-        &[&to_account, &amount_units],
-    ).await?;
+#[derive(Debug, PartialEq, Serialize, ToSchema)]
+pub struct BatchItemResult {
+    pub request_id: String,
+    /// APPLIED, DUPLICATE (idempotent replay) or SPOOLED.
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spool_id: Option<String>,
+}
 
-    let payload = json!({
-        "event_id": "generated_by_db",
-        "type": "TransferPosted",
-        "transaction_id": txn_id,
-        "request_id": request_id,
-        "zone_id": zone_id,
-        "amount_units": amount_units,
-        "created_at": fmt_rfc3339(created_at),
-    });
-    tx.execute(
-        "INSERT INTO outbox_events(event_type,aggregate_type,aggregate_id,payload) VALUES('TransferPosted','transaction',$1,$2)",
-        &[&txn_id, &payload],
-    ).await?;
+impl From<&TransferOutcome> for BatchItemResult {
+    fn from(outcome: &TransferOutcome) -> Self {
+        match outcome {
+            TransferOutcome::Applied(r, _) | TransferOutcome::Duplicate(r) => Self {
+                request_id: r.request_id.clone(),
+                status: if matches!(outcome, TransferOutcome::Applied(..)) { "APPLIED" } else { "DUPLICATE" },
+                transaction_id: Some(r.transaction_id.clone()),
+                spool_id: None,
+            },
+            TransferOutcome::Spooled { response, .. } => Self {
+                request_id: response.request_id.clone(),
+                status: "SPOOLED",
+                transaction_id: None,
+                spool_id: Some(response.spool_id.clone()),
+            },
+        }
+    }
+}
 
-    Ok((txn_id, created_at))
+fn check_batch_size(len: usize, max: usize) -> Result<(), AppError> {
+    if len > max {
+        return Err(AppError::Detailed {
+            status: StatusCode::PAYLOAD_TOO_LARGE,
+            code: "batch_too_large",
+            message: format!("batch of {len} transfers exceeds the limit of {max}"),
+            details: json!({ "count": len, "max": max }),
+        });
+    }
+    Ok(())
 }
 
-/// Apply a transfer bypassing zone gating (used by spool replay).
-/// Idempotency is still enforced.
-pub async fn apply_transfer_bypass(
-    st: &AppState,
-    inp: &TransferInput<'_>,
-) -> Result<String, AppError> {
-    let TransferInput { request_id, payload_hash, from_account, to_account, zone_id, .. } = inp;
+/// Apply many transfers in one DB transaction. Items are processed in order with
+/// the same gating and idempotency as `create_transfer`, so a request_id repeated
+/// with the same payload is reported as DUPLICATE. Any item error rolls back the
+/// whole batch. Once every item has run, each one that posted or spooled takes a
+/// rate-limit token from its zone, as a single transfer would; replays take none.
+/// A zone whose bucket cannot cover its items rolls the batch back.
+#[utoipa::path(
+    post,
+    path = "/v1/transfers/batch",
+    tag = "transfers",
+    params(BatchTransferQuery),
+    request_body = BatchTransferRequest,
+    responses(
+        (status = 200, description = "Per-item results (BatchItemResult) in request order; with validate_only, `would_succeed` and per-item BatchValidation instead", body = serde_json::Value),
+        (status = 400, description = "Malformed body or unknown field, empty batch, or an item failed with 400 (batch_rolled_back)", body = ErrorBody),
+        (status = 401, description = "invalid_token: Authorization is not a known bearer token", body = ErrorBody),
+        (status = 413, description = "batch_too_large", body = ErrorBody),
+        (status = 403, description = "counterparty_not_whitelisted", body = ErrorBody),
+        (status = 404, description = "unknown_zone", body = ErrorBody),
+        (status = 409, description = "Idempotency conflict (details carry the existing transaction id and both payload hashes), account_zone_mismatch or account_currency_mismatch", body = ErrorBody),
+        (status = 422, description = "currency_mismatch, insufficient_available_funds or balance_overflow", body = ErrorBody),
+        (status = 429, description = "rate_limited (see Retry-After), or batch_exceeds_rate_limit: a zone has more items than its per-second rate", body = ErrorBody),
+        (status = 503, description = "zone_down, zone_degraded, writes_blocked, throttled, or database unavailable", body = ErrorBody),
+    )
+)]
+pub async fn create_transfer_batch(
+    State(st): State<AppState>,
+    Query(q): Query<BatchTransferQuery>,
+    headers: HeaderMap,
+    claims: Option<Extension<Claims>>,
+    body: Result<Json<BatchTransferRequest>, JsonRejection>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let principal = resolve_principal(&st.api_tokens, &headers, claims.as_deref())?;
+    let Json(batch) = body?;
+    check_batch_size(batch.transfers.len(), st.transfer_batch_max)?;
+    if batch.transfers.is_empty() {
+        return Err(AppError::BadRequest("transfers must not be empty".into()));
+    }
+    if q.validate_only {
+        return validate_batch(&st, batch.transfers, &principal).await;
+    }
     let mut client = st.db.get().await?;
     let tx = client.transaction().await?;
 
-    // idempotency check
-    let existing = tx
-        .query_opt("SELECT id::text, payload_hash FROM transactions WHERE request_id=$1", &[&request_id])
-        .await?;
-    if let Some(r) = existing {
-        let ph: String = r.get(1);
-        if ph != *payload_hash {
-            return Err(AppError::Conflict("idempotency conflict".into()));
-        }
-        tx.commit().await?;
-        return Ok(r.get(0));
+    let mut outcomes = Vec::with_capacity(batch.transfers.len());
+    for (index, req) in batch.transfers.into_iter().enumerate() {
+        let request_id = req.request_id.clone();
+        let amount_units = req.amount_units;
+        let zone_id = req.zone_id.clone();
+        let outcome = process_transfer(&st, &tx, req, &principal, false).await.map_err(|e| {
+            let (status, code) = e.status_and_code();
+            AppError::Detailed {
+                status,
+                code: "batch_rolled_back",
+                message: format!("transfer {index} ({request_id}) failed: {}; no transfers were applied", e.message()),
+                details: json!({ "index": index, "request_id": request_id, "error": { "code": code, "message": e.message() } }),
+            }
+        })?;
+        outcomes.push((outcome, zone_id, amount_units));
     }
 
-    tx.execute(
-        "INSERT INTO accounts(id, zone_id) VALUES($1,$2) ON CONFLICT DO NOTHING",
-        &[&from_account, &zone_id],
-    ).await?;
-    tx.execute(
-        "INSERT INTO accounts(id, zone_id) VALUES($1,$2) ON CONFLICT DO NOTHING",
-        &[&to_account, &zone_id],
-    ).await?;
-
-    let (txn_id, _) = apply_transfer_inner(&tx, inp).await?;
+    // every item passed, so its zone exists; a refusal drops tx, rolling the batch back
+    let mut per_zone = BTreeMap::<&str, u32>::new();
+    for (outcome, zone_id, _) in &outcomes {
+        if outcome.takes_token() {
+            *per_zone.entry(zone_id.as_str()).or_default() += 1;
+        }
+    }
+    for (zone_id, count) in per_zone {
+        let row = tx.query_one("SELECT rate_limit_per_sec FROM zones WHERE id=$1 AND NOT internal", &[&zone_id]).await?;
+        let rate = row.get::<_, Option<i32>>(0).map(|r| r.max(0) as u32).unwrap_or(st.zone_rate_limit);
+        acquire_zone_tokens(&st, zone_id, count, rate)?;
+    }
 
     tx.commit().await?;
-    Ok(txn_id)
+    for (outcome, zone_id, amount_units) in &outcomes {
+        outcome.finish(&st, zone_id, *amount_units);
+    }
+    let results: Vec<BatchItemResult> = outcomes.iter().map(|(o, _, _)| BatchItemResult::from(o)).collect();
+    Ok(Json(json!({ "results": results })))
+}
+
+/// `?validate_only=true`: the items run in order, each in its own savepoint of one
+/// transaction that is always rolled back. An item that would fail is undone and
+/// the rest still run; later items see the effects of earlier ones that would
+/// succeed, so a repeat is a DUPLICATE and balances carry over. Rate limits are
+/// neither charged nor checked.
+async fn validate_batch(
+    st: &AppState,
+    transfers: Vec<CreateTransferRequest>,
+    principal: &Principal,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let mut client = st.db.get().await?;
+    let tx = client.transaction().await?;
+    let mut results = Vec::with_capacity(transfers.len());
+    for req in transfers {
+        let request_id = req.request_id.clone();
+        tx.batch_execute("SAVEPOINT batch_item").await?;
+        let result = process_transfer(st, &tx, req, principal, false).await;
+        let end = if result.is_ok() { "RELEASE SAVEPOINT batch_item" } else { "ROLLBACK TO SAVEPOINT batch_item" };
+        tx.batch_execute(end).await?;
+        results.push(BatchValidation::new(request_id, &result));
+    }
+    tx.rollback().await?;
+    let would_succeed = results.iter().all(|r| r.would_succeed);
+    Ok(Json(json!({ "validate_only": true, "would_succeed": would_succeed, "results": results })))
+}
+
+pub(crate) fn acquire_zone_token(st: &AppState, zone_id: &str, rate_per_sec: u32) -> Result<(), AppError> {
+    try_acquire(&st.zone_buckets, zone_id, rate_per_sec, Instant::now()).map_err(|wait| rate_limited(zone_id, wait))
+}
+
+/// Take `count` tokens from `zone_id`'s bucket for a batch. More items than the
+/// rate can never fit in the bucket, so that is refused without a Retry-After.
+fn acquire_zone_tokens(st: &AppState, zone_id: &str, count: u32, rate_per_sec: u32) -> Result<(), AppError> {
+    if rate_per_sec > 0 && count > rate_per_sec {
+        return Err(AppError::Detailed {
+            status: StatusCode::TOO_MANY_REQUESTS,
+            code: "batch_exceeds_rate_limit",
+            message: format!("batch has {count} transfers in zone {zone_id}, above its limit of {rate_per_sec} per second"),
+            details: json!({ "zone_id": zone_id, "count": count, "rate_limit_per_sec": rate_per_sec }),
+        });
+    }
+    try_acquire_n(&st.zone_buckets, zone_id, count, rate_per_sec, Instant::now()).map_err(|wait| rate_limited(zone_id, wait))
+}
+
+pub(crate) fn rate_limited(zone_id: &str, wait: Duration) -> AppError {
+    AppError::TooManyRequests {
+        message: format!("zone {zone_id} is rate limited"),
+        retry_after_secs: wait.as_secs_f64().ceil() as u64,
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ReverseRequest {
+    pub request_id: String,
+    pub actor: String,
+    #[serde(default)]
+    pub reason: String,
+}
+
+/// Idempotency hash for a reversal: the request_id plus the transaction it targets.
+fn reversal_hash(request_id: &str, transaction_id: &str) -> Result<String, AppError> {
+    payload_hash(&json!({ "request_id": request_id, "reverses_txn_id": transaction_id }))
+}
+
+/// Post a compensating transaction that swaps from/to of the original.
+/// Idempotent on `request_id`; a transaction can only be reversed once.
+/// A reversal is an operator override: it skips the zone gate (status, controls,
+/// degraded policy), the rate limit and whitelists, so a bad transfer can be
+/// undone while its zone is DOWN or blocked.
+#[utoipa::path(
+    post,
+    path = "/v1/transactions/{transaction_id}/reverse",
+    tag = "transfers",
+    params(("transaction_id" = String, Path, description = "Transaction id")),
+    request_body = ReverseRequest,
+    responses(
+        (status = 200, description = "Compensating transaction applied, or idempotent replay", body = TransferResponse),
+        (status = 400, description = "Missing request_id or actor", body = ErrorBody),
+        (status = 401, description = "invalid_token: Authorization is not a known bearer token", body = ErrorBody),
+        (status = 404, description = "Transaction not found, or transaction_id is not a UUID", body = ErrorBody),
+        (status = 409, description = "Already reversed, multi_leg_reversal, idempotency conflict or account_currency_mismatch", body = ErrorBody),
+        (status = 422, description = "insufficient_available_funds or balance_overflow", body = ErrorBody),
+        (status = 503, description = "Database unavailable", body = ErrorBody),
+    )
+)]
+pub async fn reverse_transaction(
+    State(st): State<AppState>,
+    Path(transaction_id): Path<String>,
+    headers: HeaderMap,
+    claims: Option<Extension<Claims>>,
+    Json(req): Json<ReverseRequest>,
+) -> Result<Json<TransferResponse>, AppError> {
+    let principal = resolve_principal(&st.api_tokens, &headers, claims.as_deref())?;
+    if req.request_id.is_empty() {
+        return Err(AppError::BadRequest("request_id required".into()));
+    }
+    if req.actor.is_empty() {
+        return Err(AppError::BadRequest("actor required".into()));
+    }
+    // no transaction has a non-UUID id; the canonical form keeps the hash stable
+    let Ok(transaction_id) = uuid::Uuid::parse_str(&transaction_id).map(|id| id.to_string()) else {
+        return Err(AppError::NotFound("transaction not found".into()));
+    };
+    let hash = reversal_hash(&req.request_id, &transaction_id)?;
+    let mut client = st.db.get().await?;
+    let tx = client.transaction().await?;
+
+    // lock the original so concurrent reversals serialize on it
+    let original = tx
+        .query_opt(
+            "SELECT id::text, from_account, to_account, amount_units, zone_id, currency FROM transactions WHERE id=$1::text::uuid FOR UPDATE",
+            &[&transaction_id],
+        )
+        .await?
+        .ok_or_else(|| AppError::NotFound("transaction not found".into()))?;
+
+    // idempotency check
+    let existing = find_idempotent(&tx, &req.request_id, &st).await?;
+    if let Some(r) = existing {
+        let ph: String = r.get(1);
+        if ph != hash {
+            return Err(idempotency_conflict(&req.request_id, Existing::Transaction(r.get(0)), &ph, &hash));
+        }
+        tx.commit().await?;
+        let created_at: time::OffsetDateTime = r.get(2);
+        return Ok(Json(TransferResponse {
+            status: "APPLIED".into(),
+            transaction_id: r.get(0),
+            request_id: req.request_id,
+            created_at: to_rfc3339(created_at)?,
+            from_balance: None,
+            to_balance: None,
+            dry_run: false,
+            payload_hash: Some(ph),
+        }));
+    }
+
+    // the swapped from/to of a split would only compensate two of its legs
+    let legs: i64 = tx
+        .query_one("SELECT COUNT(*) FROM postings WHERE txn_id=$1::text::uuid", &[&transaction_id])
+        .await?
+        .get(0);
+    if legs > 2 {
+        return Err(AppError::Detailed {
+            status: StatusCode::CONFLICT,
+            code: "multi_leg_reversal",
+            message: "transactions with more than two legs cannot be reversed; post the compensating legs instead".into(),
+            details: json!({ "transaction_id": transaction_id, "legs": legs }),
+        });
+    }
+
+    let already = tx
+        .query_opt("SELECT id::text FROM transactions WHERE reverses_txn_id=$1::text::uuid", &[&transaction_id])
+        .await?;
+    if let Some(r) = already {
+        let reversal_id: String = r.get(0);
+        return Err(AppError::Detailed {
+            status: StatusCode::CONFLICT,
+            code: "conflict",
+            message: "transaction has already been reversed".into(),
+            details: json!({ "transaction_id": transaction_id, "reversal_transaction_id": reversal_id }),
+        });
+    }
+
+    let from_account: String = original.get("to_account");
+    let to_account: String = original.get("from_account");
+    let amount_units: i64 = original.get("amount_units");
+    let zone_id: String = original.get("zone_id");
+    let currency: Option<String> = original.get("currency");
+    let metadata = json!({ "reverses_txn_id": transaction_id, "reason": req.reason });
+
+    let AppliedTransfer { txn_id, created_at, event, .. } = apply_transfer_inner(&tx, &TransferInput {
+        request_id: &req.request_id, payload_hash: &hash,
+        from_account: &from_account, to_account: &to_account,
+        amount_units, zone_id: &zone_id, metadata: &metadata,
+        currency: currency.as_deref(), reverses_txn_id: Some(transaction_id.as_str()),
+        created_by: principal.subject(),
+    }, &st).await?;
+
+    let audit = tx.query_one(
+        "INSERT INTO audit_log(actor,action,target_type,target_id,reason,details) VALUES($1,'REVERSE_TRANSACTION','transaction',$2,$3, jsonb_build_object('reversal_txn_id',$4,'request_id',$5)) RETURNING id::text, actor, action, target_type, target_id, reason, details, created_at",
+        &[&req.actor, &transaction_id, &req.reason, &txn_id, &req.request_id],
+    ).await?;
+
+    tx.commit().await?;
+    publish_audit(&st, &audit);
+    publish_event(&st, event);
+    st.metrics.transfers_total.with_label_values(&[zone_id.as_str(), "posted"]).inc();
+
+    Ok(Json(TransferResponse {
+        status: "APPLIED".into(),
+        transaction_id: txn_id,
+        request_id: req.request_id,
+        created_at: to_rfc3339(created_at)?,
+        from_balance: None,
+        to_balance: None,
+        dry_run: false,
+        payload_hash: Some(hash),
+    }))
+}
+
+pub struct TransferInput<'a> {
+    pub request_id: &'a str,
+    pub payload_hash: &'a str,
+    pub from_account: &'a str,
+    pub to_account: &'a str,
+    pub amount_units: i64,
+    pub zone_id: &'a str,
+    pub metadata: &'a serde_json::Value,
+    /// Validated currency code; None for transfers in zones without one.
+    pub currency: Option<&'a str>,
+    /// Set when this transaction compensates an earlier one.
+    pub reverses_txn_id: Option<&'a str>,
+    /// Subject of the principal that initiated it; see [`crate::auth`].
+    pub created_by: &'a str,
+}
+
+/// The outcome for a request whose key is held by `existing`, a row from
+/// [`find_idempotent`]: a replay if the payloads match, else an idempotency conflict.
+fn replay_existing(existing: &tokio_postgres::Row, request_id: String, hash: &str) -> Result<TransferOutcome, AppError> {
+    let ph: String = existing.get(1);
+    if ph != hash {
+        return Err(idempotency_conflict(&request_id, Existing::Transaction(existing.get(0)), &ph, hash));
+    }
+    let created_at: time::OffsetDateTime = existing.get(2);
+    Ok(TransferOutcome::Duplicate(TransferResponse {
+        status: "APPLIED".into(),
+        transaction_id: existing.get(0),
+        request_id,
+        created_at: to_rfc3339(created_at)?,
+        from_balance: None,
+        to_balance: None,
+        dry_run: false,
+        payload_hash: Some(ph),
+    }))
+}
+
+/// Another transaction committed this `request_id` between our idempotency
+/// check and the INSERT. Rust writers serialize on the advisory lock in
+/// [`find_idempotent`]; the Go service does not, so the unique index catches it.
+fn duplicate_request(request_id: &str, payload_hash: &str) -> AppError {
+    AppError::Detailed {
+        status: StatusCode::CONFLICT,
+        code: "duplicate_request_id",
+        message: format!("request {request_id} was applied concurrently; retry to replay it"),
+        details: json!({ "request_id": request_id, "payload_hash": payload_hash }),
+    }
+}
+
+/// `(request_id, payload_hash)` of a [`duplicate_request`] error.
+fn duplicate_of(e: &AppError) -> Option<(String, String)> {
+    let AppError::Detailed { code: "duplicate_request_id", details, .. } = e else { return None };
+    Some((details["request_id"].as_str()?.to_string(), details["payload_hash"].as_str()?.to_string()))
+}
+
+/// Whether a transaction created at `created_at` still holds its idempotency key at `now`.
+/// No TTL means keys never expire.
+fn within_window(created_at: OffsetDateTime, now: OffsetDateTime, ttl: Option<Duration>) -> bool {
+    ttl.is_none_or(|ttl| now - created_at < ttl)
+}
+
+/// Transaction holding `request_id`, if its idempotency window (`st.idempotency_ttl`,
+/// measured against `st.clock`) is still open. Only the key's latest use counts.
+/// Locks the key for the rest of the DB transaction first, so concurrent requests
+/// reusing it serialize. Rows whose window has closed are left as written; the
+/// next use gets the next `request_epoch` (see [`NEXT_REQUEST_EPOCH`]).
+pub(crate) async fn find_idempotent(
+    tx: &deadpool_postgres::Transaction<'_>,
+    request_id: &str,
+    st: &AppState,
+) -> Result<Option<tokio_postgres::Row>, AppError> {
+    tx.execute("SELECT pg_advisory_xact_lock(hashtextextended($1, 0))", &[&request_id]).await?;
+    let row = tx
+        .query_opt(
+            "SELECT id::text, payload_hash, created_at FROM transactions WHERE request_id=$1 ORDER BY request_epoch DESC LIMIT 1",
+            &[&request_id],
+        )
+        .await?;
+    let now = st.clock.now();
+    Ok(row.filter(|r| within_window(r.get("created_at"), now, st.idempotency_ttl)))
+}
+
+/// `request_epoch` for a new transaction with request_id `$1`: one past the latest
+/// use whose window closed at or before `$11`, else 0. `(request_id, request_epoch)`
+/// is unique (migration 0026). Only expired uses count, so a use committed inside
+/// the window by a writer without the key lock (the Go service) still collides.
+pub(crate) const NEXT_REQUEST_EPOCH: &str =
+    "(SELECT COALESCE(max(request_epoch) + 1, 0) FROM transactions WHERE request_id=$1 AND created_at <= $11)";
+
+/// `balance + delta`, or None if `balances` could not store it: outside i64, or
+/// exactly `i64::MIN`, which `balances_units_range` (migration 0007) rejects.
+pub(crate) fn checked_balance(balance: i64, delta: i64) -> Option<i64> {
+    balance.checked_add(delta).filter(|b| *b != i64::MIN)
+}
+
+/// Resulting (from, to) balances of moving `amount` between two accounts,
+/// or None if either could not be stored (see [`checked_balance`]).
+pub(crate) fn checked_transfer(from_balance: i64, to_balance: i64, amount: i64) -> Option<(i64, i64)> {
+    Some((checked_balance(from_balance, amount.checked_neg()?)?, checked_balance(to_balance, amount)?))
+}
+
+/// Debits must be covered by available funds when `REJECT_OVERDRAFTS` is on.
+pub(crate) fn insufficient_available(available: i64, amount: i64) -> bool {
+    available < amount
+}
+
+pub(crate) fn insufficient_funds(from_account: &str, available: i64, amount_units: i64) -> AppError {
+    AppError::Detailed {
+        status: StatusCode::UNPROCESSABLE_ENTITY,
+        code: "insufficient_available_funds",
+        message: "transfer exceeds the settled balance of the source account".into(),
+        details: json!({ "from_account": from_account, "available_units": available, "amount_units": amount_units }),
+    }
+}
+
+pub(crate) fn balance_overflow(from_account: &str, to_account: &str, amount_units: i64) -> AppError {
+    AppError::Detailed {
+        status: StatusCode::UNPROCESSABLE_ENTITY,
+        code: "balance_overflow",
+        message: "transfer would overflow an account balance".into(),
+        details: json!({ "from_account": from_account, "to_account": to_account, "amount_units": amount_units }),
+    }
+}
+
+/// What [`apply_transfer_inner`] wrote, reported by the caller after commit.
+struct AppliedTransfer {
+    txn_id: String,
+    created_at: OffsetDateTime,
+    event: LedgerEvent,
+    /// `(from, to)` available balances afterwards; None with asynchronous projection.
+    balances: Option<(i64, i64)>,
+}
+
+async fn apply_transfer_inner(
+    tx: &deadpool_postgres::Transaction<'_>,
+    inp: &TransferInput<'_>,
+    st: &AppState,
+) -> Result<AppliedTransfer, AppError> {
+    let legs = [
+        Leg { account: inp.from_account, direction: Direction::Debit, amount_units: inp.amount_units },
+        Leg { account: inp.to_account, direction: Direction::Credit, amount_units: inp.amount_units },
+    ];
+    let PostedLegs { txn_id, created_at, event, balances } = post_legs(tx, inp, &legs, st).await?;
+    let balances = balances.map(|b| (b[inp.from_account], b[inp.to_account]));
+    Ok(AppliedTransfer { txn_id, created_at, event, balances })
+}
+
+/// One posting of a transaction.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Leg<'a> {
+    pub account: &'a str,
+    pub direction: Direction,
+    pub amount_units: i64,
+}
+
+/// Total `(debits, credits)` per account, in account order (the lock order);
+/// None if a total leaves the i64 range.
+pub(crate) fn leg_totals<'a>(legs: &[Leg<'a>]) -> Option<BTreeMap<&'a str, (i64, i64)>> {
+    let mut totals: BTreeMap<&str, (i64, i64)> = BTreeMap::new();
+    for leg in legs {
+        let (debits, credits) = totals.entry(leg.account).or_default();
+        let side = if leg.direction == Direction::Debit { debits } else { credits };
+        *side = side.checked_add(leg.amount_units)?;
+    }
+    Some(totals)
+}
+
+/// What [`post_legs`] wrote, reported by the caller after commit.
+pub(crate) struct PostedLegs {
+    pub txn_id: String,
+    pub created_at: OffsetDateTime,
+    pub event: LedgerEvent,
+    /// Available balance of every account afterwards; None with asynchronous projection.
+    pub balances: Option<BTreeMap<String, i64>>,
+}
+
+/// Write a transaction with `legs` as its postings and project the balances.
+/// `inp` supplies the transaction row: its `from_account`, `to_account` and
+/// `amount_units` summarise the legs, which are the authoritative postings.
+pub(crate) async fn post_legs(
+    tx: &deadpool_postgres::Transaction<'_>,
+    inp: &TransferInput<'_>,
+    legs: &[Leg<'_>],
+    st: &AppState,
+) -> Result<PostedLegs, AppError> {
+    let TransferInput { request_id, payload_hash: hash, from_account, to_account, amount_units, zone_id, metadata, currency, reverses_txn_id, created_by } = inp;
+
+    // defense in depth: never write postings that break double-entry
+    let postings: Vec<(Direction, i64)> = legs.iter().map(|l| (l.direction, l.amount_units)).collect();
+    if !postings_balanced(&postings) {
+        error!(request_id = %request_id, amount_units = *amount_units, "postings failed double-entry invariant");
+        return Err(AppError::Internal("ledger invariant violated".into()));
+    }
+    let totals = leg_totals(legs).ok_or_else(|| balance_overflow(from_account, to_account, *amount_units))?;
+    let accounts: Vec<&str> = totals.keys().copied().collect();
+
+    // an account's currency is fixed by its first transfer that carries one;
+    // the row lock keeps two first transfers from establishing different ones
+    let account_currencies: Vec<(String, Option<String>)> = tx
+        .query("SELECT id, currency FROM accounts WHERE id = ANY($1) ORDER BY id FOR UPDATE", &[&accounts])
+        .await?
+        .iter()
+        .map(|r| (r.get(0), r.get(1)))
+        .collect();
+    check_account_currencies(*currency, &account_currencies)?;
+    if currency.is_some() {
+        tx.execute("UPDATE accounts SET currency=$1 WHERE id = ANY($2) AND currency IS NULL", &[currency, &accounts]).await?;
+    }
+
+    // uses created at or before this have expired; None when keys never do
+    let window_start = st.idempotency_ttl.map(|ttl| st.clock.now() - ttl);
+    let row = tx
+        .query_one(
+            &format!(
+                "INSERT INTO transactions(request_id,payload_hash,from_account,to_account,amount_units,zone_id,metadata,reverses_txn_id,currency,created_by,request_epoch) \
+                 VALUES($1,$2,$3,$4,$5,$6,$7,$8::text::uuid,$9,$10,{NEXT_REQUEST_EPOCH}) RETURNING id::text, created_at"
+            ),
+            &[&request_id, &hash, &from_account, &to_account, &amount_units, &zone_id, metadata, reverses_txn_id, currency, created_by, &window_start],
+        )
+        .await
+        .map_err(|e| match e.code() {
+            Some(&SqlState::UNIQUE_VIOLATION) => duplicate_request(request_id, hash),
+            _ => e.into(),
+        })?;
+    let txn_id: String = row.get(0);
+    let created_at: time::OffsetDateTime = row.get(1);
+
+    // in async mode postings stay unprojected and the BalanceProjector applies them;
+    // with settlement enabled credits stay pending until the Settler promotes them
+    let project_now = st.balance_projection == BalanceProjection::Sync;
+    let defer_credit = st.settlement_delay.is_some();
+    let leg_accounts: Vec<&str> = legs.iter().map(|l| l.account).collect();
+    let leg_directions: Vec<&str> = legs.iter().map(|l| l.direction.as_str()).collect();
+    let leg_amounts: Vec<i64> = legs.iter().map(|l| l.amount_units).collect();
+    tx.execute(
+        "INSERT INTO postings(txn_id,account_id,direction,amount_units,projected_at,settled_at) \
+         SELECT $1::uuid, l.account_id, l.direction, l.amount_units, CASE WHEN $5 THEN now() END, \
+                CASE WHEN $6 AND l.direction=$7 THEN NULL ELSE now() END \
+         FROM UNNEST($2::text[], $3::text[], $4::bigint[]) AS l(account_id, direction, amount_units)",
+        &[&txn_id, &leg_accounts, &leg_directions, &leg_amounts, &project_now, &defer_credit, &Direction::Credit.as_str()],
+    ).await?;
+
+    let balances = if project_now {
+        let current = tx
+            .query(
+                "SELECT account_id, balance_units, pending_units FROM balances WHERE account_id = ANY($1) ORDER BY account_id FOR UPDATE",
+                &[&accounts],
+            )
+            .await?;
+        let balance_of = |account: &str, column: &str| {
+            current
+                .iter()
+                .find(|r| r.get::<_, &str>("account_id") == account)
+                .map(|r| r.get::<_, i64>(column))
+                .unwrap_or(0)
+        };
+        if st.reject_overdrafts {
+            for (account, &(debits, _)) in &totals {
+                let available = balance_of(account, "balance_units");
+                // the clearing account is the counterweight of adjustments and may go negative
+                if debits > 0 && *account != CLEARING_ACCOUNT && insufficient_available(available, debits) {
+                    return Err(insufficient_funds(account, available, debits));
+                }
+            }
+        }
+        for (account, &(debits, credits)) in &totals {
+            let credited = if defer_credit { 0 } else { credits };
+            // both totals are non-negative, so their difference cannot overflow
+            let fits = checked_balance(balance_of(account, "balance_units"), credited - debits).is_some()
+                && (!defer_credit || balance_of(account, "pending_units").checked_add(credits).is_some());
+            if !fits {
+                return Err(balance_overflow(from_account, to_account, *amount_units));
+            }
+        }
+
+        let mut after = BTreeMap::new();
+        for (account, &(debits, credits)) in &totals {
+            let credited = if defer_credit { 0 } else { credits };
+            let balance = if debits > 0 || credited > 0 {
+                apply_balance_delta(tx, account, credited - debits).await?
+            } else {
+                balance_of(account, "balance_units")
+            };
+            if defer_credit && credits > 0 {
+                apply_pending_delta(tx, account, credits).await?;
+            }
+            after.insert(account.to_string(), balance);
+        }
+        Some(after)
+    } else {
+        None
+    };
+
+    let payload = json!({
+        "event_id": "generated_by_db",
+        "type": "TransferPosted",
+        "transaction_id": txn_id,
+        "request_id": request_id,
+        "zone_id": zone_id,
+        "amount_units": amount_units,
+        "created_at": to_rfc3339(created_at)?,
+    });
+    let event = insert_outbox_event(tx, "TransferPosted", "transaction", &txn_id, &payload).await?;
+
+    Ok(PostedLegs { txn_id, created_at, event, balances })
+}
+
+/// Apply a transfer bypassing zone gating (used by spool replay).
+/// Idempotency is still enforced.
+pub async fn apply_transfer_bypass(
+    st: &AppState,
+    inp: &TransferInput<'_>,
+) -> Result<String, AppError> {
+    let TransferInput { request_id, payload_hash, from_account, to_account, zone_id, .. } = inp;
+    let mut client = st.db.get().await?;
+    let tx = client.transaction().await?;
+
+    // idempotency check
+    let existing = find_idempotent(&tx, request_id, st).await?;
+    if let Some(r) = existing {
+        let ph: String = r.get(1);
+        if ph != *payload_hash {
+            return Err(idempotency_conflict(request_id, Existing::Transaction(r.get(0)), &ph, payload_hash));
+        }
+        tx.commit().await?;
+        return Ok(r.get(0));
+    }
+
+    tx.execute(
+        "INSERT INTO accounts(id, zone_id) VALUES($1,$2) ON CONFLICT DO NOTHING",
+        &[&from_account, &zone_id],
+    ).await?;
+    tx.execute(
+        "INSERT INTO accounts(id, zone_id) VALUES($1,$2) ON CONFLICT DO NOTHING",
+        &[&to_account, &zone_id],
+    ).await?;
+
+    let AppliedTransfer { txn_id, event, .. } = apply_transfer_inner(&tx, inp, st).await?;
+
+    tx.commit().await?;
+    publish_event(st, event);
+    Ok(txn_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::BodyExt;
+
+    fn transfer_req() -> CreateTransferRequest {
+        CreateTransferRequest {
+            request_id: "req-1".into(),
+            from_account: "acct-a".into(),
+            to_account: "acct-b".into(),
+            amount_units: 100,
+            zone_id: "zone-eu".into(),
+            metadata: json!({"note": "demo"}),
+            currency: None,
+        }
+    }
+
+    /// `(field, rule)` of an `invalid_transfer` rejection.
+    fn violation(req: &CreateTransferRequest, limits: &TransferLimits) -> Option<(String, String)> {
+        match validate_transfer(req, limits) {
+            Ok(()) => None,
+            Err(AppError::Detailed { status, code, details, .. }) => {
+                assert_eq!((status, code), (StatusCode::BAD_REQUEST, "invalid_transfer"));
+                Some((details["field"].as_str().unwrap().into(), details["rule"].as_str().unwrap().into()))
+            }
+            Err(e) => panic!("unexpected error: {e}"),
+        }
+    }
+
+    fn rule(field: &str, rule: &str) -> Option<(String, String)> {
+        Some((field.into(), rule.into()))
+    }
+
+    #[test]
+    fn ids_are_required_bounded_and_plain() {
+        let limits = TransferLimits::default();
+        assert_eq!(violation(&transfer_req(), &limits), None);
+
+        let mut req = transfer_req();
+        req.from_account = String::new();
+        assert_eq!(violation(&req, &limits), rule("from_account", "required"));
+
+        req.from_account = "a".repeat(MAX_ID_LEN);
+        assert_eq!(violation(&req, &limits), None);
+        req.from_account.push('a');
+        assert_eq!(violation(&req, &limits), rule("from_account", "max_length"));
+
+        let mut req = transfer_req();
+        req.request_id = "req-1\nreq-2".into();
+        assert_eq!(violation(&req, &limits), rule("request_id", "charset"));
+        req.request_id = "ord:2026.04_05-x".into();
+        assert_eq!(violation(&req, &limits), None);
+        req.to_account = "acct b".into();
+        assert_eq!(violation(&req, &limits), rule("to_account", "charset"));
+        req.to_account = "acct-é".into();
+        assert_eq!(violation(&req, &limits), rule("to_account", "charset"));
+    }
+
+    #[test]
+    fn amount_must_be_positive_and_within_limit() {
+        let limits = TransferLimits { max_amount_units: 1_000, ..TransferLimits::default() };
+        let mut req = transfer_req();
+        for (amount, expected) in [
+            (-1, rule("amount_units", "positive")),
+            (0, rule("amount_units", "positive")),
+            (1, None),
+            (1_000, None),
+            (1_001, rule("amount_units", "max_amount")),
+        ] {
+            req.amount_units = amount;
+            assert_eq!(violation(&req, &limits), expected, "amount {amount}");
+        }
+        req.amount_units = i64::MAX;
+        assert_eq!(violation(&req, &TransferLimits::default()), None);
+    }
+
+    #[test]
+    fn metadata_size_is_bounded() {
+        let mut req = transfer_req();
+        req.metadata = json!({"note": "x".repeat(20)});
+        // {"note":"xxxxxxxxxxxxxxxxxxxx"}
+        let size = serde_json::to_vec(&req.metadata).unwrap().len();
+        assert_eq!(size, 31);
+        let at = TransferLimits { max_metadata_bytes: size, ..TransferLimits::default() };
+        assert_eq!(violation(&req, &at), None);
+        let under = TransferLimits { max_metadata_bytes: size - 1, ..TransferLimits::default() };
+        assert_eq!(violation(&req, &under), rule("metadata", "max_bytes"));
+    }
+
+    #[test]
+    fn currency_hint_must_match_zone() {
+        assert!(check_currency(Some("EUR"), &json!({"currency": "EUR"})).is_ok());
+        assert!(check_currency(Some("EUR"), &json!({"currency": "eur"})).is_ok());
+        let err = check_currency(Some("EUR"), &json!({"currency": "USD"})).unwrap_err();
+        assert_eq!(err.into_response().status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[test]
+    fn transfer_currency_defaults_to_zone() {
+        assert_eq!(transfer_currency(Some("usd"), Some("USD")).unwrap().as_deref(), Some("USD"));
+        assert_eq!(transfer_currency(None, Some("JPY")).unwrap().as_deref(), Some("JPY"));
+        assert_eq!(transfer_currency(Some("KWD"), None).unwrap().as_deref(), Some("KWD"));
+        assert_eq!(transfer_currency(None, None).unwrap(), None);
+        let err = transfer_currency(Some("USD"), Some("EUR")).unwrap_err();
+        assert_eq!(err.status_and_code(), (StatusCode::UNPROCESSABLE_ENTITY, "currency_mismatch"));
+    }
+
+    #[test]
+    fn unknown_currency_is_400() {
+        let err = unknown_currency("XYZ");
+        assert_eq!(err.status_and_code(), (StatusCode::BAD_REQUEST, "unknown_currency"));
+        assert!(err.message().contains("XYZ"));
+    }
+
+    #[test]
+    fn account_currency_mismatch_is_409() {
+        let accounts = vec![("acct-a".to_string(), Some("USD".to_string())), ("acct-b".to_string(), None)];
+        assert!(check_account_currencies(Some("USD"), &accounts).is_ok());
+        let err = check_account_currencies(Some("JPY"), &accounts).unwrap_err();
+        assert_eq!(err.status_and_code(), (StatusCode::CONFLICT, "account_currency_mismatch"));
+        assert!(err.message().contains("acct-a holds USD, not JPY"));
+
+        // without a transfer currency the accounts must still agree with each other
+        let mixed = vec![("acct-a".to_string(), Some("USD".to_string())), ("acct-b".to_string(), Some("EUR".to_string()))];
+        assert!(check_account_currencies(None, &mixed).is_err());
+        assert!(check_account_currencies(None, &accounts).is_ok());
+        assert!(check_account_currencies(Some("USD"), &[]).is_ok());
+    }
+
+    #[test]
+    fn absent_currency_keeps_payload_hash() {
+        let req: CreateTransferRequest = serde_json::from_value(json!({
+            "request_id": "r1", "from_account": "a", "to_account": "b", "amount_units": 5, "zone_id": "zone-eu",
+        }))
+        .unwrap();
+        let legacy = json!({
+            "request_id": "r1", "from_account": "a", "to_account": "b", "amount_units": 5, "zone_id": "zone-eu",
+            "metadata": null,
+        });
+        assert_eq!(payload_hash(&req).unwrap(), payload_hash(&legacy).unwrap());
+    }
+
+    #[test]
+    fn unknown_transfer_fields_are_rejected() {
+        let valid = json!({
+            "request_id": "r1", "from_account": "a", "to_account": "b", "amount_units": 5, "zone_id": "zone-eu",
+            "metadata": {"note": "x"}, "currency": "EUR",
+        });
+        assert!(serde_json::from_value::<CreateTransferRequest>(valid).is_ok());
+        let typo = json!({
+            "request_id": "r1", "from_account": "a", "to_account": "b", "amount_unit": 5, "zone_id": "zone-eu",
+        });
+        let err = serde_json::from_value::<CreateTransferRequest>(typo).err().unwrap();
+        assert!(err.to_string().contains("unknown field `amount_unit`"), "{err}");
+    }
+
+    #[test]
+    fn currency_check_skipped_without_both_sides() {
+        assert!(check_currency(None, &json!({"currency": "USD"})).is_ok());
+        assert!(check_currency(Some("EUR"), &json!({})).is_ok());
+        assert!(check_currency(Some("EUR"), &serde_json::Value::Null).is_ok());
+    }
+
+    fn applied(request_id: &str) -> TransferResponse {
+        TransferResponse {
+            status: "APPLIED".into(),
+            transaction_id: format!("txn-{request_id}"),
+            request_id: request_id.into(),
+            created_at: "2026-01-01T00:00:00Z".into(),
+            from_balance: None,
+            to_balance: None,
+            dry_run: false,
+            payload_hash: None,
+        }
+    }
+
+    fn posted(request_id: &str) -> TransferOutcome {
+        let response = applied(request_id);
+        let event = LedgerEvent {
+            id: format!("evt-{request_id}"),
+            event_type: "TransferPosted".into(),
+            payload: json!({ "event_id": format!("evt-{request_id}"), "transaction_id": response.transaction_id }),
+        };
+        TransferOutcome::Applied(response, event)
+    }
+
+    #[test]
+    fn batch_results_mixed_outcomes() {
+        let outcomes = [
+            posted("r1"),
+            TransferOutcome::Spooled {
+                response: SpooledResponse { status: "SPOOLED".into(), spool_id: "s2".into(), request_id: "r2".into(), dry_run: false },
+                audit: None,
+            },
+        ];
+        let results: Vec<BatchItemResult> = outcomes.iter().map(BatchItemResult::from).collect();
+        assert_eq!(results[0].status, "APPLIED");
+        assert_eq!(results[0].transaction_id.as_deref(), Some("txn-r1"));
+        assert_eq!(results[1].status, "SPOOLED");
+        assert_eq!(results[1].spool_id.as_deref(), Some("s2"));
+        assert_eq!(results[1].transaction_id, None);
+    }
+
+    #[test]
+    fn batch_duplicate_reports_original_transaction() {
+        // a request_id repeated within the batch resolves to the first item's transaction
+        let first = BatchItemResult::from(&posted("r1"));
+        let dup = BatchItemResult::from(&TransferOutcome::Duplicate(applied("r1")));
+        assert_eq!(dup.status, "DUPLICATE");
+        assert_eq!(dup.transaction_id, first.transaction_id);
+    }
+
+    #[test]
+    fn balances_only_sent_on_request() {
+        let mut outcome = posted("r1");
+        if let TransferOutcome::Applied(r, _) = &mut outcome {
+            (r.from_balance, r.to_balance) = (Some(-100), Some(100));
+        }
+        let TransferOutcome::Applied(r, _) = &outcome else { unreachable!() };
+        let shown = serde_json::to_value(r).unwrap();
+        assert_eq!((shown["from_balance"].as_i64(), shown["to_balance"].as_i64()), (Some(-100), Some(100)));
+
+        outcome.hide_balances();
+        let TransferOutcome::Applied(r, _) = &outcome else { unreachable!() };
+        let hidden = serde_json::to_value(r).unwrap();
+        assert!(hidden.get("from_balance").is_none() && hidden.get("to_balance").is_none(), "{hidden}");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn returned_balances_match_list_balances() {
+        use crate::handlers::balances::{list_balances, BalanceQuery};
+        let st = crate::testing::test_db().await;
+        let run = uuid::Uuid::new_v4();
+        let req = CreateTransferRequest {
+            request_id: format!("req-{run}"),
+            from_account: format!("acct-a-{run}"),
+            to_account: format!("acct-b-{run}"),
+            ..transfer_req()
+        };
+        let (from, to) = (req.from_account.clone(), req.to_account.clone());
+        let res = create_transfer(State(st.clone()), Query(CreateTransferQuery { include_balances: true, dry_run: false }), Default::default(), None, Ok(Json(req)))
+            .await
+            .unwrap();
+        let body: serde_json::Value =
+            serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+
+        for (account, field) in [(from, "from_balance"), (to, "to_balance")] {
+            let q = BalanceQuery {
+                account: Some(account),
+                zone_id: None,
+                min_balance: None,
+                max_balance: None,
+                limit: 1,
+                offset: 0,
+            };
+            let Json(listed) = list_balances(State(st.clone()), Query(q)).await.unwrap();
+            assert_eq!(body[field], listed["balances"][0]["balance_units"], "{field}");
+        }
+        assert_eq!((body["from_balance"].as_i64(), body["to_balance"].as_i64()), (Some(-100), Some(100)));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn returned_payload_hash_matches_recomputed_hash() {
+        use crate::handlers::transactions::fetch_transaction;
+        let st = crate::testing::test_db().await;
+        let run = uuid::Uuid::new_v4();
+        let req = || CreateTransferRequest {
+            request_id: format!("req-{run}"),
+            from_account: format!("acct-a-{run}"),
+            to_account: format!("acct-b-{run}"),
+            ..transfer_req()
+        };
+        let expected = payload_hash(&req()).unwrap();
+        let send = || async {
+            let q = Query(CreateTransferQuery { include_balances: false, dry_run: false });
+            let res = create_transfer(State(st.clone()), q, Default::default(), None, Ok(Json(req()))).await.unwrap();
+            serde_json::from_slice::<serde_json::Value>(&res.into_body().collect().await.unwrap().to_bytes()).unwrap()
+        };
+
+        let created = send().await;
+        assert_eq!(created["payload_hash"], expected.as_str());
+        let retried = send().await;
+        assert_eq!(retried["payload_hash"], expected.as_str(), "a replay reports the stored hash");
+
+        let txn_id = created["transaction_id"].as_str().unwrap().to_string();
+        let txn = fetch_transaction(&st, &txn_id).await.unwrap();
+        assert_eq!(txn["payload_hash"], expected.as_str());
+    }
+
+    #[test]
+    fn excluded_metadata_fields_do_not_change_the_hash() {
+        let excluded = vec!["client_ts".to_string(), "gateway.received_at".to_string()];
+        let with = |client_ts: i64, received_at: &str, note: &str| CreateTransferRequest {
+            metadata: json!({ "note": note, "client_ts": client_ts, "gateway": { "received_at": received_at, "hop": 1 } }),
+            ..transfer_req()
+        };
+        let first = with(1_700_000_000, "10:00:00", "rent");
+        let retry = with(1_700_000_042, "10:00:42", "rent");
+        assert_eq!(transfer_hash(&first, &excluded).unwrap(), transfer_hash(&retry, &excluded).unwrap());
+        assert_ne!(payload_hash(&first).unwrap(), payload_hash(&retry).unwrap(), "differs without exclusions");
+        // the rest of the metadata still counts
+        let changed = with(1_700_000_000, "10:00:00", "groceries");
+        assert_ne!(transfer_hash(&first, &excluded).unwrap(), transfer_hash(&changed, &excluded).unwrap());
+        // hashing a copy leaves the request's metadata intact for storage
+        assert_eq!(first.metadata["client_ts"], 1_700_000_000);
+        // no exclusions hashes exactly as before
+        assert_eq!(transfer_hash(&first, &[]).unwrap(), payload_hash(&first).unwrap());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn retry_differing_only_in_excluded_field_dedups() {
+        use crate::handlers::transactions::fetch_transaction;
+        let config = crate::config::Config {
+            idempotency_hash_exclude: vec!["client_ts".into()],
+            ..crate::testing::test_db_config()
+        };
+        let st = crate::app::build_state(config).await.unwrap();
+        let run = uuid::Uuid::new_v4();
+        let send = |client_ts: i64| {
+            let st = st.clone();
+            async move {
+                let req = CreateTransferRequest {
+                    request_id: format!("req-{run}"),
+                    from_account: format!("acct-a-{run}"),
+                    to_account: format!("acct-b-{run}"),
+                    metadata: json!({ "note": "rent", "client_ts": client_ts }),
+                    ..transfer_req()
+                };
+                let q = Query(CreateTransferQuery { include_balances: false, dry_run: false });
+                let res = create_transfer(State(st), q, Default::default(), None, Ok(Json(req))).await.unwrap();
+                let status = res.status();
+                let body: serde_json::Value =
+                    serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+                (status, body)
+            }
+        };
+
+        let (_, first) = send(1).await;
+        let (status, retry) = send(2).await;
+        assert_eq!(status, StatusCode::OK, "not a 409");
+        assert_eq!(retry["transaction_id"], first["transaction_id"]);
+
+        let txn_id = first["transaction_id"].as_str().unwrap().to_string();
+        let txn = fetch_transaction(&st, &txn_id).await.unwrap();
+        assert_eq!(txn["metadata"]["client_ts"], 1, "the excluded field is still stored, from the first request");
+    }
+
+    #[test]
+    fn only_the_duplicate_request_error_is_replayed() {
+        let err = duplicate_request("req-1", "hash-1");
+        assert_eq!(err.status_and_code(), (StatusCode::CONFLICT, "duplicate_request_id"));
+        assert_eq!(duplicate_of(&err), Some(("req-1".into(), "hash-1".into())));
+        assert_eq!(duplicate_of(&AppError::Conflict("duplicate_request_id".into())), None);
+        assert_eq!(duplicate_of(&AppError::Internal("duplicate key value violates unique constraint".into())), None);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn simultaneous_identical_transfers_apply_once() {
+        let st = crate::testing::test_db().await;
+        let run = uuid::Uuid::new_v4();
+        let send = || {
+            let st = st.clone();
+            async move {
+                let req = CreateTransferRequest {
+                    request_id: format!("req-{run}"),
+                    from_account: format!("acct-a-{run}"),
+                    to_account: format!("acct-b-{run}"),
+                    ..transfer_req()
+                };
+                let q = Query(CreateTransferQuery { include_balances: false, dry_run: false });
+                let res = create_transfer(State(st), q, Default::default(), None, Ok(Json(req))).await.unwrap();
+                let status = res.status();
+                let body: serde_json::Value =
+                    serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+                (status, body)
+            }
+        };
+
+        let ((first_status, first), (second_status, second)) = tokio::join!(send(), send());
+        assert_eq!((first_status, second_status), (StatusCode::OK, StatusCode::OK));
+        assert!(first["transaction_id"].is_string(), "{first}");
+        assert_eq!(first["transaction_id"], second["transaction_id"]);
+
+        let client = st.db.get().await.unwrap();
+        let row = client
+            .query_one("SELECT count(*) FROM transactions WHERE request_id=$1", &[&format!("req-{run}")])
+            .await
+            .unwrap();
+        assert_eq!(row.get::<_, i64>(0), 1, "applied exactly once");
+    }
+
+    /// The other writer inserts without the advisory lock, as the Go service does,
+    /// and commits while our INSERT waits on the unique index.
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn insert_race_with_an_unlocked_writer_replays_its_transaction() {
+        let st = crate::testing::test_db().await;
+        let run = uuid::Uuid::new_v4();
+        let req = CreateTransferRequest {
+            request_id: format!("req-{run}"),
+            from_account: format!("acct-a-{run}"),
+            to_account: format!("acct-b-{run}"),
+            ..transfer_req()
+        };
+        let hash = transfer_hash(&req, &st.idempotency_hash_exclude).unwrap();
+
+        let mut other = st.db.get().await.unwrap();
+        let other_tx = other.transaction().await.unwrap();
+        let theirs: String = other_tx
+            .query_one(
+                "INSERT INTO transactions(request_id,payload_hash,from_account,to_account,amount_units,zone_id) VALUES($1,$2,$3,$4,$5,$6) RETURNING id::text",
+                &[&req.request_id, &hash, &req.from_account, &req.to_account, &req.amount_units, &req.zone_id],
+            )
+            .await
+            .unwrap()
+            .get(0);
+        let ours = tokio::spawn({
+            let st = st.clone();
+            let q = Query(CreateTransferQuery { include_balances: false, dry_run: false });
+            async move { create_transfer(State(st), q, Default::default(), None, Ok(Json(req))).await }
+        });
+        // let our INSERT reach the index; committing first replays through find_idempotent instead
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        other_tx.commit().await.unwrap();
+
+        let res = ours.await.unwrap().unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        assert_eq!(body["transaction_id"], theirs.as_str());
+    }
+
+    fn order_schema() -> serde_json::Value {
+        json!({
+            "type": "object",
+            "required": ["order_id"],
+            "properties": {
+                "order_id": { "type": "string", "pattern": "^ord-[0-9]+$" },
+                "channel": { "enum": ["web", "pos"] }
+            }
+        })
+    }
+
+    #[test]
+    fn metadata_matching_the_zone_schema_passes() {
+        let schema = order_schema();
+        assert!(check_metadata_schema("zone-eu", Some(&schema), &json!({ "order_id": "ord-42", "channel": "pos" })).is_ok());
+    }
+
+    #[test]
+    fn metadata_violations_are_listed() {
+        let schema = order_schema();
+        let err = check_metadata_schema("zone-eu", Some(&schema), &json!({ "channel": "fax" })).unwrap_err();
+        let AppError::Detailed { status, code, details, .. } = err else { panic!("expected invalid_metadata") };
+        assert_eq!((status, code), (StatusCode::BAD_REQUEST, "invalid_metadata"));
+        assert_eq!(details["zone_id"], "zone-eu");
+        let violations = details["violations"].as_array().unwrap();
+        assert_eq!(violations.len(), 2, "{violations:?}");
+        let message_at = |path: &str| {
+            violations.iter().find(|v| v["path"] == path).map(|v| v["message"].as_str().unwrap().to_string()).unwrap()
+        };
+        assert!(message_at("").contains("\"order_id\" is a required property"), "{violations:?}");
+        assert!(message_at("/channel").contains("\"fax\""), "{violations:?}");
+
+        let err = check_metadata_schema("zone-eu", Some(&schema), &json!({ "order_id": "42" })).unwrap_err();
+        let AppError::Detailed { details, .. } = err else { panic!("expected invalid_metadata") };
+        assert_eq!(details["violations"][0]["path"], "/order_id");
+        assert!(details["violations"][0]["message"].as_str().unwrap().contains("^ord-[0-9]+$"));
+    }
+
+    #[test]
+    fn schemaless_zone_accepts_any_metadata() {
+        for metadata in [json!({}), json!({ "anything": [1, 2, 3] }), json!("free text"), json!(null)] {
+            assert!(check_metadata_schema("zone-na", None, &metadata).is_ok());
+        }
+        // a broken stored schema is a server-side problem, not the client's
+        let broken = json!({ "type": "no-such-type" });
+        let err = check_metadata_schema("zone-na", Some(&broken), &json!({})).unwrap_err();
+        assert!(matches!(err, AppError::Internal(_)));
+    }
+
+    /// Request ids a DEGRADED zone with `policy` rejects, out of `req-0000`..`req-0999`.
+    fn shed_ids(policy: &serde_json::Value) -> Vec<String> {
+        (0..1000)
+            .map(|i| CreateTransferRequest { request_id: format!("req-{i:04}"), ..transfer_req() })
+            .filter(|req| check_degraded_policy("zone-eu", "DEGRADED", Some(policy), req).is_err())
+            .map(|req| req.request_id)
+            .collect()
+    }
+
+    #[test]
+    fn degraded_zone_sheds_half_deterministically() {
+        let policy = json!({ "shed_percent": 50, "seed": "outage-1" });
+        let shed = shed_ids(&policy);
+        assert!((400..600).contains(&shed.len()), "shed {} of 1000", shed.len());
+        assert_eq!(shed_ids(&policy), shed, "same seed, same request ids shed");
+        let reseeded = shed_ids(&json!({ "shed_percent": 50, "seed": "outage-2" }));
+        assert_ne!(reseeded, shed, "a new seed sheds a different set");
+
+        let req = CreateTransferRequest { request_id: shed[0].clone(), ..transfer_req() };
+        match check_degraded_policy("zone-eu", "DEGRADED", Some(&policy), &req).unwrap_err() {
+            AppError::Detailed { status, code, details, .. } => {
+                assert_eq!((status, code), (StatusCode::SERVICE_UNAVAILABLE, "zone_degraded"));
+                assert_eq!(details["reason"], "load_shed");
+            }
+            other => panic!("expected zone_degraded, got {other:?}"),
+        }
+        // the policy only applies while the zone is DEGRADED
+        assert!(check_degraded_policy("zone-eu", "OK", Some(&policy), &req).is_ok());
+    }
+
+    #[test]
+    fn degraded_zone_rejects_large_transfers() {
+        let policy = json!({ "max_amount_units": 1000 });
+        let at_limit = CreateTransferRequest { amount_units: 1000, ..transfer_req() };
+        assert!(check_degraded_policy("zone-eu", "DEGRADED", Some(&policy), &at_limit).is_ok());
+        let large = CreateTransferRequest { amount_units: 1001, ..transfer_req() };
+        match check_degraded_policy("zone-eu", "DEGRADED", Some(&policy), &large).unwrap_err() {
+            AppError::Detailed { status, code, details, .. } => {
+                assert_eq!((status, code), (StatusCode::SERVICE_UNAVAILABLE, "zone_degraded"));
+                assert_eq!(details["reason"], "amount_over_limit");
+                assert_eq!(details["max_amount_units"], 1000);
+            }
+            other => panic!("expected zone_degraded, got {other:?}"),
+        }
+        assert!(shed_ids(&policy).is_empty(), "no shed_percent sheds nothing");
+    }
+
+    #[test]
+    fn degraded_zone_without_policy_accepts_transfers() {
+        let req = CreateTransferRequest { amount_units: i64::MAX, ..transfer_req() };
+        assert!(check_degraded_policy("zone-eu", "DEGRADED", None, &req).is_ok());
+        assert!(check_degraded_policy("zone-eu", "DEGRADED", Some(&json!({})), &req).is_ok());
+        let broken = json!({ "shed_percent": "half" });
+        assert!(matches!(check_degraded_policy("zone-eu", "DEGRADED", Some(&broken), &req), Err(AppError::Internal(_))));
+    }
+
+    #[test]
+    fn idempotency_key_from_header_or_body() {
+        let header = HeaderValue::from_static("key-1");
+        // header only: a body without request_id deserializes and takes the header's key
+        let req: CreateTransferRequest = serde_json::from_value(json!({
+            "from_account": "acct-a", "to_account": "acct-b", "amount_units": 100, "zone_id": "zone-eu"
+        }))
+        .unwrap();
+        assert_eq!(resolve_request_id(req.request_id, Some(&header)).unwrap(), "key-1");
+        // body only
+        assert_eq!(resolve_request_id("req-1".into(), None).unwrap(), "req-1");
+        // both, agreeing
+        assert_eq!(resolve_request_id("key-1".into(), Some(&header)).unwrap(), "key-1");
+        // neither: left empty for validate_transfer to reject as required
+        assert_eq!(resolve_request_id(String::new(), None).unwrap(), "");
+    }
+
+    #[test]
+    fn conflicting_idempotency_key_is_400() {
+        let header = HeaderValue::from_static("key-1");
+        match resolve_request_id("req-1".into(), Some(&header)) {
+            Err(AppError::Detailed { status, code, details, .. }) => {
+                assert_eq!((status, code), (StatusCode::BAD_REQUEST, "idempotency_key_mismatch"));
+                assert_eq!(details, json!({ "request_id": "req-1", "idempotency_key": "key-1" }));
+            }
+            other => panic!("expected mismatch, got {other:?}"),
+        }
+        let opaque = HeaderValue::from_bytes(b"key-\xff").unwrap();
+        assert!(resolve_request_id(String::new(), Some(&opaque)).is_err());
+    }
+
+    #[test]
+    fn header_and_body_keys_hash_the_same() {
+        let from_body = transfer_req();
+        let mut from_header = CreateTransferRequest { request_id: String::new(), ..transfer_req() };
+        from_header.request_id = resolve_request_id(from_header.request_id, Some(&HeaderValue::from_static("req-1"))).unwrap();
+        assert_eq!(payload_hash(&from_body).unwrap(), payload_hash(&from_header).unwrap());
+    }
+
+    #[test]
+    fn dry_run_flag_only_on_dry_runs() {
+        let mut outcome = posted("r1");
+        let TransferOutcome::Applied(r, _) = &outcome else { unreachable!() };
+        assert!(serde_json::to_value(r).unwrap().get("dry_run").is_none());
+        outcome.mark_dry_run();
+        let TransferOutcome::Applied(r, _) = &outcome else { unreachable!() };
+        assert_eq!(serde_json::to_value(r).unwrap()["dry_run"], true);
+    }
+
+    async fn dry_run(st: &AppState, req: CreateTransferRequest) -> Result<serde_json::Value, AppError> {
+        let q = CreateTransferQuery { include_balances: false, dry_run: true };
+        let res = create_transfer(State(st.clone()), Query(q), Default::default(), None, Ok(Json(req))).await?;
+        Ok(serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap())
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn dry_run_leaves_the_database_unchanged() {
+        let st = crate::testing::test_db().await;
+        let run = uuid::Uuid::new_v4();
+        let req = CreateTransferRequest {
+            request_id: format!("req-{run}"),
+            from_account: format!("acct-a-{run}"),
+            to_account: format!("acct-b-{run}"),
+            ..transfer_req()
+        };
+        let (request_id, from) = (req.request_id.clone(), req.from_account.clone());
+        let body = dry_run(&st, req).await.unwrap();
+        assert_eq!(body["status"], "APPLIED");
+        assert_eq!(body["dry_run"], true);
+        assert_eq!((body["from_balance"].as_i64(), body["to_balance"].as_i64()), (Some(-100), Some(100)));
+
+        let client = st.db.get().await.unwrap();
+        let db = &client;
+        let count = move |sql: &'static str, arg: String| async move {
+            db.query_one(sql, &[&arg]).await.unwrap().get::<_, i64>(0)
+        };
+        assert_eq!(count("SELECT COUNT(*) FROM transactions WHERE request_id=$1", request_id.clone()).await, 0);
+        assert_eq!(count("SELECT COUNT(*) FROM outbox_events WHERE payload->>'request_id'=$1", request_id).await, 0);
+        assert_eq!(count("SELECT COUNT(*) FROM accounts WHERE id=$1", from.clone()).await, 0);
+        assert_eq!(count("SELECT COUNT(*) FROM balances WHERE account_id=$1", from).await, 0);
+        assert_eq!(st.metrics.transfers_total.with_label_values(&["zone-eu", "posted"]).get(), 0);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn dry_run_reports_why_a_transfer_would_fail() {
+        let st = crate::testing::test_db().await;
+        let run = uuid::Uuid::new_v4();
+        let zone_id = format!("zone-dry-{run}");
+        st.db
+            .get()
+            .await
+            .unwrap()
+            .execute("INSERT INTO zones(id,name,status) VALUES($1,'Dry run test','DOWN')", &[&zone_id])
+            .await
+            .unwrap();
+        let req = CreateTransferRequest { request_id: format!("req-{run}"), zone_id, ..transfer_req() };
+
+        match dry_run(&st, req).await {
+            Err(AppError::Detailed { status, code, .. }) => {
+                assert_eq!((status, code), (StatusCode::SERVICE_UNAVAILABLE, "zone_down"));
+            }
+            other => panic!("expected zone_down, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn applied_transfer_reaches_event_stream() {
+        use crate::handlers::events::stream_events;
+        let st = crate::app::build_state(crate::config::Config::new("postgres://ledger@primary.invalid/ledger")).await.unwrap();
+        let res = stream_events(State(st.clone()), axum::http::HeaderMap::new()).await.unwrap().into_response();
+        assert_eq!(res.headers()[axum::http::header::CONTENT_TYPE], "text/event-stream");
+        let mut body = res.into_body();
+
+        // create_transfer commits, then finishes the outcome
+        posted("r1").finish(&st, "zone-eu", 100);
+        let frame = tokio::time::timeout(Duration::from_secs(5), body.frame()).await.unwrap().unwrap().unwrap();
+        let chunk = String::from_utf8(frame.into_data().unwrap().to_vec()).unwrap();
+        assert!(chunk.contains("event: TransferPosted"), "{chunk}");
+        assert!(chunk.contains("id: evt-r1"), "{chunk}");
+        assert!(chunk.contains(r#""transaction_id":"txn-r1""#), "{chunk}");
+    }
+
+    #[test]
+    fn oversize_batch_is_413() {
+        assert!(check_batch_size(1000, 1000).is_ok());
+        let err = check_batch_size(1001, 1000).unwrap_err();
+        assert_eq!(err.into_response().status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn idempotency_window() {
+        let created = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let ttl = Some(Duration::from_secs(3600));
+        // in window: a replay is a duplicate (and a hash mismatch is a 409)
+        assert!(within_window(created, created + Duration::from_secs(3599), ttl));
+        // expired: the key may be reused for a new transfer
+        assert!(!within_window(created, created + Duration::from_secs(3600), ttl));
+        assert!(!within_window(created, created + Duration::from_secs(86_400 * 90), ttl));
+        // no TTL: keys never expire
+        assert!(within_window(created, created + Duration::from_secs(86_400 * 365), None));
+    }
+
+    #[test]
+    fn settled_funds_must_cover_debit() {
+        assert!(!insufficient_available(100, 100));
+        assert!(insufficient_available(99, 100));
+        assert!(insufficient_available(-5, 1));
+    }
+
+    #[test]
+    fn cross_zone_account_reuse_is_409() {
+        let accounts = vec![("acct-a".to_string(), "zone-eu".to_string()), ("acct-b".to_string(), "zone-eu".to_string())];
+        let err = check_account_zones("zone-us", &accounts).unwrap_err();
+        assert_eq!(err.status_and_code(), (StatusCode::CONFLICT, "account_zone_mismatch"));
+        assert!(err.message().contains("acct-a belongs to zone zone-eu"));
+    }
+
+    #[test]
+    fn same_zone_accounts_pass() {
+        let accounts = vec![("acct-a".to_string(), "zone-eu".to_string()), ("acct-b".to_string(), "zone-eu".to_string())];
+        assert!(check_account_zones("zone-eu", &accounts).is_ok());
+        assert!(check_account_zones("zone-eu", &[]).is_ok());
+    }
+
+    #[test]
+    fn checked_transfer_detects_overflow() {
+        assert_eq!(checked_transfer(0, 0, 100), Some((-100, 100)));
+        assert_eq!(checked_transfer(0, i64::MAX - 100, 100), Some((-100, i64::MAX)));
+        assert_eq!(checked_transfer(0, i64::MAX - 100, 101), None);
+        assert_eq!(checked_transfer(i64::MIN + 5, 0, 6), None);
+        // i64::MIN itself fits in an i64 but not in balances (balances_units_range)
+        assert_eq!(checked_transfer(i64::MIN + 5, 0, 5), None);
+        assert_eq!(checked_transfer(i64::MIN + 6, 0, 5), Some((i64::MIN + 1, 5)));
+        assert_eq!(checked_balance(0, i64::MIN), None);
+        assert_eq!(checked_balance(-1, -i64::MAX), None);
+    }
+
+    #[test]
+    fn reversal_hash_binds_request_to_target() {
+        let a = reversal_hash("rev-1", "txn-a").unwrap();
+        assert_eq!(a, reversal_hash("rev-1", "txn-a").unwrap());
+        assert_ne!(a, reversal_hash("rev-1", "txn-b").unwrap());
+        assert_ne!(a, reversal_hash("rev-2", "txn-a").unwrap());
+    }
+
+    #[tokio::test]
+    async fn idempotency_conflict_is_409_with_request_id() {
+        let res = idempotency_conflict("req-42", Existing::Transaction("txn-1"), "hash-old", "hash-new").into_response();
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(v["code"], "conflict");
+        assert_eq!(v["details"]["request_id"], "req-42");
+        assert_eq!(v["details"]["reason"], "payload_hash_mismatch");
+        assert!(v["error"].as_str().unwrap().contains("payload hash mismatch"));
+        assert_eq!(v["details"]["existing_transaction_id"], "txn-1");
+        assert_eq!(v["details"]["stored_payload_hash"], "hash-old");
+        assert_eq!(v["details"]["request_payload_hash"], "hash-new");
+    }
+
+    #[test]
+    fn spooled_conflict_names_the_spool_entry() {
+        let err = idempotency_conflict("req-42", Existing::Spooled("spool-7"), "a", "b");
+        let AppError::Detailed { details, .. } = err else { panic!("expected a detailed error") };
+        assert_eq!(details["existing_spool_id"], "spool-7");
+        assert!(details.get("existing_transaction_id").is_none());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn reused_request_id_conflict_reports_both_hashes() {
+        let st = crate::testing::test_db().await;
+        let run = uuid::Uuid::new_v4();
+        let req = |amount_units| CreateTransferRequest {
+            request_id: format!("req-{run}"),
+            from_account: format!("acct-a-{run}"),
+            to_account: format!("acct-b-{run}"),
+            amount_units,
+            ..transfer_req()
+        };
+        let send = |body: CreateTransferRequest| {
+            let q = Query(CreateTransferQuery { include_balances: false, dry_run: false });
+            create_transfer(State(st.clone()), q, Default::default(), None, Ok(Json(body)))
+        };
+
+        let res = send(req(100)).await.unwrap();
+        let created: serde_json::Value = serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        let res = send(req(101)).await.unwrap_err().into_response();
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+        let v: serde_json::Value = serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        assert_eq!(v["details"]["existing_transaction_id"], created["transaction_id"]);
+        assert_eq!(v["details"]["stored_payload_hash"], payload_hash(&req(100)).unwrap().as_str());
+        assert_eq!(v["details"]["request_payload_hash"], payload_hash(&req(101)).unwrap().as_str());
+    }
+
+    /// The TTL is measured on a [`FixedClock`], so the key expires exactly when the test says so.
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn request_id_is_reusable_once_the_ttl_passes_on_the_clock() {
+        use crate::clock::FixedClock;
+        use std::sync::Arc;
+        let mut config = crate::testing::test_db_config();
+        config.idempotency_ttl = Some(Duration::from_secs(60));
+        let mut st = crate::app::build_state(config).await.unwrap();
+        let clock = FixedClock::at(OffsetDateTime::now_utc());
+        st.clock = Arc::new(clock.clone());
+        let run = uuid::Uuid::new_v4();
+        let req = |amount_units| CreateTransferRequest {
+            request_id: format!("req-ttl-{run}"),
+            from_account: format!("acct-a-{run}"),
+            to_account: format!("acct-b-{run}"),
+            amount_units,
+            ..transfer_req()
+        };
+        let send = |body: CreateTransferRequest| {
+            let q = Query(CreateTransferQuery { include_balances: false, dry_run: false });
+            create_transfer(State(st.clone()), q, Default::default(), None, Ok(Json(body)))
+        };
+        let body_of = |res: axum::response::Response| async move {
+            serde_json::from_slice::<serde_json::Value>(&res.into_body().collect().await.unwrap().to_bytes()).unwrap()
+        };
+
+        let first = body_of(send(req(100)).await.unwrap()).await;
+        // the window starts at the row's created_at, which Postgres stamps
+        let created_at = crate::util::parse_rfc3339("created_at", first["created_at"].as_str().unwrap()).unwrap();
+
+        clock.set(created_at + Duration::from_secs(59));
+        let replay = body_of(send(req(100)).await.unwrap()).await;
+        assert_eq!(replay["transaction_id"], first["transaction_id"], "still in the window: a replay");
+        let err = send(req(101)).await.unwrap_err();
+        assert_eq!(err.status_and_code(), (StatusCode::CONFLICT, "conflict"));
+
+        clock.advance(Duration::from_secs(1));
+        let reused = body_of(send(req(101)).await.unwrap()).await;
+        assert_ne!(reused["transaction_id"], first["transaction_id"], "expired: a new transfer");
+        assert_eq!(reused["request_id"], first["request_id"]);
+
+        // both rows keep the key as written; the reuse is the key's next epoch
+        let client = st.db.get().await.unwrap();
+        let rows = client
+            .query(
+                "SELECT id::text, request_epoch FROM transactions WHERE request_id=$1 ORDER BY request_epoch",
+                &[&format!("req-ttl-{run}")],
+            )
+            .await
+            .unwrap();
+        let uses: Vec<(String, i32)> = rows.iter().map(|r| (r.get(0), r.get(1))).collect();
+        let id = |v: &serde_json::Value| v["transaction_id"].as_str().unwrap().to_string();
+        assert_eq!(uses, vec![(id(&first), 0), (id(&reused), 1)]);
+
+        // and the latest use is the one replayed
+        let replay = body_of(send(req(101)).await.unwrap()).await;
+        assert_eq!(replay["transaction_id"], reused["transaction_id"]);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn batch_repeat_within_the_batch_is_a_duplicate_of_the_first_item() {
+        let st = crate::testing::test_db().await;
+        let run = uuid::Uuid::new_v4();
+        let req = |n: u32| CreateTransferRequest {
+            request_id: format!("req-batch-{n}-{run}"),
+            from_account: format!("acct-a-{run}"),
+            to_account: format!("acct-b-{run}"),
+            ..transfer_req()
+        };
+        let batch = BatchTransferRequest { transfers: vec![req(1), req(1), req(2)] };
+        let Json(body) = create_transfer_batch(State(st.clone()), Query(Default::default()), Default::default(), None, Ok(Json(batch))).await.unwrap();
+
+        let results = body["results"].as_array().unwrap();
+        let statuses: Vec<&str> = results.iter().map(|r| r["status"].as_str().unwrap()).collect();
+        assert_eq!(statuses, ["APPLIED", "DUPLICATE", "APPLIED"]);
+        assert_eq!(results[1]["transaction_id"], results[0]["transaction_id"]);
+        assert_ne!(results[2]["transaction_id"], results[0]["transaction_id"]);
+
+        let client = st.db.get().await.unwrap();
+        let balance: i64 = client
+            .query_one("SELECT balance_units FROM balances WHERE account_id=$1", &[&format!("acct-a-{run}")])
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(balance, -200, "the repeat moved nothing");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn batch_item_failure_rolls_back_the_items_before_it() {
+        let st = crate::testing::test_db().await;
+        let run = uuid::Uuid::new_v4();
+        let req = |amount_units| CreateTransferRequest {
+            request_id: format!("req-batch-{run}"),
+            from_account: format!("acct-a-{run}"),
+            to_account: format!("acct-b-{run}"),
+            amount_units,
+            ..transfer_req()
+        };
+        // same request_id, different payload: an idempotency conflict on item 1
+        let batch = BatchTransferRequest { transfers: vec![req(100), req(101)] };
+        let err = create_transfer_batch(State(st.clone()), Query(Default::default()), Default::default(), None, Ok(Json(batch))).await.unwrap_err();
+
+        let AppError::Detailed { status, code, details, .. } = err else { panic!("unexpected error: {err}") };
+        assert_eq!((status, code), (StatusCode::CONFLICT, "batch_rolled_back"));
+        assert_eq!(details["index"], 1);
+        let client = st.db.get().await.unwrap();
+        let kept: i64 = client
+            .query_one("SELECT COUNT(*) FROM transactions WHERE request_id=$1", &[&format!("req-batch-{run}")])
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(kept, 0, "item 0 was rolled back with the batch");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn batch_takes_a_rate_limit_token_per_item() {
+        let st = crate::testing::test_db().await;
+        let run = uuid::Uuid::new_v4();
+        let zone_id = format!("zone-rl-{run}");
+        st.db
+            .get()
+            .await
+            .unwrap()
+            .execute("INSERT INTO zones(id,name,status,rate_limit_per_sec) VALUES($1,'Rate limit test','OK',3)", &[&zone_id])
+            .await
+            .unwrap();
+        let batch = |n: u32, size: u32| BatchTransferRequest {
+            transfers: (0..size)
+                .map(|i| CreateTransferRequest {
+                    request_id: format!("req-rl-{n}-{i}-{run}"),
+                    from_account: format!("acct-a-{run}"),
+                    to_account: format!("acct-b-{run}"),
+                    zone_id: zone_id.clone(),
+                    ..transfer_req()
+                })
+                .collect(),
+        };
+        let send = |b| create_transfer_batch(State(st.clone()), Query(Default::default()), Default::default(), None, Ok(Json(b)));
+
+        let err = send(batch(1, 4)).await.unwrap_err();
+        assert_eq!(err.status_and_code(), (StatusCode::TOO_MANY_REQUESTS, "batch_exceeds_rate_limit"));
+        send(batch(2, 2)).await.unwrap();
+        let err = send(batch(3, 2)).await.unwrap_err();
+        assert!(matches!(err, AppError::TooManyRequests { .. }), "two items, one token left: {err}");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn batch_validate_only_reports_every_item_and_posts_nothing() {
+        let st = crate::testing::test_db().await;
+        let run = uuid::Uuid::new_v4();
+        let item = |n: u32, amount_units: i64, zone_id: &str| CreateTransferRequest {
+            request_id: format!("req-val-{n}-{run}"),
+            from_account: format!("acct-a-{run}"),
+            to_account: format!("acct-b-{run}"),
+            amount_units,
+            zone_id: zone_id.into(),
+            ..transfer_req()
+        };
+        let transfers = vec![
+            item(1, 100, "zone-eu"),
+            item(2, 100, &format!("zone-missing-{run}")),
+            // sees item 1 as already applied
+            item(1, 100, "zone-eu"),
+            item(1, 101, "zone-eu"),
+            item(3, 0, "zone-eu"),
+        ];
+        let q = Query(BatchTransferQuery { validate_only: true });
+        let Json(body) =
+            create_transfer_batch(State(st.clone()), q, Default::default(), None, Ok(Json(BatchTransferRequest { transfers })))
+                .await
+                .unwrap();
+
+        assert_eq!(body["validate_only"], true);
+        assert_eq!(body["would_succeed"], false);
+        let results = body["results"].as_array().unwrap();
+        let summary: Vec<(bool, &str)> = results
+            .iter()
+            .map(|r| (r["would_succeed"].as_bool().unwrap(), r["status"].as_str().or(r["reason"].as_str()).unwrap()))
+            .collect();
+        assert_eq!(
+            summary,
+            [(true, "APPLIED"), (false, "unknown_zone"), (true, "DUPLICATE"), (false, "conflict"), (false, "invalid_transfer")]
+        );
+        assert_eq!(results[1]["request_id"], format!("req-val-2-{run}"));
+        assert!(results[1]["message"].as_str().unwrap().contains("zone not found"));
+
+        let client = st.db.get().await.unwrap();
+        let posted: i64 = client
+            .query_one("SELECT COUNT(*) FROM transactions WHERE request_id LIKE $1", &[&format!("req-val-%-{run}")])
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(posted, 0, "validation rolls everything back");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn batch_replays_and_failed_batches_take_no_tokens() {
+        let st = crate::testing::test_db().await;
+        let run = uuid::Uuid::new_v4();
+        let zone_id = format!("zone-rl-{run}");
+        st.db
+            .get()
+            .await
+            .unwrap()
+            .execute("INSERT INTO zones(id,name,status,rate_limit_per_sec) VALUES($1,'Rate limit test','OK',2)", &[&zone_id])
+            .await
+            .unwrap();
+        let item = |n: u32, amount_units: i64| CreateTransferRequest {
+            request_id: format!("req-rl-{n}-{run}"),
+            from_account: format!("acct-a-{run}"),
+            to_account: format!("acct-b-{run}"),
+            amount_units,
+            zone_id: zone_id.clone(),
+            ..transfer_req()
+        };
+        let send = |transfers| create_transfer_batch(State(st.clone()), Query(Default::default()), Default::default(), None, Ok(Json(BatchTransferRequest { transfers })));
+
+        send(vec![item(1, 100), item(2, 100)]).await.unwrap();
+        // the bucket is empty now: a retry of the same batch replays without tokens
+        let Json(body) = send(vec![item(1, 100), item(2, 100)]).await.unwrap();
+        let statuses: Vec<&str> = body["results"].as_array().unwrap().iter().map(|r| r["status"].as_str().unwrap()).collect();
+        assert_eq!(statuses, ["DUPLICATE", "DUPLICATE"]);
+        // and a batch failing on item 0 is refused for that, not for the rate limit
+        let err = send(vec![item(3, 0), item(4, 100)]).await.unwrap_err();
+        assert_eq!(err.status_and_code(), (StatusCode::BAD_REQUEST, "batch_rolled_back"));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn batch_into_the_ledger_zone_is_unknown_and_takes_no_tokens() {
+        use crate::reconcile::LEDGER_ZONE;
+        let mut config = crate::testing::test_db_config();
+        config.zone_rate_limit = 5;
+        let st = crate::app::build_state(config).await.unwrap();
+        let run = uuid::Uuid::new_v4();
+        let batch = BatchTransferRequest {
+            transfers: vec![CreateTransferRequest {
+                request_id: format!("req-ledger-{run}"),
+                from_account: format!("acct-a-{run}"),
+                to_account: format!("acct-b-{run}"),
+                zone_id: LEDGER_ZONE.into(),
+                ..transfer_req()
+            }],
+        };
+        let err = create_transfer_batch(State(st.clone()), Query(Default::default()), Default::default(), None, Ok(Json(batch))).await.unwrap_err();
+
+        let AppError::Detailed { status, code, details, .. } = err else { panic!("unexpected error: {err}") };
+        assert_eq!((status, code), (StatusCode::NOT_FOUND, "batch_rolled_back"));
+        assert_eq!(details["error"]["code"], "unknown_zone");
+        assert!(!st.zone_buckets.contains_key(LEDGER_ZONE), "no bucket for an internal zone");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn transfer_responses_carry_the_zone_quota() {
+        let st = crate::testing::test_db().await;
+        let run = uuid::Uuid::new_v4();
+        let zone_id = format!("zone-rl-{run}");
+        st.db
+            .get()
+            .await
+            .unwrap()
+            .execute("INSERT INTO zones(id,name,status,rate_limit_per_sec) VALUES($1,'Rate limit test','OK',2)", &[&zone_id])
+            .await
+            .unwrap();
+        let send = |n: u32| {
+            let req = CreateTransferRequest {
+                request_id: format!("req-rl-{n}-{run}"),
+                from_account: format!("acct-a-{run}"),
+                to_account: format!("acct-b-{run}"),
+                zone_id: zone_id.clone(),
+                ..transfer_req()
+            };
+            let q = Query(CreateTransferQuery { include_balances: false, dry_run: false });
+            create_transfer(State(st.clone()), q, Default::default(), None, Ok(Json(req)))
+        };
+        let quota_headers = |res: &axum::response::Response| {
+            ["x-ratelimit-limit", "x-ratelimit-remaining", "x-ratelimit-reset"].map(|h| res.headers()[h].to_str().unwrap().to_string())
+        };
+
+        let res = send(1).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(quota_headers(&res), ["2", "1", "1"]);
+        send(2).await.unwrap();
+        let refused = send(3).await.unwrap();
+        assert_eq!(refused.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(quota_headers(&refused)[..2], ["2", "0"]);
+        assert!(refused.headers().contains_key("retry-after"));
+    }
+
+    /// A transfer of 100 from `acct-a-{run}` to `acct-b-{run}` in `zone_id`; returns its id.
+    async fn posted_transfer(st: &AppState, run: uuid::Uuid, zone_id: &str) -> String {
+        let req = CreateTransferRequest {
+            request_id: format!("req-{run}"),
+            from_account: format!("acct-a-{run}"),
+            to_account: format!("acct-b-{run}"),
+            zone_id: zone_id.into(),
+            ..transfer_req()
+        };
+        let q = Query(CreateTransferQuery { include_balances: false, dry_run: false });
+        let res = create_transfer(State(st.clone()), q, Default::default(), None, Ok(Json(req))).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        body["transaction_id"].as_str().unwrap().to_string()
+    }
+
+    fn reverse(st: &AppState, txn_id: &str, request_id: String) -> impl std::future::Future<Output = Result<Json<TransferResponse>, AppError>> {
+        let req = ReverseRequest { request_id, actor: "ops".into(), reason: "customer dispute".into() };
+        reverse_transaction(State(st.clone()), Path(txn_id.to_string()), HeaderMap::new(), None, Json(req))
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn reversal_restores_balances_once() {
+        let st = crate::testing::test_db().await;
+        let run = uuid::Uuid::new_v4();
+        let original = posted_transfer(&st, run, "zone-eu").await;
+        let client = st.db.get().await.unwrap();
+        let balances = || {
+            let client = &client;
+            async move {
+                let mut out = Vec::new();
+                for account in [format!("acct-a-{run}"), format!("acct-b-{run}")] {
+                    let row = client.query_one("SELECT balance_units FROM balances WHERE account_id=$1", &[&account]).await.unwrap();
+                    out.push(row.get::<_, i64>(0));
+                }
+                out
+            }
+        };
+        assert_eq!(balances().await, [-100, 100]);
+
+        let Json(reversal) = reverse(&st, &original, format!("rev-{run}")).await.unwrap();
+        assert_ne!(reversal.transaction_id, original);
+        assert_eq!(balances().await, [0, 0]);
+        let reverses: Option<String> = client
+            .query_one("SELECT reverses_txn_id::text FROM transactions WHERE id=$1::text::uuid", &[&reversal.transaction_id])
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(reverses.as_deref(), Some(original.as_str()));
+
+        let Json(replay) = reverse(&st, &original, format!("rev-{run}")).await.unwrap();
+        assert_eq!(replay.transaction_id, reversal.transaction_id, "same request_id: a replay");
+        let Json(upper) = reverse(&st, &original.to_uppercase(), format!("rev-{run}")).await.unwrap();
+        assert_eq!(upper.transaction_id, reversal.transaction_id, "the id is matched as a UUID, not as text");
+        let err = reverse(&st, "not-a-uuid", format!("rev-bad-{run}")).await.unwrap_err();
+        assert_eq!(err.status_and_code().0, StatusCode::NOT_FOUND);
+
+        let err = reverse(&st, &original, format!("rev-again-{run}")).await.unwrap_err();
+        let AppError::Detailed { status, details, .. } = err else { panic!("unexpected error: {err}") };
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(details["reversal_transaction_id"], reversal.transaction_id.as_str());
+        assert_eq!(balances().await, [0, 0], "the second reversal moved nothing");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn reversal_goes_through_while_the_zone_is_down() {
+        let st = crate::testing::test_db().await;
+        let run = uuid::Uuid::new_v4();
+        let zone_id = format!("zone-rev-{run}");
+        let client = st.db.get().await.unwrap();
+        client
+            .execute("INSERT INTO zones(id,name,status) VALUES($1,'Reversal test','OK')", &[&zone_id])
+            .await
+            .unwrap();
+        let original = posted_transfer(&st, run, &zone_id).await;
+        client.execute("UPDATE zones SET status='DOWN' WHERE id=$1", &[&zone_id]).await.unwrap();
+
+        reverse(&st, &original, format!("rev-{run}")).await.expect("an operator override ignores the zone status");
+    }
 }
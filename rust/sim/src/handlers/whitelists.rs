@@ -0,0 +1,218 @@
+use axum::{extract::{Path, State}, http::{HeaderMap, StatusCode}, Json};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use utoipa::ToSchema;
+
+use crate::error::{AppError, ErrorBody};
+use crate::handlers::admin::admin_guard;
+use crate::handlers::audit::publish_audit;
+use crate::state::AppState;
+
+#[derive(Serialize, ToSchema)]
+pub struct AccountWhitelist {
+    pub account_id: String,
+    /// Empty means unrestricted.
+    pub counterparties: Vec<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SetWhitelistRequest {
+    pub counterparties: Vec<String>,
+    #[serde(default)]
+    pub actor: String,
+    #[serde(default)]
+    pub reason: String,
+}
+
+/// Whether `account` may transact with `counterparty` under its whitelist.
+/// No whitelist or an empty one allows everyone.
+pub fn counterparty_allowed(whitelist: Option<&[String]>, counterparty: &str) -> bool {
+    match whitelist {
+        Some(list) if !list.is_empty() => list.iter().any(|c| c == counterparty),
+        _ => true,
+    }
+}
+
+/// Enforce both sides' whitelists: the sender must allow the receiver and vice versa.
+pub fn check_whitelists(
+    from_account: &str,
+    to_account: &str,
+    from_whitelist: Option<&[String]>,
+    to_whitelist: Option<&[String]>,
+) -> Result<(), AppError> {
+    let violation = if !counterparty_allowed(from_whitelist, to_account) {
+        Some((from_account, to_account))
+    } else if !counterparty_allowed(to_whitelist, from_account) {
+        Some((to_account, from_account))
+    } else {
+        None
+    };
+    match violation {
+        Some((account, counterparty)) => Err(AppError::Detailed {
+            status: StatusCode::FORBIDDEN,
+            code: "counterparty_not_whitelisted",
+            message: format!("account {account} may not transact with {counterparty}"),
+            details: json!({ "account_id": account, "counterparty": counterparty }),
+        }),
+        None => Ok(()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/accounts/{account_id}/whitelist",
+    tag = "accounts",
+    params(("account_id" = String, Path, description = "Account id"), ("x-admin-key" = String, Header, description = "One of the comma-separated ADMIN_KEY values")),
+    responses(
+        (status = 200, description = "Counterparty whitelist; empty means unrestricted", body = AccountWhitelist),
+        (status = 400, description = "Invalid input", body = ErrorBody),
+        (status = 403, description = "Missing or wrong admin key", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn get_whitelist(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(account_id): Path<String>,
+) -> Result<Json<AccountWhitelist>, AppError> {
+    admin_guard(&st, &headers)?;
+    let client = st.db.get().await?;
+    let counterparties = client
+        .query_opt("SELECT counterparties FROM account_whitelists WHERE account_id=$1", &[&account_id])
+        .await?
+        .map(|r| r.get::<_, Vec<String>>(0))
+        .unwrap_or_default();
+    Ok(Json(AccountWhitelist { account_id, counterparties }))
+}
+
+#[utoipa::path(
+    put,
+    path = "/v1/accounts/{account_id}/whitelist",
+    tag = "accounts",
+    params(("account_id" = String, Path, description = "Account id"), ("x-admin-key" = String, Header, description = "One of the comma-separated ADMIN_KEY values")),
+    request_body = SetWhitelistRequest,
+    responses(
+        (status = 200, description = "Whitelist replaced", body = AccountWhitelist),
+        (status = 400, description = "Invalid input", body = ErrorBody),
+        (status = 403, description = "Missing or wrong admin key", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn set_whitelist(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(account_id): Path<String>,
+    Json(req): Json<SetWhitelistRequest>,
+) -> Result<Json<AccountWhitelist>, AppError> {
+    admin_guard(&st, &headers)?;
+    if req.actor.is_empty() {
+        return Err(AppError::BadRequest("actor required".into()));
+    }
+    let mut counterparties: Vec<String> = req
+        .counterparties
+        .iter()
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty())
+        .collect();
+    counterparties.sort();
+    counterparties.dedup();
+
+    let mut client = st.db.get().await?;
+    let tx = client.transaction().await?;
+    tx.execute(
+        "INSERT INTO account_whitelists(account_id,counterparties) VALUES($1,$2) \
+         ON CONFLICT (account_id) DO UPDATE SET counterparties=EXCLUDED.counterparties, updated_at=now()",
+        &[&account_id, &counterparties],
+    )
+    .await?;
+    let audit = tx
+        .query_one(
+            "INSERT INTO audit_log(actor,action,target_type,target_id,reason,details) VALUES($1,'SET_ACCOUNT_WHITELIST','account',$2,$3, jsonb_build_object('counterparties',$4::text[])) \
+             RETURNING id::text, actor, action, target_type, target_id, reason, details, created_at",
+            &[&req.actor, &account_id, &req.reason, &counterparties],
+        )
+        .await?;
+    tx.commit().await?;
+    publish_audit(&st, &audit);
+
+    Ok(Json(AccountWhitelist { account_id, counterparties }))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ClearWhitelistRequest {
+    #[serde(default)]
+    pub actor: String,
+    #[serde(default)]
+    pub reason: String,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/accounts/{account_id}/whitelist",
+    tag = "accounts",
+    params(("account_id" = String, Path, description = "Account id"), ("x-admin-key" = String, Header, description = "One of the comma-separated ADMIN_KEY values")),
+    request_body = ClearWhitelistRequest,
+    responses(
+        (status = 200, description = "Whitelist removed", body = AccountWhitelist),
+        (status = 400, description = "Invalid input", body = ErrorBody),
+        (status = 403, description = "Missing or wrong admin key", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn clear_whitelist(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(account_id): Path<String>,
+    Json(req): Json<ClearWhitelistRequest>,
+) -> Result<Json<AccountWhitelist>, AppError> {
+    admin_guard(&st, &headers)?;
+    if req.actor.is_empty() {
+        return Err(AppError::BadRequest("actor required".into()));
+    }
+    let mut client = st.db.get().await?;
+    let tx = client.transaction().await?;
+    tx.execute("DELETE FROM account_whitelists WHERE account_id=$1", &[&account_id]).await?;
+    let audit = tx
+        .query_one(
+            "INSERT INTO audit_log(actor,action,target_type,target_id,reason) VALUES($1,'CLEAR_ACCOUNT_WHITELIST','account',$2,$3) \
+             RETURNING id::text, actor, action, target_type, target_id, reason, details, created_at",
+            &[&req.actor, &account_id, &req.reason],
+        )
+        .await?;
+    tx.commit().await?;
+    publish_audit(&st, &audit);
+
+    Ok(Json(AccountWhitelist { account_id, counterparties: Vec::new() }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn missing_or_empty_whitelist_is_unrestricted() {
+        assert!(counterparty_allowed(None, "anyone"));
+        assert!(counterparty_allowed(Some(&[][..]), "anyone"));
+    }
+
+    #[test]
+    fn listed_counterparty_only() {
+        let wl = list(&["acct-b", "acct-c"]);
+        assert!(counterparty_allowed(Some(wl.as_slice()), "acct-b"));
+        assert!(!counterparty_allowed(Some(wl.as_slice()), "acct-z"));
+    }
+
+    #[test]
+    fn either_side_can_reject() {
+        let closed = list(&["acct-b"]);
+        assert!(check_whitelists("acct-a", "acct-b", Some(closed.as_slice()), None).is_ok());
+        assert!(check_whitelists("acct-a", "acct-z", Some(closed.as_slice()), None).is_err());
+        // receiver only accepts from acct-b
+        let err = check_whitelists("acct-a", "acct-r", None, Some(closed.as_slice())).unwrap_err();
+        assert_eq!(err.status_and_code(), (StatusCode::FORBIDDEN, "counterparty_not_whitelisted"));
+    }
+}
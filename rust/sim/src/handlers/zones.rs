@@ -1,95 +1,1311 @@
-use axum::{extract::{Path, State}, http::StatusCode, Json};
+use axum::{
+    extract::{
+        rejection::JsonRejection,
+        ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+        Path, Query, State,
+    },
+    http::StatusCode,
+    response::Response,
+    Json,
+};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, warn};
+use utoipa::{IntoParams, ToSchema};
 
+use crate::error::{AppError, ErrorBody};
+use crate::handlers::audit::publish_audit;
+use crate::handlers::events::{insert_outbox_event, publish_event, LedgerEvent};
+use crate::handlers::incidents::open_incident;
 use crate::state::AppState;
-use crate::util::fmt_rfc3339;
+use crate::util::{parse_rfc3339, to_rfc3339};
+use crate::ZoneStatus;
 
-#[derive(Serialize)]
-struct Zone {
+/// Map a missing zone lookup to `404 unknown_zone`, keeping DB errors as 500 via `?`.
+pub fn require_zone<T>(found: Option<T>, zone_id: &str) -> Result<T, AppError> {
+    found.ok_or_else(|| AppError::Detailed {
+        status: StatusCode::NOT_FOUND,
+        code: "unknown_zone",
+        message: format!("zone not found: {zone_id}"),
+        details: json!({ "zone_id": zone_id }),
+    })
+}
+
+/// `column` of a zones row as a [`ZoneStatus`]. The column is CHECK-constrained,
+/// so anything else was written around it: logged and a 500, never passed on.
+pub(crate) fn zone_status(r: &tokio_postgres::Row, column: &str) -> Result<ZoneStatus, AppError> {
+    r.try_get(column).map_err(|e| {
+        error!(error = %e, column, "zone with unknown status");
+        AppError::Internal("zone has an unknown status".into())
+    })
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Zone {
     id: String,
     name: String,
-    status: String,
+    description: Option<String>,
+    /// OK, DEGRADED or DOWN.
+    #[schema(value_type = String, example = "OK")]
+    status: ZoneStatus,
     updated_at: String,
 }
 
-pub async fn list_zones(State(st): State<AppState>) -> Result<Json<serde_json::Value>, StatusCode> {
-    let client = st.db.get().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+impl Zone {
+    /// From a row with `id`, `name`, `description`, `status` and `updated_at`.
+    fn from_row(r: &tokio_postgres::Row) -> Result<Self, AppError> {
+        let updated_at: time::OffsetDateTime = r.get("updated_at");
+        Ok(Self {
+            id: r.get("id"),
+            name: r.get("name"),
+            description: r.get("description"),
+            status: zone_status(r, "status")?,
+            updated_at: to_rfc3339(updated_at)?,
+        })
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ZoneList {
+    zones: Vec<Zone>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/zones",
+    tag = "zones",
+    responses(
+        (status = 200, description = "All zones", body = ZoneList),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn list_zones(State(st): State<AppState>) -> Result<Json<ZoneList>, AppError> {
+    let client = st.db_read.get().await?;
     let rows = client
-        .query("SELECT id,name,status,updated_at FROM zones ORDER BY id", &[])
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .query("SELECT id,name,description,status,updated_at FROM zones WHERE NOT internal ORDER BY id", &[])
+        .await?;
 
-    let zones: Vec<Zone> = rows
-        .into_iter()
-        .map(|r| {
-            let updated_at: time::OffsetDateTime = r.get("updated_at");
-            Zone {
-                id: r.get("id"),
-                name: r.get("name"),
-                status: r.get("status"),
-                updated_at: fmt_rfc3339(updated_at),
-            }
-        })
-        .collect();
+    let zones: Vec<Zone> = rows.iter().map(Zone::from_row).collect::<Result<_, _>>()?;
 
-    Ok(Json(json!({ "zones": zones })))
+    Ok(Json(ZoneList { zones }))
 }
 
-#[derive(Deserialize)]
-pub struct SetZoneStatusRequest {
-    status: String,
+#[derive(Serialize, ToSchema)]
+pub struct ZoneDetail {
+    #[serde(flatten)]
+    zone: Zone,
+    account_count: i64,
+    transactions_today: i64,
+    open_incidents: i64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/zones/{zone_id}",
+    tag = "zones",
+    params(("zone_id" = String, Path, description = "Zone id")),
+    responses(
+        (status = 200, description = "Zone with activity counts", body = ZoneDetail),
+        (status = 404, description = "unknown_zone", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn get_zone(
+    State(st): State<AppState>,
+    Path(zone_id): Path<String>,
+) -> Result<Json<ZoneDetail>, AppError> {
+    let client = st.db_read.get().await?;
+    let row = client
+        .query_opt(
+            "SELECT z.id, z.name, z.description, z.status, z.updated_at, \
+             (SELECT COUNT(*) FROM accounts a WHERE a.zone_id=z.id) AS account_count, \
+             (SELECT COUNT(*) FROM transactions t WHERE t.zone_id=z.id \
+              AND t.created_at >= date_trunc('day', now() AT TIME ZONE 'UTC') AT TIME ZONE 'UTC') AS transactions_today, \
+             (SELECT COUNT(*) FROM incidents i WHERE i.zone_id=z.id AND i.status <> 'RESOLVED') AS open_incidents \
+             FROM zones z WHERE z.id=$1 AND NOT z.internal",
+            &[&zone_id],
+        )
+        .await?;
+    let r = require_zone(row, &zone_id)?;
+
+    Ok(Json(ZoneDetail {
+        zone: Zone::from_row(&r)?,
+        account_count: r.get("account_count"),
+        transactions_today: r.get("transactions_today"),
+        open_incidents: r.get("open_incidents"),
+    }))
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateZoneRequest {
+    /// New display name; omitted keeps the current one.
+    #[serde(default)]
+    name: Option<String>,
+    /// New description; omitted keeps the current one, null clears it.
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<String>, nullable)]
+    description: Option<Option<String>>,
+    /// Required when the request changes anything.
+    #[serde(default)]
     actor: String,
     #[serde(default)]
     reason: String,
 }
 
-pub async fn set_zone_status(
+/// Tells a field sent as `null` (`Some(None)`) from one left out (`None`, via `default`).
+fn present<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Option<Option<String>>, D::Error> {
+    Option::<String>::deserialize(d).map(Some)
+}
+
+impl UpdateZoneRequest {
+    /// Whether the request changes anything; a change needs an actor and a non-blank name.
+    fn changes(&self) -> Result<bool, AppError> {
+        if self.name.is_none() && self.description.is_none() {
+            return Ok(false);
+        }
+        if self.name.as_deref().is_some_and(|n| n.trim().is_empty()) {
+            return Err(AppError::BadRequest("name must not be blank".into()));
+        }
+        if self.actor.is_empty() {
+            return Err(AppError::BadRequest("actor required".into()));
+        }
+        Ok(true)
+    }
+
+    /// Audit details: just the fields being changed, with their new values.
+    fn audit_details(&self) -> serde_json::Value {
+        let mut details = serde_json::Map::new();
+        if let Some(name) = &self.name {
+            details.insert("name".into(), json!(name));
+        }
+        if let Some(description) = &self.description {
+            details.insert("description".into(), json!(description));
+        }
+        details.into()
+    }
+}
+
+/// Change a zone's `name` and/or `description`; fields left out of the body are
+/// untouched, and `"description": null` clears it. An empty body changes nothing
+/// and returns the zone as it is. Status has its own endpoint.
+#[utoipa::path(
+    patch,
+    path = "/v1/zones/{zone_id}",
+    tag = "zones",
+    params(("zone_id" = String, Path, description = "Zone id")),
+    request_body = UpdateZoneRequest,
+    responses(
+        (status = 200, description = "The zone after the update", body = Zone),
+        (status = 400, description = "Malformed body or unknown field, blank name or missing actor", body = ErrorBody),
+        (status = 404, description = "unknown_zone", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn update_zone(
     State(st): State<AppState>,
     Path(zone_id): Path<String>,
-    Json(req): Json<SetZoneStatusRequest>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    if req.actor.is_empty() {
-        return Err(StatusCode::BAD_REQUEST);
-    }
-    if req.status != "OK" && req.status != "DEGRADED" && req.status != "DOWN" {
-        return Err(StatusCode::BAD_REQUEST);
+    body: Result<Json<UpdateZoneRequest>, JsonRejection>,
+) -> Result<Json<Zone>, AppError> {
+    let Json(req) = body?;
+    let mut client = st.db.get().await?;
+    if !req.changes()? {
+        let row = client
+            .query_opt("SELECT id,name,description,status,updated_at FROM zones WHERE id=$1 AND NOT internal", &[&zone_id])
+            .await?;
+        return Ok(Json(Zone::from_row(&require_zone(row, &zone_id)?)?));
     }
-    let mut client = st.db.get().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    let tx = client.transaction().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    let tx = client.transaction().await?;
+    let set_description = req.description.is_some();
+    let description = req.description.clone().flatten();
     let row = tx
+        .query_opt(
+            "UPDATE zones SET name=COALESCE($2,name), description=CASE WHEN $3 THEN $4 ELSE description END, updated_at=now() \
+             WHERE id=$1 AND NOT internal RETURNING id,name,description,status,updated_at",
+            &[&zone_id, &req.name, &set_description, &description],
+        )
+        .await?;
+    let row = require_zone(row, &zone_id)?;
+    let audit = tx
         .query_one(
-            "UPDATE zones SET status=$2, updated_at=now() WHERE id=$1 RETURNING id,name,status,updated_at",
-            &[&zone_id, &req.status],
+            "INSERT INTO audit_log(actor,action,target_type,target_id,reason,details) VALUES($1,'UPDATE_ZONE','zone',$2,$3,$4) RETURNING id::text, actor, action, target_type, target_id, reason, details, created_at",
+            &[&req.actor, &zone_id, &req.reason, &req.audit_details()],
         )
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .await?;
+    tx.commit().await?;
+    publish_audit(&st, &audit);
 
-    tx.execute(
-        "INSERT INTO audit_log(actor,action,target_type,target_id,reason,details) VALUES($1,'SET_ZONE_STATUS','zone',$2,$3, jsonb_build_object('status',$4))",
-        &[&req.actor, &zone_id, &req.reason, &req.status],
-    )
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(Zone::from_row(&row)?))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StatusAsOfQuery {
+    pub as_of: Option<String>,
+}
+
+/// Status in effect at a point in time, from the last transition at or before it.
+/// With no transition before, the current status applies only if it has never
+/// changed since (it is the zone's initial status); otherwise it is unknown.
+pub(crate) fn status_at(
+    last_before: Option<(String, time::OffsetDateTime)>,
+    changed_after: bool,
+    current: String,
+) -> Option<(String, Option<time::OffsetDateTime>)> {
+    match last_before {
+        Some((status, set_at)) => Some((status, Some(set_at))),
+        None if !changed_after => Some((current, None)),
+        None => None,
+    }
+}
 
-    if req.status == "DOWN" {
-        tx.execute(
-            "INSERT INTO incidents(zone_id,severity,title,details) VALUES($1,'CRITICAL','Zone marked DOWN', jsonb_build_object('reason',$2,'actor',$3))",
-            &[&zone_id, &req.reason, &req.actor],
+/// Zone status at `as_of` (default now), reconstructed from the SET_ZONE_STATUS
+/// transitions recorded in the audit log.
+#[utoipa::path(
+    get,
+    path = "/v1/zones/{zone_id}/status",
+    tag = "zones",
+    params(("zone_id" = String, Path, description = "Zone id"), StatusAsOfQuery),
+    responses(
+        (status = 200, description = "Current status, or the status at as_of", body = serde_json::Value),
+        (status = 400, description = "Invalid as_of", body = ErrorBody),
+        (status = 404, description = "Zone not found", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn get_zone_status(
+    State(st): State<AppState>,
+    Path(zone_id): Path<String>,
+    Query(q): Query<StatusAsOfQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let as_of = match q.as_of.as_deref() {
+        Some(s) => parse_rfc3339("as_of", s)?,
+        None => st.clock.now(),
+    };
+    let client = st.db_read.get().await?;
+    let row = client
+        .query_opt(
+            "SELECT z.status, \
+             (SELECT a.details->>'status' FROM audit_log a WHERE a.action='SET_ZONE_STATUS' AND a.target_id=z.id AND a.created_at <= $2 ORDER BY a.created_at DESC LIMIT 1) AS status_before, \
+             (SELECT max(a.created_at) FROM audit_log a WHERE a.action='SET_ZONE_STATUS' AND a.target_id=z.id AND a.created_at <= $2) AS set_at, \
+             EXISTS (SELECT 1 FROM audit_log a WHERE a.action='SET_ZONE_STATUS' AND a.target_id=z.id AND a.created_at > $2) AS changed_after \
+             FROM zones z WHERE z.id=$1 AND NOT z.internal",
+            &[&zone_id, &as_of],
         )
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .await?;
+    let row = require_zone(row, &zone_id)?;
+
+    let last_before = row
+        .get::<_, Option<String>>("status_before")
+        .zip(row.get::<_, Option<time::OffsetDateTime>>("set_at"));
+    let as_of_text = to_rfc3339(as_of)?;
+    let (status, set_at) = status_at(last_before, row.get("changed_after"), row.get("status"))
+        .ok_or_else(|| AppError::NotFound(format!("no recorded status for {zone_id} at {as_of_text}")))?;
+
+    Ok(Json(json!({
+        "zone_id": zone_id,
+        "as_of": as_of_text,
+        "status": status,
+        "set_at": set_at.map(to_rfc3339).transpose()?,
+    })))
+}
+
+/// Title of the incident opened when a zone goes DOWN. Only incidents with this
+/// title are auto-resolved, so manually created ones stay open.
+const ZONE_DOWN_INCIDENT_TITLE: &str = "Zone marked DOWN";
+
+#[derive(Debug, PartialEq, Eq)]
+enum IncidentEffect {
+    OpenZoneDown,
+    ResolveZoneDown,
+}
+
+/// Incident bookkeeping for a zone moving to `new_status`.
+fn incident_effect(new_status: ZoneStatus) -> Option<IncidentEffect> {
+    match new_status {
+        ZoneStatus::Down => Some(IncidentEffect::OpenZoneDown),
+        ZoneStatus::Ok => Some(IncidentEffect::ResolveZoneDown),
+        ZoneStatus::Degraded => None,
     }
+}
 
-    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+/// Outbox payload for a zone status transition.
+fn zone_status_event(
+    zone_id: &str,
+    previous_status: ZoneStatus,
+    req: &SetZoneStatusRequest,
+    changed_at: time::OffsetDateTime,
+) -> Result<serde_json::Value, AppError> {
+    Ok(json!({
+        "event_id": "generated_by_db",
+        "type": "ZoneStatusChanged",
+        "zone_id": zone_id,
+        "previous_status": previous_status,
+        "status": req.status,
+        "actor": req.actor,
+        "reason": req.reason,
+        "changed_at": to_rfc3339(changed_at)?,
+    }))
+}
 
+/// Which zone status changes `set_zone_status` accepts (`ZONE_STATUS_TRANSITIONS`).
+///
+/// `Permissive` allows any change. `Strict` makes a zone recover through
+/// DEGRADED: a DOWN zone may only move to DEGRADED. Setting the status a zone
+/// already has is allowed under both.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TransitionPolicy {
+    #[default]
+    Permissive,
+    Strict,
+}
+
+impl TransitionPolicy {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "permissive" => Some(Self::Permissive),
+            "strict" => Some(Self::Strict),
+            _ => None,
+        }
+    }
+
+    /// Statuses a zone in `from` may move to, other than `from` itself.
+    pub fn allowed_next(self, from: ZoneStatus) -> &'static [ZoneStatus] {
+        use ZoneStatus::{Degraded, Down, Ok};
+        match (self, from) {
+            (_, Ok) => &[Degraded, Down],
+            (_, Degraded) => &[Ok, Down],
+            (Self::Permissive, Down) => &[Ok, Degraded],
+            (Self::Strict, Down) => &[Degraded],
+        }
+    }
+
+    /// 409 `illegal_zone_transition`, listing the allowed next statuses, unless
+    /// `zone_id` may move from `from` to `to`.
+    fn check(self, zone_id: &str, from: ZoneStatus, to: ZoneStatus) -> Result<(), AppError> {
+        let allowed = self.allowed_next(from);
+        if from == to || allowed.contains(&to) {
+            return Ok(());
+        }
+        Err(AppError::Detailed {
+            status: StatusCode::CONFLICT,
+            code: "illegal_zone_transition",
+            message: format!("zone {zone_id} cannot go from {} to {}", from.as_str(), to.as_str()),
+            details: json!({ "zone_id": zone_id, "from": from, "to": to, "allowed": allowed }),
+        })
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SetZoneStatusRequest {
+    /// OK, DEGRADED or DOWN; anything else is rejected with the body.
+    #[schema(value_type = String, example = "DOWN")]
+    status: ZoneStatus,
+    actor: String,
+    #[serde(default)]
+    reason: String,
+}
+
+impl SetZoneStatusRequest {
+    /// A change made by the service itself rather than an operator.
+    pub(crate) fn system(status: ZoneStatus, reason: String) -> Self {
+        Self { status, actor: "system".into(), reason }
+    }
+
+    fn validate(&self) -> Result<(), AppError> {
+        if self.actor.is_empty() {
+            return Err(AppError::BadRequest("actor required".into()));
+        }
+        Ok(())
+    }
+}
+
+/// Response body for a status change: the zone as updated.
+fn zone_status_json(row: &tokio_postgres::Row) -> Result<serde_json::Value, AppError> {
     let id: String = row.get("id");
     let name: String = row.get("name");
-    let status: String = row.get("status");
+    let status = zone_status(row, "status")?;
     let updated_at: time::OffsetDateTime = row.get("updated_at");
-    Ok(Json(json!({
+    Ok(json!({
         "id": id, "name": name, "status": status,
-        "updated_at": fmt_rfc3339(updated_at)
-    })))
+        "updated_at": to_rfc3339(updated_at)?
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/zones/{zone_id}/status",
+    tag = "zones",
+    params(("zone_id" = String, Path, description = "Zone id")),
+    request_body = SetZoneStatusRequest,
+    responses(
+        (status = 200, description = "Status updated", body = serde_json::Value),
+        (status = 400, description = "Malformed body or unknown field, invalid status or missing actor", body = ErrorBody),
+        (status = 401, description = "With JWT verification enabled: missing_token, invalid_token or token_expired", body = ErrorBody),
+        (status = 403, description = "With JWT verification enabled: token_not_accepted (wrong audience or issuer)", body = ErrorBody),
+        (status = 404, description = "unknown_zone", body = ErrorBody),
+        (status = 409, description = "illegal_zone_transition under ZONE_STATUS_TRANSITIONS=strict; details.allowed lists the permitted next statuses", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn set_zone_status(
+    State(st): State<AppState>,
+    Path(zone_id): Path<String>,
+    body: Result<Json<SetZoneStatusRequest>, JsonRejection>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let Json(req) = body?;
+    req.validate()?;
+    let mut client = st.db.get().await?;
+    let tx = client.transaction().await?;
+    let change = change_zone_status(&tx, &zone_id, &req, st.zone_transitions).await?;
+    tx.commit().await?;
+    let row = change.publish(&st);
+
+    Ok(Json(zone_status_json(&row)?))
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct BulkZoneStatusRequest {
+    /// Zones to change; duplicates are ignored.
+    zone_ids: Vec<String>,
+    #[schema(value_type = String, example = "DOWN")]
+    status: ZoneStatus,
+    actor: String,
+    #[serde(default)]
+    reason: String,
+}
+
+/// Requested ids not among `found`, sorted and without duplicates.
+fn missing_zone_ids(requested: &[String], found: &[String]) -> Vec<String> {
+    let found: std::collections::HashSet<&str> = found.iter().map(String::as_str).collect();
+    let missing: std::collections::BTreeSet<&String> = requested.iter().filter(|id| !found.contains(id.as_str())).collect();
+    missing.into_iter().cloned().collect()
+}
+
+/// Set several zones to one status in a single transaction, e.g. a regional outage.
+/// Each zone gets the same audit entry, outbox event and incident handling as
+/// `POST /v1/zones/{zone_id}/status`. If any id is unknown nothing changes.
+#[utoipa::path(
+    post,
+    path = "/v1/zones/status",
+    tag = "zones",
+    request_body = BulkZoneStatusRequest,
+    responses(
+        (status = 200, description = "All zones updated; `zones` lists them in id order", body = serde_json::Value),
+        (status = 400, description = "Malformed body or unknown field, invalid status, missing actor or zone_ids, or unknown_zones listing the missing ids", body = ErrorBody),
+        (status = 401, description = "With JWT verification enabled: missing_token, invalid_token or token_expired", body = ErrorBody),
+        (status = 403, description = "With JWT verification enabled: token_not_accepted (wrong audience or issuer)", body = ErrorBody),
+        (status = 409, description = "illegal_zone_transition for one of the zones; nothing changes", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn set_zones_status(
+    State(st): State<AppState>,
+    body: Result<Json<BulkZoneStatusRequest>, JsonRejection>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let Json(bulk) = body?;
+    let req = SetZoneStatusRequest { status: bulk.status, actor: bulk.actor, reason: bulk.reason };
+    req.validate()?;
+    if bulk.zone_ids.is_empty() {
+        return Err(AppError::BadRequest("zone_ids required".into()));
+    }
+    // lock in id order so concurrent bulk updates cannot deadlock
+    let zone_ids: Vec<String> = bulk.zone_ids.iter().collect::<std::collections::BTreeSet<_>>().into_iter().cloned().collect();
+
+    let mut client = st.db.get().await?;
+    let tx = client.transaction().await?;
+    let found: Vec<String> = tx
+        .query("SELECT id FROM zones WHERE id = ANY($1) AND NOT internal ORDER BY id FOR UPDATE", &[&zone_ids])
+        .await?
+        .iter()
+        .map(|r| r.get(0))
+        .collect();
+    let missing = missing_zone_ids(&zone_ids, &found);
+    if !missing.is_empty() {
+        // dropping tx rolls it back
+        return Err(AppError::Detailed {
+            status: StatusCode::BAD_REQUEST,
+            code: "unknown_zones",
+            message: format!("zones not found: {}", missing.join(", ")),
+            details: json!({ "zone_ids": missing }),
+        });
+    }
+
+    let mut changes = Vec::with_capacity(zone_ids.len());
+    for zone_id in &zone_ids {
+        changes.push(change_zone_status(&tx, zone_id, &req, st.zone_transitions).await?);
+    }
+    tx.commit().await?;
+
+    let zones = changes
+        .into_iter()
+        .map(|change| zone_status_json(&change.publish(&st)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Json(json!({ "zones": zones })))
+}
+
+/// A zone status change written inside a transaction, published once it commits.
+pub(crate) struct ZoneStatusChange {
+    row: tokio_postgres::Row,
+    audits: Vec<tokio_postgres::Row>,
+    event: LedgerEvent,
+}
+
+impl ZoneStatusChange {
+    /// Push the audit entries and the `ZoneStatusChanged` event to live subscribers;
+    /// call only after commit. Returns the updated zone row.
+    pub(crate) fn publish(self, st: &AppState) -> tokio_postgres::Row {
+        for audit in &self.audits {
+            publish_audit(st, audit);
+        }
+        publish_event(st, self.event);
+        self.row
+    }
+}
+
+/// Set `zone_id` to `req.status` within `tx`, with the SET_ZONE_STATUS audit entry,
+/// outbox event and DOWN incident bookkeeping every transition gets, whether made
+/// by an operator or a maintenance window. `req` must already be validated; the
+/// change itself is checked against `policy`.
+pub(crate) async fn change_zone_status(
+    tx: &deadpool_postgres::Transaction<'_>,
+    zone_id: &str,
+    req: &SetZoneStatusRequest,
+    policy: TransitionPolicy,
+) -> Result<ZoneStatusChange, AppError> {
+    let prev = tx.query_opt("SELECT status FROM zones WHERE id=$1 AND NOT internal FOR UPDATE", &[&zone_id]).await?;
+    let previous_status = zone_status(&require_zone(prev, zone_id)?, "status")?;
+    policy.check(zone_id, previous_status, req.status)?;
+    let row = tx
+        .query_one(
+            "UPDATE zones SET status=$2, updated_at=now() WHERE id=$1 RETURNING id, name, status, updated_at",
+            &[&zone_id, &req.status.as_str()],
+        )
+        .await?;
+
+    let mut audits = vec![
+        tx.query_one(
+            "INSERT INTO audit_log(actor,action,target_type,target_id,reason,details) VALUES($1,'SET_ZONE_STATUS','zone',$2,$3, jsonb_build_object('status',$4)) RETURNING id::text, actor, action, target_type, target_id, reason, details, created_at",
+            &[&req.actor, &zone_id, &req.reason, &req.status.as_str()],
+        )
+        .await?,
+    ];
+
+    let changed_at: time::OffsetDateTime = row.get("updated_at");
+    let event = zone_status_event(zone_id, previous_status, req, changed_at)?;
+    let event = insert_outbox_event(tx, "ZoneStatusChanged", "zone", zone_id, &event).await?;
+
+    match incident_effect(req.status) {
+        Some(IncidentEffect::OpenZoneDown) => {
+            let details = json!({ "reason": req.reason, "actor": req.actor });
+            open_incident(tx, zone_id, "CRITICAL", ZONE_DOWN_INCIDENT_TITLE, &details).await?;
+        }
+        Some(IncidentEffect::ResolveZoneDown) => {
+            let resolved: Vec<String> = tx
+                .query(
+                    "UPDATE incidents SET status='RESOLVED', resolved_at=now(), \
+                     details = details || jsonb_build_object('resolved_by',$3::text,'auto_resolved',true) \
+                     WHERE zone_id=$1 AND title=$2 AND status <> 'RESOLVED' RETURNING id::text",
+                    &[&zone_id, &ZONE_DOWN_INCIDENT_TITLE, &req.actor],
+                )
+                .await?
+                .iter()
+                .map(|r| r.get(0))
+                .collect();
+            if !resolved.is_empty() {
+                audits.push(
+                    tx.query_one(
+                        "INSERT INTO audit_log(actor,action,target_type,target_id,reason,details) VALUES($1,'AUTO_RESOLVE_INCIDENTS','zone',$2,$3, jsonb_build_object('incident_ids',$4::text[],'status',$5::text)) RETURNING id::text, actor, action, target_type, target_id, reason, details, created_at",
+                        &[&req.actor, &zone_id, &req.reason, &resolved, &req.status.as_str()],
+                    )
+                    .await?,
+                );
+            }
+        }
+        None => {}
+    }
+
+    Ok(ZoneStatusChange { row, audits, event })
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleMaintenanceRequest {
+    /// RFC 3339; the zone goes DOWN at this time.
+    starts_at: String,
+    /// RFC 3339; the zone goes back to OK at this time.
+    ends_at: String,
+    actor: String,
+    #[serde(default)]
+    reason: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MaintenanceWindow {
+    id: String,
+    zone_id: String,
+    starts_at: String,
+    ends_at: String,
+    reason: String,
+    created_by: String,
+}
+
+/// Parsed `(starts_at, ends_at)` of a maintenance request, checked against `now`.
+fn maintenance_window(
+    req: &ScheduleMaintenanceRequest,
+    now: time::OffsetDateTime,
+) -> Result<(time::OffsetDateTime, time::OffsetDateTime), AppError> {
+    if req.actor.is_empty() {
+        return Err(AppError::BadRequest("actor required".into()));
+    }
+    let starts_at = parse_rfc3339("starts_at", &req.starts_at)?;
+    let ends_at = parse_rfc3339("ends_at", &req.ends_at)?;
+    if ends_at <= starts_at {
+        return Err(AppError::BadRequest("ends_at must be after starts_at".into()));
+    }
+    if ends_at <= now {
+        return Err(AppError::BadRequest("ends_at must be in the future".into()));
+    }
+    Ok((starts_at, ends_at))
+}
+
+/// Schedule a maintenance window: the zone goes DOWN at `starts_at` and back to
+/// OK at `ends_at`, applied by the maintenance scheduler like a manual status
+/// change by actor `system`. Windows of one zone may not overlap.
+#[utoipa::path(
+    post,
+    path = "/v1/zones/{zone_id}/maintenance",
+    tag = "zones",
+    params(("zone_id" = String, Path, description = "Zone id")),
+    request_body = ScheduleMaintenanceRequest,
+    responses(
+        (status = 200, description = "Window scheduled", body = MaintenanceWindow),
+        (status = 400, description = "Malformed body or unknown field, missing actor, bad timestamps or a window already over", body = ErrorBody),
+        (status = 404, description = "unknown_zone", body = ErrorBody),
+        (status = 409, description = "maintenance_overlap", body = ErrorBody),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn schedule_maintenance(
+    State(st): State<AppState>,
+    Path(zone_id): Path<String>,
+    body: Result<Json<ScheduleMaintenanceRequest>, JsonRejection>,
+) -> Result<Json<MaintenanceWindow>, AppError> {
+    let Json(req) = body?;
+    let (starts_at, ends_at) = maintenance_window(&req, st.clock.now())?;
+    let mut client = st.db.get().await?;
+    let tx = client.transaction().await?;
+
+    // the zone row lock serializes scheduling, so the overlap check holds
+    let zone = tx.query_opt("SELECT id FROM zones WHERE id=$1 AND NOT internal FOR UPDATE", &[&zone_id]).await?;
+    require_zone(zone, &zone_id)?;
+    let overlapping = tx
+        .query_opt(
+            "SELECT id::text FROM zone_maintenance WHERE zone_id=$1 AND ended_at IS NULL AND starts_at < $3 AND ends_at > $2 LIMIT 1",
+            &[&zone_id, &starts_at, &ends_at],
+        )
+        .await?;
+    if let Some(r) = overlapping {
+        let window_id: String = r.get(0);
+        return Err(AppError::Detailed {
+            status: StatusCode::CONFLICT,
+            code: "maintenance_overlap",
+            message: "zone already has a maintenance window in that period".into(),
+            details: json!({ "zone_id": zone_id, "window_id": window_id }),
+        });
+    }
+
+    let id: String = tx
+        .query_one(
+            "INSERT INTO zone_maintenance(zone_id,starts_at,ends_at,reason,created_by) VALUES($1,$2,$3,$4,$5) RETURNING id::text",
+            &[&zone_id, &starts_at, &ends_at, &req.reason, &req.actor],
+        )
+        .await?
+        .get(0);
+    let audit = tx
+        .query_one(
+            "INSERT INTO audit_log(actor,action,target_type,target_id,reason,details) VALUES($1,'SCHEDULE_MAINTENANCE','zone',$2,$3, jsonb_build_object('window_id',$4::text,'starts_at',$5::text,'ends_at',$6::text)) RETURNING id::text, actor, action, target_type, target_id, reason, details, created_at",
+            &[&req.actor, &zone_id, &req.reason, &id, &req.starts_at, &req.ends_at],
+        )
+        .await?;
+    tx.commit().await?;
+    publish_audit(&st, &audit);
+
+    Ok(Json(MaintenanceWindow {
+        id,
+        zone_id,
+        starts_at: to_rfc3339(starts_at)?,
+        ends_at: to_rfc3339(ends_at)?,
+        reason: req.reason,
+        created_by: req.actor,
+    }))
+}
+
+/// The `ZoneStatusChanged` payload as sent to `zone_id`'s WebSocket clients, if `event` is one.
+fn zone_transition(event: &LedgerEvent, zone_id: &str) -> Option<String> {
+    (event.event_type == "ZoneStatusChanged" && event.payload["zone_id"] == zone_id).then(|| event.payload.to_string())
+}
+
+/// Push `zone_id`'s transitions from `rx` until either side closes.
+/// An unknown zone is closed straight away with 1008 (policy violation).
+async fn push_zone_transitions(
+    mut socket: WebSocket,
+    zone_id: String,
+    known: bool,
+    mut rx: broadcast::Receiver<LedgerEvent>,
+    shutdown: CancellationToken,
+) {
+    if !known {
+        let frame = CloseFrame { code: close_code::POLICY, reason: format!("zone not found: {zone_id}").into() };
+        let _ = socket.send(Message::Close(Some(frame))).await;
+        return;
+    }
+    loop {
+        tokio::select! {
+            event = rx.recv() => match event {
+                Ok(event) => {
+                    let Some(text) = zone_transition(&event, &zone_id) else { continue };
+                    if socket.send(Message::Text(text.into())).await.is_err() {
+                        return;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => warn!(zone_id = %zone_id, skipped, "zone status subscriber lagged"),
+                Err(RecvError::Closed) => return,
+            },
+            msg = socket.recv() => match msg {
+                // pings are answered by axum; anything else from the client is ignored
+                Some(Ok(Message::Close(_)) | Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            },
+            _ = shutdown.cancelled() => {
+                let frame = CloseFrame { code: close_code::AWAY, reason: "server shutting down".into() };
+                let _ = socket.send(Message::Close(Some(frame))).await;
+                return;
+            }
+        }
+    }
+}
+
+/// WebSocket of `zone_id`'s status transitions: one text message per change,
+/// the same payload as the `ZoneStatusChanged` outbox event, sent after commit.
+/// Transitions missed by a lagging client are skipped; re-read
+/// `/v1/zones/{zone_id}/status` to resync. On shutdown the socket is closed
+/// with code 1001.
+#[utoipa::path(
+    get,
+    path = "/v1/zones/{zone_id}/ws",
+    tag = "zones",
+    params(("zone_id" = String, Path, description = "Zone id")),
+    responses(
+        (status = 101, description = "Upgraded; each text message is a ZoneStatusChanged payload. An unknown zone is closed with code 1008"),
+        (status = 500, description = "Database error", body = ErrorBody),
+    )
+)]
+pub async fn zone_status_ws(
+    ws: WebSocketUpgrade,
+    State(st): State<AppState>,
+    Path(zone_id): Path<String>,
+) -> Result<Response, AppError> {
+    // subscribe before the lookup so a change committed in between is still pushed
+    let rx = st.events_tx.subscribe();
+    let known = st.db_read.get().await?.query_opt("SELECT 1 FROM zones WHERE id=$1 AND NOT internal", &[&zone_id]).await?.is_some();
+    let shutdown = st.shutdown.clone();
+    Ok(ws.on_upgrade(move |socket| push_zone_transitions(socket, zone_id, known, rx, shutdown)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::response::IntoResponse;
+    use http_body_util::BodyExt;
+
+    #[tokio::test]
+    async fn missing_zone_is_404() {
+        let err = require_zone(None::<String>, "zone-does-not-exist").unwrap_err();
+        let response = err.into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "unknown_zone");
+        assert_eq!(json["details"]["zone_id"], "zone-does-not-exist");
+        assert_eq!(require_zone(Some("OK"), "zone-eu").unwrap(), "OK");
+    }
+
+    #[test]
+    fn status_at_uses_last_transition() {
+        let t = time::OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        assert_eq!(
+            status_at(Some(("DOWN".into(), t)), true, "OK".into()),
+            Some(("DOWN".into(), Some(t)))
+        );
+        // never changed: current status has always applied
+        assert_eq!(status_at(None, false, "OK".into()), Some(("OK".into(), None)));
+        // only later transitions: the earlier status was not recorded
+        assert_eq!(status_at(None, true, "DOWN".into()), None);
+    }
+
+    #[test]
+    fn ok_down_ok_resolves_the_down_incident() {
+        // (title, resolved) per incident, driven through the same effects as set_zone_status
+        let mut incidents = vec![("Manual investigation".to_string(), false)];
+        for status in [ZoneStatus::Ok, ZoneStatus::Down, ZoneStatus::Degraded, ZoneStatus::Ok] {
+            match incident_effect(status) {
+                Some(IncidentEffect::OpenZoneDown) => incidents.push((ZONE_DOWN_INCIDENT_TITLE.to_string(), false)),
+                Some(IncidentEffect::ResolveZoneDown) => {
+                    for (title, resolved) in incidents.iter_mut() {
+                        *resolved |= title.as_str() == ZONE_DOWN_INCIDENT_TITLE;
+                    }
+                }
+                None => {}
+            }
+        }
+        assert_eq!(
+            incidents,
+            vec![("Manual investigation".to_string(), false), (ZONE_DOWN_INCIDENT_TITLE.to_string(), true)]
+        );
+        assert_eq!(incident_effect(ZoneStatus::Degraded), None);
+    }
+
+    #[test]
+    fn status_event_carries_old_and_new_status() {
+        let req = SetZoneStatusRequest { status: ZoneStatus::Down, actor: "ops".into(), reason: "fiber cut".into() };
+        let at = time::OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let ev = zone_status_event("zone-eu", ZoneStatus::Ok, &req, at).unwrap();
+        assert_eq!(ev["type"], "ZoneStatusChanged");
+        assert_eq!(ev["previous_status"], "OK");
+        assert_eq!(ev["status"], "DOWN");
+        assert_eq!(ev["actor"], "ops");
+        assert_eq!(ev["reason"], "fiber cut");
+        assert_eq!(ev["changed_at"], "2023-11-14T22:13:20Z");
+    }
+
+    #[test]
+    fn status_request_rejects_unknown_fields() {
+        let req: SetZoneStatusRequest = serde_json::from_value(json!({"status": "DOWN", "actor": "ops"})).unwrap();
+        assert_eq!((req.status, req.reason.as_str()), (ZoneStatus::Down, ""));
+        let err = serde_json::from_value::<SetZoneStatusRequest>(json!({"status": "DOWN", "actor": "ops", "reson": "typo"}))
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("unknown field `reson`"), "{err}");
+    }
+
+    #[test]
+    fn status_request_takes_each_status_and_nothing_else() {
+        for (text, status) in [("OK", ZoneStatus::Ok), ("DEGRADED", ZoneStatus::Degraded), ("DOWN", ZoneStatus::Down)] {
+            let req: SetZoneStatusRequest = serde_json::from_value(json!({"status": text, "actor": "ops"})).unwrap();
+            assert_eq!(req.status, status);
+        }
+        let err = serde_json::from_value::<SetZoneStatusRequest>(json!({"status": "down", "actor": "ops"})).err().unwrap();
+        assert!(err.to_string().contains("unknown variant `down`"), "{err}");
+    }
+
+    #[test]
+    fn strict_policy_recovers_through_degraded() {
+        use ZoneStatus::{Degraded, Down, Ok};
+        let strict = TransitionPolicy::Strict;
+        for (from, to) in [(Ok, Degraded), (Ok, Down), (Degraded, Ok), (Degraded, Down), (Down, Degraded), (Down, Down)] {
+            assert!(strict.check("zone-eu", from, to).is_ok(), "{from:?} -> {to:?}");
+        }
+        match strict.check("zone-eu", Down, Ok).unwrap_err() {
+            AppError::Detailed { status, code, details, .. } => {
+                assert_eq!((status, code), (StatusCode::CONFLICT, "illegal_zone_transition"));
+                assert_eq!(details, json!({ "zone_id": "zone-eu", "from": "DOWN", "to": "OK", "allowed": ["DEGRADED"] }));
+            }
+            other => panic!("expected a detailed error, got {other:?}"),
+        }
+        assert!(TransitionPolicy::Permissive.check("zone-eu", Down, Ok).is_ok());
+        assert_eq!(TransitionPolicy::parse(" STRICT"), Some(TransitionPolicy::Strict));
+        assert_eq!(TransitionPolicy::parse("lenient"), None);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn strict_transitions_reject_down_to_ok_and_change_nothing() {
+        let mut config = crate::testing::test_db_config();
+        config.zone_transitions = TransitionPolicy::Strict;
+        let st = crate::app::build_state(config).await.unwrap();
+        let zone_id = format!("zone-strict-{}", uuid::Uuid::new_v4());
+        st.db
+            .get()
+            .await
+            .unwrap()
+            .execute("INSERT INTO zones(id,name,status) VALUES($1,'Strict test','DOWN')", &[&zone_id])
+            .await
+            .unwrap();
+        let set = |status: ZoneStatus| {
+            let req = SetZoneStatusRequest { status, actor: "ops".into(), reason: String::new() };
+            set_zone_status(State(st.clone()), Path(zone_id.clone()), Ok(Json(req)))
+        };
+
+        let response = set(ZoneStatus::Ok).await.unwrap_err().into_response();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let body: serde_json::Value = serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        assert_eq!(body["code"], "illegal_zone_transition");
+        assert_eq!(body["details"]["allowed"], json!(["DEGRADED"]));
+        let Json(zone) = get_zone(State(st.clone()), Path(zone_id.clone())).await.unwrap();
+        assert_eq!(zone.zone.status, ZoneStatus::Down, "rejected change is not applied");
+
+        assert_eq!(set(ZoneStatus::Degraded).await.unwrap().0["status"], "DEGRADED");
+        assert_eq!(set(ZoneStatus::Ok).await.unwrap().0["status"], "OK");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn status_cycles_through_every_variant() {
+        let st = crate::testing::test_db().await;
+        let zone_id = format!("zone-status-{}", uuid::Uuid::new_v4());
+        st.db
+            .get()
+            .await
+            .unwrap()
+            .execute("INSERT INTO zones(id,name,status) VALUES($1,'Status test','OK')", &[&zone_id])
+            .await
+            .unwrap();
+        for status in [ZoneStatus::Degraded, ZoneStatus::Down, ZoneStatus::Degraded, ZoneStatus::Ok] {
+            let req = SetZoneStatusRequest { status, actor: "ops".into(), reason: String::new() };
+            let Json(zone) = set_zone_status(State(st.clone()), Path(zone_id.clone()), Ok(Json(req))).await.unwrap();
+            assert_eq!(zone["status"], status.as_str());
+        }
+        let Json(zone) = get_zone(State(st), Path(zone_id)).await.unwrap();
+        assert_eq!(zone.zone.status, ZoneStatus::Ok);
+    }
+
+    /// Serves [`push_zone_transitions`] for `known` on a local port; returns the event
+    /// sender `set_zone_status` publishes on, the shutdown token and the socket URL for zone-eu.
+    async fn zone_ws_server(known: bool) -> (broadcast::Sender<LedgerEvent>, CancellationToken, String) {
+        let (events, _) = broadcast::channel(16);
+        let shutdown = CancellationToken::new();
+        let (tx, token) = (events.clone(), shutdown.clone());
+        let app = axum::Router::new().route(
+            "/v1/zones/{zone_id}/ws",
+            axum::routing::get(move |ws: WebSocketUpgrade, Path(zone_id): Path<String>| {
+                let (rx, token) = (tx.subscribe(), token.clone());
+                async move { ws.on_upgrade(move |socket| push_zone_transitions(socket, zone_id, known, rx, token)) }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (events, shutdown, format!("ws://{addr}/v1/zones/zone-eu/ws"))
+    }
+
+    fn status_changed(zone_id: &str, status: ZoneStatus) -> LedgerEvent {
+        let req = SetZoneStatusRequest { status, actor: "ops".into(), reason: "fiber cut".into() };
+        let at = time::OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        LedgerEvent {
+            id: format!("evt-{zone_id}"),
+            event_type: "ZoneStatusChanged".into(),
+            payload: zone_status_event(zone_id, ZoneStatus::Ok, &req, at).unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn ws_client_receives_zone_down() {
+        use futures::StreamExt;
+        use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+        let (events, _shutdown, url) = zone_ws_server(true).await;
+        let (mut client, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        // other zones' changes and other event types are filtered out
+        events.send(status_changed("zone-us", ZoneStatus::Down)).unwrap();
+        events.send(LedgerEvent { id: "evt-t".into(), event_type: "TransferPosted".into(), payload: json!({}) }).unwrap();
+        events.send(status_changed("zone-eu", ZoneStatus::Down)).unwrap();
+
+        let msg = tokio::time::timeout(std::time::Duration::from_secs(5), client.next()).await.unwrap().unwrap().unwrap();
+        let WsMessage::Text(text) = msg else { panic!("expected text, got {msg:?}") };
+        let payload: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(payload["zone_id"], "zone-eu");
+        assert_eq!(payload["previous_status"], "OK");
+        assert_eq!(payload["status"], "DOWN");
+    }
+
+    #[tokio::test]
+    async fn ws_unknown_zone_closes_with_policy_violation() {
+        use futures::StreamExt;
+        use tokio_tungstenite::tungstenite::{protocol::frame::coding::CloseCode, Message as WsMessage};
+
+        let (_events, _shutdown, url) = zone_ws_server(false).await;
+        let (mut client, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        let msg = tokio::time::timeout(std::time::Duration::from_secs(5), client.next()).await.unwrap().unwrap().unwrap();
+        let WsMessage::Close(Some(frame)) = msg else { panic!("expected close, got {msg:?}") };
+        assert_eq!(frame.code, CloseCode::Policy);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn get_zone_counts_accounts_todays_transactions_and_open_incidents() {
+        use crate::handlers::transfers::{create_transfer, CreateTransferQuery, CreateTransferRequest};
+        use axum::extract::Query;
+        let st = crate::testing::test_db().await;
+        let run = uuid::Uuid::new_v4();
+        let zone_id = format!("zone-detail-{run}");
+        let client = st.db.get().await.unwrap();
+        client
+            .execute("INSERT INTO zones(id,name,status) VALUES($1,'Detail test','OK')", &[&zone_id])
+            .await
+            .unwrap();
+        let req: CreateTransferRequest = serde_json::from_value(json!({
+            "request_id": format!("req-{run}"),
+            "from_account": format!("acct-a-{run}"),
+            "to_account": format!("acct-b-{run}"),
+            "amount_units": 100,
+            "zone_id": zone_id,
+        }))
+        .unwrap();
+        let q = Query(CreateTransferQuery { include_balances: false, dry_run: false });
+        create_transfer(State(st.clone()), q, Default::default(), None, Ok(Json(req))).await.unwrap();
+        // yesterday's transaction is not counted
+        client
+            .execute(
+                "INSERT INTO transactions(request_id,payload_hash,from_account,to_account,amount_units,zone_id,created_at) \
+                 VALUES($1,'h',$2,$3,1,$4,now() - interval '1 day')",
+                &[&format!("req-old-{run}"), &format!("acct-a-{run}"), &format!("acct-b-{run}"), &zone_id],
+            )
+            .await
+            .unwrap();
+        for status in ["OPEN", "ACK", "RESOLVED"] {
+            client
+                .execute(
+                    "INSERT INTO incidents(zone_id,severity,status,title) VALUES($1,'WARN',$2,'detail test')",
+                    &[&zone_id, &status],
+                )
+                .await
+                .unwrap();
+        }
+
+        let Json(detail) = get_zone(State(st.clone()), Path(zone_id.clone())).await.unwrap();
+        let detail = serde_json::to_value(detail).unwrap();
+        assert_eq!(detail["id"], zone_id.as_str());
+        assert_eq!(detail["status"], "OK");
+        assert_eq!((detail["account_count"].as_i64(), detail["transactions_today"].as_i64()), (Some(2), Some(1)));
+        assert_eq!(detail["open_incidents"], 2, "OPEN and ACK are open");
+
+        let err = get_zone(State(st), Path(format!("zone-missing-{run}"))).await.err().unwrap();
+        assert_eq!(err.status_and_code(), (StatusCode::NOT_FOUND, "unknown_zone"));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn ledger_zone_is_unlisted_and_takes_no_writes() {
+        use crate::handlers::transfers::{create_transfer, CreateTransferQuery, CreateTransferRequest};
+        use crate::reconcile::LEDGER_ZONE;
+        use axum::extract::Query;
+        let st = crate::testing::test_db().await;
+        let unknown = (StatusCode::NOT_FOUND, "unknown_zone");
+
+        let Json(list) = list_zones(State(st.clone())).await.unwrap();
+        assert!(!list.zones.is_empty());
+        assert!(list.zones.iter().all(|z| z.id != LEDGER_ZONE));
+        let err = get_zone(State(st.clone()), Path(LEDGER_ZONE.into())).await.err().unwrap();
+        assert_eq!(err.status_and_code(), unknown);
+
+        let status: SetZoneStatusRequest =
+            serde_json::from_value(json!({ "status": "DOWN", "actor": "ops", "reason": "test" })).unwrap();
+        let err = set_zone_status(State(st.clone()), Path(LEDGER_ZONE.into()), Ok(Json(status))).await.err().unwrap();
+        assert_eq!(err.status_and_code(), unknown);
+
+        let now = st.clock.now();
+        let window = ScheduleMaintenanceRequest {
+            starts_at: crate::util::to_rfc3339(now + time::Duration::hours(1)).unwrap(),
+            ends_at: crate::util::to_rfc3339(now + time::Duration::hours(2)).unwrap(),
+            actor: "ops".into(),
+            reason: "test".into(),
+        };
+        let err = schedule_maintenance(State(st.clone()), Path(LEDGER_ZONE.into()), Ok(Json(window))).await.err().unwrap();
+        assert_eq!(err.status_and_code(), unknown);
+
+        let run = uuid::Uuid::new_v4();
+        let req: CreateTransferRequest = serde_json::from_value(json!({
+            "request_id": format!("req-{run}"),
+            "from_account": format!("acct-a-{run}"),
+            "to_account": format!("acct-b-{run}"),
+            "amount_units": 100,
+            "zone_id": LEDGER_ZONE,
+        }))
+        .unwrap();
+        let q = Query(CreateTransferQuery { include_balances: false, dry_run: false });
+        let err = create_transfer(State(st), q, Default::default(), None, Ok(Json(req))).await.err().unwrap();
+        assert_eq!(err.status_and_code(), unknown);
+    }
+
+    #[tokio::test]
+    async fn ws_is_closed_as_going_away_on_shutdown() {
+        use futures::StreamExt;
+        use tokio_tungstenite::tungstenite::{protocol::frame::coding::CloseCode, Message as WsMessage};
+
+        let (_events, shutdown, url) = zone_ws_server(true).await;
+        let (mut client, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        shutdown.cancel();
+        let msg = tokio::time::timeout(std::time::Duration::from_secs(5), client.next()).await.unwrap().unwrap().unwrap();
+        let WsMessage::Close(Some(frame)) = msg else { panic!("expected close, got {msg:?}") };
+        assert_eq!(frame.code, CloseCode::Away);
+    }
+
+    #[test]
+    fn maintenance_window_validation() {
+        let now = time::OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let req = |starts_at: &str, ends_at: &str| ScheduleMaintenanceRequest {
+            starts_at: starts_at.into(),
+            ends_at: ends_at.into(),
+            actor: "ops".into(),
+            reason: "patching".into(),
+        };
+        let (starts_at, ends_at) = maintenance_window(&req("2023-11-15T02:00:00Z", "2023-11-15T04:00:00+01:00"), now).unwrap();
+        assert_eq!(ends_at - starts_at, time::Duration::hours(1));
+        // already started but not over is fine: the scheduler flips it at once
+        assert!(maintenance_window(&req("2023-11-14T00:00:00Z", "2023-11-15T00:00:00Z"), now).is_ok());
+
+        let rejected = |r: ScheduleMaintenanceRequest| match maintenance_window(&r, now) {
+            Err(AppError::BadRequest(msg)) => msg,
+            other => panic!("expected 400, got {other:?}"),
+        };
+        assert!(rejected(req("2023-11-15T04:00:00Z", "2023-11-15T02:00:00Z")).contains("after starts_at"));
+        assert!(rejected(req("2023-11-14T00:00:00Z", "2023-11-14T01:00:00Z")).contains("future"));
+        assert!(rejected(req("tomorrow", "2023-11-15T02:00:00Z")).contains("starts_at"));
+        assert!(rejected(ScheduleMaintenanceRequest { actor: String::new(), ..req("2023-11-15T02:00:00Z", "2023-11-15T03:00:00Z") })
+            .contains("actor"));
+    }
+
+    fn update(body: serde_json::Value) -> UpdateZoneRequest {
+        serde_json::from_value(body).unwrap()
+    }
+
+    #[test]
+    fn name_only_update_leaves_description_alone() {
+        let req = update(json!({ "name": "Europe West", "actor": "ops" }));
+        assert_eq!(req.name.as_deref(), Some("Europe West"));
+        assert_eq!(req.description, None, "omitted, not cleared");
+        assert!(req.changes().unwrap());
+        assert_eq!(req.audit_details(), json!({ "name": "Europe West" }));
+    }
+
+    #[test]
+    fn description_only_update_leaves_name_alone() {
+        let req = update(json!({ "description": "Frankfurt and Dublin", "actor": "ops" }));
+        assert_eq!(req.name, None);
+        assert_eq!(req.description, Some(Some("Frankfurt and Dublin".into())));
+        assert_eq!(req.audit_details(), json!({ "description": "Frankfurt and Dublin" }));
+
+        // an explicit null clears the description, which an omitted field never does
+        let clear = update(json!({ "description": null, "actor": "ops" }));
+        assert_eq!(clear.description, Some(None));
+        assert!(clear.changes().unwrap());
+        assert_eq!(clear.audit_details(), json!({ "description": null }));
+    }
+
+    #[test]
+    fn empty_update_is_a_no_op() {
+        let req = update(json!({}));
+        // no actor needed when nothing changes
+        assert!(!req.changes().unwrap());
+    }
+
+    #[test]
+    fn update_needs_actor_and_a_real_name() {
+        assert!(matches!(update(json!({ "name": "Europe" })).changes(), Err(AppError::BadRequest(m)) if m.contains("actor")));
+        assert!(matches!(update(json!({ "name": "  ", "actor": "ops" })).changes(), Err(AppError::BadRequest(m)) if m.contains("name")));
+        assert!(serde_json::from_value::<UpdateZoneRequest>(json!({ "status": "DOWN" })).is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn partial_zone_updates_touch_only_given_fields() {
+        let st = crate::testing::test_db().await;
+        let zone_id = format!("zone-patch-{}", uuid::Uuid::new_v4());
+        st.db
+            .get()
+            .await
+            .unwrap()
+            .execute("INSERT INTO zones(id,name,status,description) VALUES($1,'Before','OK','first')", &[&zone_id])
+            .await
+            .unwrap();
+        let patch = |body: serde_json::Value| {
+            let st = st.clone();
+            let zone_id = zone_id.clone();
+            async move {
+                let Json(zone) = update_zone(State(st), Path(zone_id), Ok(Json(update(body)))).await.unwrap();
+                serde_json::to_value(zone).unwrap()
+            }
+        };
+
+        let zone = patch(json!({ "name": "After", "actor": "ops" })).await;
+        assert_eq!((zone["name"].as_str(), zone["description"].as_str()), (Some("After"), Some("first")));
+        let zone = patch(json!({ "description": "second", "actor": "ops" })).await;
+        assert_eq!((zone["name"].as_str(), zone["description"].as_str()), (Some("After"), Some("second")));
+        let unchanged = patch(json!({})).await;
+        assert_eq!(unchanged, zone);
+
+        let audits: i64 = st
+            .db
+            .get()
+            .await
+            .unwrap()
+            .query_one("SELECT COUNT(*) FROM audit_log WHERE action='UPDATE_ZONE' AND target_id=$1", &[&zone_id])
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(audits, 2, "the no-op writes no audit entry");
+    }
+
+    #[test]
+    fn missing_zone_ids_are_sorted_and_unique() {
+        let ids = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let requested = ids(&["zone-c", "zone-a", "zone-b", "zone-c"]);
+        assert_eq!(missing_zone_ids(&requested, &ids(&["zone-a"])), ids(&["zone-b", "zone-c"]));
+        assert!(missing_zone_ids(&requested, &ids(&["zone-a", "zone-b", "zone-c"])).is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn bulk_zone_status_is_all_or_nothing() {
+        let st = crate::testing::test_db().await;
+        let suffix = uuid::Uuid::new_v4();
+        let zone_ids = vec![format!("zone-bulk-a-{suffix}"), format!("zone-bulk-b-{suffix}")];
+        let client = st.db.get().await.unwrap();
+        for zone_id in &zone_ids {
+            client
+                .execute("INSERT INTO zones(id,name,status) VALUES($1,'Bulk test','OK')", &[zone_id])
+                .await
+                .unwrap();
+        }
+        let bulk = |ids: Vec<String>| {
+            let st = st.clone();
+            async move {
+                let req = BulkZoneStatusRequest { zone_ids: ids, status: ZoneStatus::Down, actor: "ops".into(), reason: "region outage".into() };
+                set_zones_status(State(st), Ok(Json(req))).await
+            }
+        };
+        let (db, ids) = (&client, &zone_ids);
+        let count = move |sql: &'static str| async move { db.query_one(sql, &[ids]).await.unwrap().get::<_, i64>(0) };
+        const DOWN: &str = "SELECT COUNT(*) FROM zones WHERE id = ANY($1) AND status='DOWN'";
+        const INCIDENTS: &str = "SELECT COUNT(*) FROM incidents WHERE zone_id = ANY($1) AND status <> 'RESOLVED'";
+        const AUDITS: &str = "SELECT COUNT(*) FROM audit_log WHERE target_id = ANY($1) AND action='SET_ZONE_STATUS'";
+
+        let unknown = format!("zone-bulk-missing-{suffix}");
+        let err = bulk(vec![zone_ids[0].clone(), unknown.clone(), zone_ids[1].clone()]).await.unwrap_err();
+        let response = err.into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "unknown_zones");
+        assert_eq!(json["details"]["zone_ids"], json!([unknown]));
+        assert_eq!((count(DOWN).await, count(INCIDENTS).await, count(AUDITS).await), (0, 0, 0), "nothing applied");
+
+        let Json(updated) = bulk(zone_ids.clone()).await.unwrap();
+        assert_eq!(updated["zones"].as_array().unwrap().len(), 2);
+        assert_eq!(count(DOWN).await, 2);
+        assert_eq!(count(INCIDENTS).await, 2, "one zone-down incident per zone");
+        assert_eq!(count(AUDITS).await, 2, "one audit entry per zone");
+    }
+
+    #[test]
+    fn db_failure_stays_500() {
+        let err = AppError::from(deadpool_postgres::PoolError::Closed);
+        assert_eq!(err.into_response().status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
 }
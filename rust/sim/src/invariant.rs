@@ -0,0 +1,162 @@
+use deadpool_postgres::Pool;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::handlers::incidents::open_incident;
+use crate::reconcile::{drift_action, DriftAction, LEDGER_ZONE};
+use crate::state::Metrics;
+
+const IMBALANCE_INCIDENT_TITLE: &str = "Ledger imbalance detected";
+
+/// Signed sum of every stored balance (available plus pending) and of the
+/// postings the async projector has not folded in yet, read in one snapshot.
+/// Every transaction's postings net to zero, so this is zero unless a balance
+/// row was written outside the posting path.
+const IMBALANCE_SQL: &str = "SELECT \
+     (SELECT COALESCE(SUM(balance_units + pending_units), 0) FROM balances) + \
+     (SELECT COALESCE(SUM(CASE WHEN direction='CREDIT' THEN amount_units ELSE -amount_units END), 0) \
+      FROM postings WHERE projected_at IS NULL)";
+
+/// Periodically checks that the ledger nets to zero. A non-zero sum sets the
+/// `ledger_imbalance_units` gauge and files a CRITICAL incident under
+/// [`LEDGER_ZONE`], which resolves once the sum is back to zero.
+///
+/// Unlike the [`crate::reconcile::Reconciler`] this does not compare accounts
+/// with their postings, so it is cheap enough to run often; it only says that
+/// something is wrong, and reconcile says where.
+pub struct ImbalanceChecker {
+    db: Pool,
+    metrics: Arc<Metrics>,
+    interval: Duration,
+}
+
+impl ImbalanceChecker {
+    pub fn new(db: Pool, metrics: Arc<Metrics>, interval: Duration) -> Self {
+        Self { db, metrics, interval }
+    }
+
+    pub async fn run(&self, cancel: CancellationToken) {
+        let mut interval = tokio::time::interval(self.interval);
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => return,
+                _ = interval.tick() => {
+                    if let Err(e) = self.check().await {
+                        warn!(error = %e, "ledger imbalance check failed");
+                    }
+                }
+            }
+        }
+    }
+
+    /// One check in its own transaction; returns the imbalance.
+    pub async fn check(&self) -> Result<i64, Box<dyn std::error::Error>> {
+        let mut client = self.db.get().await?;
+        let tx = client.transaction().await?;
+        let imbalance = check_imbalance(&tx, &self.metrics).await?;
+        tx.commit().await?;
+        Ok(imbalance)
+    }
+}
+
+/// Sum the ledger inside `tx`, update the gauge and open, refresh or resolve the
+/// imbalance incident. Returns the signed imbalance in units.
+pub async fn check_imbalance(
+    tx: &deadpool_postgres::Transaction<'_>,
+    metrics: &Metrics,
+) -> Result<i64, tokio_postgres::Error> {
+    let imbalance: i64 = tx.query_one(&format!("SELECT ({IMBALANCE_SQL})::bigint"), &[]).await?.get(0);
+    metrics.ledger_imbalance_units.set(imbalance);
+
+    let open = tx
+        .query_opt(
+            "SELECT id::text FROM incidents WHERE zone_id=$1 AND title=$2 AND status <> 'RESOLVED' ORDER BY detected_at DESC LIMIT 1 FOR UPDATE",
+            &[&LEDGER_ZONE, &IMBALANCE_INCIDENT_TITLE],
+        )
+        .await?
+        .map(|r| r.get::<_, String>(0));
+
+    let details = json!({ "imbalance_units": imbalance });
+    match (drift_action(imbalance.abs(), 0, open.is_some()), open) {
+        (Some(DriftAction::Open), _) => {
+            warn!(imbalance_units = imbalance, "ledger does not net to zero, opening incident");
+            open_incident(tx, LEDGER_ZONE, "CRITICAL", IMBALANCE_INCIDENT_TITLE, &details).await?;
+        }
+        (Some(DriftAction::Update), Some(id)) => {
+            tx.execute("UPDATE incidents SET details = details || $2 WHERE id=$1::uuid", &[&id, &details]).await?;
+        }
+        (Some(DriftAction::Resolve), Some(id)) => {
+            info!(incident_id = %id, "ledger nets to zero again, resolving imbalance incident");
+            tx.execute(
+                "UPDATE incidents SET status='RESOLVED', resolved_at=now(), details = details || $2 WHERE id=$1::uuid",
+                &[&id, &json!({ "imbalance_units": 0, "resolved_by": "imbalance_checker" })],
+            )
+            .await?;
+        }
+        _ => {}
+    }
+    Ok(imbalance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `(severity, details)` of the open imbalance incident, if any.
+    async fn open_imbalance_incident(tx: &deadpool_postgres::Transaction<'_>) -> Option<(String, serde_json::Value)> {
+        tx.query_opt(
+            "SELECT severity, details FROM incidents WHERE zone_id=$1 AND title=$2 AND status <> 'RESOLVED'",
+            &[&LEDGER_ZONE, &IMBALANCE_INCIDENT_TITLE],
+        )
+        .await
+        .unwrap()
+        .map(|r| (r.get("severity"), r.get("details")))
+    }
+
+    /// Everything happens in one rolled-back transaction, so the stray row never
+    /// becomes visible to other tests or a running checker. Rows other tests
+    /// committed earlier may already be off balance, so only the change from
+    /// the baseline is asserted; repeatable read keeps that baseline fixed.
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn unbalanced_row_raises_an_incident() {
+        let st = crate::testing::test_db().await;
+        let (_, metrics) = crate::state::init_metrics().unwrap();
+        let mut client = st.db.get().await.unwrap();
+        let tx = client
+            .build_transaction()
+            .isolation_level(tokio_postgres::IsolationLevel::RepeatableRead)
+            .start()
+            .await
+            .unwrap();
+        let baseline = check_imbalance(&tx, &metrics).await.unwrap();
+
+        let account = format!("acct-stray-{}", uuid::Uuid::new_v4());
+        tx.execute("INSERT INTO accounts(id,zone_id) VALUES($1,'zone-eu')", &[&account]).await.unwrap();
+        tx.execute("INSERT INTO balances(account_id,balance_units) VALUES($1,125)", &[&account]).await.unwrap();
+
+        assert_eq!(check_imbalance(&tx, &metrics).await.unwrap() - baseline, 125);
+        assert_eq!(metrics.ledger_imbalance_units.get() - baseline, 125);
+        if baseline != -125 {
+            let (severity, details) = open_imbalance_incident(&tx).await.expect("imbalance incident");
+            assert_eq!(severity, "CRITICAL");
+            assert_eq!(details["imbalance_units"], baseline + 125);
+        }
+
+        // a second check refreshes the same incident rather than opening another
+        tx.execute("UPDATE balances SET balance_units=-30 WHERE account_id=$1", &[&account]).await.unwrap();
+        assert_eq!(check_imbalance(&tx, &metrics).await.unwrap() - baseline, -30);
+        if baseline != 30 {
+            let (_, details) = open_imbalance_incident(&tx).await.expect("still open");
+            assert_eq!(details["imbalance_units"], baseline - 30);
+        }
+
+        tx.execute("DELETE FROM balances WHERE account_id=$1", &[&account]).await.unwrap();
+        assert_eq!(check_imbalance(&tx, &metrics).await.unwrap(), baseline);
+        assert_eq!(metrics.ledger_imbalance_units.get(), baseline);
+        assert_eq!(open_imbalance_incident(&tx).await.is_some(), baseline != 0, "open exactly while the ledger is off balance");
+    }
+}
@@ -1,19 +1,195 @@
+pub mod app;
+pub mod auth;
+pub mod clock;
+pub mod config;
+pub mod db;
 pub mod error;
 pub mod handlers;
+pub mod invariant;
+pub mod jwt;
+pub mod maintenance;
+pub mod merkle;
+pub mod microbatch;
 pub mod messaging;
 pub mod middleware;
+pub mod projection;
+pub mod ratelimit;
+pub mod reconcile;
+pub mod settlement;
+pub mod shutdown;
 pub mod state;
+#[cfg(test)]
+mod testing;
 pub mod util;
 
-pub fn net_zero(amount: i64) -> i64 {
-    -amount + amount
+use serde::{Deserialize, Serialize};
+use tokio_postgres::types::{FromSql, Type};
+
+/// Side of a double-entry posting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Direction {
+    Debit,
+    Credit,
+}
+
+impl Direction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Direction::Debit => "DEBIT",
+            Direction::Credit => "CREDIT",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "DEBIT" => Some(Direction::Debit),
+            "CREDIT" => Some(Direction::Credit),
+            _ => None,
+        }
+    }
+}
+
+/// Reads `postings.direction`; any other text is an error rather than a guess.
+impl<'a> FromSql<'a> for Direction {
+    fn from_sql(ty: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        let s = <&str as FromSql>::from_sql(ty, raw)?;
+        Direction::parse(s).ok_or_else(|| format!("unknown posting direction {s:?}").into())
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        <&str as FromSql>::accepts(ty)
+    }
+}
+
+/// Operational status of a zone, as stored in `zones.status`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ZoneStatus {
+    Ok,
+    Degraded,
+    Down,
+}
+
+impl ZoneStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ZoneStatus::Ok => "OK",
+            ZoneStatus::Degraded => "DEGRADED",
+            ZoneStatus::Down => "DOWN",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "OK" => Some(ZoneStatus::Ok),
+            "DEGRADED" => Some(ZoneStatus::Degraded),
+            "DOWN" => Some(ZoneStatus::Down),
+            _ => None,
+        }
+    }
+}
+
+/// Reads `zones.status`; text written around the CHECK constraint is an error.
+impl<'a> FromSql<'a> for ZoneStatus {
+    fn from_sql(ty: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        let s = <&str as FromSql>::from_sql(ty, raw)?;
+        ZoneStatus::parse(s).ok_or_else(|| format!("unknown zone status {s:?}").into())
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        <&str as FromSql>::accepts(ty)
+    }
+}
+
+/// Double-entry invariant: at least one posting, every amount positive,
+/// and total debits equal total credits.
+pub fn postings_balanced(postings: &[(Direction, i64)]) -> bool {
+    if postings.is_empty() || postings.iter().any(|(_, amount)| *amount <= 0) {
+        return false;
+    }
+    let (mut debits, mut credits) = (0i128, 0i128);
+    for (direction, amount) in postings {
+        match direction {
+            Direction::Debit => debits += *amount as i128,
+            Direction::Credit => credits += *amount as i128,
+        }
+    }
+    debits == credits
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    #[test]
+    fn balanced_postings() {
+        assert!(postings_balanced(&[(Direction::Debit, 100), (Direction::Credit, 100)]));
+        assert!(postings_balanced(&[
+            (Direction::Debit, 100),
+            (Direction::Credit, 60),
+            (Direction::Credit, 40),
+        ]));
+    }
+
+    #[test]
+    fn unbalanced_postings() {
+        assert!(!postings_balanced(&[(Direction::Debit, 100), (Direction::Credit, 99)]));
+        assert!(!postings_balanced(&[(Direction::Debit, 100)]));
+        assert!(!postings_balanced(&[]));
+    }
+
+    #[test]
+    fn direction_serde_round_trip() {
+        for (direction, text) in [(Direction::Debit, "DEBIT"), (Direction::Credit, "CREDIT")] {
+            assert_eq!(serde_json::to_value(direction).unwrap(), text);
+            assert_eq!(serde_json::from_value::<Direction>(text.into()).unwrap(), direction);
+            assert_eq!(direction.as_str(), text);
+        }
+        assert!(serde_json::from_value::<Direction>("debit".into()).is_err());
+    }
+
+    #[test]
+    fn direction_from_sql_rejects_unknown_text() {
+        assert_eq!(Direction::from_sql(&Type::TEXT, b"DEBIT").unwrap(), Direction::Debit);
+        assert_eq!(Direction::from_sql(&Type::TEXT, b"CREDIT").unwrap(), Direction::Credit);
+        let err = Direction::from_sql(&Type::TEXT, b"DEBT").unwrap_err();
+        assert!(err.to_string().contains("DEBT"), "{err}");
+        assert!(<Direction as FromSql>::accepts(&Type::TEXT));
+    }
+
+    #[test]
+    fn zone_status_serde_round_trip() {
+        for (status, text) in [(ZoneStatus::Ok, "OK"), (ZoneStatus::Degraded, "DEGRADED"), (ZoneStatus::Down, "DOWN")] {
+            assert_eq!(serde_json::to_value(status).unwrap(), text);
+            assert_eq!(serde_json::from_value::<ZoneStatus>(text.into()).unwrap(), status);
+            assert_eq!(ZoneStatus::parse(text), Some(status));
+            assert_eq!(status.as_str(), text);
+        }
+        assert!(serde_json::from_value::<ZoneStatus>("Ok".into()).is_err());
+        assert!(serde_json::from_value::<ZoneStatus>("UP".into()).is_err());
+    }
+
+    #[test]
+    fn zone_status_from_sql_rejects_unknown_text() {
+        assert_eq!(ZoneStatus::from_sql(&Type::TEXT, b"DEGRADED").unwrap(), ZoneStatus::Degraded);
+        let err = ZoneStatus::from_sql(&Type::TEXT, b"MAINTENANCE").unwrap_err();
+        assert!(err.to_string().contains("MAINTENANCE"), "{err}");
+    }
+
+    #[test]
+    fn zero_or_negative_amounts_rejected() {
+        assert!(!postings_balanced(&[(Direction::Debit, 0), (Direction::Credit, 0)]));
+        assert!(!postings_balanced(&[(Direction::Debit, -5), (Direction::Credit, -5)]));
+    }
+
     #[test]
-    fn test_net_zero() {
-        assert_eq!(net_zero(123), 0);
+    fn large_amounts_do_not_overflow() {
+        assert!(postings_balanced(&[
+            (Direction::Debit, i64::MAX),
+            (Direction::Debit, i64::MAX),
+            (Direction::Credit, i64::MAX),
+            (Direction::Credit, i64::MAX),
+        ]));
     }
 }
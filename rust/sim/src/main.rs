@@ -1,13 +1,23 @@
-use axum::{middleware, routing::{get, post}, Router};
-use std::{env, net::SocketAddr};
-use tokio_postgres::NoTls;
-use tokio_util::sync::CancellationToken;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
 use tracing::{info, warn};
 
-use time_ledger_sim_rust::handlers::{admin, audit, balances, controls, incidents, spool, transactions, transfers, zones};
-use time_ledger_sim_rust::messaging;
-use time_ledger_sim_rust::middleware::cors;
-use time_ledger_sim_rust::state::{init_metrics, AppState};
+use time_ledger_sim_rust::app::{build_app, build_state};
+use time_ledger_sim_rust::config::Config;
+use time_ledger_sim_rust::invariant::ImbalanceChecker;
+use time_ledger_sim_rust::maintenance::MaintenanceScheduler;
+use time_ledger_sim_rust::{db, messaging};
+use time_ledger_sim_rust::messaging::delivery::{OutboxDelivery, OutboxDepthGauge};
+use time_ledger_sim_rust::messaging::notify::OutboxListener;
+use time_ledger_sim_rust::messaging::sink::OutboxSinkKind;
+use time_ledger_sim_rust::messaging::webhook::WebhookSink;
+use time_ledger_sim_rust::microbatch::MicroBatcher;
+use time_ledger_sim_rust::projection::{BalanceProjection, BalanceProjector};
+use time_ledger_sim_rust::reconcile::Reconciler;
+use time_ledger_sim_rust::settlement::Settler;
+use time_ledger_sim_rust::shutdown::{serve_until, shutdown_signal};
 
 fn init_tracing() {
     let filter = tracing_subscriber::EnvFilter::try_from_default_env()
@@ -22,25 +32,16 @@ fn init_tracing() {
 async fn main() {
     init_tracing();
 
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL required");
-    let port = env::var("PORT").unwrap_or_else(|_| "8081".into());
-    let admin_key = env::var("ADMIN_KEY").ok();
-
-    let (registry, metrics_state) = init_metrics();
-
-    let pg_config = database_url
-        .parse::<tokio_postgres::Config>()
-        .expect("invalid DATABASE_URL");
-    let mgr = deadpool_postgres::Manager::new(pg_config, NoTls);
-    let pool = deadpool_postgres::Pool::builder(mgr)
-        .max_size(16)
-        .build()
-        .expect("pool build");
+    let config = Config::from_env().unwrap_or_else(|e| panic!("invalid configuration: {e}"));
+    let mut st = build_state(config.clone()).await.unwrap_or_else(|e| panic!("{e:#}"));
+    let pool = st.db.clone();
+    let read_pool = st.db_read.clone();
 
     // NATS messaging (optional: skip if NATS_URL not set)
     let cancel = CancellationToken::new();
-    if let Ok(nats_url) = env::var("NATS_URL") {
-        match async_nats::connect(&nats_url).await {
+    let tasks = TaskTracker::new();
+    if let Some(nats_url) = &config.nats_url {
+        match async_nats::connect(nats_url).await {
             Ok(nc) => {
                 let js = async_nats::jetstream::new(nc);
                 if let Err(e) = messaging::streams::ensure_streams(&js).await {
@@ -51,8 +52,8 @@ async fn main() {
                     let fraud = messaging::fraud::FraudConsumer::new(pool.clone(), js);
                     let c1 = cancel.clone();
                     let c2 = cancel.clone();
-                    tokio::spawn(async move { outbox.run(c1).await });
-                    tokio::spawn(async move { fraud.run(c2).await });
+                    tasks.spawn(async move { outbox.run(c1).await });
+                    tasks.spawn(async move { fraud.run(c2).await });
                 }
             }
             Err(e) => warn!(error = %e, "NATS connection failed, messaging disabled"),
@@ -61,45 +62,121 @@ async fn main() {
         info!("NATS_URL not set, messaging disabled");
     }
 
-    let st = AppState {
-        db: pool,
-        admin_key,
-        registry,
-        metrics: metrics_state,
+    // Delivery of outbox events to the sink chosen by OUTBOX_SINK, woken by
+    // NOTIFY outbox_new unless OUTBOX_LISTEN=false
+    let wake = config.outbox_listen.then(|| Arc::new(Notify::new()));
+    let delivering = match (config.outbox_sink, config.webhook_url.clone()) {
+        (OutboxSinkKind::Webhook, Some(webhook_url)) => {
+            info!(url = %webhook_url, "starting outbox webhook delivery");
+            let sink = WebhookSink::new(webhook_url, config.webhook_signing_secret.clone());
+            let delivery = OutboxDelivery::new(pool.clone(), sink, config.webhook_max_backoff, st.metrics.clone())
+                .with_polling(config.outbox_poll_interval, wake.clone());
+            let c = cancel.clone();
+            tasks.spawn(async move { delivery.run(c).await });
+            true
+        }
+        #[cfg(feature = "kafka")]
+        (OutboxSinkKind::Kafka, _) => {
+            let (brokers, topic) = (config.kafka_brokers.clone().unwrap_or_default(), config.kafka_topic.clone().unwrap_or_default());
+            info!(brokers = %brokers, topic = %topic, "starting outbox kafka delivery");
+            let sink = messaging::kafka::KafkaSink::new(&brokers, topic).unwrap_or_else(|e| panic!("{e}"));
+            let delivery = OutboxDelivery::new(pool.clone(), sink, config.webhook_max_backoff, st.metrics.clone())
+                .with_polling(config.outbox_poll_interval, wake.clone());
+            let c = cancel.clone();
+            tasks.spawn(async move { delivery.run(c).await });
+            true
+        }
+        _ => {
+            info!("OUTBOX_SINK is none, outbox delivery disabled");
+            false
+        }
     };
+    if delivering {
+        if let Some(wake) = wake {
+            let listener = OutboxListener::new(config.database_url.clone(), wake);
+            let c = cancel.clone();
+            tasks.spawn(async move { listener.run(c).await });
+        }
+        // without a sink nothing is ever delivered, so the backlog would only grow
+        let gauge = OutboxDepthGauge::new(pool.clone(), st.metrics.clone(), Duration::from_secs(5));
+        let c = cancel.clone();
+        tasks.spawn(async move { gauge.run(c).await });
+    }
+
+    if config.balance_projection == BalanceProjection::Async {
+        info!("BALANCE_PROJECTION=async, starting balance projector");
+        let projector = BalanceProjector::new(pool.clone());
+        let c = cancel.clone();
+        tasks.spawn(async move { projector.run(c).await });
+    }
+
+    if let Some(delay) = config.settlement_delay {
+        info!(delay_secs = delay.as_secs(), "settlement delay enabled, starting settler");
+        let settler = Settler::new(pool.clone(), delay);
+        let c = cancel.clone();
+        tasks.spawn(async move { settler.run(c).await });
+    }
+
+    if let Some(interval) = config.reconcile_interval {
+        let threshold = config.drift_threshold_units;
+        info!(interval_secs = interval.as_secs(), threshold_units = threshold, "starting balance reconciler");
+        let reconciler = Reconciler::new(pool.clone(), interval, threshold);
+        let c = cancel.clone();
+        tasks.spawn(async move { reconciler.run(c).await });
+    }
+
+    if let Some(interval) = config.imbalance_check_interval {
+        info!(interval_secs = interval.as_secs(), "starting ledger imbalance checker");
+        let checker = ImbalanceChecker::new(pool.clone(), st.metrics.clone(), interval);
+        let c = cancel.clone();
+        tasks.spawn(async move { checker.run(c).await });
+    }
+
+    if config.pool.idle_timeout.is_some() || config.pool.min_idle > 0 {
+        let reaper = db::IdleReaper::new(pool.clone(), config.pool.clone());
+        let c = cancel.clone();
+        tasks.spawn(async move { reaper.run(c).await });
+        if config.database_replica_url.is_some() {
+            let reaper = db::IdleReaper::new(read_pool.clone(), config.pool.clone());
+            let c = cancel.clone();
+            tasks.spawn(async move { reaper.run(c).await });
+        }
+    }
+
+    if let Some(window) = config.microbatch_window {
+        let max_batch = config.microbatch_max;
+        info!(window_ms = window.as_millis() as u64, max_batch, "micro-batching enabled, starting transfer writer");
+        let (tx, rx) = tokio::sync::mpsc::channel(max_batch * 4);
+        let writer = MicroBatcher::new(st.clone(), rx, window, max_batch);
+        st.transfer_batcher = Some(tx);
+        let c = cancel.clone();
+        tasks.spawn(async move { writer.run(c).await });
+    }
+
+    info!("starting zone maintenance scheduler");
+    let scheduler = MaintenanceScheduler::new(st.clone(), st.clock.clone());
+    let c = cancel.clone();
+    tasks.spawn(async move { scheduler.run(c).await });
+
+    let streams = st.shutdown.clone();
+    let app = build_app(st);
+
+    info!(addr = %config.addr, "sim-rust listening");
+    let listener = tokio::net::TcpListener::bind(config.addr).await.unwrap();
+    serve_until(listener, app, async {
+        shutdown_signal().await;
+        info!("shutdown signal received, draining in-flight requests");
+        // SSE streams and zone sockets never finish on their own
+        streams.cancel();
+    })
+    .await
+    .unwrap();
 
-    let app = Router::new()
-        .route("/healthz", get(admin::healthz))
-        .route("/metrics", get(admin::metrics))
-        .route("/v1/version", get(admin::version))
-        .route("/v1/zones", get(zones::list_zones))
-        .route("/v1/transfers", post(transfers::create_transfer))
-        .route("/v1/balances", get(balances::list_balances))
-        .route("/v1/transactions", get(transactions::list_transactions))
-        .route("/v1/transactions/{transaction_id}", get(transactions::get_transaction))
-        .route("/v1/zones/{zone_id}/status", post(zones::set_zone_status))
-        .route("/v1/zones/{zone_id}/incidents", get(incidents::list_incidents_by_zone))
-        .route("/v1/incidents", get(incidents::list_recent_incidents))
-        .route("/v1/incidents/{incident_id}", get(incidents::get_incident))
-        .route("/v1/incidents/{incident_id}/action", post(incidents::apply_incident_action))
-        .route("/v1/zones/{zone_id}/controls", get(controls::get_zone_controls).post(controls::set_zone_controls))
-        .route("/v1/zones/{zone_id}/spool", get(spool::get_spool_stats))
-        .route("/v1/zones/{zone_id}/spool/replay", post(spool::replay_spool))
-        .route("/v1/zones/{zone_id}/audit", get(audit::list_audit))
-        .route("/v1/sim/snapshot", post(admin::snapshot))
-        .route("/v1/sim/restore", post(admin::restore))
-        .layer(middleware::from_fn(cors))
-        .with_state(st);
-
-    let addr: SocketAddr = format!("0.0.0.0:{port}").parse().unwrap();
-    info!(%addr, "sim-rust listening");
-    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app)
-        .with_graceful_shutdown(async move {
-            tokio::signal::ctrl_c().await.ok();
-            info!("shutting down");
-            cancel.cancel();
-        })
-        .await
-        .unwrap();
+    // requests are drained; stop background tasks after their current unit of work
+    cancel.cancel();
+    tasks.close();
+    tasks.wait().await;
+    pool.close();
+    read_pool.close();
+    info!("shutdown complete");
 }
@@ -0,0 +1,285 @@
+use std::time::Duration;
+use time::OffsetDateTime;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::error::AppError;
+use crate::handlers::zones::{change_zone_status, SetZoneStatusRequest, TransitionPolicy};
+use crate::state::AppState;
+use crate::ZoneStatus;
+
+pub use crate::clock::{Clock, SystemClock};
+
+/// Boundary of a maintenance window that has been crossed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transition {
+    /// `starts_at` passed: set the zone DOWN.
+    Start,
+    /// `ends_at` passed after the start was applied: set the zone back to OK,
+    /// unless something else holds its status (see [`MaintenanceScheduler`]).
+    End,
+    /// The whole window passed before its start was applied; close it untouched.
+    Missed,
+}
+
+/// Reason on the SET_ZONE_STATUS audit entry of a window's flips.
+fn window_reason(window_id: &str, reason: &str) -> String {
+    format!("maintenance {window_id}: {reason}")
+}
+
+/// Whether ending window `window_id` should set `zone_id` back to OK: the zone is
+/// still DOWN, its last status change was a window's, and no other window of the
+/// zone has started and not ended. Locks the zone row.
+async fn restores_ok(tx: &deadpool_postgres::Transaction<'_>, zone_id: &str, window_id: &str) -> Result<bool, AppError> {
+    let row = tx
+        .query_opt(
+            "SELECT z.status = 'DOWN' \
+               AND COALESCE((SELECT a.actor = 'system' AND a.reason LIKE 'maintenance %' FROM audit_log a \
+                             WHERE a.target_type='zone' AND a.target_id=z.id AND a.action='SET_ZONE_STATUS' \
+                             ORDER BY a.created_at DESC LIMIT 1), false) \
+               AND NOT EXISTS (SELECT 1 FROM zone_maintenance m WHERE m.zone_id=z.id AND m.id <> $2::uuid \
+                               AND m.started_at IS NOT NULL AND m.ended_at IS NULL) \
+             FROM zones z WHERE z.id=$1 FOR UPDATE",
+            &[&zone_id, &window_id],
+        )
+        .await?;
+    Ok(row.is_some_and(|r| r.get(0)))
+}
+
+/// What an open window (no `ended_at` yet) needs at `now`, if anything.
+pub fn due_transition(
+    starts_at: OffsetDateTime,
+    ends_at: OffsetDateTime,
+    started: bool,
+    now: OffsetDateTime,
+) -> Option<Transition> {
+    match (started, now >= starts_at, now >= ends_at) {
+        (false, _, true) => Some(Transition::Missed),
+        (false, true, false) => Some(Transition::Start),
+        (true, _, true) => Some(Transition::End),
+        _ => None,
+    }
+}
+
+/// Flips zones DOWN and back to OK at the boundaries of their `zone_maintenance`
+/// windows, through the same status change as `POST /v1/zones/{zone_id}/status`
+/// (audit entry, outbox event, DOWN incident), with actor `system`. A window
+/// only sets OK again if its zone is still in the DOWN a window set and no other
+/// window is running; otherwise it is just closed.
+pub struct MaintenanceScheduler<C = SystemClock> {
+    st: AppState,
+    clock: C,
+}
+
+impl<C: Clock> MaintenanceScheduler<C> {
+    pub fn new(st: AppState, clock: C) -> Self {
+        Self { st, clock }
+    }
+
+    pub async fn run(&self, cancel: CancellationToken) {
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => return,
+                _ = interval.tick() => {
+                    if let Err(e) = self.tick().await {
+                        warn!(error = %e, "maintenance window check failed");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Apply every transition due at the clock's current time; returns how many
+    /// zone status changes were made.
+    pub async fn tick(&self) -> Result<usize, AppError> {
+        let now = self.clock.now();
+        let mut client = self.st.db.get().await?;
+        let tx = client.transaction().await?;
+        let rows = tx
+            .query(
+                "SELECT id::text, zone_id, starts_at, ends_at, reason, started_at IS NOT NULL AS started \
+                 FROM zone_maintenance WHERE ended_at IS NULL AND starts_at <= $1 \
+                 ORDER BY starts_at FOR UPDATE SKIP LOCKED",
+                &[&now],
+            )
+            .await?;
+
+        let mut changes = Vec::new();
+        for r in &rows {
+            let id: String = r.get("id");
+            let zone_id: String = r.get("zone_id");
+            let reason: String = r.get("reason");
+            let (status, mark) = match due_transition(r.get("starts_at"), r.get("ends_at"), r.get("started"), now) {
+                Some(Transition::Start) => (ZoneStatus::Down, "UPDATE zone_maintenance SET started_at=$2 WHERE id=$1::uuid"),
+                Some(Transition::End) if restores_ok(&tx, &zone_id, &id).await? => {
+                    (ZoneStatus::Ok, "UPDATE zone_maintenance SET ended_at=$2 WHERE id=$1::uuid")
+                }
+                Some(Transition::End) => {
+                    info!(window_id = %id, zone_id = %zone_id, "maintenance window ended, zone status no longer the window's, leaving it");
+                    tx.execute("UPDATE zone_maintenance SET ended_at=$2 WHERE id=$1::uuid", &[&id, &now]).await?;
+                    continue;
+                }
+                Some(Transition::Missed) => {
+                    warn!(window_id = %id, zone_id = %zone_id, "maintenance window passed before it could start, skipping");
+                    tx.execute("UPDATE zone_maintenance SET ended_at=$2 WHERE id=$1::uuid", &[&id, &now]).await?;
+                    continue;
+                }
+                None => continue,
+            };
+            info!(window_id = %id, zone_id = %zone_id, status = status.as_str(), "maintenance window boundary reached");
+            let req = SetZoneStatusRequest::system(status, window_reason(&id, &reason));
+            // a planned window ends straight back at OK, whatever ZONE_STATUS_TRANSITIONS says
+            changes.push(change_zone_status(&tx, &zone_id, &req, TransitionPolicy::Permissive).await?);
+            tx.execute(mark, &[&id, &now]).await?;
+        }
+        tx.commit().await?;
+
+        let applied = changes.len();
+        for change in changes {
+            change.publish(&self.st);
+        }
+        Ok(applied)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn window_flips_down_then_ok_as_the_clock_passes_its_boundaries() {
+        let clock = FixedClock::at(OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap());
+        let (starts_at, ends_at) = (clock.now() + 10 * MINUTE, clock.now() + 40 * MINUTE);
+        let (mut status, mut started) = ("OK", false);
+        let mut step = |clock: &FixedClock| {
+            match due_transition(starts_at, ends_at, started, clock.now()) {
+                Some(Transition::Start) => (status, started) = ("DOWN", true),
+                Some(Transition::End) => status = "OK",
+                Some(Transition::Missed) => panic!("window missed at {}", clock.now()),
+                None => {}
+            }
+            status
+        };
+
+        assert_eq!(step(&clock), "OK");
+        clock.advance(9 * MINUTE);
+        assert_eq!(step(&clock), "OK");
+        clock.advance(MINUTE);
+        assert_eq!(step(&clock), "DOWN", "flips exactly at starts_at");
+        clock.advance(29 * MINUTE);
+        assert_eq!(step(&clock), "DOWN");
+        clock.advance(2 * MINUTE);
+        assert_eq!(step(&clock), "OK", "flips back once past ends_at");
+    }
+
+    #[test]
+    fn window_that_passed_unseen_is_missed_not_replayed() {
+        let t = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let (starts_at, ends_at) = (t, t + 30 * MINUTE);
+        assert_eq!(due_transition(starts_at, ends_at, false, t + 45 * MINUTE), Some(Transition::Missed));
+        assert_eq!(due_transition(starts_at, ends_at, true, t + 45 * MINUTE), Some(Transition::End));
+        assert_eq!(due_transition(starts_at, ends_at, true, t + 10 * MINUTE), None);
+        assert_eq!(due_transition(starts_at, ends_at, false, t - MINUTE), None);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn scheduler_flips_zone_status_at_window_boundaries() {
+        let st = crate::testing::test_db().await;
+        let zone_id = format!("zone-mt-{}", uuid::Uuid::new_v4());
+        let clock = FixedClock::at(OffsetDateTime::now_utc() + Duration::from_secs(86_400 * 365));
+        let (starts_at, ends_at) = (clock.now() + 10 * MINUTE, clock.now() + 40 * MINUTE);
+        let client = st.db.get().await.unwrap();
+        client
+            .execute("INSERT INTO zones(id,name,status) VALUES($1,'Maintenance test','OK')", &[&zone_id])
+            .await
+            .unwrap();
+        client
+            .execute(
+                "INSERT INTO zone_maintenance(zone_id,starts_at,ends_at,reason,created_by) VALUES($1,$2,$3,'patching','ops')",
+                &[&zone_id, &starts_at, &ends_at],
+            )
+            .await
+            .unwrap();
+        let (db, zone) = (&client, &zone_id);
+        let status = move || async move {
+            db.query_one("SELECT status FROM zones WHERE id=$1", &[zone]).await.unwrap().get::<_, String>(0)
+        };
+
+        let scheduler = MaintenanceScheduler::new(st.clone(), clock.clone());
+        scheduler.tick().await.unwrap();
+        assert_eq!(status().await, "OK");
+
+        clock.advance(11 * MINUTE);
+        scheduler.tick().await.unwrap();
+        assert_eq!(status().await, "DOWN");
+        let open: i64 = client
+            .query_one("SELECT COUNT(*) FROM incidents WHERE zone_id=$1 AND status <> 'RESOLVED'", &[&zone_id])
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(open, 1, "going DOWN opens the zone-down incident");
+
+        clock.advance(30 * MINUTE);
+        scheduler.tick().await.unwrap();
+        assert_eq!(status().await, "OK");
+        let audits: i64 = client
+            .query_one(
+                "SELECT COUNT(*) FROM audit_log WHERE target_id=$1 AND action='SET_ZONE_STATUS' AND actor='system'",
+                &[&zone_id],
+            )
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(audits, 2);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn window_end_leaves_a_status_an_operator_set_during_the_window() {
+        let st = crate::testing::test_db().await;
+        let zone_id = format!("zone-mt-{}", uuid::Uuid::new_v4());
+        let clock = FixedClock::at(OffsetDateTime::now_utc() + Duration::from_secs(86_400 * 365));
+        let (starts_at, ends_at) = (clock.now() + 10 * MINUTE, clock.now() + 40 * MINUTE);
+        let mut client = st.db.get().await.unwrap();
+        client
+            .execute("INSERT INTO zones(id,name,status) VALUES($1,'Maintenance test','OK')", &[&zone_id])
+            .await
+            .unwrap();
+        let window: String = client
+            .query_one(
+                "INSERT INTO zone_maintenance(zone_id,starts_at,ends_at,reason,created_by) VALUES($1,$2,$3,'patching','ops') RETURNING id::text",
+                &[&zone_id, &starts_at, &ends_at],
+            )
+            .await
+            .unwrap()
+            .get(0);
+
+        let scheduler = MaintenanceScheduler::new(st.clone(), clock.clone());
+        clock.advance(11 * MINUTE);
+        assert_eq!(scheduler.tick().await.unwrap(), 1);
+
+        // an operator takes the zone over while the window runs: still DOWN, but theirs now
+        let req: SetZoneStatusRequest =
+            serde_json::from_value(serde_json::json!({"status": "DOWN", "actor": "ops", "reason": "disk failure"})).unwrap();
+        let tx = client.transaction().await.unwrap();
+        change_zone_status(&tx, &zone_id, &req, TransitionPolicy::Permissive).await.unwrap();
+        tx.commit().await.unwrap();
+
+        clock.advance(30 * MINUTE);
+        assert_eq!(scheduler.tick().await.unwrap(), 0, "no status change at the window end");
+        let row = client
+            .query_one(
+                "SELECT z.status, m.ended_at IS NOT NULL FROM zones z JOIN zone_maintenance m ON m.zone_id=z.id WHERE m.id=$1::uuid",
+                &[&window],
+            )
+            .await
+            .unwrap();
+        assert_eq!(row.get::<_, String>(0), "DOWN");
+        assert!(row.get::<_, bool>(1), "the window is closed");
+    }
+}
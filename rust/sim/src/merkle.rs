@@ -0,0 +1,125 @@
+use serde::Serialize;
+
+use crate::util::sha256_hex;
+
+// Leaves and inner nodes use distinct prefixes so a leaf can never be passed
+// off as an inner node (second-preimage protection).
+const LEAF_PREFIX: &str = "leaf:";
+const NODE_PREFIX: &str = "node:";
+
+pub fn leaf_hash(account_id: &str, balance_units: i64) -> String {
+    sha256_hex(format!("{LEAF_PREFIX}{account_id}:{balance_units}").as_bytes())
+}
+
+fn node_hash(left: &str, right: &str) -> String {
+    sha256_hex(format!("{NODE_PREFIX}{left}{right}").as_bytes())
+}
+
+/// Which side of the running hash a sibling sits on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Left,
+    Right,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ProofStep {
+    pub sibling: String,
+    pub side: Side,
+}
+
+/// One tree level up. An unpaired last node is carried up unchanged rather than
+/// duplicated, so no two leaf sets share a root.
+fn next_level(level: &[String]) -> Vec<String> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [l, r] => node_hash(l, r),
+            [single] => single.clone(),
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+/// Root over leaf hashes, in the order given. None for an empty tree.
+pub fn merkle_root(leaves: &[String]) -> Option<String> {
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    level.pop()
+}
+
+/// Inclusion proof for `leaves[index]`, ordered from leaf to root.
+pub fn merkle_proof(leaves: &[String], mut index: usize) -> Option<Vec<ProofStep>> {
+    if index >= leaves.len() {
+        return None;
+    }
+    let mut proof = Vec::new();
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        let sibling = index ^ 1;
+        if sibling < level.len() {
+            let side = if sibling < index { Side::Left } else { Side::Right };
+            proof.push(ProofStep { sibling: level[sibling].clone(), side });
+        }
+        level = next_level(&level);
+        index /= 2;
+    }
+    Some(proof)
+}
+
+pub fn verify_proof(leaf: &str, proof: &[ProofStep], root: &str) -> bool {
+    let computed = proof.iter().fold(leaf.to_string(), |acc, step| match step.side {
+        Side::Left => node_hash(&step.sibling, &acc),
+        Side::Right => node_hash(&acc, &step.sibling),
+    });
+    computed == root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves(n: usize) -> Vec<String> {
+        (0..n).map(|i| leaf_hash(&format!("acct-{i}"), i as i64 * 100)).collect()
+    }
+
+    #[test]
+    fn empty_and_single() {
+        assert_eq!(merkle_root(&[]), None);
+        let one = leaves(1);
+        assert_eq!(merkle_root(&one), Some(one[0].clone()));
+        assert_eq!(merkle_proof(&one, 0), Some(vec![]));
+    }
+
+    #[test]
+    fn every_leaf_proves_against_root() {
+        for n in [2, 3, 5, 8, 13] {
+            let ls = leaves(n);
+            let root = merkle_root(&ls).unwrap();
+            for (i, leaf) in ls.iter().enumerate() {
+                let proof = merkle_proof(&ls, i).unwrap();
+                assert!(verify_proof(leaf, &proof, &root), "n={n} i={i}");
+            }
+        }
+    }
+
+    #[test]
+    fn tampered_balance_fails() {
+        let ls = leaves(5);
+        let root = merkle_root(&ls).unwrap();
+        let proof = merkle_proof(&ls, 2).unwrap();
+        assert!(!verify_proof(&leaf_hash("acct-2", 201), &proof, &root));
+        assert!(merkle_proof(&ls, 5).is_none());
+    }
+
+    #[test]
+    fn odd_leaf_is_not_duplicated() {
+        let three = leaves(3);
+        let mut four = three.clone();
+        four.push(three[2].clone());
+        assert_ne!(merkle_root(&three), merkle_root(&four));
+    }
+}
@@ -0,0 +1,459 @@
+use deadpool_postgres::Pool;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+use super::outbox::with_event_id;
+use super::sink::{OutboxEvent, OutboxSink};
+use crate::state::Metrics;
+
+/// Rows fetched per delivery pass; a full batch is followed by another at once.
+const DELIVERY_BATCH: i64 = 50;
+
+/// Delivers outbox events to an [`OutboxSink`], at least once and oldest first.
+/// Failed rows are retried with exponential backoff; the transfer path never waits on this.
+/// Events of one aggregate stay in order: while a row waits for its retry, the
+/// later rows of the same aggregate wait behind it. Other aggregates carry on.
+///
+/// Runs a pass every `poll_every`, and immediately whenever `wake` is notified
+/// (by [`super::notify::OutboxListener`] when new events commit).
+pub struct OutboxDelivery<S> {
+    db: Pool,
+    sink: S,
+    max_backoff: Duration,
+    metrics: Arc<Metrics>,
+    poll_every: Duration,
+    wake: Option<Arc<Notify>>,
+}
+
+/// Delay before the next attempt after `attempts` failures: 1s, 2s, 4s, ... capped at `max`.
+pub fn backoff_delay(attempts: i32, max: Duration) -> Duration {
+    let exp = attempts.clamp(0, 30) as u32;
+    Duration::from_secs(1).saturating_mul(1u32 << exp).min(max)
+}
+
+/// Outcome of one delivery attempt, as recorded on the outbox row.
+#[derive(Debug, PartialEq)]
+enum Attempt {
+    Delivered,
+    Failed { error: String, retry_in: Duration },
+}
+
+/// Hand `event` to `sink`; `attempts` is the number of earlier failures.
+async fn attempt<S: OutboxSink>(sink: &S, event: &OutboxEvent, attempts: i32, max_backoff: Duration) -> Attempt {
+    match sink.deliver(event).await {
+        Ok(()) => Attempt::Delivered,
+        Err(e) => Attempt::Failed { error: e.0, retry_in: backoff_delay(attempts, max_backoff) },
+    }
+}
+
+impl<S: OutboxSink> OutboxDelivery<S> {
+    pub fn new(db: Pool, sink: S, max_backoff: Duration, metrics: Arc<Metrics>) -> Self {
+        Self { db, sink, max_backoff, metrics, poll_every: Duration::from_millis(500), wake: None }
+    }
+
+    /// Poll every `poll_every` (default 500ms) and, given `wake`, also deliver
+    /// as soon as it is notified.
+    pub fn with_polling(mut self, poll_every: Duration, wake: Option<Arc<Notify>>) -> Self {
+        self.poll_every = poll_every;
+        self.wake = wake;
+        self
+    }
+
+    pub async fn run(&self, cancel: CancellationToken) {
+        let mut interval = tokio::time::interval(self.poll_every);
+        let woken = || async {
+            match &self.wake {
+                Some(wake) => wake.notified().await,
+                None => std::future::pending().await,
+            }
+        };
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => return,
+                _ = interval.tick() => {}
+                _ = woken() => {}
+            }
+            loop {
+                match self.deliver_batch(DELIVERY_BATCH, &cancel).await {
+                    Ok(n) if n as i64 == DELIVERY_BATCH && !cancel.is_cancelled() => continue,
+                    Ok(_) => break,
+                    Err(e) => {
+                        warn!(error = %e, "outbox delivery batch failed");
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Delivers due rows one by one and returns how many were fetched; on
+    /// shutdown, stops after the row in flight. Only the oldest undelivered row
+    /// of each aggregate is due, so a batch never holds two of the same aggregate.
+    async fn deliver_batch(&self, limit: i64, cancel: &CancellationToken) -> Result<usize, Box<dyn std::error::Error>> {
+        let client = self.db.get().await?;
+        let rows = client
+            .query(
+                "SELECT o.id::text, o.event_type, o.aggregate_id, o.attempts, o.payload FROM outbox_events o \
+                 WHERE o.delivered_at IS NULL AND o.next_attempt_at <= now() \
+                 AND NOT EXISTS (SELECT 1 FROM outbox_events e WHERE e.aggregate_type=o.aggregate_type AND e.aggregate_id=o.aggregate_id \
+                                 AND e.delivered_at IS NULL AND (e.created_at, e.id) < (o.created_at, o.id)) \
+                 ORDER BY o.created_at, o.id LIMIT $1",
+                &[&limit],
+            )
+            .await?;
+
+        for row in &rows {
+            if cancel.is_cancelled() {
+                break;
+            }
+            let id: String = row.get("id");
+            let attempts: i32 = row.get("attempts");
+            let payload = with_event_id(row.get("payload"), &id);
+            let event = OutboxEvent { id, event_type: row.get("event_type"), aggregate_id: row.get("aggregate_id"), payload };
+
+            match attempt(&self.sink, &event, attempts, self.max_backoff).await {
+                Attempt::Delivered => {
+                    self.metrics.outbox_delivered_total.inc();
+                    client
+                        .execute("UPDATE outbox_events SET delivered_at=now() WHERE id=$1::uuid", &[&event.id])
+                        .await?;
+                }
+                Attempt::Failed { error, retry_in } => {
+                    self.metrics.outbox_failed_total.inc();
+                    warn!(event_id = %event.id, attempts = attempts + 1, error = %error, "outbox delivery failed");
+                    client
+                        .execute(
+                            "UPDATE outbox_events SET attempts=attempts+1, next_attempt_at=now() + make_interval(secs => $2), last_error=$3 WHERE id=$1::uuid",
+                            &[&event.id, &retry_in.as_secs_f64(), &error],
+                        )
+                        .await?;
+                }
+            }
+        }
+
+        Ok(rows.len())
+    }
+}
+
+/// Keeps the `outbox_pending_events` gauge at the number of undelivered outbox rows.
+pub struct OutboxDepthGauge {
+    db: Pool,
+    metrics: Arc<Metrics>,
+    every: Duration,
+}
+
+impl OutboxDepthGauge {
+    pub fn new(db: Pool, metrics: Arc<Metrics>, every: Duration) -> Self {
+        Self { db, metrics, every }
+    }
+
+    pub async fn run(&self, cancel: CancellationToken) {
+        let mut interval = tokio::time::interval(self.every);
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => return,
+                _ = interval.tick() => {
+                    if let Err(e) = self.refresh().await {
+                        warn!(error = %e, "outbox depth refresh failed");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Count undelivered rows now and set the gauge; returns the count.
+    pub async fn refresh(&self) -> Result<i64, Box<dyn std::error::Error>> {
+        let client = self.db.get().await?;
+        let pending: i64 = client
+            .query_one("SELECT count(*) FROM outbox_events WHERE delivered_at IS NULL", &[])
+            .await?
+            .get(0);
+        self.metrics.outbox_pending_events.set(pending);
+        Ok(pending)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messaging::sink::{SinkError, VecSink};
+
+    fn event(id: &str) -> OutboxEvent {
+        OutboxEvent {
+            id: id.into(),
+            event_type: "TransferPosted".into(),
+            aggregate_id: format!("txn-{id}"),
+            payload: serde_json::json!({ "event_id": id, "type": "TransferPosted" }),
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_per_attempt() {
+        let max = Duration::from_secs(300);
+        assert_eq!(backoff_delay(0, max), Duration::from_secs(1));
+        assert_eq!(backoff_delay(1, max), Duration::from_secs(2));
+        assert_eq!(backoff_delay(5, max), Duration::from_secs(32));
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let max = Duration::from_secs(60);
+        assert_eq!(backoff_delay(10, max), max);
+        assert_eq!(backoff_delay(i32::MAX, max), max);
+        assert_eq!(backoff_delay(-3, max), Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn pending_gauge_counts_a_new_transfer_before_delivery() {
+        use crate::handlers::{admin, transfers};
+        use axum::{extract::{Query, State}, response::IntoResponse, Json};
+        use http_body_util::BodyExt;
+
+        let st = crate::testing::test_db().await;
+        let run = uuid::Uuid::new_v4();
+        let req: transfers::CreateTransferRequest = serde_json::from_value(serde_json::json!({
+            "request_id": format!("req-{run}"),
+            "from_account": format!("acct-a-{run}"),
+            "to_account": format!("acct-b-{run}"),
+            "amount_units": 100,
+            "zone_id": "zone-eu",
+        }))
+        .unwrap();
+        let q = transfers::CreateTransferQuery { include_balances: false, dry_run: false };
+        transfers::create_transfer(State(st.clone()), Query(q), Default::default(), None, Ok(Json(req))).await.unwrap();
+
+        // no OutboxDelivery is running, so the transfer's event is still pending
+        let gauge = OutboxDepthGauge::new(st.db.clone(), st.metrics.clone(), Duration::from_secs(5));
+        gauge.refresh().await.unwrap();
+        let res = admin::metrics(State(st), Default::default()).await.unwrap().into_response();
+        let text = String::from_utf8(res.into_body().collect().await.unwrap().to_bytes().to_vec()).unwrap();
+        let pending: i64 = text
+            .lines()
+            .find_map(|l| l.strip_prefix("outbox_pending_events "))
+            .expect("outbox_pending_events exposed")
+            .parse()
+            .unwrap();
+        assert!(pending >= 1, "{text}");
+    }
+
+    /// Passes on only events mentioning `marker`; the rest of a shared test
+    /// database's backlog fails and is left undelivered.
+    struct MarkedSink {
+        marker: String,
+        tx: tokio::sync::mpsc::UnboundedSender<OutboxEvent>,
+    }
+
+    impl OutboxSink for MarkedSink {
+        async fn deliver(&self, event: &OutboxEvent) -> Result<(), SinkError> {
+            if !event.payload.to_string().contains(&self.marker) {
+                return Err(SinkError(format!("{} belongs to another test", event.id)));
+            }
+            let _ = self.tx.send(event.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn notify_wakes_delivery_well_before_the_next_poll() {
+        use crate::handlers::transfers;
+        use crate::messaging::notify::OutboxListener;
+        use axum::{extract::{Query, State}, Json};
+        use std::time::Instant;
+
+        let url = crate::testing::test_db_url();
+        let st = crate::app::build_state(crate::config::Config::new(url.clone())).await.unwrap();
+        let run = uuid::Uuid::new_v4();
+        let cancel = CancellationToken::new();
+
+        let wake = Arc::new(Notify::new());
+        let listener = OutboxListener::new(url, wake.clone());
+        let listening = listener.listening();
+        let c = cancel.clone();
+        tokio::spawn(async move { listener.run(c).await });
+        tokio::time::timeout(Duration::from_secs(5), listening.notified()).await.expect("LISTEN established");
+
+        // with an hour between polls only the notification can deliver in time
+        let (tx, mut delivered) = tokio::sync::mpsc::unbounded_channel();
+        let sink = MarkedSink { marker: run.to_string(), tx };
+        let delivery = OutboxDelivery::new(st.db.clone(), sink, Duration::from_secs(300), st.metrics.clone())
+            .with_polling(Duration::from_secs(3600), Some(wake));
+        let c = cancel.clone();
+        tokio::spawn(async move { delivery.run(c).await });
+        // let the first pass (the interval's immediate tick) clear the backlog
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        let started = Instant::now();
+        let req: transfers::CreateTransferRequest = serde_json::from_value(serde_json::json!({
+            "request_id": format!("req-{run}"),
+            "from_account": format!("acct-a-{run}"),
+            "to_account": format!("acct-b-{run}"),
+            "amount_units": 100,
+            "zone_id": "zone-eu",
+        }))
+        .unwrap();
+        let q = transfers::CreateTransferQuery { include_balances: false, dry_run: false };
+        transfers::create_transfer(State(st.clone()), Query(q), Default::default(), None, Ok(Json(req))).await.unwrap();
+
+        let event = tokio::time::timeout(Duration::from_secs(1), delivered.recv())
+            .await
+            .expect("delivered within a second of the transfer")
+            .unwrap();
+        assert_eq!(event.event_type, "TransferPosted");
+        assert!(started.elapsed() < Duration::from_secs(1), "{:?}", started.elapsed());
+        cancel.cancel();
+    }
+
+    /// Fails each event of `fail_once` the first time it is offered; records the
+    /// `seq` of everything delivered. Events without `marker` always fail.
+    struct ScriptedSink {
+        marker: String,
+        fail_once: std::sync::Mutex<std::collections::HashSet<String>>,
+        delivered: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl OutboxSink for ScriptedSink {
+        async fn deliver(&self, event: &OutboxEvent) -> Result<(), SinkError> {
+            if event.payload["marker"] != self.marker.as_str() {
+                return Err(SinkError(format!("{} belongs to another test", event.id)));
+            }
+            let seq = event.payload["seq"].as_str().unwrap().to_string();
+            if self.fail_once.lock().unwrap().remove(&seq) {
+                return Err(SinkError(format!("injected failure for {seq}")));
+            }
+            self.delivered.lock().unwrap().push(seq);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn failed_event_holds_back_its_aggregate_only() {
+        let st = crate::testing::test_db().await;
+        let run = uuid::Uuid::new_v4().to_string();
+        let client = st.db.get().await.unwrap();
+        let mut ids = std::collections::HashMap::new();
+        for (i, (aggregate, seq)) in [("a", "a1"), ("b", "b1"), ("a", "a2"), ("a", "a3")].into_iter().enumerate() {
+            let row = client
+                .query_one(
+                    "INSERT INTO outbox_events(event_type,aggregate_type,aggregate_id,payload,created_at) \
+                     VALUES('TransferPosted','test',$1,$2,now() + make_interval(secs => $3)) RETURNING id::text",
+                    &[&format!("agg-{aggregate}-{run}"), &serde_json::json!({ "marker": run, "seq": seq }), &(i as f64 / 1000.0)],
+                )
+                .await
+                .unwrap();
+            ids.insert(seq, row.get::<_, String>(0));
+        }
+        let sink = ScriptedSink {
+            marker: run.clone(),
+            fail_once: std::sync::Mutex::new(["a1".to_string()].into()),
+            delivered: Default::default(),
+        };
+        let delivery = OutboxDelivery::new(st.db.clone(), sink, Duration::from_secs(300), st.metrics.clone());
+        let cancel = CancellationToken::new();
+        let delivered = || delivery.sink.delivered.lock().unwrap().clone();
+
+        delivery.deliver_batch(10_000, &cancel).await.unwrap();
+        assert_eq!(delivered(), ["b1"], "a1 failed; b1 is another aggregate");
+        delivery.deliver_batch(10_000, &cancel).await.unwrap();
+        assert_eq!(delivered(), ["b1"], "a2 and a3 wait for a1's retry");
+
+        // a1's backoff runs out
+        client
+            .execute("UPDATE outbox_events SET next_attempt_at=now() WHERE id=$1::uuid", &[&ids["a1"]])
+            .await
+            .unwrap();
+        for _ in 0..3 {
+            delivery.deliver_batch(10_000, &cancel).await.unwrap();
+        }
+        assert_eq!(delivered(), ["b1", "a1", "a2", "a3"]);
+        let attempts: i32 = client
+            .query_one("SELECT attempts FROM outbox_events WHERE id=$1::uuid", &[&ids["a1"]])
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn events_reach_the_sink_in_order() {
+        let sink = VecSink::default();
+        let max = Duration::from_secs(300);
+        for id in ["e1", "e2", "e3"] {
+            assert_eq!(attempt(&sink, &event(id), 0, max).await, Attempt::Delivered);
+        }
+        let ids: Vec<String> = sink.events().into_iter().map(|e| e.id).collect();
+        assert_eq!(ids, ["e1", "e2", "e3"]);
+    }
+
+    #[tokio::test]
+    async fn webhook_answering_500_then_200_gets_the_event_again() {
+        use crate::messaging::webhook::{sign_webhook, WebhookSink};
+        use axum::{body::Bytes, extract::State, http::{HeaderMap, StatusCode}};
+        use std::sync::Mutex;
+
+        // answers 500 to the first request and 200 afterwards, keeping every request
+        type Seen = Arc<Mutex<Vec<(HeaderMap, Bytes)>>>;
+        let seen: Seen = Default::default();
+        let app = axum::Router::new()
+            .route(
+                "/hook",
+                axum::routing::post(|State(seen): State<Seen>, headers: HeaderMap, body: Bytes| async move {
+                    let mut seen = seen.lock().unwrap();
+                    seen.push((headers, body));
+                    if seen.len() == 1 { StatusCode::INTERNAL_SERVER_ERROR } else { StatusCode::OK }
+                }),
+            )
+            .with_state(seen.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let sink = WebhookSink::new(format!("http://{addr}/hook"), Some("whsec_test".into()));
+        let e = event("e1");
+        let max = Duration::from_secs(300);
+        let Attempt::Failed { error, retry_in } = attempt(&sink, &e, 0, max).await else { panic!("the 500 must fail") };
+        assert!(error.contains("500"), "{error}");
+        assert_eq!(retry_in, Duration::from_secs(1));
+        assert_eq!(attempt(&sink, &e, 1, max).await, Attempt::Delivered);
+
+        // at least once: the retry carries the same event, signed afresh
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 2);
+        assert_eq!(seen[0].1, seen[1].1);
+        let sent: serde_json::Value = serde_json::from_slice(&seen[1].1).unwrap();
+        assert_eq!(sent["event_id"], "e1");
+        for (headers, body) in seen.iter() {
+            let ts: i64 = headers["x-timestamp"].to_str().unwrap().parse().unwrap();
+            assert_eq!(headers["x-signature"], sign_webhook("whsec_test", ts, body).as_str());
+        }
+    }
+
+    #[tokio::test]
+    async fn transient_failures_are_retried_with_backoff() {
+        let sink = VecSink::default();
+        let max = Duration::from_secs(300);
+        sink.fail_next(2);
+
+        let e = event("e1");
+        let mut attempts = 0;
+        let mut delays = Vec::new();
+        loop {
+            match attempt(&sink, &e, attempts, max).await {
+                Attempt::Delivered => break,
+                Attempt::Failed { error, retry_in } => {
+                    assert!(error.contains("e1"));
+                    delays.push(retry_in);
+                    attempts += 1;
+                }
+            }
+        }
+        assert_eq!(delays, [Duration::from_secs(1), Duration::from_secs(2)]);
+        // delivered exactly once, after the failures
+        assert_eq!(sink.events(), vec![e]);
+    }
+}
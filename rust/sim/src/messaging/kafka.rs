@@ -0,0 +1,134 @@
+use std::time::Duration;
+
+use rdkafka::config::ClientConfig;
+use rdkafka::message::{Header, OwnedHeaders};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+
+use super::outbox::event_name;
+use super::sink::{OutboxEvent, OutboxSink, SinkError};
+
+/// How long a record may wait in the producer queue before the attempt counts as failed.
+const QUEUE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Publishes each outbox event to Kafka and reports success only once the broker
+/// has acknowledged it (`acks=all`). Records are keyed by `aggregate_id`, so all
+/// events of one transaction or zone land on the same partition in order.
+pub struct KafkaSink {
+    producer: FutureProducer,
+    topic: String,
+}
+
+/// Topic for `event`: `{event_type}` in `template` becomes the snake-case event
+/// type, so `ledger.{event_type}` routes `TransferPosted` to `ledger.transfer_posted`.
+pub fn topic_for(template: &str, event: &OutboxEvent) -> String {
+    template.replace("{event_type}", &event_name(&event.event_type))
+}
+
+impl KafkaSink {
+    /// `brokers` is a comma-separated bootstrap list; `topic` may contain `{event_type}`.
+    pub fn new(brokers: &str, topic: String) -> Result<Self, String> {
+        let producer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set("acks", "all")
+            .set("enable.idempotence", "true")
+            .set("message.timeout.ms", "30000")
+            .create()
+            .map_err(|e| format!("kafka producer: {e}"))?;
+        Ok(Self { producer, topic })
+    }
+}
+
+impl OutboxSink for KafkaSink {
+    async fn deliver(&self, event: &OutboxEvent) -> Result<(), SinkError> {
+        let body = serde_json::to_vec(&event.payload).map_err(|e| SinkError(e.to_string()))?;
+        let topic = topic_for(&self.topic, event);
+        let headers = OwnedHeaders::new()
+            .insert(Header { key: "event_id", value: Some(event.id.as_str()) })
+            .insert(Header { key: "event_type", value: Some(event.event_type.as_str()) });
+        let record = FutureRecord::to(&topic).key(&event.aggregate_id).payload(&body).headers(headers);
+        self.producer
+            .send(record, QUEUE_TIMEOUT)
+            .await
+            .map(|_| ())
+            .map_err(|(e, _)| SinkError(format!("kafka: {e}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(event_type: &str, aggregate_id: &str) -> OutboxEvent {
+        OutboxEvent {
+            id: "evt-1".into(),
+            event_type: event_type.into(),
+            aggregate_id: aggregate_id.into(),
+            payload: serde_json::json!({ "event_id": "evt-1" }),
+        }
+    }
+
+    #[test]
+    fn topic_per_event_type_or_fixed() {
+        let posted = event("TransferPosted", "txn-1");
+        assert_eq!(topic_for("ledger.{event_type}", &posted), "ledger.transfer_posted");
+        assert_eq!(topic_for("ledger-outbox", &posted), "ledger-outbox");
+        assert_eq!(topic_for("{event_type}", &event("ZoneStatusChanged", "zone-eu")), "zone_status_changed");
+    }
+}
+
+/// Needs Docker: `cargo test --features kafka-it -- kafka`.
+#[cfg(all(test, feature = "kafka-it"))]
+mod integration {
+    use super::*;
+    use rdkafka::consumer::{Consumer, StreamConsumer};
+    use rdkafka::Message;
+    use testcontainers_modules::{kafka, testcontainers::runners::AsyncRunner};
+
+    #[tokio::test]
+    async fn kafka_sink_publishes_keyed_events_in_order() {
+        let node = kafka::Kafka::default().start().await.unwrap();
+        let brokers = format!("127.0.0.1:{}", node.get_host_port_ipv4(kafka::KAFKA_PORT).await.unwrap());
+
+        let sink = KafkaSink::new(&brokers, "ledger.{event_type}".into()).unwrap();
+        for (id, txn) in [("evt-1", "txn-1"), ("evt-2", "txn-1"), ("evt-3", "txn-2")] {
+            let event = OutboxEvent {
+                id: id.into(),
+                event_type: "TransferPosted".into(),
+                aggregate_id: txn.into(),
+                payload: serde_json::json!({ "event_id": id, "transaction_id": txn }),
+            };
+            sink.deliver(&event).await.unwrap();
+        }
+
+        let consumer: StreamConsumer = ClientConfig::new()
+            .set("bootstrap.servers", &brokers)
+            .set("group.id", "kafka-sink-test")
+            .set("auto.offset.reset", "earliest")
+            .create()
+            .unwrap();
+        consumer.subscribe(&["ledger.transfer_posted"]).unwrap();
+
+        let mut seen = Vec::new();
+        while seen.len() < 3 {
+            let msg = tokio::time::timeout(Duration::from_secs(30), consumer.recv()).await.unwrap().unwrap();
+            let key = String::from_utf8(msg.key().unwrap().to_vec()).unwrap();
+            let payload: serde_json::Value = serde_json::from_slice(msg.payload().unwrap()).unwrap();
+            seen.push((key, payload["event_id"].as_str().unwrap().to_string()));
+        }
+        // one partition per auto-created topic, so delivery order is kept
+        let expected = [("txn-1", "evt-1"), ("txn-1", "evt-2"), ("txn-2", "evt-3")];
+        assert_eq!(seen, expected.map(|(k, e)| (k.to_string(), e.to_string())));
+    }
+
+    #[tokio::test]
+    async fn unreachable_broker_leaves_the_event_for_retry() {
+        let sink = KafkaSink::new("127.0.0.1:1", "ledger-outbox".into()).unwrap();
+        let event = OutboxEvent {
+            id: "evt-1".into(),
+            event_type: "TransferPosted".into(),
+            aggregate_id: "txn-1".into(),
+            payload: serde_json::json!({}),
+        };
+        assert!(sink.deliver(&event).await.is_err());
+    }
+}
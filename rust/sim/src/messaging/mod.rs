@@ -1,3 +1,9 @@
+pub mod delivery;
 pub mod fraud;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+pub mod notify;
 pub mod outbox;
+pub mod sink;
 pub mod streams;
+pub mod webhook;
@@ -0,0 +1,83 @@
+use futures::{stream, StreamExt};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, Notify};
+use tokio_postgres::{AsyncMessage, NoTls};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// Channel the `outbox_events` insert trigger notifies (migration 0024).
+pub const OUTBOX_CHANNEL: &str = "outbox_new";
+
+/// Keeps a dedicated connection `LISTEN`ing on [`OUTBOX_CHANNEL`] and wakes
+/// [`super::delivery::OutboxDelivery`] as soon as new events commit. A lost
+/// connection is reopened after a pause; meanwhile delivery falls back to polling.
+pub struct OutboxListener {
+    database_url: String,
+    wake: Arc<Notify>,
+    /// Gets a permit each time `LISTEN` is (re-)established.
+    listening: Arc<Notify>,
+}
+
+impl OutboxListener {
+    pub fn new(database_url: impl Into<String>, wake: Arc<Notify>) -> Self {
+        Self { database_url: database_url.into(), wake, listening: Arc::new(Notify::new()) }
+    }
+
+    /// Resolves once notifications are being received, so tests can start
+    /// writing without racing the `LISTEN`.
+    pub fn listening(&self) -> Arc<Notify> {
+        self.listening.clone()
+    }
+
+    pub async fn run(&self, cancel: CancellationToken) {
+        loop {
+            match self.listen(&cancel).await {
+                Ok(()) => return,
+                Err(e) => warn!(error = %e, "outbox LISTEN connection lost, polling until it is back"),
+            }
+            tokio::select! {
+                _ = cancel.cancelled() => return,
+                _ = tokio::time::sleep(Duration::from_secs(5)) => {}
+            }
+        }
+    }
+
+    /// Listen until cancelled (`Ok`) or until the connection fails.
+    async fn listen(&self, cancel: &CancellationToken) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let (client, mut connection) = tokio_postgres::connect(&self.database_url, NoTls).await?;
+        // the connection has to be polled for LISTEN itself to complete
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let driver = tokio::spawn(async move {
+            let mut messages = stream::poll_fn(move |cx| connection.poll_message(cx));
+            while let Some(message) = messages.next().await {
+                if tx.send(message?).is_err() {
+                    break;
+                }
+            }
+            Ok::<_, tokio_postgres::Error>(())
+        });
+
+        client.batch_execute(&format!("LISTEN {OUTBOX_CHANNEL}")).await?;
+        info!(channel = OUTBOX_CHANNEL, "listening for outbox inserts");
+        // events may have committed while nobody was listening
+        self.wake.notify_one();
+        self.listening.notify_one();
+
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => {
+                    driver.abort();
+                    return Ok(());
+                }
+                message = rx.recv() => match message {
+                    Some(AsyncMessage::Notification(_)) => self.wake.notify_one(),
+                    Some(_) => {}
+                    None => break,
+                },
+            }
+        }
+        driver.await??;
+        Err("LISTEN connection closed".into())
+    }
+}
@@ -4,6 +4,17 @@ use std::time::Duration;
 use tokio_util::sync::CancellationToken;
 use tracing::warn;
 
+/// Replace the `event_id` placeholder written at insert time with the outbox row id.
+pub fn with_event_id(mut payload: serde_json::Value, id: &str) -> serde_json::Value {
+    if let Some(obj) = payload.as_object_mut() {
+        let eid = obj.get("event_id").and_then(|v| v.as_str()).unwrap_or("");
+        if eid.is_empty() || eid == "generated_by_db" {
+            obj.insert("event_id".into(), serde_json::json!(id));
+        }
+    }
+    payload
+}
+
 pub struct OutboxPublisher {
     db: Pool,
     js: jetstream::Context,
@@ -43,24 +54,17 @@ impl OutboxPublisher {
 
         for row in &rows {
             let id: String = row.get("id");
+            let event_type: String = row.get("event_type");
             let payload: serde_json::Value = row.get("payload");
 
-            // replace event_id if still placeholder
-            let mut m = payload;
-            if let Some(obj) = m.as_object_mut() {
-                let eid = obj.get("event_id").and_then(|v| v.as_str()).unwrap_or("");
-                if eid.is_empty() || eid == "generated_by_db" {
-                    obj.insert("event_id".into(), serde_json::json!(id));
-                }
-            }
-            let body = serde_json::to_vec(&m)?;
+            let body = serde_json::to_vec(&with_event_id(payload, &id))?;
 
             // publish with Nats-Msg-Id for JetStream dedup
             let mut headers = async_nats::HeaderMap::new();
             headers.insert("Nats-Msg-Id", id.as_str());
 
             self.js
-                .publish_with_headers::<String>("events.transfer_posted".into(), headers, body.into())
+                .publish_with_headers::<String>(subject_for(&event_type), headers, body.into())
                 .await?
                 .await?;
 
@@ -72,3 +76,39 @@ impl OutboxPublisher {
         Ok(())
     }
 }
+
+/// JetStream subject for an outbox event type: `TransferPosted` -> `events.transfer_posted`.
+fn subject_for(event_type: &str) -> String {
+    format!("events.{}", event_name(event_type))
+}
+
+/// Snake-case name of an event type: `TransferPosted` -> `transfer_posted`.
+pub(crate) fn event_name(event_type: &str) -> String {
+    let mut name = String::new();
+    for (i, c) in event_type.chars().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            name.push('_');
+        }
+        name.push(c.to_ascii_lowercase());
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subject_per_event_type() {
+        assert_eq!(subject_for("TransferPosted"), "events.transfer_posted");
+        assert_eq!(subject_for("ZoneStatusChanged"), "events.zone_status_changed");
+    }
+
+    #[test]
+    fn with_event_id_replaces_placeholder_only() {
+        let p = with_event_id(serde_json::json!({"event_id": "generated_by_db"}), "abc");
+        assert_eq!(p["event_id"], "abc");
+        let p = with_event_id(serde_json::json!({"event_id": "keep"}), "abc");
+        assert_eq!(p["event_id"], "keep");
+    }
+}
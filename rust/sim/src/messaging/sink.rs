@@ -0,0 +1,78 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// One outbox row as handed to a sink. `payload` already carries the row id as `event_id`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutboxEvent {
+    pub id: String,
+    pub event_type: String,
+    /// Id of the transaction or zone the event is about.
+    pub aggregate_id: String,
+    pub payload: serde_json::Value,
+}
+
+/// A failed delivery. The row stays undelivered and is retried with backoff.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SinkError(pub String);
+
+impl std::fmt::Display for SinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Destination for outbox events, driven by [`super::delivery::OutboxDelivery`].
+/// Delivery is at least once, so sinks must tolerate repeats of the same `event.id`.
+pub trait OutboxSink: Send + Sync {
+    fn deliver(&self, event: &OutboxEvent) -> impl Future<Output = Result<(), SinkError>> + Send;
+}
+
+/// Which sink `OUTBOX_SINK` selects.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutboxSinkKind {
+    None,
+    Webhook,
+    /// Needs a build with the `kafka` feature.
+    Kafka,
+}
+
+impl OutboxSinkKind {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Some(Self::None),
+            "webhook" => Some(Self::Webhook),
+            "kafka" => Some(Self::Kafka),
+            _ => None,
+        }
+    }
+}
+
+/// Keeps delivered events in memory, in delivery order. [`VecSink::fail_next`]
+/// makes the following deliveries fail, to exercise retries.
+#[derive(Default)]
+pub struct VecSink {
+    events: Mutex<Vec<OutboxEvent>>,
+    failures: AtomicUsize,
+}
+
+impl VecSink {
+    pub fn fail_next(&self, n: usize) {
+        self.failures.store(n, Ordering::SeqCst);
+    }
+
+    pub fn events(&self) -> Vec<OutboxEvent> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+impl OutboxSink for VecSink {
+    async fn deliver(&self, event: &OutboxEvent) -> Result<(), SinkError> {
+        let failing = self.failures.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1)).is_ok();
+        if failing {
+            return Err(SinkError(format!("injected failure for {}", event.id)));
+        }
+        self.events.lock().unwrap().push(event.clone());
+        Ok(())
+    }
+}
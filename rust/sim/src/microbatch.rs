@@ -0,0 +1,212 @@
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+use crate::auth::Principal;
+use crate::error::AppError;
+use crate::handlers::transfers::{process_transfer, CreateTransferRequest, TransferOutcome};
+use crate::state::AppState;
+
+/// A transfer and who sent it, waiting for the writer, with the channel its handler awaits.
+pub type PendingTransfer = (CreateTransferRequest, Principal, oneshot::Sender<Result<TransferOutcome, AppError>>);
+
+/// Hand a transfer to the micro-batch writer and wait until its batch commits.
+pub async fn submit(
+    batcher: &mpsc::Sender<PendingTransfer>,
+    req: CreateTransferRequest,
+    principal: Principal,
+) -> Result<TransferOutcome, AppError> {
+    let (reply, rx) = oneshot::channel();
+    batcher
+        .send((req, principal, reply))
+        .await
+        .map_err(|_| AppError::Unavailable("transfer writer stopped".into()))?;
+    rx.await
+        .map_err(|_| AppError::Internal("transfer writer dropped the request".into()))?
+}
+
+/// Gather items arriving within `window` of `first`, up to `max` in total, in arrival order.
+pub async fn collect_batch<T>(rx: &mut mpsc::Receiver<T>, first: T, window: Duration, max: usize) -> Vec<T> {
+    let mut batch = vec![first];
+    let deadline = tokio::time::Instant::now() + window;
+    while batch.len() < max {
+        match tokio::time::timeout_at(deadline, rx.recv()).await {
+            Ok(Some(item)) => batch.push(item),
+            Ok(None) | Err(_) => break,
+        }
+    }
+    batch
+}
+
+/// Single writer for micro-batching mode: transfers arriving within a short
+/// window are applied in arrival order in one DB transaction, each inside its
+/// own savepoint so a rejected transfer does not affect the others. Replies are
+/// sent only once the batch has committed; if it fails, every transfer in it gets
+/// that error, so a pool timeout still answers 503.
+pub struct MicroBatcher {
+    st: AppState,
+    rx: mpsc::Receiver<PendingTransfer>,
+    window: Duration,
+    max_batch: usize,
+}
+
+impl MicroBatcher {
+    pub fn new(st: AppState, rx: mpsc::Receiver<PendingTransfer>, window: Duration, max_batch: usize) -> Self {
+        Self { st, rx, window, max_batch: max_batch.max(1) }
+    }
+
+    pub async fn run(mut self, cancel: CancellationToken) {
+        loop {
+            let first = tokio::select! {
+                _ = cancel.cancelled() => return,
+                item = self.rx.recv() => match item {
+                    Some(item) => item,
+                    None => return,
+                },
+            };
+            let batch = collect_batch(&mut self.rx, first, self.window, self.max_batch).await;
+            self.write_batch(batch).await;
+        }
+    }
+
+    async fn write_batch(&self, batch: Vec<PendingTransfer>) {
+        let mut replies = Vec::with_capacity(batch.len());
+        let mut pending = batch.into_iter();
+        let committed: Result<(), AppError> = async {
+            let mut client = self.st.db.get().await?;
+            let tx = client.transaction().await?;
+            for (req, principal, reply) in pending.by_ref() {
+                tx.batch_execute("SAVEPOINT transfer_item").await?;
+                match process_transfer(&self.st, &tx, req, &principal, true).await {
+                    Ok(outcome) => {
+                        tx.batch_execute("RELEASE SAVEPOINT transfer_item").await?;
+                        replies.push((reply, Ok(outcome)));
+                    }
+                    Err(e) => {
+                        tx.batch_execute("ROLLBACK TO SAVEPOINT transfer_item").await?;
+                        replies.push((reply, Err(e)));
+                    }
+                }
+            }
+            tx.commit().await?;
+            Ok(())
+        }
+        .await;
+
+        match committed {
+            Ok(()) => {
+                for (reply, result) in replies {
+                    let _ = reply.send(result);
+                }
+            }
+            Err(e) => {
+                // nothing in the batch was committed, including items not yet reached
+                warn!(error = ?e, "micro-batch write failed");
+                let unreached = pending.map(|(_, _, reply)| reply);
+                for reply in replies.into_iter().map(|(reply, _)| reply).chain(unreached) {
+                    let _ = reply.send(Err(e.clone()));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn batch_is_capped_and_ordered() {
+        let (tx, mut rx) = mpsc::channel(16);
+        for i in 1..=5 {
+            tx.send(i).await.unwrap();
+        }
+        let batch = collect_batch(&mut rx, 0, Duration::from_millis(50), 4).await;
+        assert_eq!(batch, vec![0, 1, 2, 3]);
+        let rest = collect_batch(&mut rx, 99, Duration::from_millis(5), 4).await;
+        assert_eq!(rest, vec![99, 4, 5]);
+    }
+
+    #[tokio::test]
+    async fn window_closes_on_sender_drop() {
+        let (tx, mut rx) = mpsc::channel::<u32>(4);
+        drop(tx);
+        assert_eq!(collect_batch(&mut rx, 7, Duration::from_secs(60), 10).await, vec![7]);
+    }
+
+    /// Writer over a database that accepts connections but never answers.
+    async fn stalled_writer() -> (mpsc::Sender<PendingTransfer>, std::net::TcpListener) {
+        let silent = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut config = crate::config::Config::new(format!("postgres://ledger@127.0.0.1:{}/ledger", silent.local_addr().unwrap().port()));
+        config.pool.acquire_timeout = Some(Duration::from_millis(200));
+        let st = crate::app::build_state(config).await.unwrap();
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(MicroBatcher::new(st, rx, Duration::from_millis(50), 8).run(CancellationToken::new()));
+        (tx, silent)
+    }
+
+    fn transfer(request_id: &str) -> CreateTransferRequest {
+        serde_json::from_value(serde_json::json!({
+            "request_id": request_id,
+            "from_account": "acct-a",
+            "to_account": "acct-b",
+            "amount_units": 100,
+            "zone_id": "zone-eu",
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn failed_batch_keeps_the_error_status() {
+        let (batcher, _db) = stalled_writer().await;
+        let (a, b) = tokio::join!(
+            submit(&batcher, transfer("req-1"), Principal::anonymous()),
+            submit(&batcher, transfer("req-2"), Principal::anonymous()),
+        );
+        for res in [a, b] {
+            let err = res.err().expect("the batch cannot commit");
+            assert_eq!(err.status_and_code().0, axum::http::StatusCode::SERVICE_UNAVAILABLE, "{err}");
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn committed_batch_applies_in_arrival_order_and_dedups() {
+        let st = crate::testing::test_db().await;
+        let (batcher, rx) = mpsc::channel(16);
+        tokio::spawn(MicroBatcher::new(st.clone(), rx, Duration::from_millis(200), 8).run(CancellationToken::new()));
+        let run = uuid::Uuid::new_v4();
+        let req = |n: u32| {
+            let mut req = transfer(&format!("req-mb-{n}-{run}"));
+            (req.from_account, req.to_account) = (format!("acct-a-{run}"), format!("acct-b-{run}"));
+            req
+        };
+
+        // queued back to back, so all three land in one batch in this order
+        let mut replies = Vec::new();
+        for r in [req(1), req(1), req(2)] {
+            let (reply, rx) = oneshot::channel();
+            batcher.send((r, Principal::anonymous(), reply)).await.unwrap();
+            replies.push(rx);
+        }
+        let mut outcomes = Vec::new();
+        for rx in replies {
+            outcomes.push(rx.await.unwrap().unwrap());
+        }
+
+        let [TransferOutcome::Applied(first, _), TransferOutcome::Duplicate(repeat), TransferOutcome::Applied(second, _)] = &outcomes[..]
+        else {
+            panic!("expected applied, duplicate, applied");
+        };
+        assert_eq!(repeat.transaction_id, first.transaction_id, "the repeat resolves to the first item");
+        assert_eq!(second.created_at, first.created_at, "one database transaction");
+        let client = st.db.get().await.unwrap();
+        let balance: i64 = client
+            .query_one("SELECT balance_units FROM balances WHERE account_id=$1", &[&format!("acct-a-{run}")])
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(balance, -200);
+    }
+}
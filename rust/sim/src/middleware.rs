@@ -1,48 +1,92 @@
 use axum::{
     body::Body,
-    extract::Request,
+    extract::{MatchedPath, Request, State},
     http::{header, HeaderValue, Method, StatusCode},
     middleware::Next,
     response::Response,
 };
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{info, Instrument};
 
-pub async fn cors(req: Request, next: Next) -> Response {
-    let origin = req
-        .headers()
-        .get(header::ORIGIN)
-        .and_then(|v| v.to_str().ok())
-        .map(|s| s.to_string());
-    let allowed = std::env::var("CORS_ALLOW_ORIGINS")
-        .unwrap_or_else(|_| "http://localhost:5173,http://localhost:4173".to_string());
-    let allow_any = allowed.split(',').any(|x| x.trim() == "*");
-
-    let mut allowed_origin: Option<String> = None;
-    if let Some(o) = origin {
-        if allow_any {
-            allowed_origin = Some(o);
-        } else {
-            for a in allowed.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()) {
-                if a == o {
-                    allowed_origin = Some(o);
-                    break;
-                }
-            }
+const DEFAULT_ALLOW_ORIGINS: &str = "http://localhost:5173,http://localhost:4173";
+const DEFAULT_ALLOW_METHODS: &str = "GET,POST,PUT,PATCH,DELETE,OPTIONS";
+const DEFAULT_ALLOW_HEADERS: &str = "Content-Type,X-Admin-Key,Authorization,Idempotency-Key,X-Request-Id,If-None-Match";
+const DEFAULT_EXPOSE_HEADERS: &str = "ETag,X-Request-Id,Retry-After,X-RateLimit-Limit,X-RateLimit-Remaining,X-RateLimit-Reset";
+
+/// CORS settings, read once at startup from `CORS_ALLOW_ORIGINS`,
+/// `CORS_ALLOW_METHODS`, `CORS_ALLOW_HEADERS`, `CORS_EXPOSE_HEADERS` and
+/// `CORS_ALLOW_CREDENTIALS`.
+///
+/// The allowed request origin is always echoed back, never `*`, so a wildcard
+/// allowlist stays valid for credentialed requests.
+#[derive(Clone, Debug)]
+pub struct CorsConfig {
+    allow_origins: Vec<String>,
+    allow_any: bool,
+    allow_methods: HeaderValue,
+    allow_headers: HeaderValue,
+    expose_headers: HeaderValue,
+    allow_credentials: bool,
+}
+
+impl CorsConfig {
+    pub fn from_env() -> Self {
+        Self::from_lookup(|k| std::env::var(k).ok())
+    }
+
+    pub fn from_lookup(var: impl Fn(&str) -> Option<String>) -> Self {
+        let origins = var("CORS_ALLOW_ORIGINS").unwrap_or_else(|| DEFAULT_ALLOW_ORIGINS.to_string());
+        let allow_origins: Vec<String> = origins
+            .split(',')
+            .map(|x| x.trim().to_string())
+            .filter(|x| !x.is_empty())
+            .collect();
+        let allow_any = allow_origins.iter().any(|x| x == "*");
+        Self {
+            allow_origins,
+            allow_any,
+            allow_methods: header_list(var("CORS_ALLOW_METHODS"), DEFAULT_ALLOW_METHODS),
+            allow_headers: header_list(var("CORS_ALLOW_HEADERS"), DEFAULT_ALLOW_HEADERS),
+            expose_headers: header_list(var("CORS_EXPOSE_HEADERS"), DEFAULT_EXPOSE_HEADERS),
+            allow_credentials: var("CORS_ALLOW_CREDENTIALS")
+                .is_some_and(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes")),
         }
     }
 
+    fn allowed_origin(&self, origin: Option<&str>) -> Option<String> {
+        let o = origin?;
+        (self.allow_any || self.allow_origins.iter().any(|a| a == o)).then(|| o.to_string())
+    }
+}
+
+/// Normalise a comma-separated list ("get, post" -> "get,post"), falling back to
+/// `default` when unset or not a valid header value.
+fn header_list(value: Option<String>, default: &'static str) -> HeaderValue {
+    value
+        .map(|v| v.split(',').map(str::trim).filter(|x| !x.is_empty()).collect::<Vec<_>>().join(","))
+        .filter(|v| !v.is_empty())
+        .and_then(|v| HeaderValue::from_str(&v).ok())
+        .unwrap_or_else(|| HeaderValue::from_static(default))
+}
+
+pub async fn cors(State(cfg): State<Arc<CorsConfig>>, req: Request, next: Next) -> Response {
+    let origin = req.headers().get(header::ORIGIN).and_then(|v| v.to_str().ok());
+    let allowed_origin = cfg.allowed_origin(origin);
+
     if req.method() == Method::OPTIONS {
         let mut res = Response::new(Body::empty());
         *res.status_mut() = StatusCode::NO_CONTENT;
-        apply_cors_headers(&mut res, allowed_origin);
+        apply_cors_headers(&mut res, &cfg, allowed_origin);
         return res;
     }
 
     let mut res = next.run(req).await;
-    apply_cors_headers(&mut res, allowed_origin);
+    apply_cors_headers(&mut res, &cfg, allowed_origin);
     res
 }
 
-fn apply_cors_headers(res: &mut Response, allowed_origin: Option<String>) {
+fn apply_cors_headers(res: &mut Response, cfg: &CorsConfig, allowed_origin: Option<String>) {
     if let Some(o) = allowed_origin {
         if let Ok(v) = HeaderValue::from_str(&o) {
             res.headers_mut()
@@ -50,13 +94,220 @@ fn apply_cors_headers(res: &mut Response, allowed_origin: Option<String>) {
             res.headers_mut()
                 .insert(header::VARY, HeaderValue::from_static("Origin"));
         }
-        res.headers_mut().insert(
-            header::ACCESS_CONTROL_ALLOW_METHODS,
-            HeaderValue::from_static("GET,POST,OPTIONS"),
-        );
-        res.headers_mut().insert(
-            header::ACCESS_CONTROL_ALLOW_HEADERS,
-            HeaderValue::from_static("Content-Type,X-Admin-Key"),
+        res.headers_mut()
+            .insert(header::ACCESS_CONTROL_ALLOW_METHODS, cfg.allow_methods.clone());
+        res.headers_mut()
+            .insert(header::ACCESS_CONTROL_ALLOW_HEADERS, cfg.allow_headers.clone());
+        res.headers_mut()
+            .insert(header::ACCESS_CONTROL_EXPOSE_HEADERS, cfg.expose_headers.clone());
+        if cfg.allow_credentials {
+            res.headers_mut()
+                .insert(header::ACCESS_CONTROL_ALLOW_CREDENTIALS, HeaderValue::from_static("true"));
+        }
+    }
+}
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Correlation id of the current request, stored as a request extension by
+/// [`request_id`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+/// The client's `X-Request-Id` if it is usable as a log field and header value:
+/// non-empty, at most 128 characters, printable ASCII without spaces.
+fn client_request_id(headers: &axum::http::HeaderMap) -> Option<String> {
+    let id = headers.get(REQUEST_ID_HEADER)?.to_str().ok()?.trim();
+    let valid = !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN && id.bytes().all(|b| b.is_ascii_graphic());
+    valid.then(|| id.to_string())
+}
+
+/// Takes the client's `X-Request-Id` or generates a UUID, stores it as a
+/// [`RequestId`] extension, runs the request inside a span carrying it (so every
+/// `tracing` event in the handler logs it) and echoes it on the response.
+pub async fn request_id(mut req: Request, next: Next) -> Response {
+    let id = client_request_id(req.headers()).unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    req.extensions_mut().insert(RequestId(id.clone()));
+    let span = tracing::info_span!("request", request_id = %id, method = %req.method(), path = %req.uri().path());
+
+    let mut res = next.run(req).instrument(span).await;
+    if let Ok(v) = HeaderValue::from_str(&id) {
+        res.headers_mut().insert(REQUEST_ID_HEADER, v);
+    }
+    res
+}
+
+/// One structured `access` event per request with method, route template
+/// (`/v1/transactions/{transaction_id}`, not the concrete id), status and
+/// duration. Unrouted requests log the route as `unmatched`.
+pub async fn access_log(req: Request, next: Next) -> Response {
+    let method = req.method().clone();
+    let route = req.extensions().get::<MatchedPath>().map(|p| p.as_str().to_string());
+    let start = Instant::now();
+
+    let res = next.run(req).await;
+    info!(
+        target: "access",
+        method = %method,
+        route = route.as_deref().unwrap_or("unmatched"),
+        status = res.status().as_u16(),
+        duration_ms = start.elapsed().as_secs_f64() * 1000.0,
+        "request completed"
+    );
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::get, Extension, Router};
+
+    fn config(vars: &[(&str, &str)]) -> CorsConfig {
+        CorsConfig::from_lookup(|k| vars.iter().find(|(n, _)| *n == k).map(|(_, v)| v.to_string()))
+    }
+
+    fn headers_for(cfg: &CorsConfig, origin: &str) -> axum::http::HeaderMap {
+        let mut res = Response::new(Body::empty());
+        apply_cors_headers(&mut res, cfg, cfg.allowed_origin(Some(origin)));
+        res.headers().clone()
+    }
+
+    #[test]
+    fn defaults_cover_the_api_methods_and_headers() {
+        let h = headers_for(&config(&[]), "http://localhost:5173");
+        assert_eq!(h[header::ACCESS_CONTROL_ALLOW_METHODS], "GET,POST,PUT,PATCH,DELETE,OPTIONS");
+        assert_eq!(
+            h[header::ACCESS_CONTROL_ALLOW_HEADERS],
+            "Content-Type,X-Admin-Key,Authorization,Idempotency-Key,X-Request-Id,If-None-Match"
         );
+        assert_eq!(h[header::ACCESS_CONTROL_EXPOSE_HEADERS], "ETag,X-Request-Id,Retry-After,X-RateLimit-Limit,X-RateLimit-Remaining,X-RateLimit-Reset");
+    }
+
+    #[test]
+    fn methods_and_headers_from_env() {
+        let cfg = config(&[
+            ("CORS_ALLOW_METHODS", "GET, PATCH"),
+            ("CORS_ALLOW_HEADERS", "Content-Type, X-Admin-Key, X-Request-Id"),
+            ("CORS_EXPOSE_HEADERS", "ETag"),
+        ]);
+        let h = headers_for(&cfg, "http://localhost:5173");
+        assert_eq!(h[header::ACCESS_CONTROL_ALLOW_METHODS], "GET,PATCH");
+        assert_eq!(h[header::ACCESS_CONTROL_ALLOW_HEADERS], "Content-Type,X-Admin-Key,X-Request-Id");
+        assert_eq!(h[header::ACCESS_CONTROL_EXPOSE_HEADERS], "ETag");
+    }
+
+    #[test]
+    fn credentialed_wildcard_echoes_origin() {
+        let cfg = config(&[("CORS_ALLOW_ORIGINS", "*"), ("CORS_ALLOW_CREDENTIALS", "true")]);
+        let h = headers_for(&cfg, "https://ops.example");
+        assert_eq!(h[header::ACCESS_CONTROL_ALLOW_ORIGIN], "https://ops.example");
+        assert_eq!(h[header::ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+        assert_eq!(h[header::VARY], "Origin");
+    }
+
+    #[test]
+    fn credentialed_explicit_list() {
+        let cfg = config(&[("CORS_ALLOW_ORIGINS", "https://a.example, https://b.example"), ("CORS_ALLOW_CREDENTIALS", "1")]);
+        let h = headers_for(&cfg, "https://b.example");
+        assert_eq!(h[header::ACCESS_CONTROL_ALLOW_ORIGIN], "https://b.example");
+        assert_eq!(h[header::ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+        assert!(headers_for(&cfg, "https://c.example").is_empty());
+    }
+
+    #[test]
+    fn wildcard_without_credentials() {
+        let h = headers_for(&config(&[("CORS_ALLOW_ORIGINS", "*")]), "https://ops.example");
+        assert_eq!(h[header::ACCESS_CONTROL_ALLOW_ORIGIN], "https://ops.example");
+        assert!(h.get(header::ACCESS_CONTROL_ALLOW_CREDENTIALS).is_none());
+    }
+
+    #[test]
+    fn unlisted_origin_gets_no_headers() {
+        let h = headers_for(&config(&[("CORS_ALLOW_ORIGINS", "https://ops.example")]), "https://evil.example");
+        assert!(h.is_empty());
+    }
+
+    async fn serve_echo() -> std::net::SocketAddr {
+        let app = Router::new()
+            .route("/id", get(|Extension(RequestId(id)): Extension<RequestId>| async move { id }))
+            .layer(axum::middleware::from_fn(request_id));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        addr
+    }
+
+    #[tokio::test]
+    async fn echoes_client_request_id() {
+        let addr = serve_echo().await;
+        let res = reqwest::Client::new()
+            .get(format!("http://{addr}/id"))
+            .header(REQUEST_ID_HEADER, "req-abc-123")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.headers()[REQUEST_ID_HEADER], "req-abc-123");
+        // the handler sees the same id through the extension
+        assert_eq!(res.text().await.unwrap(), "req-abc-123");
+    }
+
+    #[tokio::test]
+    async fn generates_request_id_when_missing_or_invalid() {
+        let addr = serve_echo().await;
+        let client = reqwest::Client::new();
+        let first = client.get(format!("http://{addr}/id")).send().await.unwrap();
+        let second = client.get(format!("http://{addr}/id")).header(REQUEST_ID_HEADER, "has spaces").send().await.unwrap();
+
+        let first_id = first.headers()[REQUEST_ID_HEADER].to_str().unwrap().to_string();
+        let second_id = second.headers()[REQUEST_ID_HEADER].to_str().unwrap().to_string();
+        assert!(uuid::Uuid::parse_str(&first_id).is_ok());
+        assert!(uuid::Uuid::parse_str(&second_id).is_ok());
+        assert_ne!(first_id, second_id);
+        assert_eq!(first.text().await.unwrap(), first_id);
+    }
+
+    #[derive(Clone, Default)]
+    struct Captured(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn access_log_groups_by_route_template() {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt().json().with_writer(move || writer.clone()).finish();
+        // current-thread runtime: the server task below runs under this subscriber too
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let app = Router::new()
+            .route("/v1/transactions/{transaction_id}", get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn(access_log));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        let res = reqwest::get(format!("http://{addr}/v1/transactions/txn-42")).await.unwrap();
+        assert_eq!(res.status(), 200);
+
+        let logs = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        let event: serde_json::Value = logs
+            .lines()
+            .filter_map(|l| serde_json::from_str::<serde_json::Value>(l).ok())
+            .find(|v| v["target"] == "access")
+            .expect("no access log event");
+        assert_eq!(event["level"], "INFO");
+        assert_eq!(event["fields"]["method"], "GET");
+        assert_eq!(event["fields"]["route"], "/v1/transactions/{transaction_id}");
+        assert_eq!(event["fields"]["status"], 200);
+        assert!(event["fields"]["duration_ms"].as_f64().unwrap() >= 0.0);
     }
 }
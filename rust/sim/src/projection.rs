@@ -0,0 +1,309 @@
+use deadpool_postgres::Pool;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+use crate::handlers::transfers::checked_balance;
+use crate::Direction;
+
+/// How the `balances` projection is maintained.
+///
+/// `Sync` updates balances inside the transfer transaction, so reads are always
+/// current. `Async` only writes transactions and postings on the hot path and a
+/// background [`BalanceProjector`] folds unprojected postings into balances
+/// shortly after, trading read freshness for less contention on hot rows.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BalanceProjection {
+    #[default]
+    Sync,
+    Async,
+}
+
+impl BalanceProjection {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "sync" => Some(Self::Sync),
+            "async" => Some(Self::Async),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Sync => "sync",
+            Self::Async => "async",
+        }
+    }
+}
+
+/// Net balance change per account for a set of postings, in account order
+/// so balance rows are always locked in the same order; None if a net change
+/// leaves the i64 range.
+pub fn fold_deltas<'a>(
+    postings: impl IntoIterator<Item = (&'a str, Direction, i64)>,
+) -> Option<BTreeMap<&'a str, i64>> {
+    let mut deltas = BTreeMap::new();
+    for (account, direction, amount) in postings {
+        let signed = match direction {
+            Direction::Debit => amount.checked_neg()?,
+            Direction::Credit => amount,
+        };
+        let delta = deltas.entry(account).or_insert(0i64);
+        *delta = delta.checked_add(signed)?;
+    }
+    Some(deltas)
+}
+
+/// An unprojected posting as the [`BalanceProjector`] reads it.
+#[derive(Clone, Copy, Debug)]
+pub struct Unprojected<'a> {
+    pub txn_id: &'a str,
+    pub account_id: &'a str,
+    pub direction: Direction,
+    pub amount_units: i64,
+}
+
+/// What one projector batch writes.
+#[derive(Debug, Default, PartialEq)]
+pub struct ProjectionPlan<'a> {
+    /// Net change per account of the projected transactions, in lock order.
+    pub deltas: BTreeMap<&'a str, i64>,
+    /// Transactions whose postings are marked projected.
+    pub projected: Vec<&'a str>,
+    /// Transactions set aside: a posting would take a balance out of range.
+    pub rejected: Vec<&'a str>,
+}
+
+/// Plan a batch of `postings`, grouped by transaction in batch order, against
+/// the current `balances`. Transactions apply one at a time; one that would take
+/// any balance out of the storable range (see [`checked_balance`]) is rejected
+/// whole rather than failing the batch. Should a net change for the batch leave
+/// the i64 range, the batch ends early and the rest waits for the next one.
+pub fn plan_projection<'a>(postings: &[Unprojected<'a>], balances: &BTreeMap<&str, i64>) -> ProjectionPlan<'a> {
+    let mut plan = ProjectionPlan::default();
+    let mut running: BTreeMap<&'a str, i64> = BTreeMap::new();
+    for txn in postings.chunk_by(|a, b| a.txn_id == b.txn_id) {
+        let txn_id = txn[0].txn_id;
+        let Some(deltas) = fold_deltas(txn.iter().map(|p| (p.account_id, p.direction, p.amount_units))) else {
+            plan.rejected.push(txn_id);
+            continue;
+        };
+        let moves: Option<Vec<(&'a str, i64, i64)>> = deltas
+            .into_iter()
+            .map(|(account, delta)| {
+                let current = running.get(account).or(balances.get(account)).copied().unwrap_or(0);
+                Some((account, checked_balance(current, delta)?, delta))
+            })
+            .collect();
+        let Some(moves) = moves else {
+            plan.rejected.push(txn_id);
+            continue;
+        };
+        let batch_deltas: Option<Vec<i64>> = moves
+            .iter()
+            .map(|(account, _, delta)| plan.deltas.get(account).copied().unwrap_or(0).checked_add(*delta))
+            .collect();
+        let Some(batch_deltas) = batch_deltas else { break };
+        for ((account, balance, _), batch_delta) in moves.into_iter().zip(batch_deltas) {
+            running.insert(account, balance);
+            plan.deltas.insert(account, batch_delta);
+        }
+        plan.projected.push(txn_id);
+    }
+    plan
+}
+
+/// Add `delta` to `account_id`'s available balance inside `tx` and return the new
+/// balance; a missing row starts at zero. Every balance move goes through here or
+/// [`apply_pending_delta`]. Postgres rejects a result outside `bigint` or equal to
+/// `i64::MIN` (`balances_units_range`), so callers that owe the client a clean 422
+/// check first with `checked_transfer` and `insufficient_available`.
+pub async fn apply_balance_delta(
+    tx: &deadpool_postgres::Transaction<'_>,
+    account_id: &str,
+    delta: i64,
+) -> Result<i64, tokio_postgres::Error> {
+    let row = tx
+        .query_one(
+            "INSERT INTO balances(account_id,balance_units) VALUES($1,$2) ON CONFLICT (account_id) DO UPDATE SET balance_units=balances.balance_units + EXCLUDED.balance_units, updated_at=now() RETURNING balance_units",
+            &[&account_id, &delta],
+        )
+        .await?;
+    Ok(row.get(0))
+}
+
+/// Same as [`apply_balance_delta`] for credits awaiting settlement; returns the new pending balance.
+pub async fn apply_pending_delta(
+    tx: &deadpool_postgres::Transaction<'_>,
+    account_id: &str,
+    delta: i64,
+) -> Result<i64, tokio_postgres::Error> {
+    let row = tx
+        .query_one(
+            "INSERT INTO balances(account_id,pending_units) VALUES($1,$2) ON CONFLICT (account_id) DO UPDATE SET pending_units=balances.pending_units + EXCLUDED.pending_units, updated_at=now() RETURNING pending_units",
+            &[&account_id, &delta],
+        )
+        .await?;
+    Ok(row.get(0))
+}
+
+pub struct BalanceProjector {
+    db: Pool,
+}
+
+impl BalanceProjector {
+    pub fn new(db: Pool) -> Self {
+        Self { db }
+    }
+
+    pub async fn run(&self, cancel: CancellationToken) {
+        let mut interval = tokio::time::interval(Duration::from_millis(200));
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => return,
+                _ = interval.tick() => {
+                    if let Err(e) = self.project_batch(500).await {
+                        warn!(error = %e, "balance projection batch failed");
+                    }
+                }
+            }
+        }
+    }
+
+    async fn project_batch(&self, limit: i64) -> Result<(), Box<dyn std::error::Error>> {
+        let mut client = self.db.get().await?;
+        let tx = client.transaction().await?;
+        // whole transactions, so one is never half projected
+        let rows = tx
+            .query(
+                "SELECT p.txn_id::text, p.account_id, p.direction, p.amount_units FROM postings p \
+                 WHERE p.projected_at IS NULL AND p.txn_id IN ( \
+                   SELECT u.txn_id FROM postings u WHERE u.projected_at IS NULL \
+                   AND NOT EXISTS (SELECT 1 FROM unprojectable_transactions x WHERE x.txn_id=u.txn_id) \
+                   ORDER BY u.created_at LIMIT $1) \
+                 ORDER BY p.created_at, p.txn_id, p.id FOR UPDATE OF p SKIP LOCKED",
+                &[&limit],
+            )
+            .await?;
+        if rows.is_empty() {
+            return Ok(());
+        }
+
+        let postings = rows
+            .iter()
+            .map(|r| {
+                Ok(Unprojected {
+                    txn_id: r.get("txn_id"),
+                    account_id: r.get("account_id"),
+                    direction: r.try_get("direction")?,
+                    amount_units: r.get("amount_units"),
+                })
+            })
+            .collect::<Result<Vec<_>, tokio_postgres::Error>>()?;
+        let accounts: Vec<&str> = postings.iter().map(|p| p.account_id).collect();
+        let current = tx
+            .query(
+                "SELECT account_id, balance_units FROM balances WHERE account_id = ANY($1) ORDER BY account_id FOR UPDATE",
+                &[&accounts],
+            )
+            .await?;
+        let balances: BTreeMap<&str, i64> = current.iter().map(|r| (r.get(0), r.get(1))).collect();
+
+        let plan = plan_projection(&postings, &balances);
+        for (account_id, delta) in &plan.deltas {
+            apply_balance_delta(&tx, account_id, *delta).await?;
+        }
+        tx.execute(
+            "UPDATE postings SET projected_at=now() WHERE txn_id = ANY($1::text[]::uuid[]) AND projected_at IS NULL",
+            &[&plan.projected],
+        )
+        .await?;
+        if !plan.rejected.is_empty() {
+            warn!(transactions = ?plan.rejected, "postings would take a balance out of range; left unprojected");
+            tx.execute(
+                "INSERT INTO unprojectable_transactions(txn_id, reason) SELECT unnest($1::text[]::uuid[]), 'balance_overflow' ON CONFLICT DO NOTHING",
+                &[&plan.rejected],
+            )
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_modes() {
+        assert_eq!(BalanceProjection::parse("sync"), Some(BalanceProjection::Sync));
+        assert_eq!(BalanceProjection::parse(" ASYNC "), Some(BalanceProjection::Async));
+        assert_eq!(BalanceProjection::parse("later"), None);
+    }
+
+    /// Runs in a transaction that is rolled back.
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn running_balance_follows_applied_deltas() {
+        let pool = crate::db::build_pool(&crate::testing::test_db_url(), &Default::default()).unwrap();
+        let mut client = pool.get().await.unwrap();
+        let tx = client.transaction().await.unwrap();
+        let account = format!("acct-delta-{}", uuid::Uuid::new_v4());
+        tx.execute("INSERT INTO accounts(id,zone_id) VALUES($1,'zone-eu')", &[&account]).await.unwrap();
+
+        let mut running = Vec::new();
+        for delta in [100, -30, 5, -75, -10] {
+            running.push(apply_balance_delta(&tx, &account, delta).await.unwrap());
+        }
+        assert_eq!(running, [100, 70, 75, 0, -10]);
+        assert_eq!(apply_pending_delta(&tx, &account, 40).await.unwrap(), 40);
+        assert_eq!(apply_pending_delta(&tx, &account, -40).await.unwrap(), 0);
+        // the pending moves leave the available balance alone
+        assert_eq!(apply_balance_delta(&tx, &account, 0).await.unwrap(), -10);
+
+        assert!(apply_balance_delta(&tx, &account, i64::MAX).await.is_err(), "bigint overflow must fail");
+    }
+
+    #[test]
+    fn fold_deltas_nets_per_account() {
+        let deltas = fold_deltas([
+            ("b", Direction::Credit, 100),
+            ("a", Direction::Debit, 100),
+            ("a", Direction::Credit, 30),
+            ("b", Direction::Debit, 30),
+        ]);
+        assert_eq!(deltas.unwrap().into_iter().collect::<Vec<_>>(), vec![("a", -70), ("b", 70)]);
+        assert_eq!(fold_deltas([("a", Direction::Credit, i64::MAX), ("a", Direction::Credit, 1)]), None);
+    }
+
+    fn posting(txn_id: &'static str, account_id: &'static str, direction: Direction, amount_units: i64) -> Unprojected<'static> {
+        Unprojected { txn_id, account_id, direction, amount_units }
+    }
+
+    fn transfer(txn_id: &'static str, from: &'static str, to: &'static str, amount: i64) -> [Unprojected<'static>; 2] {
+        [posting(txn_id, from, Direction::Debit, amount), posting(txn_id, to, Direction::Credit, amount)]
+    }
+
+    #[test]
+    fn overflowing_transaction_is_set_aside_and_the_rest_projects() {
+        let balances = BTreeMap::from([("a", i64::MIN + 10), ("c", i64::MAX - 5)]);
+        let postings = [transfer("t1", "a", "b", 5), transfer("t2", "a", "b", 5), transfer("t3", "b", "c", 6), transfer("t4", "b", "c", 5)].concat();
+        let plan = plan_projection(&postings, &balances);
+        // t2 would leave a at i64::MIN and t3 would push c past i64::MAX
+        assert_eq!(plan.projected, vec!["t1", "t4"]);
+        assert_eq!(plan.rejected, vec!["t2", "t3"]);
+        assert_eq!(plan.deltas.into_iter().collect::<Vec<_>>(), vec![("a", -5), ("b", 0), ("c", 5)]);
+    }
+
+    #[test]
+    fn batch_ends_before_a_net_change_leaves_the_i64_range() {
+        // each move fits, but the batch's net change for b would not
+        let balances = BTreeMap::from([("b", i64::MAX)]);
+        let postings = [transfer("t1", "b", "a", i64::MAX), transfer("t2", "b", "c", 2), transfer("t3", "c", "d", 1)].concat();
+        let plan = plan_projection(&postings, &balances);
+        assert_eq!(plan.projected, vec!["t1"]);
+        assert!(plan.rejected.is_empty(), "t2 waits for the next batch");
+    }
+}
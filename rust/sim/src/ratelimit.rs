@@ -0,0 +1,200 @@
+use dashmap::DashMap;
+use std::time::{Duration, Instant};
+
+/// Token bucket holding up to one second's worth of tokens.
+pub struct Bucket {
+    tokens: f64,
+    last: Instant,
+    /// Rate of the last take, so [`quota`] can report on the bucket alone.
+    rate_per_sec: u32,
+}
+
+impl Bucket {
+    fn full(rate_per_sec: u32, now: Instant) -> Self {
+        Self { tokens: rate_per_sec as f64, last: now, rate_per_sec }
+    }
+
+    /// Tokens available at `now`, after refilling since the last take.
+    fn available(&self, rate_per_sec: u32, now: Instant) -> f64 {
+        let rate = rate_per_sec as f64;
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        (self.tokens + elapsed * rate).min(rate)
+    }
+
+    /// Take `n` tokens at once, or none and return how long until `n` are available.
+    fn try_take(&mut self, n: u32, rate_per_sec: u32, now: Instant) -> Result<(), Duration> {
+        self.tokens = self.available(rate_per_sec, now);
+        self.last = now;
+        self.rate_per_sec = rate_per_sec;
+        if self.tokens >= n as f64 {
+            self.tokens -= n as f64;
+            Ok(())
+        } else {
+            Err(wait_for_tokens(n, self.tokens, rate_per_sec))
+        }
+    }
+}
+
+fn wait_for_token(tokens: f64, rate_per_sec: u32) -> Duration {
+    wait_for_tokens(1, tokens, rate_per_sec)
+}
+
+fn wait_for_tokens(n: u32, tokens: f64, rate_per_sec: u32) -> Duration {
+    Duration::from_secs_f64((n as f64 - tokens) / rate_per_sec as f64)
+}
+
+/// Per-zone transfer limiter. A rate of 0 means unlimited.
+pub fn try_acquire(
+    buckets: &DashMap<String, Bucket>,
+    zone_id: &str,
+    rate_per_sec: u32,
+    now: Instant,
+) -> Result<(), Duration> {
+    try_acquire_n(buckets, zone_id, 1, rate_per_sec, now)
+}
+
+/// [`try_acquire`] for `n` transfers at once: all `n` tokens are taken or none.
+/// The bucket holds at most `rate_per_sec` tokens, so a larger `n` never succeeds.
+pub fn try_acquire_n(
+    buckets: &DashMap<String, Bucket>,
+    zone_id: &str,
+    n: u32,
+    rate_per_sec: u32,
+    now: Instant,
+) -> Result<(), Duration> {
+    if rate_per_sec == 0 {
+        // a zone made unlimited must not keep reporting its old quota
+        buckets.remove(zone_id);
+        return Ok(());
+    }
+    let mut bucket = buckets
+        .entry(zone_id.to_string())
+        .or_insert_with(|| Bucket::full(rate_per_sec, now));
+    bucket.try_take(n, rate_per_sec, now)
+}
+
+/// Whether [`try_acquire`] would succeed right now, without taking a token.
+/// Returns the tokens left (None when unlimited) or how long until one is available.
+pub fn peek(
+    buckets: &DashMap<String, Bucket>,
+    zone_id: &str,
+    rate_per_sec: u32,
+    now: Instant,
+) -> Result<Option<f64>, Duration> {
+    if rate_per_sec == 0 {
+        return Ok(None);
+    }
+    let tokens = buckets
+        .get(zone_id)
+        .map_or(rate_per_sec as f64, |b| b.available(rate_per_sec, now));
+    if tokens >= 1.0 {
+        Ok(Some(tokens))
+    } else {
+        Err(wait_for_token(tokens, rate_per_sec))
+    }
+}
+
+/// A zone bucket's state, for the `X-RateLimit-*` response headers.
+#[derive(Debug, PartialEq)]
+pub struct Quota {
+    /// Tokens per second, which is also the bucket size.
+    pub limit: u32,
+    /// Whole tokens left.
+    pub remaining: u32,
+    /// Time until the bucket is full again.
+    pub reset: Duration,
+}
+
+/// The quota of `zone_id`'s bucket at `now`, or None if no token has been taken
+/// from it (an unlimited zone never has a bucket).
+pub fn quota(buckets: &DashMap<String, Bucket>, zone_id: &str, now: Instant) -> Option<Quota> {
+    let bucket = buckets.get(zone_id)?;
+    let rate = bucket.rate_per_sec;
+    let tokens = bucket.available(rate, now);
+    Some(Quota {
+        limit: rate,
+        remaining: tokens.floor() as u32,
+        reset: Duration::from_secs_f64((rate as f64 - tokens) / rate as f64),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn burst_over_rate_is_throttled_per_zone() {
+        let buckets = DashMap::new();
+        let now = Instant::now();
+        for _ in 0..5 {
+            assert!(try_acquire(&buckets, "zone-eu", 5, now).is_ok());
+        }
+        let wait = try_acquire(&buckets, "zone-eu", 5, now).unwrap_err();
+        assert!(wait > Duration::ZERO && wait <= Duration::from_millis(200));
+        // other zones have their own bucket
+        assert!(try_acquire(&buckets, "zone-na", 5, now).is_ok());
+    }
+
+    #[test]
+    fn bucket_refills_over_time() {
+        let buckets = DashMap::new();
+        let now = Instant::now();
+        assert!(try_acquire(&buckets, "zone-eu", 1, now).is_ok());
+        assert!(try_acquire(&buckets, "zone-eu", 1, now).is_err());
+        assert!(try_acquire(&buckets, "zone-eu", 1, now + Duration::from_secs(1)).is_ok());
+    }
+
+    #[test]
+    fn batch_takes_all_its_tokens_or_none() {
+        let buckets = DashMap::new();
+        let now = Instant::now();
+        assert!(try_acquire_n(&buckets, "zone-eu", 3, 5, now).is_ok());
+        let wait = try_acquire_n(&buckets, "zone-eu", 3, 5, now).unwrap_err();
+        assert!(wait > Duration::from_millis(190) && wait <= Duration::from_millis(210), "one more token needed: {wait:?}");
+        // the refused batch took nothing
+        assert!(try_acquire(&buckets, "zone-eu", 5, now).is_ok());
+        assert!(try_acquire(&buckets, "zone-eu", 5, now).is_ok());
+        assert!(try_acquire(&buckets, "zone-eu", 5, now).is_err());
+        assert!(try_acquire_n(&buckets, "zone-na", 6, 5, now).is_err());
+        assert!(try_acquire_n(&buckets, "zone-na", 6, 0, now).is_ok());
+    }
+
+    #[test]
+    fn peek_does_not_consume() {
+        let buckets = DashMap::new();
+        let now = Instant::now();
+        assert_eq!(peek(&buckets, "zone-eu", 2, now), Ok(Some(2.0)));
+        assert!(try_acquire(&buckets, "zone-eu", 2, now).is_ok());
+        assert_eq!(peek(&buckets, "zone-eu", 2, now), Ok(Some(1.0)));
+        assert_eq!(peek(&buckets, "zone-eu", 2, now), Ok(Some(1.0)));
+        assert!(try_acquire(&buckets, "zone-eu", 2, now).is_ok());
+        assert!(peek(&buckets, "zone-eu", 2, now).is_err());
+        assert_eq!(peek(&buckets, "zone-eu", 0, now), Ok(None));
+    }
+
+    #[test]
+    fn quota_reports_tokens_left_and_time_to_refill() {
+        let buckets = DashMap::new();
+        let now = Instant::now();
+        assert_eq!(quota(&buckets, "zone-eu", now), None);
+        assert!(try_acquire_n(&buckets, "zone-eu", 3, 4, now).is_ok());
+        let q = quota(&buckets, "zone-eu", now).unwrap();
+        assert_eq!((q.limit, q.remaining), (4, 1));
+        assert!(q.reset > Duration::from_millis(740) && q.reset <= Duration::from_millis(750), "{:?}", q.reset);
+        let later = quota(&buckets, "zone-eu", now + Duration::from_secs(1)).unwrap();
+        assert_eq!((later.remaining, later.reset), (4, Duration::ZERO));
+        // made unlimited: the bucket and its quota go away
+        assert!(try_acquire(&buckets, "zone-eu", 0, now).is_ok());
+        assert_eq!(quota(&buckets, "zone-eu", now), None);
+    }
+
+    #[test]
+    fn zero_rate_is_unlimited() {
+        let buckets = DashMap::new();
+        let now = Instant::now();
+        for _ in 0..1000 {
+            assert!(try_acquire(&buckets, "zone-eu", 0, now).is_ok());
+        }
+        assert!(buckets.is_empty());
+    }
+}
@@ -0,0 +1,194 @@
+use deadpool_postgres::Pool;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::handlers::incidents::open_incident;
+
+/// Synthetic zone that ledger-integrity incidents are filed under.
+pub const LEDGER_ZONE: &str = "zone-ledger";
+const DRIFT_INCIDENT_TITLE: &str = "Balance drift detected";
+
+/// `(account_id, balance_units, pending_units)` per account as the projected
+/// postings say it should be: settled legs are available, unsettled are pending.
+/// Archived postings still count, through the `ledger_postings` view.
+pub(crate) const LEDGER_BALANCES_SQL: &str = "SELECT account_id, \
+     COALESCE(SUM(CASE WHEN direction='CREDIT' THEN amount_units ELSE -amount_units END) FILTER (WHERE settled_at IS NOT NULL), 0)::bigint, \
+     COALESCE(SUM(CASE WHEN direction='CREDIT' THEN amount_units ELSE -amount_units END) FILTER (WHERE settled_at IS NULL), 0)::bigint \
+     FROM ledger_postings WHERE projected_at IS NOT NULL GROUP BY account_id";
+
+/// An account whose stored balance row disagrees with its postings.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct Discrepancy {
+    pub account_id: String,
+    pub stored_balance_units: i64,
+    pub stored_pending_units: i64,
+    pub ledger_balance_units: i64,
+    pub ledger_pending_units: i64,
+    /// Stored total minus ledger total, as the reconciler counts drift;
+    /// saturates at the i64 range.
+    pub drift_units: i64,
+}
+
+/// `(sb + sp) - (lb + lp)` for stored and ledger `(balance, pending)`, worked
+/// out in i128 so corrupt rows near the i64 limits cannot overflow it.
+fn drift_units((sb, sp): (i64, i64), (lb, lp): (i64, i64)) -> i64 {
+    let drift = (i128::from(sb) + i128::from(sp)) - (i128::from(lb) + i128::from(lp));
+    i64::try_from(drift).unwrap_or(if drift < 0 { i64::MIN } else { i64::MAX })
+}
+
+/// Accounts where `stored` and `ledger` `(balance_units, pending_units)`
+/// differ, in account order. A side with no row counts as zero.
+pub(crate) fn balance_discrepancies(
+    stored: &BTreeMap<String, (i64, i64)>,
+    ledger: &BTreeMap<String, (i64, i64)>,
+) -> Vec<Discrepancy> {
+    let accounts: std::collections::BTreeSet<&String> = stored.keys().chain(ledger.keys()).collect();
+    accounts
+        .into_iter()
+        .filter_map(|account| {
+            let (sb, sp) = stored.get(account).copied().unwrap_or_default();
+            let (lb, lp) = ledger.get(account).copied().unwrap_or_default();
+            ((sb, sp) != (lb, lp)).then(|| Discrepancy {
+                account_id: account.clone(),
+                stored_balance_units: sb,
+                stored_pending_units: sp,
+                ledger_balance_units: lb,
+                ledger_pending_units: lp,
+                drift_units: drift_units((sb, sp), (lb, lp)),
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum DriftAction {
+    Open,
+    /// Refresh the open incident's details with the latest drift.
+    Update,
+    Resolve,
+}
+
+/// What to do with the drift incident. It opens once drift exceeds the
+/// threshold and only resolves when the ledger is fully reconciled again.
+pub(crate) fn drift_action(drift_units: i64, threshold_units: i64, incident_open: bool) -> Option<DriftAction> {
+    match (incident_open, drift_units) {
+        (false, d) if d > threshold_units => Some(DriftAction::Open),
+        (true, 0) => Some(DriftAction::Resolve),
+        (true, _) => Some(DriftAction::Update),
+        (false, _) => None,
+    }
+}
+
+/// Periodically compares every balance row with the sum of its postings and
+/// files a CRITICAL incident under [`LEDGER_ZONE`] when they disagree by more
+/// than the threshold.
+///
+/// Only projected postings are counted, so async projection lag is not drift;
+/// pending (unsettled) credits count towards the balance.
+pub struct Reconciler {
+    db: Pool,
+    interval: Duration,
+    threshold_units: i64,
+}
+
+impl Reconciler {
+    pub fn new(db: Pool, interval: Duration, threshold_units: i64) -> Self {
+        Self { db, interval, threshold_units }
+    }
+
+    pub async fn run(&self, cancel: CancellationToken) {
+        let mut interval = tokio::time::interval(self.interval);
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => return,
+                _ = interval.tick() => {
+                    if let Err(e) = self.reconcile().await {
+                        warn!(error = %e, "reconciliation failed");
+                    }
+                }
+            }
+        }
+    }
+
+    async fn reconcile(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut client = self.db.get().await?;
+        let tx = client.transaction().await?;
+        let row = tx
+            .query_one(
+                "WITH ledger AS (SELECT account_id, SUM(CASE WHEN direction='CREDIT' THEN amount_units ELSE -amount_units END) AS units \
+                 FROM ledger_postings WHERE projected_at IS NOT NULL GROUP BY account_id), \
+                 diff AS (SELECT COALESCE(b.account_id, l.account_id) AS account_id, \
+                 COALESCE(b.balance_units::numeric + b.pending_units, 0) - COALESCE(l.units, 0) AS drift \
+                 FROM balances b FULL OUTER JOIN ledger l ON l.account_id=b.account_id) \
+                 SELECT COUNT(*) FILTER (WHERE drift <> 0) AS accounts, \
+                 LEAST(COALESCE(SUM(ABS(drift)), 0), 9223372036854775807)::bigint AS drift_units FROM diff",
+                &[],
+            )
+            .await?;
+        let drift_units: i64 = row.get("drift_units");
+        let accounts: i64 = row.get("accounts");
+
+        let open = tx
+            .query_opt(
+                "SELECT id::text FROM incidents WHERE zone_id=$1 AND title=$2 AND status <> 'RESOLVED' ORDER BY detected_at DESC LIMIT 1 FOR UPDATE",
+                &[&LEDGER_ZONE, &DRIFT_INCIDENT_TITLE],
+            )
+            .await?
+            .map(|r| r.get::<_, String>(0));
+
+        let details = json!({ "drift_units": drift_units, "accounts": accounts, "threshold_units": self.threshold_units });
+        match (drift_action(drift_units, self.threshold_units, open.is_some()), open) {
+            (Some(DriftAction::Open), _) => {
+                warn!(drift_units, accounts, "balance drift exceeds threshold, opening incident");
+                open_incident(&tx, LEDGER_ZONE, "CRITICAL", DRIFT_INCIDENT_TITLE, &details).await?;
+            }
+            (Some(DriftAction::Update), Some(id)) => {
+                tx.execute("UPDATE incidents SET details = details || $2 WHERE id=$1::uuid", &[&id, &details])
+                    .await?;
+            }
+            (Some(DriftAction::Resolve), Some(id)) => {
+                info!(incident_id = %id, "balance drift cleared, resolving incident");
+                tx.execute(
+                    "UPDATE incidents SET status='RESOLVED', resolved_at=now(), details = details || $2 WHERE id=$1::uuid",
+                    &[&id, &json!({ "drift_units": 0, "resolved_by": "reconciler" })],
+                )
+                .await?;
+            }
+            _ => {}
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_only_above_threshold() {
+        assert_eq!(drift_action(0, 0, false), None);
+        assert_eq!(drift_action(1, 0, false), Some(DriftAction::Open));
+        assert_eq!(drift_action(100, 100, false), None);
+        assert_eq!(drift_action(101, 100, false), Some(DriftAction::Open));
+    }
+
+    #[test]
+    fn drift_saturates_instead_of_overflowing() {
+        assert_eq!(drift_units((400, 50), (450, 50)), -50);
+        assert_eq!(drift_units((i64::MAX, i64::MAX), (0, 0)), i64::MAX);
+        assert_eq!(drift_units((i64::MIN, 0), (i64::MAX, 0)), i64::MIN);
+        assert_eq!(drift_units((i64::MAX, 1), (i64::MAX, 0)), 1, "exact even when the totals leave the i64 range");
+    }
+
+    #[test]
+    fn resolves_only_at_zero() {
+        assert_eq!(drift_action(50, 100, true), Some(DriftAction::Update));
+        assert_eq!(drift_action(500, 100, true), Some(DriftAction::Update));
+        assert_eq!(drift_action(0, 100, true), Some(DriftAction::Resolve));
+    }
+}
@@ -0,0 +1,85 @@
+use deadpool_postgres::Pool;
+use std::num::ParseIntError;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+use crate::projection::{apply_balance_delta, apply_pending_delta, fold_deltas};
+use crate::Direction;
+
+/// Parse `SETTLEMENT_DELAY_SECONDS`; 0 disables settlement so credits are available at once.
+pub fn parse_delay(s: &str) -> Result<Option<Duration>, ParseIntError> {
+    let secs: u64 = s.trim().parse()?;
+    Ok((secs > 0).then(|| Duration::from_secs(secs)))
+}
+
+/// Promotes pending credits to available balance once they are older than the
+/// settlement delay. Only runs when `SETTLEMENT_DELAY_SECONDS` is set.
+pub struct Settler {
+    db: Pool,
+    delay: Duration,
+}
+
+impl Settler {
+    pub fn new(db: Pool, delay: Duration) -> Self {
+        Self { db, delay }
+    }
+
+    pub async fn run(&self, cancel: CancellationToken) {
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => return,
+                _ = interval.tick() => {
+                    if let Err(e) = self.settle_batch(500).await {
+                        warn!(error = %e, "settlement batch failed");
+                    }
+                }
+            }
+        }
+    }
+
+    async fn settle_batch(&self, limit: i64) -> Result<(), Box<dyn std::error::Error>> {
+        let mut client = self.db.get().await?;
+        let tx = client.transaction().await?;
+        let delay_secs = self.delay.as_secs_f64();
+        let rows = tx
+            .query(
+                "WITH due AS (SELECT id FROM postings WHERE settled_at IS NULL AND created_at <= now() - make_interval(secs => $1) \
+                 ORDER BY created_at LIMIT $2 FOR UPDATE SKIP LOCKED) \
+                 UPDATE postings p SET settled_at=now() FROM due WHERE p.id=due.id \
+                 RETURNING p.account_id, p.amount_units",
+                &[&delay_secs, &limit],
+            )
+            .await?;
+        if rows.is_empty() {
+            return Ok(());
+        }
+
+        // only credits are deferred, so every row moves pending -> available
+        let deltas = fold_deltas(
+            rows.iter()
+                .map(|r| (r.get::<_, &str>("account_id"), Direction::Credit, r.get::<_, i64>("amount_units"))),
+        )
+        .ok_or("settled credits overflow an i64")?;
+        for (account_id, delta) in deltas {
+            apply_pending_delta(&tx, account_id, -delta).await?;
+            apply_balance_delta(&tx, account_id, delta).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_delay_disables_settlement() {
+        assert_eq!(parse_delay("0"), Ok(None));
+        assert_eq!(parse_delay(" 30 "), Ok(Some(Duration::from_secs(30))));
+        assert!(parse_delay("soon").is_err());
+        assert!(parse_delay("-5").is_err());
+    }
+}
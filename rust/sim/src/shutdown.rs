@@ -0,0 +1,98 @@
+use axum::Router;
+use std::future::Future;
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+
+/// Resolves on SIGINT (ctrl-c) or, on Unix, SIGTERM as sent by container runtimes.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c().await.ok();
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sig) => {
+                sig.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// Serve until `signal` resolves, then stop accepting connections and wait for
+/// in-flight requests to finish before returning.
+pub async fn serve_until(
+    listener: TcpListener,
+    app: Router,
+    signal: impl Future<Output = ()> + Send + 'static,
+) -> std::io::Result<()> {
+    axum::serve(listener, app).with_graceful_shutdown(signal).await
+}
+
+/// Address to listen on: `BIND_ADDR` verbatim when set, otherwise all
+/// interfaces on `PORT`.
+pub fn bind_addr(bind_addr: Option<&str>, port: &str) -> Result<SocketAddr, String> {
+    match bind_addr {
+        Some(a) => a
+            .trim()
+            .parse()
+            .map_err(|_| format!("BIND_ADDR must be an ip:port socket address such as 127.0.0.1:8081 or [::1]:8081, got {a:?}")),
+        None => port
+            .trim()
+            .parse::<u16>()
+            .map(|p| SocketAddr::from(([0, 0, 0, 0], p)))
+            .map_err(|_| format!("PORT must be a port number, got {port:?}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bind_addr_prefers_bind_addr() {
+        assert_eq!(bind_addr(None, "8081").unwrap(), "0.0.0.0:8081".parse().unwrap());
+        assert_eq!(bind_addr(Some("127.0.0.1:9000"), "8081").unwrap(), "127.0.0.1:9000".parse().unwrap());
+        assert_eq!(bind_addr(Some("[::1]:9000"), "8081").unwrap().port(), 9000);
+
+        let err = bind_addr(Some("localhost:9000"), "8081").unwrap_err();
+        assert!(err.contains("BIND_ADDR") && err.contains("localhost:9000"));
+        assert!(bind_addr(Some("127.0.0.1"), "8081").is_err());
+        assert!(bind_addr(None, "http").unwrap_err().contains("PORT"));
+    }
+    use axum::routing::get;
+    use std::time::Duration;
+    use tokio_util::sync::CancellationToken;
+
+    #[tokio::test]
+    async fn in_flight_request_completes_after_shutdown_signal() {
+        let app = Router::new().route(
+            "/slow",
+            get(|| async {
+                tokio::time::sleep(Duration::from_millis(300)).await;
+                "done"
+            }),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let stop = CancellationToken::new();
+        let signal = stop.clone();
+        let server = tokio::spawn(serve_until(listener, app, async move { signal.cancelled().await }));
+
+        let request = tokio::spawn(async move { reqwest::get(format!("http://{addr}/slow")).await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        stop.cancel();
+
+        let resp = request.await.unwrap().unwrap();
+        assert!(resp.status().is_success());
+        assert_eq!(resp.text().await.unwrap(), "done");
+        tokio::time::timeout(Duration::from_secs(5), server).await.unwrap().unwrap().unwrap();
+    }
+}
@@ -1,22 +1,223 @@
+use axum::http::StatusCode;
+use dashmap::DashMap;
 use deadpool_postgres::Pool;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, Semaphore};
+use tokio_util::sync::CancellationToken;
+
+use crate::auth::ApiToken;
+use crate::clock::Clock;
+use crate::error::AppError;
+use crate::handlers::admin::{LANGUAGE, REVISION, VERSION};
+use crate::handlers::audit::AuditEntry;
+use crate::handlers::events::LedgerEvent;
+use crate::handlers::transfers::TransferLimits;
+use crate::handlers::zones::TransitionPolicy;
+use crate::jwt::JwtVerifier;
+use crate::microbatch::PendingTransfer;
+use crate::middleware::CorsConfig;
+use crate::projection::BalanceProjection;
+use crate::ratelimit::Bucket;
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: Pool,
-    pub admin_key: Option<String>,
+    /// Read-only handlers query this pool: a replica when `DATABASE_REPLICA_URL`
+    /// is set, otherwise the same pool as `db`.
+    pub db_read: Pool,
+    /// Accepted `X-Admin-Key` values; several during a key rotation, none disables admin routes.
+    pub admin_keys: Vec<String>,
+    /// Bearer tokens that identify who initiates transfers; none leaves every request anonymous.
+    pub api_tokens: Vec<ApiToken>,
+    /// Guard `/metrics` with the admin key as well.
+    pub metrics_require_admin: bool,
     pub registry: Arc<prometheus::Registry>,
     pub metrics: Arc<Metrics>,
+    pub balance_projection: BalanceProjection,
+    /// Default per-zone transfer rate (per second); 0 disables limiting.
+    pub zone_rate_limit: u32,
+    pub zone_buckets: Arc<DashMap<String, Bucket>>,
+    /// When set, credits stay pending for this long before becoming available.
+    pub settlement_delay: Option<Duration>,
+    /// Refuse debits not covered by the source's available balance.
+    pub reject_overdrafts: bool,
+    /// How long a request_id stays reserved; None keeps keys forever.
+    pub idempotency_ttl: Option<Duration>,
+    /// Maximum number of transfers accepted by `/v1/transfers/batch`.
+    pub transfer_batch_max: usize,
+    pub transfer_limits: TransferLimits,
+    /// Dotted `metadata` paths left out of the transfer idempotency hash.
+    pub idempotency_hash_exclude: Vec<String>,
+    /// Set in micro-batching mode: `create_transfer` hands transfers to the batch writer.
+    pub transfer_batcher: Option<mpsc::Sender<PendingTransfer>>,
+    /// Committed audit entries, fanned out to `/v1/audit/stream` subscribers.
+    pub audit_tx: broadcast::Sender<AuditEntry>,
+    /// Committed transfer and zone status events, fanned out to `/v1/events/stream` subscribers.
+    pub events_tx: broadcast::Sender<LedgerEvent>,
+    /// One permit shared by snapshot, restore and archive, so at most one of them runs at a time.
+    pub admin_ops: Arc<Semaphore>,
+    pub cors: Arc<CorsConfig>,
+    /// Set when `JWT_PUBLIC_KEY` or `JWT_JWKS_URL` is configured; see [`crate::jwt::require_jwt`].
+    pub jwt: Option<Arc<JwtVerifier>>,
+    /// What handlers take as the current time; [`crate::clock::SystemClock`] outside tests.
+    pub clock: Arc<dyn Clock>,
+    /// Which zone status changes operators may make.
+    pub zone_transitions: TransitionPolicy,
+    /// Cancelled when the server starts draining. Responses that never finish on
+    /// their own (the SSE streams and the zone WebSocket) end on it, so they do
+    /// not hold up graceful shutdown.
+    pub shutdown: CancellationToken,
 }
 
 pub struct Metrics {
-    pub transfers_total: prometheus::IntCounter,
+    /// Labeled by `zone_id` and `outcome` (`posted`, `idempotent_replay`).
+    pub transfers_total: prometheus::IntCounterVec,
+    pub transfer_duration_seconds: prometheus::Histogram,
+    pub transfer_amount_units: prometheus::Histogram,
+    pub transfers_rejected_total: prometheus::IntCounterVec,
+    /// Attempts on known zones, labeled by `zone_id` and `outcome` (success
+    /// outcomes plus the specific rejection reason).
+    pub zone_transfer_attempts: prometheus::IntCounterVec,
+    /// Outbox rows not yet delivered to the sink, refreshed by `OutboxDepthGauge`.
+    pub outbox_pending_events: prometheus::IntGauge,
+    pub outbox_delivered_total: prometheus::IntCounter,
+    /// Failed delivery attempts; each is retried with backoff.
+    pub outbox_failed_total: prometheus::IntCounter,
+    /// Signed sum of all balances, set by the `ImbalanceChecker`; non-zero is a bug.
+    pub ledger_imbalance_units: prometheus::IntGauge,
+}
+
+impl Metrics {
+    pub fn record_rejection(&self, err: &AppError) {
+        if let Some(reason) = rejection_reason(err) {
+            self.transfers_rejected_total.with_label_values(&[reason]).inc();
+        }
+    }
 }
 
-pub fn init_metrics() -> (Arc<prometheus::Registry>, Arc<Metrics>) {
+/// `reason` label for a rejected transfer; server-side failures are not rejections.
+fn rejection_reason(err: &AppError) -> Option<&'static str> {
+    let (status, _) = err.status_and_code();
+    match status {
+        StatusCode::SERVICE_UNAVAILABLE => Some("zone_down"),
+        StatusCode::CONFLICT => Some("conflict"),
+        StatusCode::TOO_MANY_REQUESTS => Some("rate_limited"),
+        s if s.is_client_error() => Some("bad_request"),
+        _ => None,
+    }
+}
+
+/// Build and register the transfer metrics. Fails only on an invalid metric
+/// definition or a duplicate registration.
+pub fn init_metrics() -> prometheus::Result<(Arc<prometheus::Registry>, Arc<Metrics>)> {
     let reg = prometheus::Registry::new();
-    let transfers_total =
-        prometheus::IntCounter::new("transfers_total", "Transfers created").unwrap();
-    reg.register(Box::new(transfers_total.clone())).unwrap();
-    (Arc::new(reg), Arc::new(Metrics { transfers_total }))
+    let transfers_total = prometheus::IntCounterVec::new(
+        prometheus::Opts::new("transfers_total", "Transfer requests handled, by zone and outcome"),
+        &["zone_id", "outcome"],
+    )?;
+    let transfer_duration_seconds = prometheus::Histogram::with_opts(prometheus::HistogramOpts::new(
+        "transfer_duration_seconds",
+        "Wall time of create_transfer, including DB work",
+    ))?;
+    let transfer_amount_units = prometheus::Histogram::with_opts(
+        prometheus::HistogramOpts::new("transfer_amount_units", "Amount of applied transfers")
+            .buckets(prometheus::exponential_buckets(1.0, 10.0, 10)?),
+    )?;
+    let transfers_rejected_total = prometheus::IntCounterVec::new(
+        prometheus::Opts::new("transfers_rejected_total", "Transfers rejected, by reason"),
+        &["reason"],
+    )?;
+    let zone_transfer_attempts = prometheus::IntCounterVec::new(
+        prometheus::Opts::new("zone_transfer_attempts_total", "Transfer attempts per zone, by outcome"),
+        &["zone_id", "outcome"],
+    )?;
+    let outbox_pending_events =
+        prometheus::IntGauge::new("outbox_pending_events", "Outbox events not yet delivered to the outbox sink")?;
+    let outbox_delivered_total =
+        prometheus::IntCounter::new("outbox_delivered_total", "Outbox events delivered to the outbox sink")?;
+    let outbox_failed_total =
+        prometheus::IntCounter::new("outbox_failed_total", "Outbox delivery attempts that failed and will be retried")?;
+    let ledger_imbalance_units =
+        prometheus::IntGauge::new("ledger_imbalance_units", "Signed sum of all balances; zero unless the ledger is broken")?;
+    // constant 1; the labels are the point, for joining version onto other series
+    let build_info = prometheus::IntGaugeVec::new(
+        prometheus::Opts::new("build_info", "Build of the running binary; always 1"),
+        &["version", "revision", "language"],
+    )?;
+    build_info.with_label_values(&[VERSION, REVISION.unwrap_or("unknown"), LANGUAGE]).set(1);
+    reg.register(Box::new(build_info))?;
+    reg.register(Box::new(transfers_total.clone()))?;
+    reg.register(Box::new(transfer_duration_seconds.clone()))?;
+    reg.register(Box::new(transfer_amount_units.clone()))?;
+    reg.register(Box::new(transfers_rejected_total.clone()))?;
+    reg.register(Box::new(zone_transfer_attempts.clone()))?;
+    reg.register(Box::new(outbox_pending_events.clone()))?;
+    reg.register(Box::new(outbox_delivered_total.clone()))?;
+    reg.register(Box::new(outbox_failed_total.clone()))?;
+    reg.register(Box::new(ledger_imbalance_units.clone()))?;
+    Ok((
+        Arc::new(reg),
+        Arc::new(Metrics {
+            transfers_total,
+            transfer_duration_seconds,
+            transfer_amount_units,
+            transfers_rejected_total,
+            zone_transfer_attempts,
+            outbox_pending_events,
+            outbox_delivered_total,
+            outbox_failed_total,
+            ledger_imbalance_units,
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::Encoder;
+
+    fn exposition(reg: &prometheus::Registry) -> String {
+        let mut buf = Vec::new();
+        prometheus::TextEncoder::new().encode(&reg.gather(), &mut buf).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn transfer_series_exposed() {
+        let (reg, m) = init_metrics().unwrap();
+        m.transfers_total.with_label_values(&["zone-eu", "posted"]).inc();
+        m.transfers_total.with_label_values(&["zone-eu", "idempotent_replay"]).inc();
+        m.transfers_total.with_label_values(&["zone-na", "posted"]).inc();
+        m.transfer_duration_seconds.observe(0.012);
+        m.transfer_amount_units.observe(250.0);
+        m.record_rejection(&AppError::Unavailable("zone down".into()));
+        m.record_rejection(&AppError::Conflict("dup".into()));
+        m.record_rejection(&AppError::Internal("db".into()));
+        m.zone_transfer_attempts.with_label_values(&["zone-eu", "throttled"]).inc();
+        m.outbox_pending_events.set(3);
+        m.outbox_delivered_total.inc();
+        m.outbox_failed_total.inc_by(2);
+
+        let text = exposition(&reg);
+        assert!(text.contains("transfers_total{outcome=\"posted\",zone_id=\"zone-eu\"} 1"));
+        assert!(text.contains("transfers_total{outcome=\"idempotent_replay\",zone_id=\"zone-eu\"} 1"));
+        assert!(text.contains("transfers_total{outcome=\"posted\",zone_id=\"zone-na\"} 1"));
+        assert!(text.contains("transfer_duration_seconds_count 1"));
+        assert!(text.contains("transfer_amount_units_bucket{le=\"1000\"} 1"));
+        assert!(text.contains("transfers_rejected_total{reason=\"zone_down\"} 1"));
+        assert!(text.contains("transfers_rejected_total{reason=\"conflict\"} 1"));
+        assert!(!text.contains("reason=\"internal\""));
+        assert!(text.contains("zone_transfer_attempts_total{outcome=\"throttled\",zone_id=\"zone-eu\"} 1"));
+        assert!(text.contains("outbox_pending_events 3"));
+        assert!(text.contains("outbox_delivered_total 1"));
+        assert!(text.contains("outbox_failed_total 2"));
+    }
+
+    #[test]
+    fn rejection_reasons() {
+        assert_eq!(rejection_reason(&AppError::BadRequest("x".into())), Some("bad_request"));
+        assert_eq!(rejection_reason(&AppError::NotFound("x".into())), Some("bad_request"));
+        assert_eq!(rejection_reason(&AppError::Internal("x".into())), None);
+    }
 }
@@ -0,0 +1,19 @@
+//! Shared setup for the tests that need a database. They are marked
+//! `#[ignore = "needs TEST_DATABASE_URL"]` and run against a migrated database with
+//! `TEST_DATABASE_URL=postgres://... cargo test -- --include-ignored`.
+
+use crate::config::Config;
+use crate::state::AppState;
+
+pub fn test_db_url() -> String {
+    std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL must point at a migrated database")
+}
+
+/// Defaults on the test database, for tests that change a setting before `build_state`.
+pub fn test_db_config() -> Config {
+    Config::new(test_db_url())
+}
+
+pub async fn test_db() -> AppState {
+    crate::app::build_state(test_db_config()).await.unwrap()
+}
@@ -1,12 +1,22 @@
 use serde::Serialize;
+use std::cmp::Ordering;
 
 use crate::error::AppError;
 
+/// Order of object keys in canonical JSON: by Unicode code point. This is the
+/// same as comparing the UTF-8 bytes, which is what Go's `sort.Strings` does,
+/// so hashes match the Go service. Clients that sort by UTF-16 code units
+/// (JavaScript's default `sort`, Java's `compareTo`) disagree only on keys mixing
+/// characters above U+FFFF with ones in U+E000..U+FFFF.
+pub fn key_order(a: &str, b: &str) -> Ordering {
+    a.chars().cmp(b.chars())
+}
+
 pub fn canonicalize(v: &serde_json::Value) -> serde_json::Value {
     match v {
         serde_json::Value::Object(map) => {
             let mut keys: Vec<_> = map.keys().cloned().collect();
-            keys.sort();
+            keys.sort_by(|a, b| key_order(a, b));
             let mut out = serde_json::Map::new();
             for k in keys {
                 out.insert(k.clone(), canonicalize(&map[&k]));
@@ -20,6 +30,58 @@ pub fn canonicalize(v: &serde_json::Value) -> serde_json::Value {
     }
 }
 
+/// Remove the field at a dotted `path` (`a.b.c`) of `v`, if every step is an object key.
+pub fn remove_path(v: &mut serde_json::Value, path: &str) {
+    let (parent, key) = match path.rsplit_once('.') {
+        Some((parents, key)) => (parents.split('.').try_fold(v, |v, k| v.get_mut(k)), key),
+        None => (Some(v), path),
+    };
+    if let Some(serde_json::Value::Object(map)) = parent {
+        map.remove(key);
+    }
+}
+
+/// Compact JSON of `v` with object keys in [`key_order`] at every depth, written
+/// directly rather than through `serde_json::Map`, whose iteration order depends
+/// on whether the `preserve_order` feature is enabled anywhere in the build.
+/// Array order is kept; only object keys are sorted.
+pub fn canonical_json(v: &serde_json::Value) -> serde_json::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    write_canonical(v, &mut out)?;
+    Ok(out)
+}
+
+fn write_canonical(v: &serde_json::Value, out: &mut Vec<u8>) -> serde_json::Result<()> {
+    match v {
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|(a, _), (b, _)| key_order(a, b));
+            out.push(b'{');
+            for (i, (k, v)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                serde_json::to_writer(&mut *out, k)?;
+                out.push(b':');
+                write_canonical(v, out)?;
+            }
+            out.push(b'}');
+        }
+        serde_json::Value::Array(arr) => {
+            out.push(b'[');
+            for (i, v) in arr.iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                write_canonical(v, out)?;
+            }
+            out.push(b']');
+        }
+        _ => serde_json::to_writer(&mut *out, v)?,
+    }
+    Ok(())
+}
+
 pub fn sha256_hex(bytes: &[u8]) -> String {
     use sha2::{Digest, Sha256};
     let mut hasher = Sha256::new();
@@ -29,11 +91,37 @@ pub fn sha256_hex(bytes: &[u8]) -> String {
 
 pub fn payload_hash<T: Serialize>(req: &T) -> Result<String, AppError> {
     let v = serde_json::to_value(req).map_err(|e| AppError::BadRequest(e.to_string()))?;
-    let canon = canonicalize(&v);
-    let bytes = serde_json::to_vec(&canon).map_err(|e| AppError::BadRequest(e.to_string()))?;
+    let bytes = canonical_json(&v).map_err(|e| AppError::BadRequest(e.to_string()))?;
     Ok(sha256_hex(&bytes))
 }
 
+/// HMAC-SHA256 (RFC 2104) over the concatenation of `parts`, hex-encoded.
+pub fn hmac_sha256_hex(key: &[u8], parts: &[&[u8]]) -> String {
+    use sha2::{Digest, Sha256};
+    const BLOCK: usize = 64;
+    let mut block = [0u8; BLOCK];
+    if key.len() > BLOCK {
+        let d = Sha256::digest(key);
+        block[..d.len()].copy_from_slice(&d);
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let ipad: Vec<u8> = block.iter().map(|b| b ^ 0x36).collect();
+    let opad: Vec<u8> = block.iter().map(|b| b ^ 0x5c).collect();
+
+    let mut inner = Sha256::new();
+    inner.update(&ipad);
+    for p in parts {
+        inner.update(p);
+    }
+    let inner_hash = inner.finalize();
+
+    let mut outer = Sha256::new();
+    outer.update(&opad);
+    outer.update(inner_hash);
+    hex::encode(outer.finalize())
+}
+
 /// Deterministic hash to percentage (0-99).
 /// FNV-1a 32-bit on raw bytes, matching Go's fnv.New32a() + Write([]byte(s)) + Sum32().
 pub fn hash_percent(s: &str) -> u32 {
@@ -45,15 +133,50 @@ pub fn hash_percent(s: &str) -> u32 {
     h % 100
 }
 
-pub fn fmt_rfc3339(dt: time::OffsetDateTime) -> String {
-    dt.format(&time::format_description::well_known::Rfc3339)
-        .unwrap()
+/// RFC 3339 text for `dt`. Fails for years outside 0000-9999 or offsets with a
+/// seconds component, both of which Postgres can hand back.
+pub fn to_rfc3339(dt: time::OffsetDateTime) -> Result<String, AppError> {
+    dt.format(&time::format_description::well_known::Rfc3339).map_err(|e| {
+        tracing::error!(error = %e, timestamp = ?dt, "timestamp is not representable as RFC 3339");
+        AppError::Internal(format!("timestamp not representable as RFC 3339: {e}"))
+    })
+}
+
+pub fn parse_rfc3339(field: &str, s: &str) -> Result<time::OffsetDateTime, AppError> {
+    time::OffsetDateTime::parse(s, &time::format_description::well_known::Rfc3339)
+        .map_err(|_| AppError::BadRequest(format!("{field} must be an RFC3339 timestamp")))
+}
+
+/// One RFC 4180 CSV record, CRLF-terminated. Fields containing a comma,
+/// quote or line break are quoted, with inner quotes doubled.
+pub fn csv_record(fields: &[&str]) -> String {
+    let mut out = String::new();
+    for (i, f) in fields.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        if f.contains([',', '"', '\n', '\r']) {
+            out.push('"');
+            out.push_str(&f.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(f);
+        }
+    }
+    out.push_str("\r\n");
+    out
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn csv_quotes_only_when_needed() {
+        assert_eq!(csv_record(&["a", "b c", ""]), "a,b c,\r\n");
+        assert_eq!(csv_record(&["x,y", "say \"hi\"", "l1\nl2"]), "\"x,y\",\"say \"\"hi\"\"\",\"l1\nl2\"\r\n");
+    }
+
     // Cross-language parity: these values must match Go's hashPercent output.
     // Verified via: go run with fnv.New32a().Write([]byte(s)); h.Sum32() % 100
     #[test]
@@ -113,6 +236,108 @@ mod tests {
         assert_eq!(s, r#"{"a":[3,2,1],"z":{"a":2,"b":1}}"#);
     }
 
+    #[test]
+    fn keys_sort_by_code_point() {
+        let v = serde_json::json!({ "😀": 1, "～": 2, "é": 3, "z": 4, "Z": 5, "日本": 6 });
+        let canon = String::from_utf8(canonical_json(&v).unwrap()).unwrap();
+        assert_eq!(canon, r#"{"Z":5,"z":4,"é":3,"日本":6,"～":2,"😀":1}"#);
+        // code point order is UTF-8 byte order (Go's sort.Strings) ...
+        assert_eq!(key_order("😀", "～"), "😀".as_bytes().cmp("～".as_bytes()));
+        // ... which UTF-16 code unit order disagrees with above U+FFFF
+        assert_eq!(key_order("😀", "～"), Ordering::Greater);
+        assert_eq!("😀".encode_utf16().cmp("～".encode_utf16()), Ordering::Less);
+    }
+
+    #[test]
+    fn canonical_json_matches_canonicalize() {
+        let v = serde_json::json!({ "b": [{ "y": 1, "x": null }], "a": { "ä": "\"q\"\n", "a": 1.5 } });
+        assert_eq!(canonical_json(&v).unwrap(), serde_json::to_vec(&canonicalize(&v)).unwrap());
+    }
+
+    /// Nested JSON tree whose object keys can be rendered in any order.
+    enum Doc {
+        Obj(Vec<(&'static str, Doc)>),
+        Arr(Vec<Doc>),
+        Leaf(&'static str),
+    }
+
+    impl Doc {
+        /// JSON text with each object's keys in an order drawn from `rng`.
+        fn render(&self, rng: &mut impl FnMut() -> u64) -> String {
+            match self {
+                Doc::Obj(fields) => {
+                    let mut order: Vec<usize> = (0..fields.len()).collect();
+                    for i in (1..order.len()).rev() {
+                        order.swap(i, (rng() % (i as u64 + 1)) as usize);
+                    }
+                    let parts: Vec<String> = order
+                        .into_iter()
+                        .map(|i| format!("{}:{}", serde_json::to_string(fields[i].0).unwrap(), fields[i].1.render(rng)))
+                        .collect();
+                    format!("{{{}}}", parts.join(","))
+                }
+                Doc::Arr(items) => format!("[{}]", items.iter().map(|d| d.render(rng)).collect::<Vec<_>>().join(",")),
+                Doc::Leaf(json) => json.to_string(),
+            }
+        }
+    }
+
+    #[test]
+    fn payload_hash_ignores_key_order_at_every_depth() {
+        use Doc::*;
+        let doc = Obj(vec![
+            ("request_id", Leaf(r#""req-1""#)),
+            ("amount_units", Leaf("100")),
+            ("zone_id", Leaf(r#""zone-eu""#)),
+            ("metadata", Obj(vec![
+                ("Zürich", Leaf(r#""ü""#)),
+                ("zebra", Leaf("true")),
+                ("日本", Obj(vec![("東京", Leaf("1")), ("大阪", Leaf("2")), ("a", Leaf("null"))])),
+                ("😀", Leaf(r#""astral""#)),
+                ("～", Leaf(r#""bmp""#)),
+                ("lines", Arr(vec![
+                    Obj(vec![("sku", Leaf(r#""x""#)), ("qty", Leaf("2")), ("ñ", Leaf("0.5"))]),
+                    Obj(vec![("qty", Leaf("1")), ("sku", Leaf(r#""y""#)), ("tags", Arr(vec![Leaf(r#""b""#), Leaf(r#""a""#)]))]),
+                ])),
+            ])),
+        ]);
+
+        // xorshift64, seeded so failures reproduce
+        let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+        let mut rng = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let hash_of = |text: &str| payload_hash(&serde_json::from_str::<serde_json::Value>(text).unwrap()).unwrap();
+        let first = doc.render(&mut rng);
+        let expected = hash_of(&first);
+        let mut renderings = std::collections::HashSet::from([first.clone()]);
+        for _ in 0..200 {
+            let text = doc.render(&mut rng);
+            assert_eq!(hash_of(&text), expected, "{text}");
+            renderings.insert(text);
+        }
+        assert!(renderings.len() > 50, "shuffles should produce many distinct key orders");
+
+        // array order is data, not formatting
+        let reordered = first.replace(r#"["b","a"]"#, r#"["a","b"]"#);
+        assert_ne!(hash_of(&reordered), expected);
+    }
+
+    #[test]
+    fn remove_path_only_follows_objects() {
+        let mut v = serde_json::json!({ "a": { "b": 1, "c": 2 }, "d": [{ "b": 3 }], "e": 4 });
+        remove_path(&mut v, "a.b");
+        remove_path(&mut v, "d.b");
+        remove_path(&mut v, "e.x");
+        remove_path(&mut v, "missing.b");
+        assert_eq!(v, serde_json::json!({ "a": { "c": 2 }, "d": [{ "b": 3 }], "e": 4 }));
+        remove_path(&mut v, "e");
+        assert_eq!(v, serde_json::json!({ "a": { "c": 2 }, "d": [{ "b": 3 }] }));
+    }
+
     #[test]
     fn payload_hash_deterministic() {
         #[derive(serde::Serialize)]
@@ -131,6 +356,39 @@ mod tests {
         assert_ne!(payload_hash(&r1).unwrap(), payload_hash(&r2).unwrap());
     }
 
+    #[test]
+    fn parse_rfc3339_roundtrip() {
+        let dt = parse_rfc3339("from", "2026-04-05T12:30:00Z").unwrap();
+        assert_eq!(to_rfc3339(dt).unwrap(), "2026-04-05T12:30:00Z");
+        assert!(parse_rfc3339("from", "yesterday").is_err());
+    }
+
+    #[test]
+    fn to_rfc3339_rejects_sub_minute_offsets() {
+        let dt = parse_rfc3339("at", "2026-04-05T12:30:00+02:00").unwrap();
+        assert_eq!(to_rfc3339(dt).unwrap(), "2026-04-05T12:30:00+02:00");
+        // RFC 3339 offsets stop at minutes
+        let odd = dt.to_offset(time::UtcOffset::from_hms(1, 0, 30).unwrap());
+        assert!(matches!(to_rfc3339(odd), Err(AppError::Internal(_))));
+    }
+
+    #[test]
+    fn hmac_sha256_rfc4231_case2() {
+        let parts: [&[u8]; 2] = [b"what do ya ", b"want for nothing?"];
+        assert_eq!(
+            hmac_sha256_hex(b"Jefe", &parts),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn hmac_sha256_long_key_is_hashed() {
+        assert_eq!(
+            hmac_sha256_hex(&[b'k'; 100], &[b"msg".as_slice()]),
+            "bd56a1782c2830e8abc6ed866a57a1230661e650b84c62f7ee3accc5fa5af491"
+        );
+    }
+
     #[test]
     fn sha256_hex_known_value() {
         // SHA256("") = e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855
@@ -5,7 +5,7 @@ import { ToastHost, ToastMsg } from "./components/Toast";
 import { api, getApiBase, setApiBase } from "./lib/api";
 import { fmtRfc3339, fmtUnits } from "./lib/time";
 import { blastRadius, recommendedControlsFor } from "./lib/risk";
-import { ZONES, zoneNumber } from "./zones";
+import { ZONES, zoneAccount, zoneNumber } from "./zones";
 
 type Zone = { id: string; name: string; status: "OK" | "DEGRADED" | "DOWN"; updated_at: string };
 
@@ -100,8 +100,8 @@ export default function App() {
   const [reason, setReason] = useState("sim action");
   const [adminKey, setAdminKey] = useState("");
 
-  const [transferFrom, setTransferFrom] = useState("acct-A");
-  const [transferTo, setTransferTo] = useState("acct-B");
+  const [transferFrom, setTransferFrom] = useState(zoneAccount(ZONES[0].id, "A"));
+  const [transferTo, setTransferTo] = useState(zoneAccount(ZONES[0].id, "B"));
   const [transferAmount, setTransferAmount] = useState(120);
 
   const [autoTraffic, setAutoTraffic] = useState(false);
@@ -206,6 +206,8 @@ export default function App() {
 
   useEffect(() => {
     if (!selectedZoneId) return;
+    setTransferFrom(zoneAccount(selectedZoneId, "A"));
+    setTransferTo(zoneAccount(selectedZoneId, "B"));
     loadZoneDrilldown(selectedZoneId).catch((e: any) => toast("Zone drilldown failed", String(e?.message || e)));
     // eslint-disable-next-line react-hooks/exhaustive-deps
   }, [selectedZoneId]);
@@ -233,8 +235,8 @@ export default function App() {
     }
     autoRef.current = window.setInterval(() => {
       const z = selectedZoneId;
-      const from = `acct-${String.fromCharCode(65 + secureRandomInt(6))}`;
-      const to = `acct-${String.fromCharCode(65 + secureRandomInt(6))}`;
+      const from = zoneAccount(z, String.fromCharCode(65 + secureRandomInt(6)));
+      const to = zoneAccount(z, String.fromCharCode(65 + secureRandomInt(6)));
       if (from === to) return;
       const amt = [30, 60, 120, 300, 600, 1200, 3600][secureRandomInt(7)];
       createTransfer(from, to, amt, z, { mode: "auto" }).catch(() => {});
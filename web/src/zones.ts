@@ -21,3 +21,9 @@ export function zoneNumber(id: string): number {
 export function zoneLabel(id: string): string {
   return ZONES.find(z => z.id === id)?.label ?? id;
 }
+
+// Accounts belong to one zone, so the same id reused in another zone is rejected
+// (409 account_zone_mismatch). Sim accounts carry their zone: acct-eu-A, acct-na-B, ...
+export function zoneAccount(zoneId: string, letter: string): string {
+  return `acct-${zoneId.replace(/^zone-/, "")}-${letter}`;
+}